@@ -177,6 +177,11 @@ impl CommChannel {
         }
     }
 
+    /// send req, DOCA_ERROR_AGAIN if the send queue is full
+    pub fn send_req(&self, raw: &RawPointer) -> doca_error {
+        unsafe { ffi::doca_comm_channel_ep_sendto(self.inner_ptr(), raw.inner.as_ptr(), raw.payload, 0, self.peer_addr.as_ptr()) }
+    }
+
     /// block recv req
     pub fn block_recv_req(&self, raw: &mut RawPointer) {
         let mut peer_addr: *mut ffi::doca_comm_channel_addr_t = std::ptr::null_mut();
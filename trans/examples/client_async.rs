@@ -120,7 +120,7 @@ async fn main() {
     }
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let worker = Arc::new(AskServerWorker::new(&scheduler));
@@ -9,6 +9,8 @@ mod test_dpu {
 
 use std::sync::{ Arc, Mutex };
 
+use trans::common::cluster::{ get_cluster, set_cluster, ClusterConfig };
+use trans::common::consistency::serve_check;
 use trans::rdma::control::RdmaControl;
 use trans::doca_comm_chan::connection::DocaCommChannel;
 use trans::memstore::memdb::MemDB;
@@ -17,28 +19,15 @@ use trans::app::small_bank::dpu_helpers::dpu_worker::SmallBankDpuWorker;
 use trans::app::small_bank::dpu_helpers::loader::SmallBankDpuLoader;
 use trans::SMALL_BANK_NTHREADS;
 
-const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
-const COMM_NAMES: [&str; 8] = ["comm0\0", "comm1\0", "comm2\0", "comm3\0", "comm4\0", "comm5\0", "comm6\0", "comm7\0"];
-
-async fn init_and_run(tid: usize, memdb: Arc<MemDB>) {
-    let pci_addr = if tid < 4 {
-        "03:00.0"
-    } else {
-        "03:00.1"
-    };
-
-    let pci_addr_rep = if tid < 4 {
-        "af:00.0"
-    } else {
-        "af:00.1"
-    };
+async fn init_and_run(self_id: u64, tid: usize, memdb: Arc<MemDB>) {
+    let cluster = get_cluster().unwrap();
+
     // comm chan
-    let comm_chan = DocaCommChannel::new_server(COMM_NAMES[tid], pci_addr, pci_addr_rep);
+    let comm_chan = DocaCommChannel::new_from_cluster(cluster, self_id, tid).unwrap();
 
     // rdma conn
-    let mut rdma = RdmaControl::new(100);
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(1);
+    let mut rdma = RdmaControl::new(self_id);
+    rdma.connect_cluster(cluster, tid).unwrap();
 
     // scheduler
     let allocator = rdma.get_allocator();
@@ -49,16 +38,27 @@ async fn init_and_run(tid: usize, memdb: Arc<MemDB>) {
         Arc::get_mut_unchecked(&mut scheduler).set_comm_chan(comm_chan);
     }
 
-    // scheduler add rdma conn
-    let conn = rdma.get_connection(1);
-    conn.lock().unwrap().init_and_start_recvs().unwrap();
-    conn.lock()
-        .unwrap()
-        .register_recv_callback(&scheduler)
-        .unwrap();
-
+    // scheduler add rdma conns
+    for peer_id in rdma.get_peer_ids() {
+        let conn = rdma.get_connection(peer_id);
+        conn.lock().unwrap().init_and_start_recvs().unwrap();
+        conn.lock()
+            .unwrap()
+            .register_callbacks(&scheduler)
+            .unwrap();
+
+        unsafe {
+            Arc::get_mut_unchecked(&mut scheduler).append_conn(peer_id, &conn);
+        }
+    }
+    if let Some(srq) = rdma.get_srq() {
+        unsafe {
+            Arc::get_mut_unchecked(&mut scheduler).set_srq(&srq);
+        }
+    }
+    // the broken peers are re-established from the poll loop
     unsafe {
-        Arc::get_mut_unchecked(&mut scheduler).append_conn(1, &conn);
+        Arc::get_mut_unchecked(&mut scheduler).set_reconnect(rdma, cluster);
     }
 
     // worker
@@ -76,8 +76,35 @@ async fn init_and_run(tid: usize, memdb: Arc<MemDB>) {
     worker.run(tid as _).await;
 }
 
+// the cluster config, the id of the dpu and check to answer the consistency check of the
+// host, configs/cluster.json, 100 and no check by default
 pub fn test() {
-    let memdb = SmallBankDpuLoader::new_dpudb(0);
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or(String::from("configs/cluster.json"));
+    let self_id: u64 = args.next().map_or(100, |id| id.parse().unwrap());
+    let serve: bool = args.next().map_or(false, |arg| arg == "check");
+    set_cluster(ClusterConfig::load(std::path::Path::new(&path)).unwrap()).unwrap();
+
+    let cluster = get_cluster().unwrap();
+    let host_id = cluster.host_of_dpu(self_id).unwrap();
+    let memdb = SmallBankDpuLoader::new_dpudb(cluster.get_node(host_id).unwrap().partitions[0]);
+
+    // the host ends the run with the check
+    if serve {
+        let memdb_clone = memdb.clone();
+        std::thread::spawn(move || {
+            let dpu = cluster.get_node(self_id).unwrap();
+            let host = cluster.get_node(host_id).unwrap();
+            let report = serve_check(
+                &memdb_clone,
+                dpu.get_dma_addr(dpu.threads).parse().unwrap(),
+                host.get_dma_addr(host.threads).parse().unwrap(),
+            ).unwrap();
+
+            print!("{}", report);
+            std::process::exit(if report.is_consistent() { 0 } else { 1 });
+        });
+    }
 
     let mut ths = Vec::new();
     for i in 0..SMALL_BANK_NTHREADS {
@@ -88,7 +115,7 @@ pub fn test() {
                 .build()
                 .unwrap()
                 .block_on(async move {
-                    init_and_run(i, memdb_clone).await;
+                    init_and_run(self_id, i, memdb_clone).await;
             });
         }));
     }
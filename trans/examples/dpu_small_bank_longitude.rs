@@ -23,8 +23,8 @@ const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0",
 async fn listen_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize) {
     // scheduler
     let mut rdma = RdmaControl::new(100);
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(2);
+    rdma.init("0.0.0.0\0", CONN_PORTS[tid]).unwrap();
+    rdma.listen_task(2).unwrap();
 
     let allocator = rdma.get_allocator();
     let mut scheduler = Arc::new(AsyncScheduler::new(tid, 1, &allocator));
@@ -36,7 +36,7 @@ async fn listen_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize) {
     }
     conn_host.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let conn_hybrid = rdma.get_connection(1);
@@ -46,7 +46,7 @@ async fn listen_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize) {
     }
     conn_hybrid.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let worker = Arc::new(SmallBankHostLongitudeWorker::new(0, tid as _, &memdb, &scheduler));
@@ -15,6 +15,7 @@ use trans::memstore::memdb::MemDB;
 use trans::framework::scheduler::AsyncScheduler;
 use trans::app::tpcc::dpu_helpers::dpu_worker::TpccDpuWorker;
 use trans::app::tpcc::dpu_helpers::loader::TpccDpuLoader;
+use trans::common::consistency::serve_check;
 use trans::TPCC_NTHREADS;
 
 const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
@@ -37,8 +38,8 @@ async fn init_and_run(tid: usize, memdb: Arc<MemDB>) {
 
     // rdma conn
     let mut rdma = RdmaControl::new(100);
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(1);
+    rdma.init("0.0.0.0\0", CONN_PORTS[tid]).unwrap();
+    rdma.listen_task(1).unwrap();
 
     // scheduler
     let allocator = rdma.get_allocator();
@@ -54,7 +55,7 @@ async fn init_and_run(tid: usize, memdb: Arc<MemDB>) {
     conn.lock().unwrap().init_and_start_recvs().unwrap();
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     unsafe {
@@ -76,9 +77,24 @@ async fn init_and_run(tid: usize, memdb: Arc<MemDB>) {
     worker.run(tid as _).await;
 }
 
+// the addresses the dpu and the host take the consistency check on, no check by default
 pub fn test() {
+    let mut args = std::env::args().skip(1);
+    let check_addrs = args.next().zip(args.next());
+
     let memdb = TpccDpuLoader::new_dpudb(0);
 
+    // the host ends the run with the check
+    if let Some((dpu_addr, host_addr)) = check_addrs {
+        let memdb_clone = memdb.clone();
+        std::thread::spawn(move || {
+            let report = serve_check(&memdb_clone, dpu_addr.parse().unwrap(), host_addr.parse().unwrap()).unwrap();
+
+            print!("{}", report);
+            std::process::exit(if report.is_consistent() { 0 } else { 1 });
+        });
+    }
+
     let mut ths = Vec::new();
     for i in 0..TPCC_NTHREADS {
         let memdb_clone = memdb.clone();
@@ -229,8 +229,8 @@ pub async fn test() {
 
     // rdma conn
     let mut rdma = RdmaControl::new(0);
-    rdma.init("0.0.0.0\0", "7472\0");
-    rdma.listen_task(1);
+    rdma.init("0.0.0.0\0", "7472\0").unwrap();
+    rdma.listen_task(1).unwrap();
 
     // scheduler
     let allocator = rdma.get_allocator();
@@ -246,7 +246,7 @@ pub async fn test() {
     conn.lock().unwrap().init_and_start_recvs().unwrap();
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     unsafe {
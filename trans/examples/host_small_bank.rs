@@ -5,6 +5,7 @@ use trans::common::logs::init_log;
 mod test_dpu {
 
 use std::sync::{ Arc, Mutex };
+use std::time::Duration;
 use tokio::sync::Mutex as AsyncMutex;
 use tokio::sync::mpsc;
 
@@ -12,31 +13,31 @@ use trans::app::small_bank::local_client::SmallBankClient;
 use trans::app::small_bank::SmallBankClientReq;
 use trans::app::small_bank::dpu_helpers::loader::SmallBankDpuLoader;
 use trans::app::small_bank::dpu_helpers::host_worker::SmallBankHostWorker;
+use trans::app::small_bank::small_bank_table_id;
+use trans::common::cluster::{ get_cluster, set_cluster, ClusterConfig, NodeRole };
+use trans::common::consistency::{ check_with_dpu, serve_quiesce };
 use trans::common::random::FastRandom;
 use trans::rdma::control::RdmaControl;
 use trans::rdma::rcconn::RdmaRcConn;
 use trans::framework::scheduler::AsyncScheduler;
+use trans::framework::quiesce::QuiesceGate;
 use trans::memstore::memdb::ValueDB;
 use trans::SMALL_BANK_NTHREADS;
 use trans::SMALL_BANK_NROUTINES;
 use trans::doca_comm_chan::connection::DocaCommChannel;
 
-const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
-const COMM_NAMES: [&str; 8] = ["comm0\0", "comm1\0", "comm2\0", "comm3\0", "comm4\0", "comm5\0", "comm6\0", "comm7\0"];
+const CHECK_TIMEOUT_SECS: u64 = 10;
+
+async fn init_and_run(self_id: u64, tid: usize, valuedb: Arc<ValueDB>, quiesce: Arc<QuiesceGate>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<SmallBankClientReq>>>) {
+    let cluster = get_cluster().unwrap();
+    let part_id = cluster.get_node(self_id).unwrap().partitions[0];
 
-async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<SmallBankClientReq>>>) {
-    let pci_addr = if tid < 4 {
-        "af:00.0"
-    } else {
-        "af:00.1"
-    };
     // comm chan
-    let comm_chan = DocaCommChannel::new_client(COMM_NAMES[tid], pci_addr);
+    let comm_chan = DocaCommChannel::new_from_cluster(cluster, self_id, tid).unwrap();
 
     // rdma conn
-    let mut rdma = RdmaControl::new(0);
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(1);
+    let mut rdma = RdmaControl::new(self_id);
+    rdma.connect_cluster(cluster, tid).unwrap();
 
     // scheduler
     let allocator = rdma.get_allocator();
@@ -47,20 +48,31 @@ async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, clien
         Arc::get_mut_unchecked(&mut scheduler).set_comm_chan(comm_chan);
     }
 
-    // scheduler add rdma conn
-    let conn = rdma.get_connection(1);
-    conn.lock().unwrap().init_and_start_recvs().unwrap();
-    conn.lock()
-        .unwrap()
-        .register_recv_callback(&scheduler)
-        .unwrap();
-
+    // scheduler add rdma conns
+    for peer_id in rdma.get_peer_ids() {
+        let conn = rdma.get_connection(peer_id);
+        conn.lock().unwrap().init_and_start_recvs().unwrap();
+        conn.lock()
+            .unwrap()
+            .register_callbacks(&scheduler)
+            .unwrap();
+
+        unsafe {
+            Arc::get_mut_unchecked(&mut scheduler).append_conn(peer_id, &conn);
+        }
+    }
+    if let Some(srq) = rdma.get_srq() {
+        unsafe {
+            Arc::get_mut_unchecked(&mut scheduler).set_srq(&srq);
+        }
+    }
+    // the broken peers are re-established from the poll loop
     unsafe {
-        Arc::get_mut_unchecked(&mut scheduler).append_conn(1, &conn);
+        Arc::get_mut_unchecked(&mut scheduler).set_reconnect(rdma, cluster);
     }
     
     // worker
-    let worker = Arc::new(SmallBankHostWorker::new(0, tid as _, &valuedb, &scheduler));
+    let worker = Arc::new(SmallBankHostWorker::new(part_id, tid as _, &valuedb, &scheduler, &quiesce));
     // worker comm chan handler
     unsafe {
         Arc::get_mut_unchecked(&mut scheduler).register_comm_handler(&worker);
@@ -75,9 +87,19 @@ async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, clien
     worker.run(rand_seed, &client).await;
 }
 
+// the cluster config, the id of the host and the seconds of the run before the consistency
+// check with the dpu, configs/cluster.json, 0 and no check by default
 pub fn test()
 {
-    let valuedb = SmallBankDpuLoader::new_hostdb(0);
+    let mut args = std::env::args().skip(1);
+    let path = args.next().unwrap_or(String::from("configs/cluster.json"));
+    let self_id: u64 = args.next().map_or(0, |id| id.parse().unwrap());
+    let check_secs: Option<u64> = args.next().map(|secs| secs.parse().unwrap());
+    set_cluster(ClusterConfig::load(std::path::Path::new(&path)).unwrap()).unwrap();
+
+    let part_id = get_cluster().unwrap().get_node(self_id).unwrap().partitions[0];
+    let valuedb = SmallBankDpuLoader::new_hostdb(part_id);
+    let quiesce = Arc::new(QuiesceGate::new());
     let mut sb_client = SmallBankClient::new();
 
     let mut rand_gen = FastRandom::new(23984543 + 1);
@@ -89,6 +111,7 @@ pub fn test()
 
         let rand_seed = rand_gen.next();
         let valuedb_clone = valuedb.clone();
+        let quiesce_clone = quiesce.clone();
 
         std::thread::spawn(move || {
             tokio::runtime::Builder::new_current_thread()
@@ -96,13 +119,47 @@ pub fn test()
                 .build()
                 .unwrap()
                 .block_on(async move {
-                    init_and_run(i, valuedb_clone, rand_seed, receiver).await;
+                    init_and_run(self_id, i, valuedb_clone, quiesce_clone, rand_seed, receiver).await;
             });
         });
 
         sb_client.add_sender(&sender);
     }
 
+    // the run ends with the check, on the ports after the dma ones of the workers,
+    // the checks of the other hosts pause this one on the next port
+    let cluster = get_cluster().unwrap();
+    let host = cluster.get_node(self_id).unwrap();
+    let quiesce_addr = host.get_dma_addr(host.threads + 1).parse().unwrap();
+    let quiesce_clone = quiesce.clone();
+    std::thread::spawn(move || {
+        serve_quiesce(&quiesce_clone, quiesce_addr, Duration::from_secs(CHECK_TIMEOUT_SECS)).unwrap();
+    });
+
+    if let Some(check_secs) = check_secs {
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(check_secs));
+
+            let dpu = cluster.get_node(cluster.dpu_of_part(part_id).unwrap()).unwrap();
+            let peer_addrs: Vec<_> = cluster.get_nodes().iter()
+                .filter(|node| node.role == NodeRole::Host && node.id != self_id)
+                .map(|node| node.get_dma_addr(node.threads + 1).parse().unwrap())
+                .collect();
+            let report = check_with_dpu(
+                &quiesce,
+                &valuedb,
+                &[small_bank_table_id::ACCOUNTS_TABLE_ID, small_bank_table_id::SAVINGS_TABLE_ID, small_bank_table_id::CHECKING_TABLE_ID],
+                dpu.get_dma_addr(dpu.threads).parse().unwrap(),
+                host.get_dma_addr(host.threads).parse().unwrap(),
+                &peer_addrs,
+                Duration::from_secs(CHECK_TIMEOUT_SECS),
+            ).unwrap();
+
+            print!("{}", report);
+            std::process::exit(if report.is_consistent() { 0 } else { 1 });
+        });
+    }
+
     let rand_seed = rand_gen.next();
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
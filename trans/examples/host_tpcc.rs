@@ -5,6 +5,7 @@ use trans::common::logs::init_log;
 mod test_dpu {
 
 use std::sync::{ Arc, Mutex };
+use std::time::Duration;
 use tokio::sync::Mutex as AsyncMutex;
 use tokio::sync::mpsc;
 
@@ -12,10 +13,13 @@ use trans::app::tpcc::local_client::TpccClient;
 use trans::app::tpcc::TpccClientReq;
 use trans::app::tpcc::dpu_helpers::loader::TpccDpuLoader;
 use trans::app::tpcc::dpu_helpers::host_worker::TpccHostWorker;
+use trans::app::tpcc::tpcc_table_id;
+use trans::common::consistency::{ check_with_dpu, serve_quiesce };
 use trans::common::random::FastRandom;
 use trans::rdma::control::RdmaControl;
 use trans::rdma::rcconn::RdmaRcConn;
 use trans::framework::scheduler::AsyncScheduler;
+use trans::framework::quiesce::QuiesceGate;
 use trans::memstore::memdb::ValueDB;
 use trans::TPCC_NTHREADS;
 use trans::TPCC_NROUTINES;
@@ -23,8 +27,9 @@ use trans::doca_comm_chan::connection::DocaCommChannel;
 
 const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
 const COMM_NAMES: [&str; 8] = ["comm0\0", "comm1\0", "comm2\0", "comm3\0", "comm4\0", "comm5\0", "comm6\0", "comm7\0"];
+const CHECK_TIMEOUT_SECS: u64 = 10;
 
-async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<TpccClientReq>>>) {
+async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, quiesce: Arc<QuiesceGate>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<TpccClientReq>>>) {
     let pci_addr = if tid < 4 {
         "af:00.0"
     } else {
@@ -35,8 +40,8 @@ async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, clien
 
     // rdma conn
     let mut rdma = RdmaControl::new(0);
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(1);
+    rdma.init("0.0.0.0\0", CONN_PORTS[tid]).unwrap();
+    rdma.listen_task(1).unwrap();
 
     // scheduler
     let allocator = rdma.get_allocator();
@@ -52,7 +57,7 @@ async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, clien
     conn.lock().unwrap().init_and_start_recvs().unwrap();
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     unsafe {
@@ -60,7 +65,7 @@ async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, clien
     }
     
     // worker
-    let worker = Arc::new(TpccHostWorker::new(0, tid as _, &valuedb, &scheduler));
+    let worker = Arc::new(TpccHostWorker::new(0, tid as _, &valuedb, &scheduler, &quiesce));
     // worker comm chan handler
     unsafe {
         Arc::get_mut_unchecked(&mut scheduler).register_comm_handler(&worker);
@@ -75,9 +80,20 @@ async fn init_and_run(tid: usize, valuedb: Arc<ValueDB>, rand_seed: usize, clien
     worker.run(rand_seed, &client).await;
 }
 
+// the seconds of the run before the consistency check with the dpu, the addresses the
+// dpu and the host take it on, the one the host pauses on for the checks of the other
+// hosts and theirs, no check by default
 pub fn test()
 {
+    let mut args = std::env::args().skip(1);
+    let check_secs: Option<u64> = args.next().map(|secs| secs.parse().unwrap());
+    let dpu_addr = args.next();
+    let host_addr = args.next();
+    let quiesce_addr = args.next();
+    let peer_addrs: Vec<std::net::SocketAddr> = args.map(|addr| addr.parse().unwrap()).collect();
+
     let valuedb = TpccDpuLoader::new_hostdb(0);
+    let quiesce = Arc::new(QuiesceGate::new());
     let mut sb_client = TpccClient::new();
 
     let mut rand_gen = FastRandom::new(23984543 + 1);
@@ -89,6 +105,7 @@ pub fn test()
 
         let rand_seed = rand_gen.next();
         let valuedb_clone = valuedb.clone();
+        let quiesce_clone = quiesce.clone();
 
         std::thread::spawn(move || {
             tokio::runtime::Builder::new_current_thread()
@@ -96,13 +113,41 @@ pub fn test()
                 .build()
                 .unwrap()
                 .block_on(async move {
-                    init_and_run(i, valuedb_clone, rand_seed, receiver).await;
+                    init_and_run(i, valuedb_clone, quiesce_clone, rand_seed, receiver).await;
             });
         });
 
         sb_client.add_sender(&sender);
     }
 
+    if let Some(quiesce_addr) = quiesce_addr {
+        let quiesce_addr = quiesce_addr.parse().unwrap();
+        let quiesce_clone = quiesce.clone();
+        std::thread::spawn(move || {
+            serve_quiesce(&quiesce_clone, quiesce_addr, Duration::from_secs(CHECK_TIMEOUT_SECS)).unwrap();
+        });
+    }
+
+    // the run ends with the check
+    if let Some(check_secs) = check_secs {
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(check_secs));
+
+            let report = check_with_dpu(
+                &quiesce,
+                &valuedb,
+                &[tpcc_table_id::DISTRICTS_TABLE_ID, tpcc_table_id::STOCKS_TABLE_ID, tpcc_table_id::ORDERS_TABLE_ID],
+                dpu_addr.unwrap().parse().unwrap(),
+                host_addr.unwrap().parse().unwrap(),
+                &peer_addrs,
+                Duration::from_secs(CHECK_TIMEOUT_SECS),
+            ).unwrap();
+
+            print!("{}", report);
+            std::process::exit(if report.is_consistent() { 0 } else { 1 });
+        });
+    }
+
     let rand_seed = rand_gen.next();
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
@@ -1,5 +1,7 @@
 #![feature(get_mut_unchecked)]
 use std::sync::{ Arc, Mutex };
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use tokio::sync::Mutex as AsyncMutex;
 use tokio::sync::mpsc;
 use std::env;
@@ -10,6 +12,8 @@ use trans::app::tpcc::local_client::TpccClient;
 use trans::app::tpcc::TpccClientReq;
 use trans::app::tpcc::loader_longitude::TpccLongitudeLoader;
 use trans::app::tpcc::TpccHostLongitudeWorker;
+use trans::app::tpcc::utils::new_stock_offload_map;
+use trans::common::offload::OffloadMap;
 use trans::common::random::FastRandom;
 use trans::rdma::control::RdmaControl;
 use trans::rdma::rcconn::RdmaRcConn;
@@ -20,15 +24,21 @@ use trans::TPCC_NROUTINES;
 
 const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
 
-async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<TpccClientReq>>>) {
+// the share of the stock accesses the dpu serves, as the static split
+const OFFLOAD_DPU_SHARE: f64 = 0.5;
+const OFFLOAD_INTERVAL_MS: u64 = 1000;
+
+async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, map: Arc<OffloadMap>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<TpccClientReq>>>) {
     // scheduler
     let mut rdma = RdmaControl::new(0);
     rdma.connect(100, "10.10.10.26\0", CONN_PORTS[tid]).unwrap();
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(2);
+    rdma.init("0.0.0.0\0", CONN_PORTS[tid]).unwrap();
+    rdma.listen_task(2).unwrap();
 
     let allocator = rdma.get_allocator();
-    let mut scheduler = Arc::new(AsyncScheduler::new(tid, TPCC_NROUTINES as _, &allocator));
+    // the first thread runs the offload controller on one more routine
+    let routine_num = if tid == 0 { TPCC_NROUTINES + 1 } else { TPCC_NROUTINES };
+    let mut scheduler = Arc::new(AsyncScheduler::new(tid, routine_num as _, &allocator));
 
     let conn_dpu = rdma.get_connection(100);
     conn_dpu.lock().unwrap().init_and_start_recvs().unwrap();
@@ -37,7 +47,7 @@ async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client
     }
     conn_dpu.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let conn_hybrid = rdma.get_connection(1);
@@ -47,14 +57,26 @@ async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client
     }
     conn_hybrid.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
-    let worker = Arc::new(TpccHostLongitudeWorker::new(0, tid as _, &memdb, &scheduler));
+    let mut worker = TpccHostLongitudeWorker::new(0, tid as _, &memdb, &scheduler);
+    worker.set_offload_map(&map);
+    let worker = Arc::new(worker);
     unsafe {
         Arc::get_mut_unchecked(&mut scheduler).register_callback(&worker);
     }
 
+    // the hybrid node routes to the keys of the partition
+    if tid == 0 {
+        let worker_clone = worker.clone();
+        tokio::spawn(async move {
+            let running = Arc::new(AtomicBool::new(true));
+            let interval = Duration::from_millis(OFFLOAD_INTERVAL_MS);
+            worker_clone.run_offload(TPCC_NROUTINES as _, &[1], OFFLOAD_DPU_SHARE, interval, &running).await;
+        });
+    }
+
     worker.run(rand_seed, &client).await;
 }
 
@@ -64,6 +86,7 @@ fn main()
     init_log(log_path.as_path());
     
     let memdb = TpccLongitudeLoader::new_hostdb(0);
+    let map = Arc::new(new_stock_offload_map());
     let mut sb_client = TpccClient::new();
 
     let mut rand_gen = FastRandom::new(23984543 + 0);
@@ -75,6 +98,7 @@ fn main()
 
         let rand_seed = rand_gen.next();
         let memdb_clone = memdb.clone();
+        let map_clone = map.clone();
 
         std::thread::spawn(move || {
             tokio::runtime::Builder::new_current_thread()
@@ -82,7 +106,7 @@ fn main()
                 .build()
                 .unwrap()
                 .block_on(async move {
-                    connect_and_run(i, memdb_clone, rand_seed, receiver).await;
+                    connect_and_run(i, memdb_clone, map_clone, rand_seed, receiver).await;
             });
         });
 
@@ -182,7 +182,7 @@ async fn test() {
     }
     conn_host.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let conn_dpu = rdma.get_connection(100);
@@ -192,7 +192,7 @@ async fn test() {
     }
     conn_dpu.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let worker = Arc::new(OccCtrlWorker::new(&memdb, &scheduler));
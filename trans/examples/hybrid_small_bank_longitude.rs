@@ -11,6 +11,8 @@ use trans::app::small_bank::local_client::SmallBankClient;
 use trans::app::small_bank::SmallBankClientReq;
 use trans::app::small_bank::loader::SmallBankLoader;
 use trans::app::small_bank::SmallBankHybridLongitudeWorker;
+use trans::app::small_bank::utils::new_offload_map;
+use trans::common::offload::OffloadMap;
 use trans::common::random::FastRandom;
 use trans::rdma::control::RdmaControl;
 use trans::rdma::rcconn::RdmaRcConn;
@@ -21,7 +23,7 @@ use trans::SMALL_BANK_NROUTINES;
 
 const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
 
-async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<SmallBankClientReq>>>) {
+async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, map: Arc<OffloadMap>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<SmallBankClientReq>>>) {
     // scheduler
     let mut rdma = RdmaControl::new(1);
     rdma.connect(100, "10.10.10.26\0", CONN_PORTS[tid]).unwrap();
@@ -37,7 +39,7 @@ async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client
     }
     conn_host.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let conn_dpu = rdma.get_connection(100);
@@ -47,10 +49,13 @@ async fn connect_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client
     }
     conn_dpu.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
-    let worker = Arc::new(SmallBankHybridLongitudeWorker::new(1, tid as _, &memdb, &scheduler));
+    // the host of partition 0 publishes the moves of its ranges
+    let mut worker = SmallBankHybridLongitudeWorker::new(1, tid as _, &memdb, &scheduler);
+    worker.set_offload_map(&map);
+    let worker = Arc::new(worker);
     unsafe {
         Arc::get_mut_unchecked(&mut scheduler).register_callback(&worker);
     }
@@ -63,6 +68,7 @@ fn main()
     let log_path = env::current_dir().unwrap().join("test.log");
     init_log(log_path.as_path());
     let memdb = SmallBankLoader::new_memdb(1);
+    let map = Arc::new(new_offload_map());
     let mut sb_client = SmallBankClient::new();
 
     let mut rand_gen = FastRandom::new(23984543 + 0);
@@ -74,6 +80,7 @@ fn main()
 
         let rand_seed = rand_gen.next();
         let memdb_clone = memdb.clone();
+        let map_clone = map.clone();
 
         std::thread::spawn(move || {
             tokio::runtime::Builder::new_current_thread()
@@ -81,7 +88,7 @@ fn main()
                 .build()
                 .unwrap()
                 .block_on(async move {
-                    connect_and_run(i, memdb_clone, rand_seed, receiver).await;
+                    connect_and_run(i, memdb_clone, map_clone, rand_seed, receiver).await;
             });
         });
 
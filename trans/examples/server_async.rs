@@ -105,8 +105,8 @@ impl AsyncWorker for AnswerClientWorker {
 #[tokio::main]
 async fn main() {
     let mut rdma = RdmaControl::new(1);
-    rdma.init("0.0.0.0\0", "7472\0");
-    rdma.listen_task(1);
+    rdma.init("0.0.0.0\0", "7472\0").unwrap();
+    rdma.listen_task(1).unwrap();
 
     let allocator = rdma.get_allocator();
     let mut scheduler = Arc::new(AsyncScheduler::new(0, 1, &allocator));
@@ -119,7 +119,7 @@ async fn main() {
     }
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let worker = Arc::new(AnswerClientWorker::new(&scheduler));
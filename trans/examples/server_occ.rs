@@ -103,8 +103,8 @@ async fn main() {
     Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
     // scheduler
     let mut rdma = RdmaControl::new(1);
-    rdma.init("0.0.0.0\0", "7472\0");
-    rdma.listen_task(1);
+    rdma.init("0.0.0.0\0", "7472\0").unwrap();
+    rdma.listen_task(1).unwrap();
 
     let allocator = rdma.get_allocator();
     let mut scheduler = Arc::new(AsyncScheduler::new(0, 1, &allocator));
@@ -117,7 +117,7 @@ async fn main() {
     }
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let worker = Arc::new(OccProcWorker::new(&memdb, &scheduler));
@@ -11,7 +11,7 @@ use trans::app::small_bank::SmallBankClientReq;
 use trans::app::small_bank::loader::SmallBankLoader;
 use trans::app::small_bank::SmallBankWorker;
 use trans::common::random::FastRandom;
-use trans::rdma::control::RdmaControl;
+use trans::rdma::control::{RdmaBaseAllocator, RdmaControl};
 use trans::rdma::rcconn::RdmaRcConn;
 use trans::framework::scheduler::AsyncScheduler;
 use trans::memstore::memdb::MemDB;
@@ -20,11 +20,11 @@ use trans::SMALL_BANK_NROUTINES;
 
 const CONN_PORTS: [&str; 8] = ["7472\0", "7473\0", "7474\0", "7475\0", "7476\0", "7477\0", "7478\0", "7479\0"];
 
-async fn listen_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<SmallBankClientReq>>>) {
+async fn listen_and_run(tid: usize, region: Arc<RdmaBaseAllocator>, memdb: Arc<MemDB>, rand_seed: usize, client: Arc<AsyncMutex<mpsc::Receiver<SmallBankClientReq>>>) {
     // scheduler
-    let mut rdma = RdmaControl::new(1);
-    rdma.init("0.0.0.0\0", CONN_PORTS[tid]);
-    rdma.listen_task(1);
+    let mut rdma = RdmaControl::new_on(1, &region);
+    rdma.init("0.0.0.0\0", CONN_PORTS[tid]).unwrap();
+    rdma.listen_task(1).unwrap();
 
     let allocator = rdma.get_allocator();
     let mut scheduler = Arc::new(AsyncScheduler::new(tid, SMALL_BANK_NROUTINES as _, &allocator));
@@ -38,7 +38,7 @@ async fn listen_and_run(tid: usize, memdb: Arc<MemDB>, rand_seed: usize, client:
 
     conn.lock()
         .unwrap()
-        .register_recv_callback(&scheduler)
+        .register_callbacks(&scheduler)
         .unwrap();
 
     let worker = Arc::new(SmallBankWorker::new(1, tid as _, &memdb, &scheduler));
@@ -55,7 +55,9 @@ fn main()
     let log_path = env::current_dir().unwrap().join("test.log");
     init_log(log_path.as_path());
     
-    let memdb = SmallBankLoader::new_memdb(1);
+    // one region for the accounts and the connections of all threads
+    let region = SmallBankLoader::new_region();
+    let memdb = SmallBankLoader::new_memdb_on(1, &region);
     let mut sb_client = SmallBankClient::new();
 
     let mut rand_gen = FastRandom::new(23984543 + 1);
@@ -66,6 +68,7 @@ fn main()
         let receiver = Arc::new(AsyncMutex::new(rx));
 
         let rand_seed = rand_gen.next();
+        let region_clone = region.clone();
         let memdb_clone = memdb.clone();
 
         std::thread::spawn(move || {
@@ -74,7 +77,7 @@ fn main()
                 .build()
                 .unwrap()
                 .block_on(async move {
-                    listen_and_run(i, memdb_clone, rand_seed, receiver).await;
+                    listen_and_run(i, region_clone, memdb_clone, rand_seed, receiver).await;
             });
         });
 
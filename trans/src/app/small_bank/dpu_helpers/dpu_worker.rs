@@ -12,6 +12,7 @@ use crate::framework::rpc::*;
 use crate::doca_comm_chan::connection::DocaCommHandler;
 use crate::doca_comm_chan::comm_buf::DocaCommBuf;
 use crate::rdma::rcconn::RdmaRcConn;
+use crate::BUF_STATS_POLLS;
 use crate::SMALL_BANK_NROUTINES;
 
 pub struct SmallBankDpuWorker {
@@ -54,6 +55,9 @@ impl DocaCommHandler for SmallBankDpuWorker {
             doca_comm_info_id::LOCAL_ABORT_INFO => {
                 self.proc.local_abort_info_handler(buf, info_payload, info_pid, info_tid, info_cid);
             }
+            doca_comm_info_id::REMOTE_PROC_DONE_INFO => {
+                self.proc.remote_proc_done_info_handler(buf, info_payload, info_pid, info_tid, info_cid);
+            }
             _ => { panic!("unsupported!"); }
         }
     }
@@ -87,20 +91,33 @@ impl RpcHandler for SmallBankDpuWorker {
             occ_rpc_id::ABORT_RPC => {
                 self.proc.abort_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::PROC_RPC => {
+                self.proc.proc_rpc_handler(src_conn, msg, size, meta);
+            }
             _ => {
                 unimplemented!();
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(peer_id);
+    }
 }
 
 impl SmallBankDpuWorker {
     async fn main_routine(&self, tid: u32) {
+        let mut round = 0;
         loop {
             self.scheduler.poll_recvs();
             self.scheduler.poll_sends();
             self.scheduler.poll_comm_chan();
 
+            round += 1;
+            if round % BUF_STATS_POLLS == 0 {
+                self.proc.report_buf_stats();
+            }
+
             // sleep(Duration::from_millis(1));
 
             // self.scheduler.yield_now(0).await;
@@ -3,11 +3,13 @@ use tokio::sync::mpsc;
 use tokio::sync::Mutex as AsyncMutex;
 
 use crate::common::random::FastRandom;
+use crate::common::partition::{ Partitioner, PlacementView };
 use crate::memstore::memdb::ValueDB;
 use crate::occ::occ_rpc_id;
 use crate::occ::HostRpcProc;
 use crate::occ::doca_comm_info_id;
 use crate::framework::scheduler::AsyncScheduler;
+use crate::framework::quiesce::QuiesceGate;
 use crate::framework::rpc::*;
 use crate::doca_comm_chan::connection::DocaCommHandler;
 use crate::doca_comm_chan::comm_buf::DocaCommBuf;
@@ -22,16 +24,21 @@ pub struct SmallBankHostWorker {
     pub valuedb: Arc<ValueDB>,
     pub scheduler: Arc<AsyncScheduler>,
     pub proc: HostRpcProc,
+    // shared by the workers of the host, see the consistency checker
+    pub quiesce: Arc<QuiesceGate>,
+    pub placement: Arc<dyn Partitioner>,
 }
 
 impl SmallBankHostWorker {
-    pub fn new(part_id: u64, tid: u32, valuedb: &Arc<ValueDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+    pub fn new(part_id: u64, tid: u32, valuedb: &Arc<ValueDB>, scheduler: &Arc<AsyncScheduler>, quiesce: &Arc<QuiesceGate>) -> Self {
         Self {
             part_id: part_id,
             tid: tid, 
             scheduler: scheduler.clone(),
             valuedb: valuedb.clone(),
             proc: HostRpcProc::new(tid, valuedb, scheduler),
+            quiesce: quiesce.clone(),
+            placement: utils::account_partitioner(part_id, PlacementView::Plain),
         }
     }
 }
@@ -53,6 +60,9 @@ impl DocaCommHandler for SmallBankHostWorker {
             doca_comm_info_id::REMOTE_FETCHWRITE_INFO => {
                 self.proc.remote_fetch_write_info_handler(buf, info_payload, info_pid, info_tid, info_cid);
             }
+            doca_comm_info_id::REMOTE_PROC_INFO => {
+                self.proc.remote_proc_info_handler(buf, info_payload, info_pid, info_tid, info_cid);
+            }
             _ => { panic!("unsupported!"); }
         }
     }
@@ -71,6 +81,9 @@ impl RpcHandler for SmallBankHostWorker {
             occ_rpc_id::COMMIT_RPC => {
                 self.proc.commit_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::DELTA_RPC => {
+                self.proc.delta_rpc_handler(src_conn, msg, size, meta);
+            }
             _ => {
                 unimplemented!();
             }
@@ -87,6 +100,7 @@ impl SmallBankHostWorker {
             let req = receiver.recv().await.unwrap();
             drop(receiver);
 
+            self.quiesce.enter().await;
             match req.workload {
                 SmallBankWordLoadId::TxnSendPayment => {
                     self.txn_send_payment(&mut rand_gen, cid).await;
@@ -113,6 +127,7 @@ impl SmallBankHostWorker {
                     self.txn_exchange_check(&mut rand_gen, cid).await;
                 }
             }
+            self.quiesce.leave();
         }
     }
 
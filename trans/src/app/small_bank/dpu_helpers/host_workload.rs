@@ -7,7 +7,7 @@ use crate::occ::occ_host::OccHost;
 
 use super::host_worker::SmallBankHostWorker;
 use super::super::*;
-use super::super::utils::{ random_get_accounts, account_to_part };
+use super::super::utils::random_get_accounts;
 
 impl SmallBankHostWorker {
     // update checking * 2
@@ -28,13 +28,13 @@ impl SmallBankHostWorker {
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _,
         );
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[1]) as _,
+            self.placement.part_of(accounts[1]) as _,
             accounts[1] as _,
         );
 
@@ -74,7 +74,7 @@ impl SmallBankHostWorker {
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _,
         );
 
@@ -107,12 +107,12 @@ impl SmallBankHostWorker {
 
         txn.read::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _
         );
         txn.read::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _
         );
 
@@ -144,7 +144,7 @@ impl SmallBankHostWorker {
 
         txn.fetch_write::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _,
         );
 
@@ -177,12 +177,12 @@ impl SmallBankHostWorker {
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _
         );
         txn.read::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _
         );
 
@@ -223,19 +223,19 @@ impl SmallBankHostWorker {
 
         txn.fetch_write::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _,
         );
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _,
         );
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[1]) as _,
+            self.placement.part_of(accounts[1]) as _,
             accounts[1] as _,
         );
 
@@ -273,13 +273,13 @@ impl SmallBankHostWorker {
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(lid) as _,
+            self.placement.part_of(lid) as _,
             lid as _,
         );
 
         txn.fetch_write::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(rid) as _,
+            self.placement.part_of(rid) as _,
             rid as _,
         );
 
@@ -309,7 +309,7 @@ impl SmallBankHostWorker {
 
         for i in 0..4 {
             txn.read::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
-                account_to_part(i) as _,
+                self.placement.part_of(i) as _,
                 i as _,
             );
         }
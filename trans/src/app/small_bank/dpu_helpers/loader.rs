@@ -3,12 +3,15 @@ use std::sync::Arc;
 
 use crate::memstore::memdb::TableSchema;
 use crate::memstore::{ RobinhoodMemStore, RobinhoodValueStore };
+use crate::memstore::delta_add_f64;
 use crate::SMALL_BANK_MIN_BALANCE;
 use crate::SMALL_BANK_MAX_BALANCE;
 use crate::common::random::FastRandom;
 use crate::memstore::memdb::{ MemDB, ValueDB };
 
-use super::super::utils::{ accounts_num, account_to_part };
+use crate::common::partition::PlacementView;
+use super::super::utils::{ accounts_num, account_partitioner };
+use super::super::procs::{ keys_transact_savings, value_transact_savings };
 use super::super::*;
 
 #[inline]
@@ -29,6 +32,8 @@ impl SmallBankDpuLoader {
         Arc::get_mut(&mut valuedb).unwrap().add_schema(0, TableSchema::default(), valuestore0);
         Arc::get_mut(&mut valuedb).unwrap().add_schema(1, TableSchema::default(), valuestore1);
         Arc::get_mut(&mut valuedb).unwrap().add_schema(2, TableSchema::default(), valuestore2);
+        Arc::get_mut(&mut valuedb).unwrap().add_merge(small_bank_merge_id::CHECKING_DEPOSIT, delta_add_f64::<0>);
+        Arc::get_mut(&mut valuedb).unwrap().add_procedure(small_bank_proc::TRANSACT_SAVINGS, value_transact_savings);
 
         Self::hostdb_do_load((23984543 + part_id * 73) as usize, part_id, &valuedb);
 
@@ -37,9 +42,10 @@ impl SmallBankDpuLoader {
 
     pub fn hostdb_do_load(rand_seed: usize, part_id: u64, valuedb: &Arc<ValueDB>) {
         let mut rand_gen = FastRandom::new(rand_seed);
+        let placement = account_partitioner(part_id, PlacementView::Plain);
 
         for account in 0..accounts_num() {
-            if account_to_part(account) != part_id as usize {
+            if placement.part_of(account) != part_id as usize {
                 continue;
             }
 
@@ -79,6 +85,8 @@ impl SmallBankDpuLoader {
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
         Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
         Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+        // the dpu locks the rows of the procedures, its host runs them
+        Arc::get_mut(&mut memdb).unwrap().add_proc_keys(small_bank_proc::TRANSACT_SAVINGS, keys_transact_savings);
 
         Self::dpudb_do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
@@ -87,9 +95,10 @@ impl SmallBankDpuLoader {
 
     pub fn dpudb_do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
         let mut rand_gen = FastRandom::new(rand_seed);
+        let placement = account_partitioner(part_id, PlacementView::Plain);
 
         for account in 0..accounts_num() {
-            if account_to_part(account) != part_id as usize {
+            if placement.part_of(account) != part_id as usize {
                 continue;
             }
 
@@ -2,16 +2,25 @@ use std::sync::Arc;
 
 use crate::memstore::memdb::TableSchema;
 use crate::memstore::RobinhoodMemStore;
+use crate::memstore::MvccMemStore;
+use crate::memstore::delta_add_f64;
+use crate::SMALL_BANK_SNAPSHOT_BALANCE;
 use crate::SMALL_BANK_MIN_BALANCE;
 use crate::SMALL_BANK_MAX_BALANCE;
+use crate::{NPAGES, SMALL_BANK_NTHREADS, SMALL_BANK_ONE_SIDE_READ};
 use crate::common::random::FastRandom;
+use crate::common::partition::PlacementView;
 use crate::memstore::memdb::MemDB;
+use crate::rdma::control::RdmaBaseAllocator;
 
-use super::utils::{ accounts_num, account_to_part };
+use super::utils::{ accounts_num, account_partitioner };
 use super::SmallBankAccounts;
 use super::SmallBankSavings;
 use super::SmallBankChecking;
 use super::small_bank_table_id;
+use super::small_bank_merge_id;
+use super::small_bank_proc;
+use super::procs::proc_transact_savings;
 
 #[inline]
 pub fn random_account_number(rand_gen: &mut FastRandom) -> f64 {
@@ -21,15 +30,65 @@ pub fn random_account_number(rand_gen: &mut FastRandom) -> f64 {
 pub struct SmallBankLoader {}
 
 impl SmallBankLoader {
+    // the region of the node, registered by the connections of all its threads
+    pub fn new_region() -> Arc<RdmaBaseAllocator> {
+        let table_pages = (RobinhoodMemStore::<SmallBankSavings>::region_len() + 4095) / 4096
+            + (RobinhoodMemStore::<SmallBankChecking>::region_len() + 4095) / 4096;
+        Arc::new(RdmaBaseAllocator::with_pages(NPAGES as usize * SMALL_BANK_NTHREADS + table_pages))
+    }
+
     pub fn new_memdb(part_id: u64) -> Arc<MemDB> {
+        Self::new_memdb_with(part_id, None)
+    }
+
+    // savings and checking are placed in region, so that the peers read them by RDMA READ
+    pub fn new_memdb_on(part_id: u64, region: &Arc<RdmaBaseAllocator>) -> Arc<MemDB> {
+        Self::new_memdb_with(part_id, Some(region))
+    }
+
+    fn new_memdb_with(part_id: u64, region: Option<&Arc<RdmaBaseAllocator>>) -> Arc<MemDB> {
         let mut memdb = Arc::new(MemDB::new());
         let memstore0 = RobinhoodMemStore::<SmallBankAccounts>::new();
-        let memstore1 = RobinhoodMemStore::<SmallBankSavings>::new();
-        let memstore2 = RobinhoodMemStore::<SmallBankChecking>::new();
 
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
-        Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
-        Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+
+        if SMALL_BANK_SNAPSHOT_BALANCE {
+            // balance reads savings and checking at a snapshot
+            let clock = memdb.get_clock();
+            let memstore1 = MvccMemStore::<SmallBankSavings>::new(&clock);
+            let memstore2 = MvccMemStore::<SmallBankChecking>::new(&clock);
+
+            Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
+            Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+        } else {
+            let memstores = region
+                .filter(|_| SMALL_BANK_ONE_SIDE_READ)
+                .and_then(|region| Some((
+                    region,
+                    RobinhoodMemStore::<SmallBankSavings>::new_in(region)?,
+                    RobinhoodMemStore::<SmallBankChecking>::new_in(region)?,
+                )));
+
+            match memstores {
+                Some((region, memstore1, memstore2)) => {
+                    // the rkey is per connection, the peers only take offsets from it
+                    Arc::get_mut(&mut memdb).unwrap().set_region(region.get_lm(), region.get_len(), 0);
+                    Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default().with_one_side_read(), memstore1);
+                    Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default().with_one_side_read(), memstore2);
+                }
+                None => {
+                    let memstore1 = RobinhoodMemStore::<SmallBankSavings>::new();
+                    let memstore2 = RobinhoodMemStore::<SmallBankChecking>::new();
+
+                    Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
+                    Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+                }
+            }
+        }
+
+        // c_balance is the first field of checking
+        Arc::get_mut(&mut memdb).unwrap().add_merge(small_bank_merge_id::CHECKING_DEPOSIT, delta_add_f64::<0>);
+        Arc::get_mut(&mut memdb).unwrap().add_procedure(small_bank_proc::TRANSACT_SAVINGS, proc_transact_savings);
 
         Self::do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
@@ -38,9 +97,10 @@ impl SmallBankLoader {
 
     pub fn do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
         let mut rand_gen = FastRandom::new(rand_seed);
+        let placement = account_partitioner(part_id, PlacementView::Plain);
 
         for account in 0..accounts_num() {
-            if account_to_part(account) != part_id as usize {
+            if placement.part_of(account) != part_id as usize {
                 continue;
             }
 
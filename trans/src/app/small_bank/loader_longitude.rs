@@ -2,17 +2,20 @@ use std::sync::Arc;
 
 use crate::memstore::memdb::TableSchema;
 use crate::memstore::RobinhoodMemStore;
+use crate::memstore::delta_add_f64;
 use crate::SMALL_BANK_MIN_BALANCE;
 use crate::SMALL_BANK_MAX_BALANCE;
 use crate::common::random::FastRandom;
+use crate::common::partition::PlacementView;
+use crate::common::cluster::dpu_peer_id;
 use crate::memstore::memdb::MemDB;
 
-use super::utils::accout_to_part_host_longitude;
-use super::utils::{ accounts_num, account_to_part };
+use super::utils::{ accounts_num, account_partitioner };
 use super::SmallBankAccounts;
 use super::SmallBankSavings;
 use super::SmallBankChecking;
 use super::small_bank_table_id;
+use super::small_bank_merge_id;
 
 #[inline]
 pub fn random_account_number(rand_gen: &mut FastRandom) -> f64 {
@@ -31,6 +34,7 @@ impl SmallBankLongitudeLoader {
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
         Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
         Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+        Arc::get_mut(&mut memdb).unwrap().add_merge(small_bank_merge_id::CHECKING_DEPOSIT, delta_add_f64::<0>);
 
         Self::hostdb_do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
@@ -39,9 +43,10 @@ impl SmallBankLongitudeLoader {
 
     pub fn hostdb_do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
         let mut rand_gen = FastRandom::new(rand_seed);
+        let placement = account_partitioner(part_id, PlacementView::Host);
 
         for account in 0..accounts_num() {
-            if accout_to_part_host_longitude(account, part_id as usize) != part_id as usize {
+            if placement.part_of(account) != part_id as usize {
                 continue;
             }
 
@@ -99,6 +104,7 @@ impl SmallBankLongitudeLoader {
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
         Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
         Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+        Arc::get_mut(&mut memdb).unwrap().add_merge(small_bank_merge_id::CHECKING_DEPOSIT, delta_add_f64::<0>);
 
         Self::dpudb_do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
@@ -107,9 +113,11 @@ impl SmallBankLongitudeLoader {
 
     pub fn dpudb_do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
         let mut rand_gen = FastRandom::new(rand_seed);
+        let placement = account_partitioner(part_id, PlacementView::Host);
+        let dpu_id = dpu_peer_id(part_id).expect("the cluster config gives the partition no dpu");
 
         for account in 0..accounts_num() {
-            if accout_to_part_host_longitude(account, part_id as usize) != (part_id + 100) as usize {
+            if placement.part_of(account) != dpu_id as usize {
                 continue;
             }
 
@@ -1,4 +1,5 @@
-mod utils;
+pub mod utils;
+mod procs;
 pub mod workload;
 pub mod worker;
 pub mod local_client;
@@ -7,7 +8,7 @@ pub mod workload_longitude;
 pub mod worker_longitude;
 pub mod loader_longitude;
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub mod dpu_helpers;
 
 use std::sync::Arc;
@@ -15,6 +16,11 @@ use std::sync::Arc;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::memstore::memdb::MemDB;
 use crate::occ::BatchRpcProc;
+use crate::occ::CcProtocol;
+use crate::occ::{OneSideReqCtrl, RemoteAddrCache};
+use crate::{SMALL_BANK_CC_PROTOCOL, SMALL_BANK_NROUTINES};
+use crate::common::partition::{ Partitioner, PlacementView };
+use crate::common::offload::OffloadMap;
 
 pub mod small_bank_table_id {
     pub const ACCOUNTS_TABLE_ID: usize = 0;
@@ -22,6 +28,14 @@ pub mod small_bank_table_id {
     pub const CHECKING_TABLE_ID: usize = 2;
 }
 
+pub mod small_bank_merge_id {
+    pub const CHECKING_DEPOSIT: u32 = 1;
+}
+
+pub mod small_bank_proc {
+    pub const TRANSACT_SAVINGS: &str = "transact_savings";
+}
+
 const SMALL_BANK_MAX_ITEM_SIZE: usize = 64;
 
 #[derive(Clone)]
@@ -50,6 +64,13 @@ pub struct SmallBankChecking {
     c_balance: f64,
 }
 
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct TransactSavingsArgs {
+    account: u64,
+    amount:  f64,
+}
+
 pub enum SmallBankWordLoadId {
     TxnSendPayment,
     TxnDepositChecking,
@@ -87,18 +108,35 @@ pub struct SmallBankWorker {
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
     proc: BatchRpcProc,
+    placement: Arc<dyn Partitioner>,
+    cc: CcProtocol,
+    one_side: Arc<OneSideReqCtrl>,
 }
 
 impl SmallBankWorker {
     pub fn new(part_id: u64, tid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        // the node offsets resolved by the routines of the thread
+        let addrs = Arc::new(RemoteAddrCache::new(scheduler, SMALL_BANK_NROUTINES as _));
         Self {
             part_id: part_id,
             tid: tid, 
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: utils::account_partitioner(part_id, PlacementView::Plain),
+            cc: CcProtocol::configured(SMALL_BANK_CC_PROTOCOL),
+            one_side: Arc::new(OneSideReqCtrl::new(scheduler, &addrs, SMALL_BANK_NROUTINES as _, SMALL_BANK_MAX_ITEM_SIZE)),
         }
     }
+
+    // e.g. with the split of an offload map
+    pub fn set_placement(&mut self, placement: &Arc<dyn Partitioner>) {
+        self.placement = placement.clone();
+    }
+
+    pub fn set_cc_protocol(&mut self, cc: CcProtocol) {
+        self.cc = cc;
+    }
 }
 
 pub struct SmallBankHybridLongitudeWorker {
@@ -107,6 +145,8 @@ pub struct SmallBankHybridLongitudeWorker {
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
     proc: BatchRpcProc,
+    placement: Arc<dyn Partitioner>,
+    offload: Option<Arc<OffloadMap>>,
 }
 
 impl SmallBankHybridLongitudeWorker {
@@ -117,8 +157,20 @@ impl SmallBankHybridLongitudeWorker {
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: utils::account_partitioner(part_id, PlacementView::Hybrid),
+            offload: None,
         }
     }
+
+    pub fn set_placement(&mut self, placement: &Arc<dyn Partitioner>) {
+        self.placement = placement.clone();
+    }
+
+    // the map the controllers of the other hosts publish to
+    pub fn set_offload_map(&mut self, map: &Arc<OffloadMap>) {
+        self.placement = utils::account_partitioner_offload(self.part_id, PlacementView::Hybrid, map);
+        self.offload = Some(map.clone());
+    }
 }
 
 pub struct SmallBankHostLongitudeWorker {
@@ -127,6 +179,8 @@ pub struct SmallBankHostLongitudeWorker {
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
     proc: BatchRpcProc,
+    placement: Arc<dyn Partitioner>,
+    offload: Option<Arc<OffloadMap>>,
 }
 
 impl SmallBankHostLongitudeWorker {
@@ -137,6 +191,18 @@ impl SmallBankHostLongitudeWorker {
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: utils::account_partitioner(part_id, PlacementView::Host),
+            offload: None,
         }
     }
+
+    pub fn set_placement(&mut self, placement: &Arc<dyn Partitioner>) {
+        self.placement = placement.clone();
+    }
+
+    // the split of the dpu follows the map, the offload controller of the host moves it
+    pub fn set_offload_map(&mut self, map: &Arc<OffloadMap>) {
+        self.placement = utils::account_partitioner_offload(self.part_id, PlacementView::Host, map);
+        self.offload = Some(map.clone());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use crate::memstore::memdb::{ MemDB, ValueDB };
+use crate::occ::occ_local::OccLocal;
+use crate::PROC_ROUTINE_ID;
+
+use super::small_bank_table_id;
+use super::SMALL_BANK_MAX_ITEM_SIZE;
+use super::SmallBankSavings;
+use super::TransactSavingsArgs;
+
+// stored procedures, executed on the owner of the accounts
+
+// update saving
+pub fn proc_transact_savings(memdb: &Arc<MemDB>, tid: u32, args: &[u8], out: &mut [u8]) -> Option<usize> {
+    let len = std::mem::size_of::<SmallBankSavings>();
+    if args.len() < std::mem::size_of::<TransactSavingsArgs>() || out.len() < len {
+        return None;
+    }
+
+    let args = unsafe { std::ptr::read_unaligned(args.as_ptr() as *const TransactSavingsArgs) };
+
+    let mut txn = OccLocal::<SMALL_BANK_MAX_ITEM_SIZE>::new_on(tid, PROC_ROUTINE_ID, memdb);
+
+    txn.start();
+
+    txn.fetch_write::<SmallBankSavings>(
+        small_bank_table_id::SAVINGS_TABLE_ID,
+        0,
+        args.account,
+    );
+
+    let sv = txn.get_value::<SmallBankSavings>(true, 0).s_balance;
+    let s_new = SmallBankSavings{ s_balance: sv + args.amount };
+
+    txn.set_value(true, 0, &s_new);
+
+    txn.commit();
+
+    if !txn.is_commited() {
+        return None;
+    }
+
+    // return the new balance
+    unsafe { std::ptr::write_unaligned(out.as_mut_ptr() as *mut SmallBankSavings, s_new) };
+
+    Some(len)
+}
+
+// as proc_transact_savings, on the host of a value-less dpu
+pub fn keys_transact_savings(args: &[u8], keys: &mut Vec<(usize, u64)>) -> bool {
+    if args.len() < std::mem::size_of::<TransactSavingsArgs>() {
+        return false;
+    }
+
+    let args = unsafe { std::ptr::read_unaligned(args.as_ptr() as *const TransactSavingsArgs) };
+    keys.push((small_bank_table_id::SAVINGS_TABLE_ID, args.account));
+    true
+}
+
+pub fn value_transact_savings(valuedb: &ValueDB, args: &[u8], out: &mut [u8]) -> Option<usize> {
+    let len = std::mem::size_of::<SmallBankSavings>();
+    if args.len() < std::mem::size_of::<TransactSavingsArgs>() || out.len() < len {
+        return None;
+    }
+
+    let args = unsafe { std::ptr::read_unaligned(args.as_ptr() as *const TransactSavingsArgs) };
+
+    let mut sv = SmallBankSavings::default();
+    if !valuedb.local_get_value(small_bank_table_id::SAVINGS_TABLE_ID, args.account, &mut sv as *mut _ as _, len as _) {
+        return None;
+    }
+
+    let s_new = SmallBankSavings{ s_balance: sv.s_balance + args.amount };
+    valuedb.local_set_value(small_bank_table_id::SAVINGS_TABLE_ID, args.account, &s_new as *const _ as _, len as _);
+
+    // return the new balance
+    unsafe { std::ptr::write_unaligned(out.as_mut_ptr() as *mut SmallBankSavings, s_new) };
+
+    Some(len)
+}
@@ -2,9 +2,12 @@ use std::collections::HashSet;
 use std::hash::Hash;
 use std::hash::RandomState;
 use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
 use lazy_static::lazy_static;
 
 use crate::common::random::FastRandom;
+use crate::common::offload::OffloadMap;
+use crate::common::partition::{ get_placement, Partitioner, PartitionerConfig, PlacementView };
 use crate::SMALL_BANK_NROUTINES;
 use crate::SMALL_BANK_NTHREADS;
 use crate::SMALL_BANK_NPARTITIONS;
@@ -13,6 +16,7 @@ use crate::SMALL_BANK_DEFAULT_NHOTACCOUTS;
 use crate::SMALL_BANK_PART_OFFLOAD_RATIO;
 use crate::SMALL_BANK_SCALE;
 use crate::SMALL_BANK_TX_HOT;
+use crate::OFFLOAD_RANGE_SIZE;
 
 const fn scale_factor() -> usize {
     return SMALL_BANK_SCALE;
@@ -54,33 +58,34 @@ lazy_static! {
     static ref HASH_BUILDER: RandomState = RandomState::new();
 }
 
-pub fn account_to_part(account: usize) -> usize {
-    // let mut hasher = HASH_BUILDER.build_hasher();
-    // account.hash(&mut hasher);
+const ACCOUNTS_PLACEMENT: &str = "small_bank.accounts";
 
-    account % SMALL_BANK_NPARTITIONS
+// the accounts modulo the partitions, some of the ones of a partition on its dpu
+pub fn default_account_placement() -> PartitionerConfig {
+    PartitionerConfig::Split {
+        base: Box::new(PartitionerConfig::Hash { parts: SMALL_BANK_NPARTITIONS, mixed: false }),
+        modulo: 100,
+        ratio: SMALL_BANK_PART_OFFLOAD_RATIO,
+    }
 }
 
-pub fn accout_to_part_hybrid_longitude(account: usize, part_id: usize) -> usize {
-    let p_id = account % SMALL_BANK_NPARTITIONS;
+// as the cluster config places the accounts, the default without one
+pub fn account_partitioner(part_id: u64, view: PlacementView) -> Arc<dyn Partitioner> {
+    get_placement(ACCOUNTS_PLACEMENT, default_account_placement()).build(part_id as _, view)
+}
 
-    if p_id == part_id {
-        return p_id;
-    } else if account % 100 >= SMALL_BANK_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
-    }
+// the split of the dpu follows the offload map
+pub fn account_partitioner_offload(part_id: u64, view: PlacementView, map: &Arc<OffloadMap>) -> Arc<dyn Partitioner> {
+    get_placement(ACCOUNTS_PLACEMENT, default_account_placement()).build_with_map(part_id as _, view, Some(map))
 }
 
-pub fn accout_to_part_host_longitude(account: usize, part_id: usize) -> usize {
-    let p_id = account % SMALL_BANK_NPARTITIONS;
+// the static split of the default placement
+#[inline]
+pub fn account_on_dpu(account: usize) -> bool {
+    account % 100 < SMALL_BANK_PART_OFFLOAD_RATIO
+}
 
-    if p_id != part_id {
-        return p_id;
-    } else if account % 100 >= SMALL_BANK_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
-    }
-}
\ No newline at end of file
+// starts as the static split, then follows the accesses
+pub fn new_offload_map() -> OffloadMap {
+    OffloadMap::new(accounts_num(), OFFLOAD_RANGE_SIZE, account_on_dpu)
+}
@@ -6,11 +6,15 @@ use crate::rdma::control::RdmaControl;
 use crate::rdma::rcconn::RdmaRcConn;
 use crate::memstore::memdb::MemDB;
 use crate::SMALL_BANK_NROUTINES;
+use crate::SMALL_BANK_SNAPSHOT_BALANCE;
+use crate::SMALL_BANK_STORED_PROC;
+use crate::MVCC_GC_POLLS;
 use crate::common::random::FastRandom;
 use crate::framework::worker::AsyncWorker;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::framework::rpc::*;
 use crate::occ::occ_rpc_id;
+use crate::occ::CcProtocol;
 
 use super::SmallBankWorker;
 use super::SmallBankClientReq;
@@ -24,6 +28,13 @@ impl AsyncWorker for SmallBankWorker {
     fn has_stopped(&self) -> bool {
         false
     }
+
+    // one thread of the partition collects
+    fn poll_background(&self, round: usize) {
+        if SMALL_BANK_SNAPSHOT_BALANCE && self.tid == 0 && round % MVCC_GC_POLLS == 0 {
+            self.memdb.collect_versions();
+        }
+    }
 }
 
 impl RpcHandler for SmallBankWorker {
@@ -45,23 +56,76 @@ impl RpcHandler for SmallBankWorker {
             occ_rpc_id::LOCK_RPC => {
                 self.proc.lock_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::NODE_ADDR_RPC => {
+                self.proc.node_addr_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TABLE_LAYOUT_RPC => {
+                self.proc.table_layout_rpc_handler(src_conn, msg, size, meta);
+            }
             occ_rpc_id::VALIDATE_RPC => {
                 self.proc.validate_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            // 2pl and tictoc share the plain handlers
             occ_rpc_id::COMMIT_RPC => {
-                self.proc.commit_cache_rpc_handler(src_conn, msg, size, meta);
+                if self.cc == CcProtocol::Occ {
+                    self.proc.commit_cache_rpc_handler(src_conn, msg, size, meta);
+                } else {
+                    self.proc.commit_rpc_handler(src_conn, msg, size, meta);
+                }
             }
             occ_rpc_id::RELEASE_RPC => {
-                self.proc.release_cache_rpc_handler(src_conn, msg, size, meta);
+                if self.cc == CcProtocol::Occ {
+                    self.proc.release_cache_rpc_handler(src_conn, msg, size, meta);
+                } else {
+                    self.proc.release_rpc_handler(src_conn, msg, size, meta);
+                }
             }
             occ_rpc_id::ABORT_RPC => {
-                self.proc.abort_cache_rpc_handler(src_conn, msg, size, meta);
+                if self.cc == CcProtocol::Occ {
+                    self.proc.abort_cache_rpc_handler(src_conn, msg, size, meta);
+                } else {
+                    self.proc.abort_rpc_handler(src_conn, msg, size, meta);
+                }
+            }
+            occ_rpc_id::SNAPSHOT_READ_RPC => {
+                self.proc.snapshot_read_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::DELTA_RPC => {
+                self.proc.delta_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::PROC_RPC => {
+                self.proc.proc_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_READ_RPC => {
+                self.proc.tpl_read_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_LOCK_RPC => {
+                self.proc.tpl_lock_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_RELEASE_RPC => {
+                self.proc.tpl_release_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_READ_RPC => {
+                self.proc.tictoc_read_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_LOCK_RPC => {
+                self.proc.tictoc_lock_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_VALIDATE_RPC => {
+                self.proc.tictoc_validate_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_COMMIT_RPC => {
+                self.proc.tictoc_commit_rpc_handler(src_conn, msg, size, meta);
             }
             _ => {
                 unimplemented!();
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(self.cc, peer_id);
+    }
 }
 
 
@@ -82,10 +146,18 @@ impl SmallBankWorker {
                     self.txn_deposit_checking(&mut rand_gen, cid).await;
                 }
                 SmallBankWordLoadId::TxnBalance => {
-                    self.txn_balance(&mut rand_gen, cid).await;
+                    if SMALL_BANK_SNAPSHOT_BALANCE {
+                        self.txn_balance_snapshot(&mut rand_gen, cid).await;
+                    } else {
+                        self.txn_balance(&mut rand_gen, cid).await;
+                    }
                 }
                 SmallBankWordLoadId::TxnTransactSavings => {
-                    self.txn_transact_savings(&mut rand_gen, cid).await;
+                    if SMALL_BANK_STORED_PROC {
+                        self.txn_transact_savings_proc(&mut rand_gen, cid).await;
+                    } else {
+                        self.txn_transact_savings(&mut rand_gen, cid).await;
+                    }
                 }
                 SmallBankWordLoadId::TxnWriteCheck => {
                     self.txn_write_check(&mut rand_gen, cid).await;
@@ -3,15 +3,22 @@ use std::sync::Arc;
 use crate::common::random::FastRandom;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::memstore::memdb::MemDB;
-use crate::occ::occ_trans_cache::OccTransCache;
+use crate::occ::CcTxn;
+use crate::occ::occ_readonly::OccReadOnly;
+use crate::occ::occ_proc::OccProc;
+use crate::memstore::memdb::proc_id;
+use crate::SMALL_BANK_ONE_SIDE_READ;
 
 use super::SmallBankWorker;
 use super::small_bank_table_id;
+use super::small_bank_merge_id;
+use super::small_bank_proc;
 use super::SMALL_BANK_MAX_ITEM_SIZE;
 use super::SmallBankAccounts;
 use super::SmallBankChecking;
 use super::SmallBankSavings;
-use super::utils::{ random_get_accounts, account_to_part };
+use super::TransactSavingsArgs;
+use super::utils::random_get_accounts;
 
 // polymorphic manually
 
@@ -20,7 +27,8 @@ impl SmallBankWorker {
     // update checking * 2
     pub async fn txn_send_payment(&self, rand_gen: &mut FastRandom, cid: u32) {
         // println!("txn_send_payment");
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
             self.part_id, 
             self.tid,
             cid, 
@@ -28,37 +36,42 @@ impl SmallBankWorker {
             &self.scheduler,
         );
 
-        txn.start();
-
         let mut accounts = Vec::new();
         random_get_accounts(2, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _,
-        );
+        loop {
+            txn.start();
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[1]) as _,
-            accounts[1] as _,
-        );
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _,
+            );
 
-        let c0 = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
-        let c1 = txn.get_value::<SmallBankChecking>(true, 1).await.c_balance;
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                accounts[1] as _,
+            );
 
-        let amount = 5.0;
-        if c0 < amount {
-            txn.set_value(true, 0, &SmallBankChecking{ c_balance: c0 });
-            txn.set_value(true, 1, &SmallBankChecking{ c_balance: c1 });
-        } else {
-            txn.set_value(true, 0, &SmallBankChecking{ c_balance: c0 + amount });
-            txn.set_value(true, 1, &SmallBankChecking{ c_balance: c1 - amount });
+            let c0 = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
+            let c1 = txn.get_value::<SmallBankChecking>(true, 1).await.c_balance;
+
+            let amount = 5.0;
+            if c0 < amount {
+                txn.set_value(true, 0, &SmallBankChecking{ c_balance: c0 });
+                txn.set_value(true, 1, &SmallBankChecking{ c_balance: c1 });
+            } else {
+                txn.set_value(true, 0, &SmallBankChecking{ c_balance: c0 + amount });
+                txn.set_value(true, 1, &SmallBankChecking{ c_balance: c1 - amount });
+            }
+
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
         }
 
-        txn.commit().await;
-
         let _ = txn.is_commited();
 
     }
@@ -66,7 +79,8 @@ impl SmallBankWorker {
     // update checking
     pub async fn txn_deposit_checking(&self, rand_gen: &mut FastRandom, cid: u32) {
         // println!("txn_deposit_checking");
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
             self.part_id, 
             self.tid,
             cid, 
@@ -74,24 +88,28 @@ impl SmallBankWorker {
             &self.scheduler,
         );
 
-        txn.start();
-
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _,
-        );
-
-        let cv = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
+        loop {
+            txn.start();
 
-        let amount = 1.3;
+            let amount: f64 = 1.3;
 
-        txn.set_value(true, 0, &SmallBankChecking{ c_balance: cv + amount });
+            // no need to read the balance out
+            txn.apply_delta(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                self.placement.part_of(accounts[0]) as _,
+                accounts[0] as _,
+                small_bank_merge_id::CHECKING_DEPOSIT,
+                &amount,
+            );
     
-        txn.commit().await;
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
 
         let _ = txn.is_commited();
     }
@@ -99,9 +117,65 @@ impl SmallBankWorker {
     // read checking && saving
     pub async fn txn_balance(&self, rand_gen: &mut FastRandom, cid: u32) {
         // println!("txn_balance");
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        // the cached handlers serve no one-sided reads
+        let mut txn = if SMALL_BANK_ONE_SIDE_READ {
+            CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+                self.cc,
+                self.part_id, 
+                self.tid,
+                cid, 
+                &self.memdb, 
+                &self.scheduler,
+            )
+        } else {
+            CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+                self.cc,
+                self.part_id, 
+                self.tid,
+                cid, 
+                &self.memdb, 
+                &self.scheduler,
+            )
+        };
+        if SMALL_BANK_ONE_SIDE_READ {
+            txn.set_one_side_read(&self.one_side);
+        }
+
+        let mut accounts = Vec::new();
+        random_get_accounts(1, rand_gen, &mut accounts);
+
+        loop {
+            txn.start();
+
+            txn.read_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _
+            );
+            txn.read_placed::<SmallBankSavings>(
+                small_bank_table_id::SAVINGS_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _
+            );
+
+            let cv = txn.get_value::<SmallBankChecking>(false, 0).await.c_balance;
+            let sv = txn.get_value::<SmallBankSavings>(false, 1).await.s_balance;
+
+            let res = cv + sv;
+
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
+
+        let _ = txn.is_commited();
+    }
+
+    // read checking && saving at a snapshot, never validated
+    pub async fn txn_balance_snapshot(&self, rand_gen: &mut FastRandom, cid: u32) {
+        let mut txn = OccReadOnly::<SMALL_BANK_MAX_ITEM_SIZE>::new(
             self.part_id, 
-            self.tid,
             cid, 
             &self.memdb, 
             &self.scheduler,
@@ -114,17 +188,17 @@ impl SmallBankWorker {
 
         txn.read::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _
         );
         txn.read::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
+            self.placement.part_of(accounts[0]) as _,
             accounts[0] as _
         );
 
-        let cv = txn.get_value::<SmallBankChecking>(false, 0).await.c_balance;
-        let sv = txn.get_value::<SmallBankSavings>(false, 1).await.s_balance;
+        let cv = txn.get_value::<SmallBankChecking>(0).await.c_balance;
+        let sv = txn.get_value::<SmallBankSavings>(1).await.s_balance;
 
         let res = cv + sv;
 
@@ -136,7 +210,8 @@ impl SmallBankWorker {
     // update saving
     pub async fn txn_transact_savings(&self, rand_gen: &mut FastRandom, cid: u32) {
         // println!("txn_transact_savings");
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
             self.part_id, 
             self.tid,
             cid, 
@@ -144,32 +219,36 @@ impl SmallBankWorker {
             &self.scheduler,
         );
 
-        txn.start();
-
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankSavings>(
-            small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _,
-        );
+        loop {
+            txn.start();
+
+            txn.fetch_write_placed::<SmallBankSavings>(
+                small_bank_table_id::SAVINGS_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _,
+            );
 
-        let sv = txn.get_value::<SmallBankSavings>(true, 0).await.s_balance;
+            let sv = txn.get_value::<SmallBankSavings>(true, 0).await.s_balance;
 
-        let amount = 20.20;
+            let amount = 20.20;
 
-        txn.set_value(true, 0, &SmallBankSavings{ s_balance: sv + amount });
+            txn.set_value(true, 0, &SmallBankSavings{ s_balance: sv + amount });
     
-        txn.commit().await;
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
 
         let _ = txn.is_commited();
     }
 
-    // read checing && saving -> write checking
-    pub async fn txn_write_check(&self, rand_gen: &mut FastRandom, cid: u32) {
-        // println!("txn_write_check");
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+    // update saving, executed entirely on the owner
+    pub async fn txn_transact_savings_proc(&self, rand_gen: &mut FastRandom, cid: u32) {
+        let mut txn = OccProc::<SMALL_BANK_MAX_ITEM_SIZE>::new(
             self.part_id, 
             self.tid,
             cid, 
@@ -182,31 +261,67 @@ impl SmallBankWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _
+        txn.call(
+            self.placement.part_of(accounts[0]) as _,
+            proc_id(small_bank_proc::TRANSACT_SAVINGS),
+            &TransactSavingsArgs{
+                account: accounts[0] as _,
+                amount:  20.20,
+            },
         );
-        txn.read::<SmallBankSavings>(
-            small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _
+
+        txn.wait().await;
+
+        let _ = txn.is_commited(0);
+    }
+
+    // read checing && saving -> write checking
+    pub async fn txn_write_check(&self, rand_gen: &mut FastRandom, cid: u32) {
+        // println!("txn_write_check");
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
+            self.part_id, 
+            self.tid,
+            cid, 
+            &self.memdb, 
+            &self.scheduler,
         );
 
-        let cv = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
-        let sv = txn.get_value::<SmallBankSavings>(false, 0).await.s_balance;
+        let mut accounts = Vec::new();
+        random_get_accounts(1, rand_gen, &mut accounts);
 
-        let total = cv + sv;
+        loop {
+            txn.start();
 
-        let amount = 5.0;
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _
+            );
+            txn.read_placed::<SmallBankSavings>(
+                small_bank_table_id::SAVINGS_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _
+            );
 
-        if total < amount {
-            txn.set_value(true, 0, &SmallBankChecking{ c_balance: cv - amount + 1.0 });
-        } else {
-            txn.set_value(true, 0, &SmallBankChecking{ c_balance: cv - amount });
-        }
+            let cv = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
+            let sv = txn.get_value::<SmallBankSavings>(false, 0).await.s_balance;
 
-        txn.commit().await;
+            let total = cv + sv;
+
+            let amount = 5.0;
+
+            if total < amount {
+                txn.set_value(true, 0, &SmallBankChecking{ c_balance: cv - amount + 1.0 });
+            } else {
+                txn.set_value(true, 0, &SmallBankChecking{ c_balance: cv - amount });
+            }
+
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
 
         let _ = txn.is_commited();
 
@@ -215,7 +330,8 @@ impl SmallBankWorker {
     // read checing && saving -> write checking
     pub async fn txn_amalgamate(&self, rand_gen: &mut FastRandom, cid: u32) {
         // println!("txn_amalgamate");
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
             self.part_id, 
             self.tid,
             cid, 
@@ -223,39 +339,44 @@ impl SmallBankWorker {
             &self.scheduler,
         );
 
-        txn.start();
-
         let mut accounts = Vec::new();
         random_get_accounts(2, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankSavings>(
-            small_bank_table_id::SAVINGS_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _,
-        );
+        loop {
+            txn.start();
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[0]) as _,
-            accounts[0] as _,
-        );
+            txn.fetch_write_placed::<SmallBankSavings>(
+                small_bank_table_id::SAVINGS_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _,
+            );
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(accounts[1]) as _,
-            accounts[1] as _,
-        );
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                accounts[0] as _,
+            );
 
-        let s0 = txn.get_value::<SmallBankSavings>(true, 0).await.s_balance;
-        let c0 = txn.get_value::<SmallBankChecking>(true, 1).await.c_balance;
-        let c1 = txn.get_value::<SmallBankChecking>(true, 2).await.c_balance;
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                accounts[1] as _,
+            );
 
+            let s0 = txn.get_value::<SmallBankSavings>(true, 0).await.s_balance;
+            let c0 = txn.get_value::<SmallBankChecking>(true, 1).await.c_balance;
+            let c1 = txn.get_value::<SmallBankChecking>(true, 2).await.c_balance;
 
-        txn.set_value(true, 0, &SmallBankSavings{ s_balance: 0.0 });
-        txn.set_value(true, 1, &SmallBankChecking{ c_balance: 0.0 });
-        txn.set_value(true, 2, &SmallBankChecking{ c_balance: s0 + c0 + c1 });
+
+            txn.set_value(true, 0, &SmallBankSavings{ s_balance: 0.0 });
+            txn.set_value(true, 1, &SmallBankChecking{ c_balance: 0.0 });
+            txn.set_value(true, 2, &SmallBankChecking{ c_balance: s0 + c0 + c1 });
     
-        txn.commit().await;
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
 
         let _ = txn.is_commited();
     }
@@ -268,7 +389,8 @@ impl SmallBankWorker {
             rid = rand_gen.next() % 4;
         }
 
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
             self.part_id, 
             self.tid,
             cid, 
@@ -276,33 +398,39 @@ impl SmallBankWorker {
             &self.scheduler,
         );
 
-        txn.start();
+        loop {
+            txn.start();
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(lid) as _,
-            lid as _,
-        );
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                lid as _,
+            );
 
-        txn.fetch_write::<SmallBankChecking>(
-            small_bank_table_id::CHECKING_TABLE_ID,
-            account_to_part(rid) as _,
-            rid as _,
-        );
+            txn.fetch_write_placed::<SmallBankChecking>(
+                small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
+                rid as _,
+            );
 
-        let lvalue = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
-        let rvalue = txn.get_value::<SmallBankChecking>(true, 1).await.c_balance;
+            let lvalue = txn.get_value::<SmallBankChecking>(true, 0).await.c_balance;
+            let rvalue = txn.get_value::<SmallBankChecking>(true, 1).await.c_balance;
     
-        txn.set_value(true, 0, &SmallBankChecking{ c_balance: rvalue });
-        txn.set_value(true, 1, &SmallBankChecking{ c_balance: lvalue });
+            txn.set_value(true, 0, &SmallBankChecking{ c_balance: rvalue });
+            txn.set_value(true, 1, &SmallBankChecking{ c_balance: lvalue });
 
-        txn.commit().await;
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
 
         let _ = txn.is_commited();
     }
 
     pub async fn txn_exchange_check(&self, rand_gen: &mut FastRandom, cid: u32) {
-        let mut txn = OccTransCache::<SMALL_BANK_MAX_ITEM_SIZE>::new(
+        let mut txn = CcTxn::<SMALL_BANK_MAX_ITEM_SIZE>::new_cached(
+            self.cc,
             self.part_id, 
             self.tid,
             cid, 
@@ -310,25 +438,30 @@ impl SmallBankWorker {
             &self.scheduler,
         );
 
-        txn.start();
-
         let start_time = std::time::SystemTime::now();
 
-        for i in 0..4 {
-            txn.read::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
-                account_to_part(i) as _,
-                i as _,
-            );
-        }
-        let mut values = Vec::new();
-
-        for i in 0..4 {
-            let value = txn.get_value::<SmallBankChecking>(false, i).await.c_balance;
-            values.push(value);
+        loop {
+            txn.start();
+
+            for i in 0..4 {
+                txn.read_placed::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
+                    &*self.placement,
+                    i as _,
+                );
+            }
+            let mut values = Vec::new();
+
+            for i in 0..4 {
+                let value = txn.get_value::<SmallBankChecking>(false, i).await.c_balance;
+                values.push(value);
+            }
+
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
         }
 
-        txn.commit().await;
-
         let end_time = std::time::SystemTime::now();
 
 
@@ -5,8 +5,6 @@ use crate::framework::scheduler::AsyncScheduler;
 use crate::memstore::memdb::MemDB;
 use crate::occ::occ_trans_cache::OccTransCache;
 
-use super::utils::accout_to_part_host_longitude;
-use super::utils::accout_to_part_hybrid_longitude;
 use super::SmallBankHybridLongitudeWorker;
 use super::SmallBankHostLongitudeWorker;
 use super::small_bank_table_id;
@@ -14,7 +12,7 @@ use super::SMALL_BANK_MAX_ITEM_SIZE;
 use super::SmallBankAccounts;
 use super::SmallBankChecking;
 use super::SmallBankSavings;
-use super::utils::{ random_get_accounts, account_to_part };
+use super::utils::random_get_accounts;
 
 // polymorphic manually
 
@@ -36,15 +34,15 @@ impl SmallBankHybridLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(2, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[1], self.part_id as _) as _,
+            &*self.placement,
             accounts[1] as _,
         );
 
@@ -82,9 +80,9 @@ impl SmallBankHybridLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
@@ -115,14 +113,14 @@ impl SmallBankHybridLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.read::<SmallBankChecking>(
+        txn.read_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
-        txn.read::<SmallBankSavings>(
+        txn.read_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
 
@@ -152,9 +150,9 @@ impl SmallBankHybridLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankSavings>(
+        txn.fetch_write_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
@@ -185,14 +183,14 @@ impl SmallBankHybridLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
-        txn.read::<SmallBankSavings>(
+        txn.read_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
 
@@ -231,21 +229,21 @@ impl SmallBankHybridLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(2, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankSavings>(
+        txn.fetch_write_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(accounts[1], self.part_id as _) as _,
+            &*self.placement,
             accounts[1] as _,
         );
 
@@ -281,15 +279,15 @@ impl SmallBankHybridLongitudeWorker {
 
         txn.start();
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(lid, self.part_id as _) as _,
+            &*self.placement,
             lid as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_hybrid_longitude(rid, self.part_id as _) as _,
+            &*self.placement,
             rid as _,
         );
 
@@ -318,8 +316,8 @@ impl SmallBankHybridLongitudeWorker {
         let start_time = std::time::SystemTime::now();
 
         for i in 0..4 {
-            txn.read::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
-                accout_to_part_hybrid_longitude(i, self.part_id as _) as _,
+            txn.read_placed::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
                 i as _,
             );
         }
@@ -362,15 +360,15 @@ impl SmallBankHostLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(2, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[1], self.part_id as _) as _,
+            &*self.placement,
             accounts[1] as _,
         );
 
@@ -408,9 +406,9 @@ impl SmallBankHostLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
@@ -441,14 +439,14 @@ impl SmallBankHostLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.read::<SmallBankChecking>(
+        txn.read_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
-        txn.read::<SmallBankSavings>(
+        txn.read_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
 
@@ -478,9 +476,9 @@ impl SmallBankHostLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankSavings>(
+        txn.fetch_write_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
@@ -511,14 +509,14 @@ impl SmallBankHostLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(1, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
-        txn.read::<SmallBankSavings>(
+        txn.read_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _
         );
 
@@ -557,21 +555,21 @@ impl SmallBankHostLongitudeWorker {
         let mut accounts = Vec::new();
         random_get_accounts(2, rand_gen, &mut accounts);
 
-        txn.fetch_write::<SmallBankSavings>(
+        txn.fetch_write_placed::<SmallBankSavings>(
             small_bank_table_id::SAVINGS_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[0], self.part_id as _) as _,
+            &*self.placement,
             accounts[0] as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(accounts[1], self.part_id as _) as _,
+            &*self.placement,
             accounts[1] as _,
         );
 
@@ -607,15 +605,15 @@ impl SmallBankHostLongitudeWorker {
 
         txn.start();
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(lid, self.part_id as _) as _,
+            &*self.placement,
             lid as _,
         );
 
-        txn.fetch_write::<SmallBankChecking>(
+        txn.fetch_write_placed::<SmallBankChecking>(
             small_bank_table_id::CHECKING_TABLE_ID,
-            accout_to_part_host_longitude(rid, self.part_id as _) as _,
+            &*self.placement,
             rid as _,
         );
 
@@ -644,8 +642,8 @@ impl SmallBankHostLongitudeWorker {
         let start_time = std::time::SystemTime::now();
 
         for i in 0..4 {
-            txn.read::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
-                accout_to_part_host_longitude(i, self.part_id as _) as _,
+            txn.read_placed::<SmallBankChecking>(small_bank_table_id::CHECKING_TABLE_ID,
+                &*self.placement,
                 i as _,
             );
         }
@@ -12,6 +12,7 @@ use crate::framework::rpc::*;
 use crate::doca_comm_chan::connection::DocaCommHandler;
 use crate::doca_comm_chan::comm_buf::DocaCommBuf;
 use crate::rdma::rcconn::RdmaRcConn;
+use crate::BUF_STATS_POLLS;
 
 pub struct TpccDpuWorker {
     scheduler: Arc<AsyncScheduler>,
@@ -91,15 +92,25 @@ impl RpcHandler for TpccDpuWorker {
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(peer_id);
+    }
 }
 
 impl TpccDpuWorker {
     async fn main_routine(&self, tid: u32) {
+        let mut round = 0;
         loop {
             self.scheduler.poll_recvs();
             self.scheduler.poll_sends();
             self.scheduler.poll_comm_chan();
 
+            round += 1;
+            if round % BUF_STATS_POLLS == 0 {
+                self.proc.report_buf_stats();
+            }
+
             // sleep(Duration::from_millis(1));
 
             // self.scheduler.yield_now(0).await;
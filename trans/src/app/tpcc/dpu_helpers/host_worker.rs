@@ -3,11 +3,13 @@ use tokio::sync::mpsc;
 use tokio::sync::Mutex as AsyncMutex;
 
 use crate::common::random::FastRandom;
+use crate::common::partition::PlacementView;
 use crate::memstore::memdb::ValueDB;
 use crate::occ::occ_rpc_id;
 use crate::occ::HostRpcProc;
 use crate::occ::doca_comm_info_id;
 use crate::framework::scheduler::AsyncScheduler;
+use crate::framework::quiesce::QuiesceGate;
 use crate::framework::rpc::*;
 use crate::doca_comm_chan::connection::DocaCommHandler;
 use crate::doca_comm_chan::comm_buf::DocaCommBuf;
@@ -15,6 +17,7 @@ use crate::rdma::rcconn::RdmaRcConn;
 use crate::TPCC_NROUTINES;
 
 use super::super::*;
+use super::super::utils::TpccPlacement;
 
 pub struct TpccHostWorker {
     pub part_id: u64,
@@ -22,16 +25,20 @@ pub struct TpccHostWorker {
     pub valuedb: Arc<ValueDB>,
     pub scheduler: Arc<AsyncScheduler>,
     pub proc: HostRpcProc,
+    pub quiesce: Arc<QuiesceGate>,
+    pub placement: TpccPlacement,
 }
 
 impl TpccHostWorker {
-    pub fn new(part_id: u64, tid: u32, valuedb: &Arc<ValueDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+    pub fn new(part_id: u64, tid: u32, valuedb: &Arc<ValueDB>, scheduler: &Arc<AsyncScheduler>, quiesce: &Arc<QuiesceGate>) -> Self {
         Self {
             part_id: part_id,
             tid: tid, 
             scheduler: scheduler.clone(),
             valuedb: valuedb.clone(),
             proc: HostRpcProc::new(tid, valuedb, scheduler),
+            quiesce: quiesce.clone(),
+            placement: TpccPlacement::new(part_id, PlacementView::Plain),
         }
     }
 }
@@ -71,6 +78,9 @@ impl RpcHandler for TpccHostWorker {
             occ_rpc_id::COMMIT_RPC => {
                 self.proc.commit_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::DELTA_RPC => {
+                self.proc.delta_rpc_handler(src_conn, msg, size, meta);
+            }
             _ => {
                 unimplemented!();
             }
@@ -87,11 +97,13 @@ impl TpccHostWorker {
             let req = receiver.recv().await.unwrap();
             drop(receiver);
 
+            self.quiesce.enter().await;
             match req.workload {
                 TpccWorkLoadId::TxnNewOrder => {
                     self.txn_new_order(&mut rand_gen, cid).await;
                 }
             }
+            self.quiesce.leave();
         }
     }
 
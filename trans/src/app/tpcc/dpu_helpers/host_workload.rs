@@ -33,14 +33,14 @@ impl TpccHostWorker {
         for i in 0..stock_count {
             txn.fetch_write::<TpccStocks>(
                 tpcc_table_id::STOCKS_TABLE_ID,
-                warehouse_id_to_part_id(stock_id_to_warehouse_id(stocks[i])) as _,
+                self.placement.stocks.part_of(stocks[i]) as _,
                 stocks[i] as _,
             );
         }
 
         let idx = txn.fetch_write::<TpccDistricts>(
             tpcc_table_id::DISTRICTS_TABLE_ID,
-            warehouse_id_to_part_id(w_id) as _,
+            self.placement.districts.part_of(d_id) as _,
             d_id as _,
         );
 
@@ -55,11 +55,7 @@ impl TpccHostWorker {
             let ol_quantity = rand_gen.next() % 10 + 1;
             let i_price = rand_gen.next_uniform() * 10000.0;
 
-            if stock.s_quantity >= 10 + ol_quantity as u64 {
-                stock.s_quantity -= ol_quantity as u64;
-            } else {
-                stock.s_quantity += 91 - ol_quantity as u64;
-            }
+            stock.s_quantity = stock_order_quantity(stock.s_quantity, ol_quantity as u64);
 
             txn.set_value(true, i, &stock)
         }
@@ -74,7 +70,7 @@ impl TpccHostWorker {
 
         let idx = txn.write::<TpccOrders>(
             tpcc_table_id::ORDERS_TABLE_ID, 
-            order_id_to_part_id(o_id) as _, 
+            self.placement.orders.part_of(o_id) as _, 
             o_id as _, 
             crate::occ::RwType::UPDATE,
         );
@@ -4,6 +4,7 @@ use std::sync::Arc;
 use crate::memstore::memdb::TableSchema;
 use crate::memstore::{ RobinhoodMemStore, RobinhoodValueStore };
 use crate::common::random::FastRandom;
+use crate::common::partition::PlacementView;
 use crate::memstore::memdb::{ MemDB, ValueDB };
 
 use super::super::*;
@@ -22,6 +23,7 @@ impl TpccDpuLoader {
         Arc::get_mut(&mut valuedb).unwrap().add_schema(0, TableSchema::default(), valuestore0);
         Arc::get_mut(&mut valuedb).unwrap().add_schema(1, TableSchema::default(), valuestore1);
         Arc::get_mut(&mut valuedb).unwrap().add_schema(2, TableSchema::default(), valuestore2);
+        Arc::get_mut(&mut valuedb).unwrap().add_merge(tpcc_merge_id::STOCK_ORDER, merge_stock_order);
 
         Self::hostdb_do_load((23984543 + part_id * 73) as usize, part_id, &valuedb);
 
@@ -29,6 +31,7 @@ impl TpccDpuLoader {
     }
 
     pub fn hostdb_do_load(rand_seed: usize, part_id: u64, valuedb: &Arc<ValueDB>) {
+        let placement = TpccPlacement::new(part_id, PlacementView::Plain);
         let w_start = warehouses_start(part_id as _);
         let w_end = warehouses_end(part_id as _);
 
@@ -60,7 +63,7 @@ impl TpccDpuLoader {
         }
 
         for i in 0..num_orders() {
-            if order_id_to_part_id(i) as u64 != part_id {
+            if placement.orders.part_of(i) as u64 != part_id {
                 continue;
             }
 
@@ -92,6 +95,7 @@ impl TpccDpuLoader {
     }
 
     pub fn dpudb_do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
+        let placement = TpccPlacement::new(part_id, PlacementView::Plain);
         let w_start = warehouses_start(part_id as _);
         let w_end = warehouses_end(part_id as _);
         for i in w_start..w_end {
@@ -133,7 +137,7 @@ impl TpccDpuLoader {
         }
 
         for i in 0..num_orders() {
-            if order_id_to_part_id(i) as u64 != part_id {
+            if placement.orders.part_of(i) as u64 != part_id {
                 continue;
             }
 
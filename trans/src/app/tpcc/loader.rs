@@ -3,7 +3,11 @@ use std::sync::Arc;
 use crate::memstore::memdb::TableSchema;
 use crate::memstore::RobinhoodMemStore;
 use crate::common::random::FastRandom;
+use crate::common::partition::PlacementView;
 use crate::memstore::memdb::MemDB;
+use crate::rdma::control::RdmaBaseAllocator;
+use crate::rdma::device::{atomics_are_global, RdmaDeviceConfig};
+use crate::{NPAGES, TPCC_CAS_LOCK, TPCC_NTHREADS};
 
 use super::*;
 use super::utils::*;
@@ -11,22 +15,55 @@ use super::utils::*;
 pub struct TpccLoader {}
 
 impl TpccLoader {
+    // the region of the node, registered by the connections of all its threads
+    pub fn new_region() -> Arc<RdmaBaseAllocator> {
+        let stock_pages = (RobinhoodMemStore::<TpccStocks>::region_len() + 4095) / 4096;
+        Arc::new(RdmaBaseAllocator::with_pages(NPAGES as usize * TPCC_NTHREADS + stock_pages))
+    }
+
     pub fn new_memdb(part_id: u64) -> Arc<MemDB> {
+        Self::new_memdb_with(part_id, None)
+    }
+
+    // the stocks are placed in region, so that the peers lock them by RDMA CAS
+    pub fn new_memdb_on(part_id: u64, region: &Arc<RdmaBaseAllocator>) -> Arc<MemDB> {
+        Self::new_memdb_with(part_id, Some(region))
+    }
+
+    fn new_memdb_with(part_id: u64, region: Option<&Arc<RdmaBaseAllocator>>) -> Arc<MemDB> {
         let mut memdb = Arc::new(MemDB::new());
         let memstore0 = RobinhoodMemStore::<TpccDistricts>::new();
-        let memstore1 = RobinhoodMemStore::<TpccStocks>::new();
         let memstore2 = RobinhoodMemStore::<TpccOrders>::new();
 
+        // our own locks are taken by the cpu, a CAS of the nic is only atomic with them
+        // if its atomics are global, otherwise the peers lock the stocks by rpc
+        let cas_region = region.filter(|_| TPCC_CAS_LOCK && atomics_are_global(&RdmaDeviceConfig::default()));
+        let memstore1 = cas_region.and_then(|region| RobinhoodMemStore::<TpccStocks>::new_in(region));
+
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
-        Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
+        match memstore1 {
+            Some(memstore1) => {
+                let region = cas_region.unwrap();
+                // the rkey is per connection, the peers only take offsets from it
+                Arc::get_mut(&mut memdb).unwrap().set_region(region.get_lm(), region.get_len(), 0);
+                Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default().with_cas_lock(), memstore1);
+            }
+            None => {
+                let memstore1 = RobinhoodMemStore::<TpccStocks>::new();
+                Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
+            }
+        }
         Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
 
+        Arc::get_mut(&mut memdb).unwrap().add_merge(tpcc_merge_id::STOCK_ORDER, merge_stock_order);
+
         Self::do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
         memdb
     }
 
     pub fn do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
+        let placement = TpccPlacement::new(part_id, PlacementView::Plain);
         let w_start = warehouses_start(part_id as _);
         let w_end = warehouses_end(part_id as _);
         for i in w_start..w_end {
@@ -68,7 +105,7 @@ impl TpccLoader {
         }
 
         for i in 0..num_orders() {
-            if order_id_to_part_id(i) as u64 != part_id {
+            if placement.orders.part_of(i) as u64 != part_id {
                 continue;
             }
 
@@ -3,6 +3,8 @@ use std::sync::Arc;
 use crate::memstore::memdb::TableSchema;
 use crate::memstore::RobinhoodMemStore;
 use crate::common::random::FastRandom;
+use crate::common::partition::PlacementView;
+use crate::common::cluster::dpu_peer_id;
 use crate::memstore::memdb::MemDB;
 
 use super::*;
@@ -20,6 +22,7 @@ impl TpccLongitudeLoader {
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
         Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
         Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+        Arc::get_mut(&mut memdb).unwrap().add_merge(tpcc_merge_id::STOCK_ORDER, merge_stock_order);
 
         Self::hostdb_do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
@@ -27,13 +30,14 @@ impl TpccLongitudeLoader {
     }
 
     pub fn hostdb_do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
+        let placement = TpccPlacement::new(part_id, PlacementView::Host);
         let w_start = warehouses_start(part_id as _);
         let w_end = warehouses_end(part_id as _);
         for i in w_start..w_end {
             for j in 0..10 {
                 let d_id = j + i * 10;
 
-                if dist_id_to_part_id_host_longitude(d_id, part_id as _) != part_id as usize {
+                if placement.districts.part_of(d_id) != part_id as usize {
                     continue;
                 }
                 memdb.local_lock(
@@ -55,7 +59,7 @@ impl TpccLongitudeLoader {
             for j in 0..num_items() {
                 let s_id = make_stock_key(i, j);
 
-                if stock_id_to_part_id_host_longitude(s_id, part_id as _) != part_id as usize {
+                if placement.stocks.part_of(s_id) != part_id as usize {
                     continue;
                 }
 
@@ -77,7 +81,7 @@ impl TpccLongitudeLoader {
         }
 
         for i in 0..num_orders() {
-            if order_id_to_part_id_host_longitude(i, part_id as _) as u64 != part_id {
+            if placement.orders.part_of(i) as u64 != part_id {
                 continue;
             }
 
@@ -107,6 +111,7 @@ impl TpccLongitudeLoader {
         Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore0);
         Arc::get_mut(&mut memdb).unwrap().add_schema(1, TableSchema::default(), memstore1);
         Arc::get_mut(&mut memdb).unwrap().add_schema(2, TableSchema::default(), memstore2);
+        Arc::get_mut(&mut memdb).unwrap().add_merge(tpcc_merge_id::STOCK_ORDER, merge_stock_order);
 
         Self::dpudb_do_load((23984543 + part_id * 73) as usize, part_id, &memdb);
 
@@ -114,13 +119,15 @@ impl TpccLongitudeLoader {
     }
 
     pub fn dpudb_do_load(rand_seed: usize, part_id: u64, memdb: &Arc<MemDB>) {
+        let placement = TpccPlacement::new(part_id, PlacementView::Host);
+        let dpu_id = dpu_peer_id(part_id).expect("the cluster config gives the partition no dpu");
         let w_start = warehouses_start(part_id as _);
         let w_end = warehouses_end(part_id as _);
         for i in w_start..w_end {
             for j in 0..10 {
                 let d_id = j + i * 10;
 
-                if dist_id_to_part_id_host_longitude(d_id, part_id as _) != (part_id + 100) as usize {
+                if placement.districts.part_of(d_id) != dpu_id as usize {
                     continue;
                 }
                 memdb.local_lock(
@@ -142,7 +149,7 @@ impl TpccLongitudeLoader {
             for j in 0..num_items() {
                 let s_id = make_stock_key(i, j);
 
-                if stock_id_to_part_id_host_longitude(s_id, part_id as _) != (part_id + 100) as usize {
+                if placement.stocks.part_of(s_id) != dpu_id as usize {
                     continue;
                 }
 
@@ -164,7 +171,7 @@ impl TpccLongitudeLoader {
         }
 
         for i in 0..num_orders() {
-            if order_id_to_part_id_host_longitude(i, part_id as _) as u64 != part_id + 100 {
+            if placement.orders.part_of(i) as u64 != dpu_id {
                 continue;
             }
 
@@ -7,12 +7,17 @@ pub mod loader_longitude;
 pub mod workload_longitude;
 pub mod local_client;
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub mod dpu_helpers;
 
 use std::sync::Arc;
 
-use crate::{framework::scheduler::AsyncScheduler, memstore::memdb::MemDB, occ::BatchRpcProc};
+use crate::{framework::scheduler::AsyncScheduler, memstore::memdb::MemDB, occ::BatchRpcProc, occ::CcProtocol, occ::GroupCommitCtrl, occ::CasLockCtrl, occ::RemoteAddrCache};
+use crate::common::partition::PlacementView;
+use crate::common::offload::OffloadMap;
+use crate::{TPCC_CC_PROTOCOL, TPCC_NROUTINES};
+
+use utils::TpccPlacement;
 
 pub mod tpcc_table_id {
     pub const DISTRICTS_TABLE_ID:  usize = 0;
@@ -23,6 +28,10 @@ pub mod tpcc_table_id {
     pub const ITEMS_TABLE_ID:      usize = 5;
 }
 
+pub mod tpcc_merge_id {
+    pub const STOCK_ORDER: u32 = 1;
+}
+
 const TPCC_ITEM_SIZE: usize = 128;
 
 // shared
@@ -92,6 +101,27 @@ impl Default for TpccStocks {
     }
 }
 
+// new order on a stock, merged by the owner of the stock
+#[derive(Clone, Default)]
+#[repr(C)]
+pub struct TpccStockDelta {
+    ol_quantity: u64,
+}
+
+// the quantity after an order, s_quantity - ol_quantity, plus 91 below 10.
+// it is a subtraction modulo 91 over [10, 101), so the orders commute
+#[inline]
+pub fn stock_order_quantity(s_quantity: u64, ol_quantity: u64) -> u64 {
+    10 + (s_quantity as i64 - 10 - ol_quantity as i64).rem_euclid(91) as u64
+}
+
+pub fn merge_stock_order(value: *mut u8, delta: *const u8, _len: u32) {
+    let stock = unsafe { (value as *mut TpccStocks).as_mut().unwrap() };
+    let delta = unsafe { (delta as *const TpccStockDelta).as_ref().unwrap() };
+
+    stock.s_quantity = stock_order_quantity(stock.s_quantity, delta.ol_quantity);
+}
+
 // only read
 #[derive(Clone)]
 #[repr(C)]
@@ -145,19 +175,38 @@ pub struct TpccWorker {
     tid: u32,
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
-    proc: BatchRpcProc
+    proc: BatchRpcProc,
+    placement: TpccPlacement,
+    cc: CcProtocol,
+    group: Arc<GroupCommitCtrl>,
+    cas_locks: Arc<CasLockCtrl>,
 }
 
 impl TpccWorker {
     pub fn new(part_id: u64, tid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        // the node offsets resolved by the routines of the thread
+        let addrs = Arc::new(RemoteAddrCache::new(scheduler, TPCC_NROUTINES as _));
         Self {
             part_id: part_id,
             tid: tid, 
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: TpccPlacement::new(part_id, PlacementView::Plain),
+            cc: CcProtocol::configured(TPCC_CC_PROTOCOL),
+            group: Arc::new(GroupCommitCtrl::new(scheduler, TPCC_NROUTINES - 1)),
+            cas_locks: Arc::new(CasLockCtrl::new(scheduler, &addrs, TPCC_NROUTINES as _)),
         }
     }
+
+    // e.g. with the split of an offload map
+    pub fn set_placement(&mut self, placement: &TpccPlacement) {
+        self.placement = placement.clone();
+    }
+
+    pub fn set_cc_protocol(&mut self, cc: CcProtocol) {
+        self.cc = cc;
+    }
 }
 
 pub struct TpccHybridWorker {
@@ -165,7 +214,8 @@ pub struct TpccHybridWorker {
     tid: u32,
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
-    proc: BatchRpcProc
+    proc: BatchRpcProc,
+    placement: TpccPlacement,
 }
 
 impl TpccHybridWorker {
@@ -176,8 +226,13 @@ impl TpccHybridWorker {
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: TpccPlacement::new(part_id, PlacementView::Plain),
         }
     }
+
+    pub fn set_placement(&mut self, placement: &TpccPlacement) {
+        self.placement = placement.clone();
+    }
 }
 
 pub struct TpccHybridLongitudeWorker {
@@ -185,7 +240,9 @@ pub struct TpccHybridLongitudeWorker {
     tid: u32,
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
-    proc: BatchRpcProc
+    proc: BatchRpcProc,
+    placement: TpccPlacement,
+    offload: Option<Arc<OffloadMap>>,
 }
 
 impl TpccHybridLongitudeWorker {
@@ -196,8 +253,20 @@ impl TpccHybridLongitudeWorker {
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: TpccPlacement::new(part_id, PlacementView::Hybrid),
+            offload: None,
         }
     }
+
+    pub fn set_placement(&mut self, placement: &TpccPlacement) {
+        self.placement = placement.clone();
+    }
+
+    // the stock map the controllers of the other hosts publish to
+    pub fn set_offload_map(&mut self, map: &Arc<OffloadMap>) {
+        self.placement = TpccPlacement::with_stock_map(self.part_id, PlacementView::Hybrid, map);
+        self.offload = Some(map.clone());
+    }
 }
 
 pub struct TpccHostLongitudeWorker {
@@ -205,7 +274,9 @@ pub struct TpccHostLongitudeWorker {
     tid: u32,
     memdb: Arc<MemDB>,
     scheduler: Arc<AsyncScheduler>,
-    proc: BatchRpcProc
+    proc: BatchRpcProc,
+    placement: TpccPlacement,
+    offload: Option<Arc<OffloadMap>>,
 }
 
 impl TpccHostLongitudeWorker {
@@ -216,6 +287,18 @@ impl TpccHostLongitudeWorker {
             scheduler: scheduler.clone(),
             memdb: memdb.clone(),
             proc: BatchRpcProc::new(tid, memdb, scheduler),
+            placement: TpccPlacement::new(part_id, PlacementView::Host),
+            offload: None,
         }
     }
+
+    pub fn set_placement(&mut self, placement: &TpccPlacement) {
+        self.placement = placement.clone();
+    }
+
+    // the split of the stocks follows the map, the offload controller of the host moves it
+    pub fn set_offload_map(&mut self, map: &Arc<OffloadMap>) {
+        self.placement = TpccPlacement::with_stock_map(self.part_id, PlacementView::Host, map);
+        self.offload = Some(map.clone());
+    }
 }
\ No newline at end of file
@@ -2,13 +2,17 @@ use std::collections::HashSet;
 use std::hash::Hash;
 use std::hash::RandomState;
 use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
 use lazy_static::lazy_static;
 
 use crate::TPCC_NPARTITIONS;
 use crate::TPCC_SCALE;
 use crate::TPCC_PART_OFFLOAD_RATIO;
+use crate::OFFLOAD_RANGE_SIZE;
 
 use crate::common::random::FastRandom;
+use crate::common::offload::OffloadMap;
+use crate::common::partition::{ get_placement, Partitioner, PartitionerConfig, PlacementView };
 
 pub fn random_get_stocks(num: usize, rand_gen: &mut FastRandom, stocks: &mut Vec<usize>) {
     let mut temp_set = HashSet::new();
@@ -64,14 +68,6 @@ pub fn stock_id_to_warehouse_id(s_id: usize) -> usize {
     return s_id / 100000;
 }
 
-pub fn warehouse_id_to_part_id(w_id: usize) -> usize {
-    return w_id / TPCC_SCALE;
-}
-
-pub fn order_id_to_part_id(o_id: usize) -> usize {
-    return o_id % TPCC_NPARTITIONS;
-}
-
 pub fn warehouses_start(part_id: usize) -> usize {
     part_id * TPCC_SCALE
 }
@@ -81,88 +77,79 @@ pub fn warehouses_end(part_id: usize) -> usize {
 }
 
 /////////////////////////////////////////////////////////
-#[inline]
-pub fn stock_id_to_part_id_hybrid_longitude(stock_id: usize, part_id: usize) -> usize {
-    let w_id = stock_id_to_warehouse_id(stock_id);
-    let i_id = stock_id_to_item_id(stock_id);
-    let p_id = warehouse_id_to_part_id(w_id);
-
-    if p_id == part_id {
-        return p_id;
-    } else if i_id % 100 >= TPCC_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
+const DISTRICTS_PLACEMENT: &str = "tpcc.districts";
+const STOCKS_PLACEMENT: &str = "tpcc.stocks";
+const ORDERS_PLACEMENT: &str = "tpcc.orders";
+
+// a partition holds the districts of its warehouses, some of them on its dpu
+pub fn default_district_placement() -> PartitionerConfig {
+    PartitionerConfig::Split {
+        base: Box::new(PartitionerConfig::Range {
+            bounds: (1..TPCC_NPARTITIONS).map(|i| i * 10 * TPCC_SCALE).collect(),
+        }),
+        modulo: 10,
+        ratio: TPCC_PART_OFFLOAD_RATIO,
     }
 }
 
-#[inline]
-pub fn stock_id_to_part_id_host_longitude(stock_id: usize, part_id: usize) -> usize {
-    let w_id = stock_id_to_warehouse_id(stock_id);
-    let i_id = stock_id_to_item_id(stock_id);
-    let p_id = warehouse_id_to_part_id(w_id);
-
-    if p_id != part_id {
-        return p_id;
-    } else if i_id % 100 >= TPCC_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
+// so does it of the stocks, split by the item
+pub fn default_stock_placement() -> PartitionerConfig {
+    PartitionerConfig::Split {
+        base: Box::new(PartitionerConfig::Range {
+            bounds: (1..TPCC_NPARTITIONS).map(|i| make_stock_key(i * TPCC_SCALE, 0)).collect(),
+        }),
+        modulo: 100,
+        ratio: TPCC_PART_OFFLOAD_RATIO,
     }
 }
 
-#[inline]
-pub fn dist_id_to_part_id_hybrid_longitude(d_id: usize, part_id: usize) -> usize {
-    let w_id = d_id / 10;
-    let d_local = d_id % 10;
-    let p_id = warehouse_id_to_part_id(w_id);
-
-    if p_id == part_id {
-        return p_id;
-    } else if d_local * 10 >= TPCC_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
+// the orders modulo the partitions
+pub fn default_order_placement() -> PartitionerConfig {
+    PartitionerConfig::Split {
+        base: Box::new(PartitionerConfig::Hash { parts: TPCC_NPARTITIONS, mixed: false }),
+        modulo: 100,
+        ratio: TPCC_PART_OFFLOAD_RATIO,
     }
 }
 
-#[inline]
-pub fn dist_id_to_part_id_host_longitude(d_id: usize, part_id: usize) -> usize {
-    let w_id = d_id / 10;
-    let d_local = d_id % 10;
-    let p_id = warehouse_id_to_part_id(w_id);
-
-    if p_id != part_id {
-        return p_id;
-    } else if d_local * 10 >= TPCC_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
+/// The placements of the tables the txns of tpcc access remotely, as the cluster
+/// config places them or the defaults without one.
+#[derive(Clone)]
+pub struct TpccPlacement {
+    pub districts: Arc<dyn Partitioner>,
+    pub stocks: Arc<dyn Partitioner>,
+    pub orders: Arc<dyn Partitioner>,
+}
+
+impl TpccPlacement {
+    pub fn new(part_id: u64, view: PlacementView) -> Self {
+        Self {
+            districts: get_placement(DISTRICTS_PLACEMENT, default_district_placement()).build(part_id as _, view),
+            stocks: get_placement(STOCKS_PLACEMENT, default_stock_placement()).build(part_id as _, view),
+            orders: get_placement(ORDERS_PLACEMENT, default_order_placement()).build(part_id as _, view),
+        }
+    }
+
+    // the split of the stocks follows the offload map
+    pub fn with_stock_map(part_id: u64, view: PlacementView, map: &Arc<OffloadMap>) -> Self {
+        let mut placement = Self::new(part_id, view);
+        placement.stocks = get_placement(STOCKS_PLACEMENT, default_stock_placement()).build_with_map(part_id as _, view, Some(map));
+        placement
     }
 }
 
+// the static split the loaders place the stocks by
 #[inline]
-pub fn order_id_to_part_id_hybrid_longitude(o_id: usize, part_id: usize) -> usize {
-    let p_id = order_id_to_part_id(o_id);
-
-    if p_id == part_id {
-        return p_id;
-    } else if o_id % 100 >= TPCC_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
-    }
+pub fn item_on_dpu(i_id: usize) -> bool {
+    i_id % 100 < TPCC_PART_OFFLOAD_RATIO
 }
 
 #[inline]
-pub fn order_id_to_part_id_host_longitude(o_id: usize, part_id: usize) -> usize {
-    let p_id = order_id_to_part_id(o_id);
-
-    if p_id != part_id {
-        return p_id;
-    } else if o_id % 100 >= TPCC_PART_OFFLOAD_RATIO {
-        return p_id;
-    } else {
-        return p_id + 100;
-    }
-}
\ No newline at end of file
+fn stock_on_dpu(stock_id: usize) -> bool {
+    item_on_dpu(stock_id_to_item_id(stock_id))
+}
+
+// of the stocks, starts as the static split, then follows the accesses
+pub fn new_stock_offload_map() -> OffloadMap {
+    OffloadMap::new(make_stock_key(num_warehouses(), 0), OFFLOAD_RANGE_SIZE, stock_on_dpu)
+}
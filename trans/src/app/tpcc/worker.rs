@@ -11,6 +11,7 @@ use crate::framework::worker::AsyncWorker;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::framework::rpc::*;
 use crate::occ::occ_rpc_id;
+use crate::occ::CcProtocol;
 
 use super::TpccWorker;
 use super::TpccHybridWorker;
@@ -46,6 +47,12 @@ impl RpcHandler for TpccWorker {
             occ_rpc_id::LOCK_RPC => {
                 self.proc.lock_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::NODE_ADDR_RPC => {
+                self.proc.node_addr_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TABLE_LAYOUT_RPC => {
+                self.proc.table_layout_rpc_handler(src_conn, msg, size, meta);
+            }
             occ_rpc_id::VALIDATE_RPC => {
                 self.proc.validate_rpc_handler(src_conn, msg, size, meta);
             }
@@ -58,11 +65,42 @@ impl RpcHandler for TpccWorker {
             occ_rpc_id::ABORT_RPC => {
                 self.proc.abort_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::DELTA_RPC => {
+                self.proc.delta_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::GROUP_COMMIT_RPC => {
+                self.proc.group_commit_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_READ_RPC => {
+                self.proc.tpl_read_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_LOCK_RPC => {
+                self.proc.tpl_lock_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_RELEASE_RPC => {
+                self.proc.tpl_release_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_READ_RPC => {
+                self.proc.tictoc_read_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_LOCK_RPC => {
+                self.proc.tictoc_lock_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_VALIDATE_RPC => {
+                self.proc.tictoc_validate_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TICTOC_COMMIT_RPC => {
+                self.proc.tictoc_commit_rpc_handler(src_conn, msg, size, meta);
+            }
             _ => {
                 unimplemented!();
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(self.cc, peer_id);
+    }
 }
 
 
@@ -154,6 +192,10 @@ impl RpcHandler for TpccHybridWorker {
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(CcProtocol::Occ, peer_id);
+    }
 }
 
 
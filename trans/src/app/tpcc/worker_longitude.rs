@@ -1,4 +1,6 @@
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
 use tokio::sync::mpsc;
 use tokio::sync::Mutex as AsyncMutex;
 
@@ -11,11 +13,16 @@ use crate::framework::worker::AsyncWorker;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::framework::rpc::*;
 use crate::occ::occ_rpc_id;
+use crate::occ::CcProtocol;
+use crate::occ::RpcMigrator;
+use crate::common::offload::OffloadCtrl;
+use crate::common::cluster::dpu_peer_id;
 
 use super::TpccHybridLongitudeWorker;
 use super::TpccHostLongitudeWorker;
 use super::TpccClientReq;
 use super::TpccWorkLoadId;
+use super::tpcc_table_id;
 
 impl AsyncWorker for TpccHybridLongitudeWorker {
     fn get_scheduler(&self) -> &crate::framework::scheduler::AsyncScheduler {
@@ -58,11 +65,21 @@ impl RpcHandler for TpccHybridLongitudeWorker {
             occ_rpc_id::ABORT_RPC => {
                 self.proc.abort_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            occ_rpc_id::OFFLOAD_FREEZE_RPC => {
+                self.proc.offload_freeze_rpc_handler(self.offload.as_ref(), src_conn, msg, size, meta);
+            }
+            occ_rpc_id::OFFLOAD_PUBLISH_RPC => {
+                self.proc.offload_publish_rpc_handler(self.offload.as_ref(), src_conn, msg, size, meta);
+            }
             _ => {
                 unimplemented!();
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(CcProtocol::Occ, peer_id);
+    }
 }
 
 
@@ -149,11 +166,25 @@ impl RpcHandler for TpccHostLongitudeWorker {
             occ_rpc_id::ABORT_RPC => {
                 self.proc.abort_cache_rpc_handler(src_conn, msg, size, meta);
             }
+            // on the dpu, from the offload controller of its host
+            occ_rpc_id::OFFLOAD_INSTALL_RPC => {
+                self.proc.offload_install_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::OFFLOAD_EXTRACT_RPC => {
+                self.proc.offload_extract_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::OFFLOAD_RELEASE_RPC => {
+                self.proc.offload_release_rpc_handler(src_conn, msg, size, meta);
+            }
             _ => {
                 unimplemented!();
             }
         }
     }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(CcProtocol::Occ, peer_id);
+    }
 }
 
 
@@ -198,6 +229,16 @@ impl TpccHostLongitudeWorker {
         }
     }
 
+    // as the one of small bank, only the stocks move
+    pub async fn run_offload(self: &Arc<Self>, cid: u32, peers: &[u64], dpu_share: f64, interval: Duration, running: &Arc<AtomicBool>) {
+        let map = self.offload.as_ref().expect("the worker has no offload map");
+        let dpu_id = dpu_peer_id(self.part_id).expect("the cluster config gives the partition no dpu");
+
+        let migrator = RpcMigrator::new(&self.memdb, &self.scheduler, cid, dpu_id, peers, &[tpcc_table_id::STOCKS_TABLE_ID]);
+        let mut ctrl = OffloadCtrl::new(map, migrator, dpu_share);
+        ctrl.run(interval, running).await;
+    }
+
     pub async fn run_main(self: &Arc<Self>, rand_seed: usize) {
     
         let self_clone = self.clone();
@@ -1,21 +1,38 @@
 use crate::common::random::FastRandom;
 use crate::occ::occ_hybrid::OccHybrid;
-use crate::occ::occ_remote::OccRemote;
+use crate::occ::CcTxn;
+use crate::TPCC_GROUP_COMMIT;
+use crate::TPCC_CAS_LOCK;
 
 use super::*;
 use super::utils::*;
 
 impl TpccWorker {
     pub async fn txn_new_order(&self, rand_gen: &mut FastRandom, cid: u32) {
-        let mut txn = OccRemote::<TPCC_ITEM_SIZE>::new(
-            self.part_id, 
-            self.tid,
-            cid, 
-            &self.memdb, 
-            &self.scheduler,
-        );
+        let mut txn = if TPCC_GROUP_COMMIT {
+            CcTxn::<TPCC_ITEM_SIZE>::new_grouped(
+                self.cc,
+                self.part_id, 
+                self.tid,
+                cid, 
+                &self.memdb, 
+                &self.scheduler,
+                &self.group,
+            )
+        } else {
+            CcTxn::<TPCC_ITEM_SIZE>::new(
+                self.cc,
+                self.part_id, 
+                self.tid,
+                cid, 
+                &self.memdb, 
+                &self.scheduler,
+            )
+        };
 
-        txn.start();
+        if TPCC_CAS_LOCK {
+            txn.set_cas_lock(&self.cas_locks);
+        }
 
         let w_id = rand_gen.next() % num_warehouses();
         let d_id = (rand_gen.next() % 10) + 10 * w_id;
@@ -26,58 +43,61 @@ impl TpccWorker {
         let mut stocks = Vec::new();
         random_get_stocks(stock_count, rand_gen, &mut stocks);
 
-        for i in 0..stock_count {
-            txn.fetch_write::<TpccStocks>(
-                tpcc_table_id::STOCKS_TABLE_ID,
-                warehouse_id_to_part_id(stock_id_to_warehouse_id(stocks[i])) as _,
-                stocks[i] as _,
-            );
+        // drawn once, a retry orders the same
+        let mut ol_quantities = Vec::new();
+        for _ in 0..stock_count {
+            ol_quantities.push(rand_gen.next() % 10 + 1);
+            let i_price = rand_gen.next_uniform() * 10000.0;
         }
 
-        let idx = txn.fetch_write::<TpccDistricts>(
-            tpcc_table_id::DISTRICTS_TABLE_ID,
-            warehouse_id_to_part_id(w_id) as _,
-            d_id as _,
-        );
+        loop {
+            txn.start();
 
-        let mut dist = txn.get_value::<TpccDistricts>(true, idx).await.clone();
-        let o_id = make_order_key(d_id, dist.d_next_o_id as _);
-
-        dist.d_next_o_id = next_o_id(dist.d_next_o_id as _) as _;
-        txn.set_value(true, idx, &dist);
-
-        for i in 0..stock_count {
-            let mut stock = txn.get_value::<TpccStocks>(true, i).await.clone();
-            let ol_quantity = rand_gen.next() % 10 + 1;
-            let i_price = rand_gen.next_uniform() * 10000.0;
+            let idx = txn.fetch_write_placed::<TpccDistricts>(
+                tpcc_table_id::DISTRICTS_TABLE_ID,
+                &*self.placement.districts,
+                d_id as _,
+            );
 
-            if stock.s_quantity >= 10 + ol_quantity as u64 {
-                stock.s_quantity -= ol_quantity as u64;
-            } else {
-                stock.s_quantity += 91 - ol_quantity as u64;
+            let mut dist = txn.get_value::<TpccDistricts>(true, idx).await.clone();
+            let o_id = make_order_key(d_id, dist.d_next_o_id as _);
+
+            dist.d_next_o_id = next_o_id(dist.d_next_o_id as _) as _;
+            txn.set_value(true, idx, &dist);
+
+            // stocks are not read, the owner merges the order into them
+            for i in 0..stock_count {
+                txn.apply_delta(
+                    tpcc_table_id::STOCKS_TABLE_ID,
+                    self.placement.stocks.part_of(stocks[i]) as _,
+                    stocks[i] as _,
+                    tpcc_merge_id::STOCK_ORDER,
+                    &TpccStockDelta{ ol_quantity: ol_quantities[i] as u64 },
+                );
             }
 
-            txn.set_value(true, i, &stock)
-        }
-
-        let order = TpccOrders {
-            o_c_id:       c_id as _,
-            o_carrier_id: 0,
-            o_all_local:  true as _,
-            o_ol_cnt:     stock_count as _,
-            o_entry_d:    0,
-        };
-
-        let idx = txn.write::<TpccOrders>(
-            tpcc_table_id::ORDERS_TABLE_ID, 
-            order_id_to_part_id(o_id) as _, 
-            o_id as _, 
-            crate::occ::RwType::UPDATE,
-        );
+            let order = TpccOrders {
+                o_c_id:       c_id as _,
+                o_carrier_id: 0,
+                o_all_local:  true as _,
+                o_ol_cnt:     stock_count as _,
+                o_entry_d:    0,
+            };
+
+            let idx = txn.write_placed::<TpccOrders>(
+                tpcc_table_id::ORDERS_TABLE_ID, 
+                &*self.placement.orders, 
+                o_id as _, 
+                crate::occ::RwType::UPDATE,
+            );
 
-        txn.set_value(false, idx, &order);
+            txn.set_value(false, idx, &order);
 
-        txn.commit().await;
+            txn.commit().await;
+            if !txn.should_retry() {
+                break;
+            }
+        }
 
     }
 
@@ -110,14 +130,14 @@ impl TpccHybridWorker {
         for i in 0..stock_count {
             txn.fetch_write::<TpccStocks>(
                 tpcc_table_id::STOCKS_TABLE_ID,
-                warehouse_id_to_part_id(stock_id_to_warehouse_id(stocks[i])) as _,
+                self.placement.stocks.part_of(stocks[i]) as _,
                 stocks[i] as _,
             );
         }
 
         let idx = txn.fetch_write::<TpccDistricts>(
             tpcc_table_id::DISTRICTS_TABLE_ID,
-            warehouse_id_to_part_id(w_id) as _,
+            self.placement.districts.part_of(d_id) as _,
             d_id as _,
         );
 
@@ -132,11 +152,7 @@ impl TpccHybridWorker {
             let ol_quantity = rand_gen.next() % 10 + 1;
             let i_price = rand_gen.next_uniform() * 10000.0;
 
-            if stock.s_quantity >= 10 + ol_quantity as u64 {
-                stock.s_quantity -= ol_quantity as u64;
-            } else {
-                stock.s_quantity += 91 - ol_quantity as u64;
-            }
+            stock.s_quantity = stock_order_quantity(stock.s_quantity, ol_quantity as u64);
 
             txn.set_value(true, i, &stock)
         }
@@ -151,7 +167,7 @@ impl TpccHybridWorker {
 
         let idx = txn.write::<TpccOrders>(
             tpcc_table_id::ORDERS_TABLE_ID, 
-            order_id_to_part_id(o_id) as _, 
+            self.placement.orders.part_of(o_id) as _, 
             o_id as _, 
             crate::occ::RwType::UPDATE,
         );
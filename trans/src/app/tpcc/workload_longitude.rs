@@ -27,16 +27,16 @@ impl TpccHybridLongitudeWorker {
         random_get_stocks(stock_count, rand_gen, &mut stocks);
 
         for i in 0..stock_count {
-            txn.fetch_write::<TpccStocks>(
+            txn.fetch_write_placed::<TpccStocks>(
                 tpcc_table_id::STOCKS_TABLE_ID,
-                stock_id_to_part_id_hybrid_longitude(stocks[i], self.part_id as _) as _,
+                &*self.placement.stocks,
                 stocks[i] as _,
             );
         }
 
-        let idx = txn.fetch_write::<TpccDistricts>(
+        let idx = txn.fetch_write_placed::<TpccDistricts>(
             tpcc_table_id::DISTRICTS_TABLE_ID,
-            dist_id_to_part_id_hybrid_longitude(d_id, self.part_id as _) as _,
+            &*self.placement.districts,
             d_id as _,
         );
 
@@ -51,11 +51,7 @@ impl TpccHybridLongitudeWorker {
             let ol_quantity = rand_gen.next() % 10 + 1;
             let i_price = rand_gen.next_uniform() * 10000.0;
 
-            if stock.s_quantity >= 10 + ol_quantity as u64 {
-                stock.s_quantity -= ol_quantity as u64;
-            } else {
-                stock.s_quantity += 91 - ol_quantity as u64;
-            }
+            stock.s_quantity = stock_order_quantity(stock.s_quantity, ol_quantity as u64);
 
             txn.set_value(true, i, &stock)
         }
@@ -68,9 +64,9 @@ impl TpccHybridLongitudeWorker {
             o_entry_d:    0,
         };
 
-        let idx = txn.write::<TpccOrders>(
+        let idx = txn.write_placed::<TpccOrders>(
             tpcc_table_id::ORDERS_TABLE_ID, 
-            order_id_to_part_id_hybrid_longitude(o_id, self.part_id as _) as _, 
+            &*self.placement.orders, 
             o_id as _, 
             crate::occ::RwType::UPDATE,
         );
@@ -108,16 +104,16 @@ impl TpccHostLongitudeWorker {
         random_get_stocks(stock_count, rand_gen, &mut stocks);
 
         for i in 0..stock_count {
-            txn.fetch_write::<TpccStocks>(
+            txn.fetch_write_placed::<TpccStocks>(
                 tpcc_table_id::STOCKS_TABLE_ID,
-                stock_id_to_part_id_host_longitude(stocks[i], self.part_id as _) as _,
+                &*self.placement.stocks,
                 stocks[i] as _,
             );
         }
 
-        let idx = txn.fetch_write::<TpccDistricts>(
+        let idx = txn.fetch_write_placed::<TpccDistricts>(
             tpcc_table_id::DISTRICTS_TABLE_ID,
-            dist_id_to_part_id_host_longitude(d_id, self.part_id as _) as _,
+            &*self.placement.districts,
             d_id as _,
         );
 
@@ -132,11 +128,7 @@ impl TpccHostLongitudeWorker {
             let ol_quantity = rand_gen.next() % 10 + 1;
             let i_price = rand_gen.next_uniform() * 10000.0;
 
-            if stock.s_quantity >= 10 + ol_quantity as u64 {
-                stock.s_quantity -= ol_quantity as u64;
-            } else {
-                stock.s_quantity += 91 - ol_quantity as u64;
-            }
+            stock.s_quantity = stock_order_quantity(stock.s_quantity, ol_quantity as u64);
 
             txn.set_value(true, i, &stock)
         }
@@ -149,9 +141,9 @@ impl TpccHostLongitudeWorker {
             o_entry_d:    0,
         };
 
-        let idx = txn.write::<TpccOrders>(
+        let idx = txn.write_placed::<TpccOrders>(
             tpcc_table_id::ORDERS_TABLE_ID, 
-            order_id_to_part_id_host_longitude(o_id, self.part_id as _) as _, 
+            &*self.placement.orders, 
             o_id as _, 
             crate::occ::RwType::UPDATE,
         );
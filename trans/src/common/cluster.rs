@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde_json::Value;
+
+use crate::common::partition::PartitionerConfig;
+use crate::occ::CcProtocol;
+use crate::rdma::device::RdmaDeviceConfig;
+use crate::{TransError, TransResult, DPU_PEER_ID_BASE};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeRole {
+    Host,
+    Dpu,
+    Client,
+}
+
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    pub id: u64,
+    pub role: NodeRole,
+    pub ip: String,
+    // thread tid listens on port + tid, and dma_port + tid for doca dma
+    pub port: u16,
+    pub dma_port: u16,
+    pub threads: usize,
+    pub partitions: Vec<u64>,
+    // the dpu peer of a host
+    pub dpu: Option<u64>,
+    // the peers it dials, the others dial it
+    pub connects: Vec<u64>,
+    // the devices of the dma and the comm channel, split evenly among the threads
+    pub pci_addrs: Vec<String>,
+    pub pci_addrs_rep: Vec<String>,
+    pub comm_name: String,
+    // the nic ports of the rdma connections, split among the threads as the devices
+    pub rdma_devices: Vec<RdmaDeviceConfig>,
+    // the rdma connections of a thread share a receive queue
+    pub srq: bool,
+    // the other ud nodes without a rc connection are reached by datagrams,
+    // thread tid exchanges the address of its ud qp on ud_port + tid
+    pub ud: bool,
+    pub ud_port: u16,
+}
+
+/// The nodes of the cluster, loaded from a json file at startup, so that the peers,
+/// their partitions and the dpus of the hosts are not compiled in.
+/// The dial edges must not form a cycle, a node dials its peers before it listens.
+#[derive(Clone, Debug, Default)]
+pub struct ClusterConfig {
+    nodes: Vec<NodeConfig>,
+    // the placements of the keys by name, the apps fall back to theirs
+    placements: HashMap<String, PartitionerConfig>,
+    // all the peers run the same one
+    cc_protocol: Option<CcProtocol>,
+    // the dpu of the host of each partition, indexed once the nodes are checked
+    part_dpus: HashMap<u64, u64>,
+}
+
+static CLUSTER: OnceLock<ClusterConfig> = OnceLock::new();
+
+// installs the config of the process, only once
+pub fn set_cluster(config: ClusterConfig) -> TransResult<()> {
+    CLUSTER.set(config).map_err(|_| TransError::TransConfigError)
+}
+
+pub fn get_cluster() -> Option<&'static ClusterConfig> {
+    CLUSTER.get()
+}
+
+// the dpu serving the partition, part_id + DPU_PEER_ID_BASE without a config,
+// an error if the installed config gives the partition no dpu
+#[inline]
+pub fn dpu_peer_id(part_id: u64) -> TransResult<u64> {
+    match get_cluster() {
+        Some(cluster) => cluster.dpu_of_part(part_id).ok_or(TransError::TransConfigError),
+        None => Ok(part_id + DPU_PEER_ID_BASE),
+    }
+}
+
+fn get_u64(value: &Value, key: &str) -> TransResult<u64> {
+    value.get(key).and_then(Value::as_u64).ok_or(TransError::TransConfigError)
+}
+
+fn get_str(value: &Value, key: &str) -> TransResult<String> {
+    value.get(key)
+        .and_then(Value::as_str)
+        .map(String::from)
+        .ok_or(TransError::TransConfigError)
+}
+
+// missing arrays are empty
+fn get_u64_vec(value: &Value, key: &str) -> TransResult<Vec<u64>> {
+    match value.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_u64().ok_or(TransError::TransConfigError))
+            .collect(),
+        Some(_) => Err(TransError::TransConfigError),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn get_str_vec(value: &Value, key: &str) -> TransResult<Vec<String>> {
+    match value.get(key) {
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(String::from).ok_or(TransError::TransConfigError))
+            .collect(),
+        Some(_) => Err(TransError::TransConfigError),
+        None => Ok(Vec::new()),
+    }
+}
+
+// {"name": "mlx5_0", "port": 1, "gid_index": 3, "ip": "10.10.10.6"}, all optional
+fn get_rdma_devices(value: &Value) -> TransResult<Vec<RdmaDeviceConfig>> {
+    let items = match value.get("rdma_devices") {
+        Some(Value::Array(items)) => items,
+        Some(_) => return Err(TransError::TransConfigError),
+        None => return Ok(Vec::new()),
+    };
+
+    let mut devices = Vec::new();
+    for item in items.iter() {
+        let mut device = RdmaDeviceConfig::default();
+        device.name = get_str(item, "name").ok();
+        device.ip = get_str(item, "ip").ok();
+        if let Ok(port_num) = get_u64(item, "port") {
+            device.port_num = port_num as _;
+        }
+        if let Ok(gid_index) = get_u64(item, "gid_index") {
+            device.gid_index = gid_index as _;
+        }
+        devices.push(device);
+    }
+    Ok(devices)
+}
+
+impl NodeConfig {
+    fn from_json(value: &Value) -> TransResult<Self> {
+        let role = match get_str(value, "role")?.as_str() {
+            "host" => NodeRole::Host,
+            "dpu" => NodeRole::Dpu,
+            "client" => NodeRole::Client,
+            _ => return Err(TransError::TransConfigError),
+        };
+
+        let port = get_u64(value, "port")?;
+        Ok(Self {
+            id: get_u64(value, "id")?,
+            role: role,
+            ip: get_str(value, "ip")?,
+            port: port as _,
+            dma_port: get_u64(value, "dma_port").unwrap_or(port + 100) as _,
+            threads: get_u64(value, "threads")? as _,
+            partitions: get_u64_vec(value, "partitions")?,
+            dpu: get_u64(value, "dpu").ok(),
+            connects: get_u64_vec(value, "connects")?,
+            pci_addrs: get_str_vec(value, "pci_addrs")?,
+            pci_addrs_rep: get_str_vec(value, "pci_addrs_rep")?,
+            comm_name: get_str(value, "comm_name").unwrap_or(String::from("comm")),
+            rdma_devices: get_rdma_devices(value)?,
+            srq: value.get("srq").and_then(Value::as_bool).unwrap_or(false),
+            ud: value.get("ud").and_then(Value::as_bool).unwrap_or(false),
+            ud_port: get_u64(value, "ud_port").unwrap_or(port + 200) as _,
+        })
+    }
+
+    // nul-terminated for rdma cm
+    #[inline]
+    pub fn get_ip(&self) -> String {
+        format!("{}\0", self.ip)
+    }
+
+    #[inline]
+    pub fn get_conn_port(&self, tid: usize) -> String {
+        format!("{}\0", self.port as usize + tid)
+    }
+
+    #[inline]
+    pub fn get_dma_addr(&self, tid: usize) -> String {
+        format!("{}:{}", self.ip, self.dma_port as usize + tid)
+    }
+
+    #[inline]
+    pub fn get_ud_addr(&self, tid: usize) -> String {
+        format!("{}:{}", self.ip, self.ud_port as usize + tid)
+    }
+
+    #[inline]
+    pub fn get_comm_name(&self, tid: usize) -> String {
+        format!("{}{}\0", self.comm_name, tid)
+    }
+
+    // the first threads take the first device
+    pub fn get_pci_addr(&self, tid: usize) -> Option<&str> {
+        Self::split_among(&self.pci_addrs, tid, self.threads).map(String::as_str)
+    }
+
+    pub fn get_pci_addr_rep(&self, tid: usize) -> Option<&str> {
+        Self::split_among(&self.pci_addrs_rep, tid, self.threads).map(String::as_str)
+    }
+
+    pub fn get_rdma_device(&self, tid: usize) -> Option<&RdmaDeviceConfig> {
+        Self::split_among(&self.rdma_devices, tid, self.threads)
+    }
+
+    fn split_among<T>(items: &[T], tid: usize, threads: usize) -> Option<&T> {
+        if items.is_empty() || threads == 0 {
+            return None;
+        }
+        items.get(tid * items.len() / threads)
+    }
+}
+
+impl ClusterConfig {
+    pub fn from_json(data: &str) -> TransResult<Self> {
+        let value: Value = serde_json::from_str(data).map_err(|_| TransError::TransConfigError)?;
+        let nodes = match value.get("nodes") {
+            Some(Value::Array(nodes)) => nodes
+                .iter()
+                .map(NodeConfig::from_json)
+                .collect::<TransResult<Vec<_>>>()?,
+            _ => return Err(TransError::TransConfigError),
+        };
+
+        let placements = match value.get("placements") {
+            Some(Value::Object(placements)) => placements
+                .iter()
+                .map(|(name, placement)| Ok((name.clone(), PartitionerConfig::from_json(placement)?)))
+                .collect::<TransResult<HashMap<_, _>>>()?,
+            Some(_) => return Err(TransError::TransConfigError),
+            None => HashMap::new(),
+        };
+
+        let cc_protocol = match value.get("cc_protocol") {
+            Some(Value::String(name)) => Some(CcProtocol::from_name(name).ok_or(TransError::TransConfigError)?),
+            Some(_) => return Err(TransError::TransConfigError),
+            None => None,
+        };
+
+        let mut config = Self {
+            nodes: nodes,
+            placements: placements,
+            cc_protocol: cc_protocol,
+            part_dpus: HashMap::new(),
+        };
+        config.check()?;
+        config.part_dpus = config.index_part_dpus();
+        Ok(config)
+    }
+
+    pub fn load(path: &std::path::Path) -> TransResult<Self> {
+        let data = std::fs::read_to_string(path).map_err(|_| TransError::TransConfigError)?;
+        Self::from_json(&data)
+    }
+
+    // unique ids, known peers, and a partition has only one host
+    fn check(&self) -> TransResult<()> {
+        for (i, node) in self.nodes.iter().enumerate() {
+            if self.nodes[..i].iter().any(|other| other.id == node.id) {
+                return Err(TransError::TransConfigError);
+            }
+
+            let peers_known = node.connects.iter().chain(node.dpu.iter())
+                .all(|peer_id| self.get_node(*peer_id).is_some());
+            if !peers_known {
+                return Err(TransError::TransConfigError);
+            }
+
+            for part_id in node.partitions.iter() {
+                if node.role == NodeRole::Host && self.host_of_part(*part_id) != Some(node.id) {
+                    return Err(TransError::TransConfigError);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index_part_dpus(&self) -> HashMap<u64, u64> {
+        self.nodes.iter()
+            .filter(|node| node.role == NodeRole::Host)
+            .filter_map(|node| Some((node, node.dpu?)))
+            .flat_map(|(node, dpu_id)| node.partitions.iter().map(move |part_id| (*part_id, dpu_id)))
+            .collect()
+    }
+
+    #[inline]
+    pub fn get_nodes(&self) -> &Vec<NodeConfig> {
+        &self.nodes
+    }
+
+    pub fn get_placement(&self, name: &str) -> Option<&PartitionerConfig> {
+        self.placements.get(name)
+    }
+
+    #[inline]
+    pub fn get_cc_protocol(&self) -> Option<CcProtocol> {
+        self.cc_protocol
+    }
+
+    pub fn get_node(&self, id: u64) -> Option<&NodeConfig> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    // the first host holding the partition
+    pub fn host_of_part(&self, part_id: u64) -> Option<u64> {
+        self.nodes.iter()
+            .find(|node| node.role == NodeRole::Host && node.partitions.contains(&part_id))
+            .map(|node| node.id)
+    }
+
+    #[inline]
+    pub fn dpu_of_part(&self, part_id: u64) -> Option<u64> {
+        self.part_dpus.get(&part_id).copied()
+    }
+
+    // the host served by the dpu
+    pub fn host_of_dpu(&self, dpu_id: u64) -> Option<u64> {
+        self.nodes.iter()
+            .find(|node| node.dpu == Some(dpu_id))
+            .map(|node| node.id)
+    }
+
+    // the peers dialing the node
+    pub fn count_accepts(&self, id: u64) -> usize {
+        self.nodes.iter()
+            .filter(|node| node.connects.contains(&id))
+            .count()
+    }
+
+    // the ud nodes the node reaches by datagrams, none if it is not one
+    pub fn get_ud_peer_ids(&self, id: u64) -> Vec<u64> {
+        let ud = self.get_node(id).map_or(false, |node| node.ud);
+        if !ud {
+            return Vec::new();
+        }
+
+        let rc_peers = self.get_peer_ids(id);
+        self.nodes.iter()
+            .filter(|node| node.ud && node.id != id && !rc_peers.contains(&node.id))
+            .map(|node| node.id)
+            .collect()
+    }
+
+    // the peers connected to the node either way
+    pub fn get_peer_ids(&self, id: u64) -> Vec<u64> {
+        let mut peers: Vec<u64> = self.get_node(id)
+            .map(|node| node.connects.clone())
+            .unwrap_or_default();
+        for node in self.nodes.iter() {
+            if node.connects.contains(&id) {
+                peers.push(node.id);
+            }
+        }
+        peers
+    }
+}
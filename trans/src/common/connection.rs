@@ -5,37 +5,215 @@
  * 
  */
 
-use std::io::{ Read, Write };
+use std::fmt;
+use std::io::{ ErrorKind, Read, Write };
 use std::net::{ SocketAddr, TcpStream, TcpListener };
+use std::thread::sleep;
+use std::time::{ Duration, Instant };
 
-const MAX_CONN_LENGTH: usize = 4096;
+use crate::{ TransError, TransResult };
 
-pub trait ConfigSerialize {
+// a frame is the magic, the version and the length in little endian, then the config
+const CONFIG_MAGIC: u32 = 0x6663_7274;
+const CONFIG_VERSION: u16 = 1;
+const CONFIG_HEADER_LENGTH: usize = 10;
+// a larger config is refused
+const MAX_CONFIG_LENGTH: usize = 1 << 20;
+
+// the status the receiver answers with
+const CONFIG_ACK_OK: u8 = 0;
+const CONFIG_ACK_BAD_VERSION: u8 = 1;
+const CONFIG_ACK_TOO_LARGE: u8 = 2;
+const CONFIG_ACK_MALFORMED: u8 = 3;
+
+// a config that does not parse is refused to its sender
+pub trait ConfigSerialize: Sized {
     fn serialize(data: Self) -> Vec<u8>;
-    fn deserialize(data: &[u8]) -> Self;
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError>;
 }
 
-#[inline]
-pub fn recv_config<T: ConfigSerialize>(addr: SocketAddr) -> T {
-    let mut conn_info = [0u8; MAX_CONN_LENGTH];
-    let mut conn_info_len = 0;
-    /* receive the buffer message from the host */
-    let listener = TcpListener::bind(addr).unwrap();
+/// Why a config was not exchanged. The receiver drops a bad frame and waits
+/// for another, the sender retries unless the receiver refused its config.
+#[derive(Debug)]
+pub enum ConfigExchangeError {
+    Io(std::io::Error),
+    Timeout,
+    BadMagic(u32),
+    BadVersion(u16),
+    TooLarge(usize),
+    Malformed(String),
+    // the status of the receiver
+    Rejected(u8),
+}
+
+impl fmt::Display for ConfigExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "config exchange failed, {}", e),
+            Self::Timeout => write!(f, "config exchange timed out"),
+            Self::BadMagic(magic) => write!(f, "not a config frame, magic = {:#x}", magic),
+            Self::BadVersion(version) => write!(f, "config version {} instead of {}", version, CONFIG_VERSION),
+            Self::TooLarge(len) => write!(f, "config of {} bytes, at most {}", len, MAX_CONFIG_LENGTH),
+            Self::Malformed(reason) => write!(f, "malformed config, {}", reason),
+            Self::Rejected(status) => write!(f, "config rejected by the peer, status = {}", status),
+        }
+    }
+}
+
+impl From<std::io::Error> for ConfigExchangeError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => Self::Timeout,
+            _ => Self::Io(e),
+        }
+    }
+}
+
+impl From<ConfigExchangeError> for TransError {
+    fn from(e: ConfigExchangeError) -> Self {
+        TransError::TransExchangeError(e)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfigExchangeOptions {
+    // the connects of the sender, and the bad frames the receiver drops
+    pub retries: usize,
+    pub retry_interval: Duration,
+    // of every read and write
+    pub io_timeout: Duration,
+    // how long the receiver waits for its peer, forever if none
+    pub accept_timeout: Option<Duration>,
+}
+
+impl Default for ConfigExchangeOptions {
+    fn default() -> Self {
+        Self {
+            retries: 100,
+            retry_interval: Duration::from_millis(100),
+            io_timeout: Duration::from_secs(5),
+            accept_timeout: None,
+        }
+    }
+}
+
+fn recv_frame<T: ConfigSerialize>(mut stream: TcpStream, options: &ConfigExchangeOptions) -> Result<T, ConfigExchangeError> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(options.io_timeout))?;
+    stream.set_write_timeout(Some(options.io_timeout))?;
+
+    let mut header = [0u8; CONFIG_HEADER_LENGTH];
+    stream.read_exact(&mut header)?;
+    let magic = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let version = u16::from_le_bytes(header[4..6].try_into().unwrap());
+    let len = u32::from_le_bytes(header[6..10].try_into().unwrap()) as usize;
+
+    if magic != CONFIG_MAGIC {
+        return Err(ConfigExchangeError::BadMagic(magic));
+    }
+    if version != CONFIG_VERSION {
+        let _ = stream.write_all(&[CONFIG_ACK_BAD_VERSION]);
+        return Err(ConfigExchangeError::BadVersion(version));
+    }
+    if len > MAX_CONFIG_LENGTH {
+        let _ = stream.write_all(&[CONFIG_ACK_TOO_LARGE]);
+        return Err(ConfigExchangeError::TooLarge(len));
+    }
+
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data)?;
+    match T::deserialize(&data) {
+        Ok(config) => {
+            stream.write_all(&[CONFIG_ACK_OK])?;
+            Ok(config)
+        }
+        Err(e) => {
+            let _ = stream.write_all(&[CONFIG_ACK_MALFORMED]);
+            Err(e)
+        }
+    }
+}
+
+fn send_frame(addr: SocketAddr, data: &[u8], options: &ConfigExchangeOptions) -> Result<(), ConfigExchangeError> {
+    let mut stream = TcpStream::connect_timeout(&addr, options.io_timeout)?;
+    stream.set_read_timeout(Some(options.io_timeout))?;
+    stream.set_write_timeout(Some(options.io_timeout))?;
+
+    let mut frame = Vec::with_capacity(CONFIG_HEADER_LENGTH + data.len());
+    frame.extend_from_slice(&CONFIG_MAGIC.to_le_bytes());
+    frame.extend_from_slice(&CONFIG_VERSION.to_le_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    frame.extend_from_slice(data);
+    stream.write_all(&frame)?;
+
+    let mut ack = [0u8; 1];
+    stream.read_exact(&mut ack)?;
+    if ack[0] != CONFIG_ACK_OK {
+        return Err(ConfigExchangeError::Rejected(ack[0]));
+    }
+    Ok(())
+}
+
+// accepts until a peer hands over a whole config
+pub fn recv_config_with<T: ConfigSerialize>(addr: SocketAddr, options: &ConfigExchangeOptions) -> TransResult<T> {
+    let listener = TcpListener::bind(addr).map_err(ConfigExchangeError::Io)?;
+    listener.set_nonblocking(true).map_err(ConfigExchangeError::Io)?;
+
+    let deadline = options.accept_timeout.map(|timeout| Instant::now() + timeout);
+    let mut dropped = 0;
     loop {
-        if let Ok(res) = listener.accept() {
-            let (mut stream, _) = res;
-            conn_info_len = stream.read(&mut conn_info).unwrap();
-            break;
+        match listener.accept() {
+            Ok((stream, peer)) => match recv_frame::<T>(stream, options) {
+                Ok(config) => return Ok(config),
+                Err(e) => {
+                    println!("config from {} dropped, {}", peer, e);
+                    dropped += 1;
+                    if dropped > options.retries {
+                        return Err(e.into());
+                    }
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => sleep(Duration::from_millis(1)),
+            Err(e) => return Err(ConfigExchangeError::Io(e).into()),
+        }
+
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Err(ConfigExchangeError::Timeout.into());
         }
     }
+}
 
-    T::deserialize(&conn_info[0..conn_info_len])
+// connects until the receiver listens and acknowledges the config
+pub fn send_config_with<T: ConfigSerialize>(addr: SocketAddr, config: T, options: &ConfigExchangeOptions) -> TransResult<()> {
+    let data = T::serialize(config);
+    if data.len() > MAX_CONFIG_LENGTH {
+        return Err(ConfigExchangeError::TooLarge(data.len()).into());
+    }
+
+    let mut tries = 0;
+    loop {
+        match send_frame(addr, &data, options) {
+            Ok(()) => return Ok(()),
+            // resending the same config does not help
+            Err(e @ ConfigExchangeError::Rejected(_)) => return Err(e.into()),
+            Err(e) => {
+                tries += 1;
+                if tries > options.retries {
+                    println!("config to {} not sent, {}", addr, e);
+                    return Err(e.into());
+                }
+                sleep(options.retry_interval);
+            }
+        }
+    }
 }
 
 #[inline]
-pub fn send_config<T: ConfigSerialize>(addr: SocketAddr, config: T) {
-    let mut stream = TcpStream::connect(addr).unwrap();
-    let data = T::serialize(config);
+pub fn recv_config<T: ConfigSerialize>(addr: SocketAddr) -> TransResult<T> {
+    recv_config_with(addr, &ConfigExchangeOptions::default())
+}
 
-    stream.write(data.as_slice()).unwrap();
-}
\ No newline at end of file
+#[inline]
+pub fn send_config<T: ConfigSerialize>(addr: SocketAddr, config: T) -> TransResult<()> {
+    send_config_with(addr, config, &ConfigExchangeOptions::default())
+}
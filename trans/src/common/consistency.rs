@@ -0,0 +1,474 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::common::connection::{ recv_config, recv_config_with, send_config, ConfigExchangeError, ConfigExchangeOptions, ConfigSerialize };
+use crate::framework::quiesce::QuiesceGate;
+use crate::memstore::memdb::{ MemDB, ValueDB };
+use crate::memstore::MemNodeMeta;
+use crate::{ TransError, TransResult };
+
+// a systematic bug would report every key, the rest are only counted
+const MAX_REPORTED_DISCREPANCIES: usize = 1024;
+
+/// The keys a host holds the values of in the host-offload mode, per table,
+/// to be checked against the meta its DPU holds for them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HostSnapshot {
+    // (table_id, item length, sorted keys)
+    tables: Vec<(usize, usize, Vec<u64>)>,
+}
+
+impl HostSnapshot {
+    pub fn of_valuedb(valuedb: &ValueDB, table_ids: &[usize]) -> TransResult<Self> {
+        let mut tables = Vec::with_capacity(table_ids.len());
+        for table_id in table_ids {
+            let mut keys = Vec::new();
+            if !valuedb.local_scan_keys(*table_id, &mut |key: u64| keys.push(key)) {
+                return Err(TransError::TransConfigError);
+            }
+
+            keys.sort_unstable();
+            tables.push((*table_id, valuedb.get_item_length(*table_id), keys));
+        }
+
+        Ok(Self { tables: tables })
+    }
+
+    pub fn get_table_ids(&self) -> Vec<usize> {
+        self.tables.iter().map(|(table_id, _, _)| *table_id).collect()
+    }
+}
+
+// the meta the dpu holds, per table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DpuSnapshot {
+    // (table_id, item length, sorted keys with their lock)
+    tables: Vec<(usize, usize, Vec<(u64, u64)>)>,
+}
+
+impl DpuSnapshot {
+    pub fn of_memdb(memdb: &MemDB, table_ids: &[usize]) -> TransResult<Self> {
+        let mut tables = Vec::with_capacity(table_ids.len());
+        for table_id in table_ids {
+            let mut keys = Vec::new();
+            if !memdb.local_scan_meta(*table_id, &mut |key: u64, meta: MemNodeMeta| keys.push((key, meta.lock))) {
+                return Err(TransError::TransConfigError);
+            }
+
+            keys.sort_unstable();
+            tables.push((*table_id, memdb.get_item_length(*table_id), keys));
+        }
+
+        Ok(Self { tables: tables })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Discrepancy {
+    // a value without meta, the txns never reach it
+    MissingOnDpu { table_id: usize, key: u64 },
+    // meta without a value, the host has nothing to return
+    MissingOnHost { table_id: usize, key: u64 },
+    // still locked with no txn of the host or of its peers in flight
+    LockHeld { table_id: usize, key: u64, lock: u64 },
+    LengthMismatch { table_id: usize, host_len: usize, dpu_len: usize },
+}
+
+impl fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingOnDpu { table_id, key } => write!(f, "table {}, key {} is not on the dpu", table_id, key),
+            Self::MissingOnHost { table_id, key } => write!(f, "table {}, key {} is not on the host", table_id, key),
+            Self::LockHeld { table_id, key, lock } => write!(f, "table {}, key {} is still locked by {:#x}", table_id, key, lock),
+            Self::LengthMismatch { table_id, host_len, dpu_len } => {
+                write!(f, "table {}, items of {} bytes on the host, {} on the dpu", table_id, host_len, dpu_len)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableSummary {
+    pub table_id: usize,
+    pub host_keys: usize,
+    pub dpu_keys: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    pub tables: Vec<TableSummary>,
+    pub discrepancies: Vec<Discrepancy>,
+    // the ones beyond MAX_REPORTED_DISCREPANCIES
+    pub dropped: usize,
+}
+
+impl ConsistencyReport {
+    fn add(&mut self, discrepancy: Discrepancy) {
+        if self.discrepancies.len() < MAX_REPORTED_DISCREPANCIES {
+            self.discrepancies.push(discrepancy);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    #[inline]
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty() && self.dropped == 0
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for table in self.tables.iter() {
+            writeln!(f, "table {}: {} keys on the host, {} on the dpu", table.table_id, table.host_keys, table.dpu_keys)?;
+        }
+        for discrepancy in self.discrepancies.iter() {
+            writeln!(f, "{}", discrepancy)?;
+        }
+        if self.dropped != 0 {
+            writeln!(f, "{} more discrepancies", self.dropped)?;
+        }
+        if self.is_consistent() {
+            writeln!(f, "consistent")?;
+        }
+        Ok(())
+    }
+}
+
+// every key on both sides and none locked, the dpu snapshot of the tables of the host one
+pub fn check(host: &HostSnapshot, dpu: &DpuSnapshot) -> ConsistencyReport {
+    let mut report = ConsistencyReport::default();
+
+    for (table_id, host_len, host_keys) in host.tables.iter() {
+        let table_id = *table_id;
+        let (dpu_len, dpu_keys) = match dpu.tables.iter().find(|(t_id, _, _)| *t_id == table_id) {
+            Some((_, dpu_len, dpu_keys)) => (*dpu_len, dpu_keys.as_slice()),
+            None => (0, &[][..]),
+        };
+
+        report.tables.push(TableSummary {
+            table_id: table_id,
+            host_keys: host_keys.len(),
+            dpu_keys: dpu_keys.len(),
+        });
+
+        // the dpu tables of the host-offload mode keep no value
+        if dpu_len != 0 && dpu_len != *host_len {
+            report.add(Discrepancy::LengthMismatch { table_id: table_id, host_len: *host_len, dpu_len: dpu_len });
+        }
+
+        // both are sorted
+        let (mut i, mut j) = (0, 0);
+        while i < host_keys.len() || j < dpu_keys.len() {
+            if j == dpu_keys.len() || (i < host_keys.len() && host_keys[i] < dpu_keys[j].0) {
+                report.add(Discrepancy::MissingOnDpu { table_id: table_id, key: host_keys[i] });
+                i += 1;
+                continue;
+            }
+
+            let (key, lock) = dpu_keys[j];
+            if i == host_keys.len() || key < host_keys[i] {
+                report.add(Discrepancy::MissingOnHost { table_id: table_id, key: key });
+            } else {
+                i += 1;
+            }
+            if lock != 0 {
+                report.add(Discrepancy::LockHeld { table_id: table_id, key: key, lock: lock });
+            }
+            j += 1;
+        }
+    }
+
+    report
+}
+
+// on the host, with its workers and the ones of the peer hosts held back by their gates,
+// the dpu answers with the report, timeout bounds the drain of the txns and each answer.
+// The dpus run no coordinator, so with every host drained no lock is taken on them.
+// No report unless every peer paused, the ones paused are resumed either way
+pub fn check_with_dpu(
+    gate: &QuiesceGate,
+    valuedb: &ValueDB,
+    table_ids: &[usize],
+    dpu_addr: SocketAddr,
+    host_addr: SocketAddr,
+    peer_addrs: &[SocketAddr],
+    timeout: Duration,
+) -> TransResult<ConsistencyReport> {
+    if !gate.pause(timeout) {
+        gate.resume();
+        return Err(TransError::TransSyncError);
+    }
+
+    let options = ConfigExchangeOptions {
+        accept_timeout: Some(timeout),
+        ..Default::default()
+    };
+    // the acks come on the port of the report, one exchange at a time
+    let mut paused = 0;
+    while paused < peer_addrs.len() {
+        if !request_quiesce(peer_addrs[paused], host_addr, true, &options) {
+            break;
+        }
+        paused += 1;
+    }
+
+    let report = if paused < peer_addrs.len() {
+        Err(TransError::TransSyncError)
+    } else {
+        HostSnapshot::of_valuedb(valuedb, table_ids)
+            .and_then(|snapshot| send_config(dpu_addr, snapshot))
+            .and_then(|_| recv_config_with::<ConsistencyReport>(host_addr, &options))
+    };
+
+    // the peer that failed to drain resumed itself, so may not answer
+    for peer_addr in peer_addrs.iter().take(paused + 1) {
+        request_quiesce(*peer_addr, host_addr, false, &options);
+    }
+    gate.resume();
+    report
+}
+
+// true once the peer acked the pause or the resume
+fn request_quiesce(peer_addr: SocketAddr, reply_to: SocketAddr, pause: bool, options: &ConfigExchangeOptions) -> bool {
+    let req = QuiesceReq { pause: pause, reply_to: reply_to };
+    send_config(peer_addr, req)
+        .and_then(|_| recv_config_with::<QuiesceAck>(reply_to, options))
+        .map_or(false, |ack| ack.paused == pause)
+}
+
+// on each host, pauses or resumes its gate for the check of another host, until an error
+pub fn serve_quiesce(gate: &QuiesceGate, addr: SocketAddr, timeout: Duration) -> TransResult<()> {
+    loop {
+        let req = recv_config::<QuiesceReq>(addr)?;
+        let paused = if req.pause {
+            let drained = gate.pause(timeout);
+            if !drained {
+                gate.resume();
+            }
+            drained
+        } else {
+            gate.resume();
+            false
+        };
+        send_config(req.reply_to, QuiesceAck { paused: paused })?;
+    }
+}
+
+// on the dpu, checks its meta against the snapshot the host sends
+pub fn serve_check(memdb: &MemDB, dpu_addr: SocketAddr, host_addr: SocketAddr) -> TransResult<ConsistencyReport> {
+    let host = recv_config_with::<HostSnapshot>(dpu_addr, &ConfigExchangeOptions::default())?;
+    let dpu = DpuSnapshot::of_memdb(memdb, &host.get_table_ids())?;
+
+    let report = check(&host, &dpu);
+    send_config(host_addr, report.clone())?;
+    Ok(report)
+}
+
+// little endian, a short frame reads as zeros and fails the check
+struct ByteReader<'a> {
+    data: &'a [u8],
+    off: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data: data,
+            off: 0,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let bytes = self.data.get(self.off..self.off.saturating_add(len)).unwrap_or(&[]);
+        self.off = self.off.saturating_add(len);
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1).first().cloned().unwrap_or(0)
+    }
+
+    fn u32(&mut self) -> u32 {
+        self.bytes(4).try_into().map(u32::from_le_bytes).unwrap_or(0)
+    }
+
+    fn u64(&mut self) -> u64 {
+        self.bytes(8).try_into().map(u64::from_le_bytes).unwrap_or(0)
+    }
+
+    #[inline]
+    fn is_short(&self) -> bool {
+        self.off > self.data.len()
+    }
+
+    fn check(&self, what: &str) -> Result<(), ConfigExchangeError> {
+        if self.is_short() {
+            return Err(ConfigExchangeError::Malformed(format!("truncated {} of {} bytes", what, self.data.len())));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuiesceReq {
+    pub pause: bool,
+    pub reply_to: SocketAddr,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuiesceAck {
+    pub paused: bool,
+}
+
+// the reply address as text, a v6 one as well
+impl ConfigSerialize for QuiesceReq {
+    fn serialize(data: Self) -> Vec<u8> {
+        let mut buf = vec![data.pause as u8];
+        buf.extend_from_slice(data.reply_to.to_string().as_bytes());
+        buf
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError> {
+        let mut reader = ByteReader::new(data);
+        let pause = reader.u8() != 0;
+        reader.check("quiesce request")?;
+
+        let reply_to = std::str::from_utf8(&data[1..]).ok()
+            .and_then(|addr| addr.parse().ok())
+            .ok_or_else(|| ConfigExchangeError::Malformed(String::from("quiesce reply address")))?;
+        Ok(Self { pause: pause, reply_to: reply_to })
+    }
+}
+
+impl ConfigSerialize for QuiesceAck {
+    fn serialize(data: Self) -> Vec<u8> {
+        vec![data.paused as u8]
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError> {
+        if data.len() != 1 {
+            return Err(ConfigExchangeError::Malformed(format!("quiesce ack of {} bytes", data.len())));
+        }
+        Ok(Self { paused: data[0] != 0 })
+    }
+}
+
+// the keys of a table as a bitmap from the smallest one
+impl ConfigSerialize for HostSnapshot {
+    fn serialize(data: Self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(data.tables.len() as u32).to_le_bytes());
+
+        for (table_id, item_len, keys) in data.tables.iter() {
+            let base = keys.first().cloned().unwrap_or(0);
+            let nbits = keys.last().map_or(0, |last| last - base + 1);
+            let mut bitmap = vec![0u8; ((nbits + 7) / 8) as usize];
+            for key in keys.iter() {
+                let off = key - base;
+                bitmap[(off / 8) as usize] |= 1 << (off % 8);
+            }
+
+            buf.extend_from_slice(&(*table_id as u32).to_le_bytes());
+            buf.extend_from_slice(&(*item_len as u32).to_le_bytes());
+            buf.extend_from_slice(&base.to_le_bytes());
+            buf.extend_from_slice(&nbits.to_le_bytes());
+            buf.extend_from_slice(&bitmap);
+        }
+        buf
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError> {
+        let mut reader = ByteReader::new(data);
+        let mut tables = Vec::new();
+
+        for _ in 0..reader.u32() {
+            if reader.is_short() {
+                break;
+            }
+            let table_id = reader.u32() as usize;
+            let item_len = reader.u32() as usize;
+            let base = reader.u64();
+            let nbits = reader.u64();
+
+            let mut keys = Vec::new();
+            for (i, byte) in reader.bytes((nbits.saturating_add(7) / 8) as usize).iter().enumerate() {
+                for bit in 0..8 {
+                    if byte & (1 << bit) != 0 {
+                        keys.push(base + (i * 8 + bit) as u64);
+                    }
+                }
+            }
+            tables.push((table_id, item_len, keys));
+        }
+
+        reader.check("host snapshot")?;
+        Ok(Self { tables: tables })
+    }
+}
+
+impl ConfigSerialize for ConsistencyReport {
+    fn serialize(data: Self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&(data.tables.len() as u32).to_le_bytes());
+        for table in data.tables.iter() {
+            buf.extend_from_slice(&(table.table_id as u32).to_le_bytes());
+            buf.extend_from_slice(&(table.host_keys as u64).to_le_bytes());
+            buf.extend_from_slice(&(table.dpu_keys as u64).to_le_bytes());
+        }
+
+        buf.extend_from_slice(&(data.dropped as u64).to_le_bytes());
+        buf.extend_from_slice(&(data.discrepancies.len() as u32).to_le_bytes());
+        for discrepancy in data.discrepancies.iter() {
+            let (kind, table_id, a, b) = match *discrepancy {
+                Discrepancy::MissingOnDpu { table_id, key } => (0u8, table_id, key, 0),
+                Discrepancy::MissingOnHost { table_id, key } => (1u8, table_id, key, 0),
+                Discrepancy::LockHeld { table_id, key, lock } => (2u8, table_id, key, lock),
+                Discrepancy::LengthMismatch { table_id, host_len, dpu_len } => (3u8, table_id, host_len as u64, dpu_len as u64),
+            };
+            buf.push(kind);
+            buf.extend_from_slice(&(table_id as u32).to_le_bytes());
+            buf.extend_from_slice(&a.to_le_bytes());
+            buf.extend_from_slice(&b.to_le_bytes());
+        }
+        buf
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError> {
+        let mut reader = ByteReader::new(data);
+        let mut report = Self::default();
+
+        for _ in 0..reader.u32() {
+            if reader.is_short() {
+                break;
+            }
+            report.tables.push(TableSummary {
+                table_id: reader.u32() as usize,
+                host_keys: reader.u64() as usize,
+                dpu_keys: reader.u64() as usize,
+            });
+        }
+
+        report.dropped = reader.u64() as usize;
+        for _ in 0..reader.u32() {
+            if reader.is_short() {
+                break;
+            }
+            let kind = reader.u8();
+            let table_id = reader.u32() as usize;
+            let (a, b) = (reader.u64(), reader.u64());
+            let discrepancy = match kind {
+                0 => Discrepancy::MissingOnDpu { table_id: table_id, key: a },
+                1 => Discrepancy::MissingOnHost { table_id: table_id, key: a },
+                2 => Discrepancy::LockHeld { table_id: table_id, key: a, lock: b },
+                3 => Discrepancy::LengthMismatch { table_id: table_id, host_len: a as usize, dpu_len: b as usize },
+                _ => return Err(ConfigExchangeError::Malformed(format!("discrepancy of kind {}", kind))),
+            };
+            report.discrepancies.push(discrepancy);
+        }
+
+        reader.check("consistency report")?;
+        Ok(report)
+    }
+}
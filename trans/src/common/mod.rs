@@ -2,4 +2,8 @@ pub mod logs;
 pub mod connection;
 pub mod pointer;
 pub mod region;
-pub mod random;
\ No newline at end of file
+pub mod cluster;
+pub mod random;
+pub mod offload;
+pub mod partition;
+pub mod consistency;
\ No newline at end of file
@@ -0,0 +1,448 @@
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{ AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering };
+use std::time::{ Duration, Instant };
+
+use crate::memstore::memdb::MemDB;
+use crate::{ OFFLOAD_DRAIN_TIMEOUT_MS, OFFLOAD_LOCK_CONTENT };
+
+// where the keys of a range are served
+pub mod offload_owner {
+    pub type Type = u8;
+    // by the static rule of the app, as the loader placed them
+    pub const STATIC: Type = 0;
+    pub const HOST: Type = 1;
+    pub const DPU: Type = 2;
+}
+
+// set on the owner while the range is handed over
+const OFFLOAD_MOVING: u8 = 0x80;
+
+/// Which ranges of the keys of a partition its dpu serves. A range follows the
+/// static rule the data was loaded with until the controller moves it as a whole,
+/// the accesses counted per range decide where it goes.
+pub struct OffloadMap {
+    key_num: usize,
+    range_size: usize,
+    static_on_dpu: fn(usize) -> bool,
+    owners: Vec<AtomicU8>,
+    // accesses since the controller last took them
+    hits: Vec<AtomicU64>,
+    // txns between routing and the end, a moving range waits for them
+    pins: Vec<AtomicU32>,
+    // bumped by every move
+    version: AtomicU64,
+}
+
+impl OffloadMap {
+    pub fn new(key_num: usize, range_size: usize, static_on_dpu: fn(usize) -> bool) -> Self {
+        let range_num = (key_num + range_size - 1) / range_size;
+        Self {
+            key_num: key_num,
+            range_size: range_size,
+            static_on_dpu: static_on_dpu,
+            owners: (0..range_num).map(|_| AtomicU8::new(offload_owner::STATIC)).collect(),
+            hits: (0..range_num).map(|_| AtomicU64::new(0)).collect(),
+            pins: (0..range_num).map(|_| AtomicU32::new(0)).collect(),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn range_of(&self, key: usize) -> usize {
+        key / self.range_size
+    }
+
+    #[inline]
+    pub fn range_num(&self) -> usize {
+        self.owners.len()
+    }
+
+    #[inline]
+    pub fn keys_of(&self, range: usize) -> Range<usize> {
+        range * self.range_size..std::cmp::min((range + 1) * self.range_size, self.key_num)
+    }
+
+    #[inline]
+    pub fn get_version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn get_owner(&self, range: usize) -> offload_owner::Type {
+        self.owners[range].load(Ordering::Acquire) & !OFFLOAD_MOVING
+    }
+
+    #[inline]
+    pub fn is_moving(&self, key: usize) -> bool {
+        self.owners[self.range_of(key)].load(Ordering::Acquire) & OFFLOAD_MOVING != 0
+    }
+
+    #[inline]
+    pub fn on_dpu(&self, key: usize) -> bool {
+        match self.get_owner(self.range_of(key)) {
+            offload_owner::HOST => false,
+            offload_owner::DPU => true,
+            _ => (self.static_on_dpu)(key),
+        }
+    }
+
+    // a txn holds the ranges it routed to until it ends, false if one is moving,
+    // then the txn aborts and retries after the move
+    pub fn pin(&self, key: usize) -> bool {
+        let range = self.range_of(key);
+        self.pins[range].fetch_add(1, Ordering::SeqCst);
+        if self.owners[range].load(Ordering::SeqCst) & OFFLOAD_MOVING != 0 {
+            self.pins[range].fetch_sub(1, Ordering::SeqCst);
+            return false;
+        }
+
+        self.hits[range].fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    #[inline]
+    pub fn unpin(&self, key: usize) {
+        self.pins[self.range_of(key)].fetch_sub(1, Ordering::SeqCst);
+    }
+
+    // the pin goes with the txn holding it
+    pub fn try_pin(self: &Arc<Self>, key: usize) -> Option<OffloadPin> {
+        if !self.pin(key) {
+            return None;
+        }
+        Some(OffloadPin {
+            map: self.clone(),
+            key: key,
+        })
+    }
+
+    // of a routing peer, its new txns on the range are refused, true once the ones on it ended
+    pub fn freeze(&self, range: usize) -> bool {
+        self.owners[range].fetch_or(OFFLOAD_MOVING, Ordering::SeqCst);
+        self.is_drained(range)
+    }
+
+    // of a routing peer, the owner the controller settled on, the old one if the move failed
+    pub fn publish(&self, range: usize, owner: offload_owner::Type, version: u64) {
+        self.owners[range].store(owner, Ordering::SeqCst);
+        self.version.fetch_max(version, Ordering::AcqRel);
+    }
+
+    fn take_hits(&self) -> Vec<u64> {
+        self.hits.iter().map(|hits| hits.swap(0, Ordering::Relaxed)).collect()
+    }
+
+    // of the keys of the range, the served by the dpu
+    fn dpu_fraction(&self, range: usize) -> f64 {
+        match self.get_owner(range) {
+            offload_owner::HOST => 0.0,
+            offload_owner::DPU => 1.0,
+            _ => {
+                let keys = self.keys_of(range);
+                let len = keys.len();
+                if len == 0 {
+                    return 0.0;
+                }
+                keys.filter(|key| (self.static_on_dpu)(*key)).count() as f64 / len as f64
+            }
+        }
+    }
+
+    // the keys of the range not yet on the side
+    fn keys_to_move(&self, range: usize, to_dpu: bool) -> Vec<usize> {
+        self.keys_of(range).filter(|key| self.on_dpu(*key) != to_dpu).collect()
+    }
+
+    // new txns on the range are refused from now on
+    fn begin_move(&self, range: usize) -> bool {
+        let owner = self.get_owner(range);
+        self.owners[range]
+            .compare_exchange(owner, owner | OFFLOAD_MOVING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    #[inline]
+    fn is_drained(&self, range: usize) -> bool {
+        self.pins[range].load(Ordering::SeqCst) == 0
+    }
+
+    fn finish_move(&self, range: usize, owner: offload_owner::Type) {
+        self.owners[range].store(owner, Ordering::SeqCst);
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn abort_move(&self, range: usize) {
+        self.owners[range].fetch_and(!OFFLOAD_MOVING, Ordering::SeqCst);
+    }
+}
+
+// unpins its key when the txn drops it
+pub struct OffloadPin {
+    map: Arc<OffloadMap>,
+    key: usize,
+}
+
+impl Drop for OffloadPin {
+    fn drop(&mut self) {
+        self.map.unpin(self.key);
+    }
+}
+
+// what the controller does beyond its own map, on the side of the host
+#[allow(async_fn_in_trait)]
+pub trait OffloadMigrator {
+    // the peers routing to the keys of the partition stop routing to the range,
+    // true once none of their txns is on it
+    async fn freeze_peers(&self, range: usize) -> bool;
+
+    // copies the records of the keys to the other side and drops them where they were,
+    // false leaves both sides as they were
+    async fn migrate(&self, keys: &[usize], to_dpu: bool) -> bool;
+
+    // the peers take the owner and the version, then route to the range again
+    async fn publish_peers(&self, range: usize, owner: offload_owner::Type, version: u64);
+
+    // lets the txns on the range end while the controller waits
+    async fn pause(&self);
+}
+
+/// Moves the records of some tables between the memdb of a host and the one of its dpu
+/// in the same process, as the dpu emulation runs them. The keys a side does not have
+/// belong to other partitions and are skipped. The routing peers share the map.
+pub struct MemDBMigrator {
+    host: Arc<MemDB>,
+    dpu: Arc<MemDB>,
+    tables: Vec<usize>,
+}
+
+impl MemDBMigrator {
+    pub fn new(host: &Arc<MemDB>, dpu: &Arc<MemDB>, tables: &[usize]) -> Self {
+        Self {
+            host: host.clone(),
+            dpu: dpu.clone(),
+            tables: tables.to_vec(),
+        }
+    }
+
+    fn unlock_all(memdb: &MemDB, locked: &[(usize, u64)]) {
+        for (table_id, key) in locked {
+            memdb.local_unlock(*table_id, *key, OFFLOAD_LOCK_CONTENT);
+        }
+    }
+}
+
+impl OffloadMigrator for MemDBMigrator {
+    async fn freeze_peers(&self, _range: usize) -> bool {
+        true
+    }
+
+    async fn migrate(&self, keys: &[usize], to_dpu: bool) -> bool {
+        let (from, to) = if to_dpu { (&self.host, &self.dpu) } else { (&self.dpu, &self.host) };
+
+        // lock every record first, one locked by a txn fails the move
+        let mut locked = Vec::new();
+        for table_id in self.tables.iter() {
+            for key in keys {
+                let key = *key as u64;
+                // seq 0 is a record never written
+                let meta = match from.local_get_meta(*table_id, key) {
+                    Some(meta) if meta.seq != 0 => meta,
+                    _ => continue,
+                };
+                if meta.lock != 0 {
+                    Self::unlock_all(from, &locked);
+                    return false;
+                }
+
+                match from.local_lock(*table_id, key, OFFLOAD_LOCK_CONTENT) {
+                    Some(meta) if meta.lock == OFFLOAD_LOCK_CONTENT => locked.push((*table_id, key)),
+                    _ => {
+                        Self::unlock_all(from, &locked);
+                        return false;
+                    }
+                }
+            }
+        }
+
+        // the seqs go along, so a later validation sees no change
+        for (table_id, key) in locked.iter() {
+            let len = from.get_item_length(*table_id);
+            let mut value = vec![0u64; (len + 7) / 8];
+            let ptr = value.as_mut_ptr() as *mut u8;
+
+            let meta = from.local_get_readonly(*table_id, *key, ptr, len as _).unwrap();
+            to.local_lock(*table_id, *key, 0);
+            to.local_upd_val_set_seq(*table_id, *key, ptr, len as _, meta.seq);
+        }
+
+        for (table_id, key) in locked.iter() {
+            from.local_erase(*table_id, *key);
+        }
+        true
+    }
+
+    async fn publish_peers(&self, _range: usize, _owner: offload_owner::Type, _version: u64) {}
+
+    async fn pause(&self) {
+        std::thread::yield_now();
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OffloadStats {
+    pub rounds: u64,
+    pub to_dpu: u64,
+    pub to_host: u64,
+    // the moves given up, the range was busy or a record locked
+    pub aborted: u64,
+}
+
+/// Keeps the share of the accesses the dpu serves near a target. Each round
+/// weighs the ranges by their recent accesses and hands over, one by one, the ranges
+/// whose move brings the share closest to the target.
+pub struct OffloadCtrl<M: OffloadMigrator> {
+    map: Arc<OffloadMap>,
+    migrator: M,
+    dpu_share: f64,
+    // a share this close to the target is left alone
+    slack: f64,
+    max_moves: usize,
+    drain_timeout: Duration,
+    // the accesses of the ranges, halved every round
+    weights: Vec<f64>,
+    stats: OffloadStats,
+}
+
+impl<M: OffloadMigrator> OffloadCtrl<M> {
+    pub fn new(map: &Arc<OffloadMap>, migrator: M, dpu_share: f64) -> Self {
+        Self {
+            map: map.clone(),
+            migrator: migrator,
+            dpu_share: dpu_share,
+            slack: 0.05,
+            max_moves: 4,
+            drain_timeout: Duration::from_millis(OFFLOAD_DRAIN_TIMEOUT_MS),
+            weights: vec![0.0; map.range_num()],
+            stats: OffloadStats::default(),
+        }
+    }
+
+    pub fn set_slack(&mut self, slack: f64) {
+        self.slack = slack;
+    }
+
+    // of a round
+    pub fn set_max_moves(&mut self, max_moves: usize) {
+        self.max_moves = max_moves;
+    }
+
+    pub fn set_drain_timeout(&mut self, drain_timeout: Duration) {
+        self.drain_timeout = drain_timeout;
+    }
+
+    #[inline]
+    pub fn get_stats(&self) -> OffloadStats {
+        self.stats
+    }
+
+    // freeze here and on the peers, wait for the txns on the range, copy, then flip
+    // the owner on the peers before here
+    pub async fn move_range(&mut self, range: usize, to_dpu: bool) -> bool {
+        let old_owner = self.map.get_owner(range);
+        if !self.map.begin_move(range) {
+            return false;
+        }
+
+        let start = Instant::now();
+        while !self.map.is_drained(range) || !self.migrator.freeze_peers(range).await {
+            if start.elapsed() > self.drain_timeout {
+                return self.abort_move(range, old_owner).await;
+            }
+            self.migrator.pause().await;
+        }
+
+        let keys = self.map.keys_to_move(range, to_dpu);
+        if !self.migrator.migrate(&keys, to_dpu).await {
+            return self.abort_move(range, old_owner).await;
+        }
+
+        let owner = if to_dpu { offload_owner::DPU } else { offload_owner::HOST };
+        self.migrator.publish_peers(range, owner, self.map.get_version() + 1).await;
+        self.map.finish_move(range, owner);
+        if to_dpu {
+            self.stats.to_dpu += 1;
+        } else {
+            self.stats.to_host += 1;
+        }
+        true
+    }
+
+    async fn abort_move(&mut self, range: usize, old_owner: offload_owner::Type) -> bool {
+        self.migrator.publish_peers(range, old_owner, self.map.get_version()).await;
+        self.map.abort_move(range);
+        self.stats.aborted += 1;
+        false
+    }
+
+    // the moves done
+    pub async fn rebalance(&mut self) -> usize {
+        self.stats.rounds += 1;
+        for (weight, hits) in self.weights.iter_mut().zip(self.map.take_hits()) {
+            *weight = *weight / 2.0 + hits as f64;
+        }
+
+        let total: f64 = self.weights.iter().sum();
+        if total == 0.0 {
+            return 0;
+        }
+
+        let mut fractions: Vec<f64> = (0..self.map.range_num()).map(|range| self.map.dpu_fraction(range)).collect();
+        let mut share = self.weights.iter().zip(fractions.iter()).map(|(w, f)| w * f).sum::<f64>() / total;
+
+        let mut moves = 0;
+        while moves < self.max_moves && (share - self.dpu_share).abs() > self.slack {
+            let to_dpu = share < self.dpu_share;
+
+            // the range whose move lands closest to the target
+            let mut best: Option<(usize, f64)> = None;
+            for range in 0..fractions.len() {
+                let moved = if to_dpu { 1.0 - fractions[range] } else { -fractions[range] };
+                let after = share + self.weights[range] * moved / total;
+                if (after - self.dpu_share).abs() >= (share - self.dpu_share).abs() {
+                    continue;
+                }
+                if best.map_or(true, |(_, best_after)| (after - self.dpu_share).abs() < (best_after - self.dpu_share).abs()) {
+                    best = Some((range, after));
+                }
+            }
+
+            let (range, after) = match best {
+                Some(best) => best,
+                None => break,
+            };
+            // a busy range is tried again next round
+            if !self.move_range(range, to_dpu).await {
+                break;
+            }
+
+            fractions[range] = if to_dpu { 1.0 } else { 0.0 };
+            share = after;
+            moves += 1;
+        }
+        moves
+    }
+
+    // as a routine beside the txns, the migrator pauses it between the polls
+    pub async fn run(&mut self, interval: Duration, running: &Arc<AtomicBool>) {
+        let mut last = Instant::now();
+        while running.load(Ordering::SeqCst) {
+            if last.elapsed() < interval {
+                self.migrator.pause().await;
+                continue;
+            }
+            self.rebalance().await;
+            last = Instant::now();
+        }
+    }
+}
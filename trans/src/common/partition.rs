@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::common::cluster::{ dpu_peer_id, get_cluster };
+use crate::common::offload::{ OffloadMap, OffloadPin };
+use crate::{ TransError, TransResult };
+
+/// Where a key is placed. The loaders, the workloads and the txns ask it instead
+/// of computing the partitions themselves, so the placement comes from the config.
+pub trait Partitioner: Send + Sync {
+    // a partition, or the dpu peer serving some keys of one
+    fn part_of(&self, key: usize) -> usize;
+
+    // holds where the key is placed until the txn drops the pins, asked before part_of,
+    // false if it is changing
+    fn pin(&self, _key: usize, _pins: &mut Vec<OffloadPin>) -> bool {
+        true
+    }
+}
+
+// the key modulo the partitions, or of its mixed bits if the low ones are skewed
+pub struct HashPartitioner {
+    parts: usize,
+    mixed: bool,
+}
+
+impl HashPartitioner {
+    pub fn new(parts: usize) -> Self {
+        Self {
+            parts: parts,
+            mixed: false,
+        }
+    }
+
+    pub fn mixed(parts: usize) -> Self {
+        Self {
+            parts: parts,
+            mixed: true,
+        }
+    }
+}
+
+impl Partitioner for HashPartitioner {
+    #[inline]
+    fn part_of(&self, key: usize) -> usize {
+        if self.mixed {
+            (key as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(32) as usize % self.parts
+        } else {
+            key % self.parts
+        }
+    }
+}
+
+// partition i holds the keys below bounds[i], the last one all the rest
+pub struct RangePartitioner {
+    bounds: Vec<usize>,
+}
+
+impl RangePartitioner {
+    pub fn new(bounds: Vec<usize>) -> Self {
+        Self {
+            bounds: bounds,
+        }
+    }
+
+    // parts ranges of width keys
+    pub fn uniform(width: usize, parts: usize) -> Self {
+        Self::new((1..parts).map(|i| i * width).collect())
+    }
+}
+
+impl Partitioner for RangePartitioner {
+    #[inline]
+    fn part_of(&self, key: usize) -> usize {
+        self.bounds.partition_point(|bound| *bound <= key)
+    }
+}
+
+// the listed keys, the others as the fallback places them
+pub struct LookupPartitioner {
+    table: HashMap<usize, usize>,
+    fallback: Arc<dyn Partitioner>,
+}
+
+impl LookupPartitioner {
+    pub fn new(table: HashMap<usize, usize>, fallback: Arc<dyn Partitioner>) -> Self {
+        Self {
+            table: table,
+            fallback: fallback,
+        }
+    }
+}
+
+impl Partitioner for LookupPartitioner {
+    #[inline]
+    fn part_of(&self, key: usize) -> usize {
+        match self.table.get(&key) {
+            Some(part_id) => *part_id,
+            None => self.fallback.part_of(key),
+        }
+    }
+
+    fn pin(&self, key: usize, pins: &mut Vec<OffloadPin>) -> bool {
+        self.table.contains_key(&key) || self.fallback.pin(key, pins)
+    }
+}
+
+// who asks, the split of a partition between its host and its dpu differs
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlacementView {
+    // no dpu, the split is off
+    Plain,
+    // the host of the partition, its own keys are split
+    Host,
+    // a coordinator beside a dpu, the keys of the other partitions are split
+    Hybrid,
+}
+
+// which keys of a partition its dpu serves
+#[derive(Clone)]
+pub enum DpuSplit {
+    // a key with key % modulo below ratio percent of modulo
+    Ratio { modulo: usize, ratio: usize },
+    // the ranges the offload controller moved, see OffloadMap
+    Map(Arc<OffloadMap>),
+}
+
+impl DpuSplit {
+    #[inline]
+    pub fn on_dpu(&self, key: usize) -> bool {
+        match self {
+            Self::Ratio { modulo, ratio } => (key % modulo) * 100 < ratio * modulo,
+            Self::Map(map) => map.on_dpu(key),
+        }
+    }
+}
+
+// the keys the base places on a partition, some of them served by its dpu
+pub struct SplitPartitioner {
+    base: Arc<dyn Partitioner>,
+    part_id: usize,
+    view: PlacementView,
+    split: DpuSplit,
+}
+
+impl SplitPartitioner {
+    pub fn new(base: Arc<dyn Partitioner>, part_id: usize, view: PlacementView, split: DpuSplit) -> Self {
+        Self {
+            base: base,
+            part_id: part_id,
+            view: view,
+            split: split,
+        }
+    }
+}
+
+impl SplitPartitioner {
+    #[inline]
+    fn is_split(&self, p_id: usize) -> bool {
+        match self.view {
+            PlacementView::Plain => false,
+            PlacementView::Host => p_id == self.part_id,
+            PlacementView::Hybrid => p_id != self.part_id,
+        }
+    }
+}
+
+impl Partitioner for SplitPartitioner {
+    #[inline]
+    fn part_of(&self, key: usize) -> usize {
+        let p_id = self.base.part_of(key);
+        if self.is_split(p_id) && self.split.on_dpu(key) {
+            dpu_peer_id(p_id as _).expect("the cluster config gives the partition no dpu") as _
+        } else {
+            p_id
+        }
+    }
+
+    // only the ranges of the map move
+    fn pin(&self, key: usize, pins: &mut Vec<OffloadPin>) -> bool {
+        let map = match &self.split {
+            DpuSplit::Map(map) if self.is_split(self.base.part_of(key)) => map,
+            _ => return self.base.pin(key, pins),
+        };
+
+        match map.try_pin(key) {
+            Some(pin) => {
+                pins.push(pin);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A placement as the cluster config describes it, built per worker as the split
+/// depends on the partition and the view of the worker, e.g.
+/// {"kind": "split", "modulo": 100, "ratio": 30, "base": {"kind": "hash", "parts": 2}}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PartitionerConfig {
+    Hash { parts: usize, mixed: bool },
+    Range { bounds: Vec<usize> },
+    Lookup { entries: Vec<(usize, usize)>, fallback: Box<PartitionerConfig> },
+    Split { base: Box<PartitionerConfig>, modulo: usize, ratio: usize },
+}
+
+fn get_usize(value: &Value, key: &str) -> TransResult<usize> {
+    value.get(key)
+        .and_then(Value::as_u64)
+        .map(|v| v as usize)
+        .ok_or(TransError::TransConfigError)
+}
+
+impl PartitionerConfig {
+    pub fn from_json(value: &Value) -> TransResult<Self> {
+        let kind = value.get("kind").and_then(Value::as_str).ok_or(TransError::TransConfigError)?;
+        let config = match kind {
+            "hash" => Self::Hash {
+                parts: get_usize(value, "parts")?,
+                mixed: value.get("mixed").and_then(Value::as_bool).unwrap_or(false),
+            },
+            // the bounds, or parts ranges of width keys
+            "range" => match value.get("bounds") {
+                Some(Value::Array(bounds)) => Self::Range {
+                    bounds: bounds
+                        .iter()
+                        .map(|bound| bound.as_u64().map(|b| b as usize).ok_or(TransError::TransConfigError))
+                        .collect::<TransResult<Vec<_>>>()?,
+                },
+                Some(_) => return Err(TransError::TransConfigError),
+                None => {
+                    let width = get_usize(value, "width")?;
+                    Self::Range { bounds: (1..get_usize(value, "parts")?).map(|i| i * width).collect() }
+                }
+            },
+            // [[key, part_id], ...]
+            "lookup" => {
+                let entries = match value.get("entries") {
+                    Some(Value::Array(entries)) => entries
+                        .iter()
+                        .map(|entry| match entry.as_array().map(|pair| pair.as_slice()) {
+                            Some([key, part_id]) => match (key.as_u64(), part_id.as_u64()) {
+                                (Some(key), Some(part_id)) => Ok((key as usize, part_id as usize)),
+                                _ => Err(TransError::TransConfigError),
+                            },
+                            _ => Err(TransError::TransConfigError),
+                        })
+                        .collect::<TransResult<Vec<_>>>()?,
+                    _ => return Err(TransError::TransConfigError),
+                };
+                let fallback = value.get("fallback").ok_or(TransError::TransConfigError)?;
+                Self::Lookup { entries: entries, fallback: Box::new(Self::from_json(fallback)?) }
+            }
+            "split" => {
+                let base = value.get("base").ok_or(TransError::TransConfigError)?;
+                Self::Split {
+                    base: Box::new(Self::from_json(base)?),
+                    modulo: get_usize(value, "modulo")?,
+                    ratio: get_usize(value, "ratio")?,
+                }
+            }
+            _ => return Err(TransError::TransConfigError),
+        };
+
+        config.check()?;
+        Ok(config)
+    }
+
+    // no partition or modulo of zero
+    fn check(&self) -> TransResult<()> {
+        match self {
+            Self::Hash { parts, .. } if *parts == 0 => Err(TransError::TransConfigError),
+            Self::Range { bounds } if bounds.windows(2).any(|w| w[0] >= w[1]) => Err(TransError::TransConfigError),
+            Self::Split { modulo, ratio, .. } if *modulo == 0 || *ratio > 100 => Err(TransError::TransConfigError),
+            _ => Ok(()),
+        }
+    }
+
+    // the split of the dpu by the offload map instead of the ratio
+    pub fn build_with_map(&self, part_id: usize, view: PlacementView, map: Option<&Arc<OffloadMap>>) -> Arc<dyn Partitioner> {
+        match self {
+            Self::Hash { parts, mixed } => {
+                if *mixed {
+                    Arc::new(HashPartitioner::mixed(*parts))
+                } else {
+                    Arc::new(HashPartitioner::new(*parts))
+                }
+            }
+            Self::Range { bounds } => Arc::new(RangePartitioner::new(bounds.clone())),
+            Self::Lookup { entries, fallback } => Arc::new(LookupPartitioner::new(
+                entries.iter().cloned().collect(),
+                fallback.build_with_map(part_id, view, map),
+            )),
+            Self::Split { base, modulo, ratio } => {
+                let base = base.build_with_map(part_id, view, map);
+                if view == PlacementView::Plain {
+                    return base;
+                }
+
+                let split = match map {
+                    Some(map) => DpuSplit::Map(map.clone()),
+                    None => DpuSplit::Ratio { modulo: *modulo, ratio: *ratio },
+                };
+                Arc::new(SplitPartitioner::new(base, part_id, view, split))
+            }
+        }
+    }
+
+    #[inline]
+    pub fn build(&self, part_id: usize, view: PlacementView) -> Arc<dyn Partitioner> {
+        self.build_with_map(part_id, view, None)
+    }
+}
+
+// the placement the cluster config names, the one of the app without it
+pub fn get_placement(name: &str, default: PartitionerConfig) -> PartitionerConfig {
+    get_cluster()
+        .and_then(|cluster| cluster.get_placement(name))
+        .cloned()
+        .unwrap_or(default)
+}
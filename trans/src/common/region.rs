@@ -20,16 +20,13 @@ impl<T: Default, const ITEM_COUNT: usize> Drop for RawArrayRegion<T, ITEM_COUNT>
 impl<T: Default, const ITEM_COUNT: usize> RawArrayRegion<T, ITEM_COUNT> {
     pub fn new_from_raw(ptr: *mut u8, len: usize) -> Option<Self> {
         let demand = std::mem::size_of::<T>() * ITEM_COUNT;
-        if ptr.is_null() || demand != len {
+        if ptr.is_null() || demand != len || ptr as usize % std::mem::align_of::<T>() != 0 {
             return None;
         }
 
-        let slice_mut = unsafe {
-            std::slice::from_raw_parts_mut::<T>(ptr as _, demand)
-        };
-
+        // the raw memory holds no valid T yet, nothing to drop
         for i in 0..ITEM_COUNT {
-            slice_mut[i] = T::default();
+            unsafe { std::ptr::write((ptr as *mut T).add(i), T::default()); }
         }
 
         Some(Self {
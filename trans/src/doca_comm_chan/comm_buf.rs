@@ -1,12 +1,30 @@
 use std::{os::raw::c_void, pin::Pin, ptr::NonNull};
 use libc::{ memalign, free };
+#[cfg(feature = "doca_deps")]
 use doca::RawPointer;
 
 use std::collections::vec_deque::VecDeque;
 
-use crate::{ MAX_CONN_INFO_BUFS, MAX_CONN_MSG_SIZE };
+use crate::{ COMM_REPLY_BUFS, MAX_CONN_INFO_BUFS, MAX_CONN_MSG_SIZE };
 use super::DocaCommHeaderMeta;
 
+// the buffer the channel sends from or receives into, as doca::RawPointer
+#[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+pub struct RawPointer {
+    pub inner: NonNull<c_void>,
+    pub payload: usize,
+}
+
+#[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+impl RawPointer {
+    pub unsafe fn from_raw_ptr(ptr: *mut u8, len: usize) -> Self {
+        Self {
+            inner: NonNull::new_unchecked(ptr as _),
+            payload: len,
+        }
+    }
+}
+
 pub struct DocaCommBuf {
     buf: NonNull<c_void>,
     pointer: RawPointer,
@@ -133,13 +151,21 @@ impl DocaCommBufAllocator {
         }
     }
 
-    pub fn alloc_buf(&mut self, cid: u32) -> DocaCommBuf {
-        self.buf_pool.pop_front().unwrap()
+    // None once the pool is out, the buffers come back as the queued sends leave
+    pub fn alloc_buf(&mut self, cid: u32) -> Option<DocaCommBuf> {
+        self.buf_pool.pop_front()
     }
 
     pub fn dealloc_buf(&mut self, buf: DocaCommBuf, cid: u32) {
         self.buf_pool.push_back(buf);
     }
+
+    // a request takes a buffer only if the replies keep theirs
+    #[inline]
+    pub fn has_free_for(&self, reply: bool) -> bool {
+        let reserved = if reply { 0 } else { COMM_REPLY_BUFS };
+        self.buf_pool.len() > reserved
+    }
 }
 
 pub struct DocaCommReply {
@@ -165,6 +191,12 @@ impl DocaCommReply {
         self.pending_count -= 1;
     }
 
+    // the channel is lost, the replies left will not come
+    pub fn fail(&mut self) {
+        self.success = false;
+        self.pending_count = 0;
+    }
+
     pub fn get_pending_count(&self) -> usize {
         self.pending_count
     }
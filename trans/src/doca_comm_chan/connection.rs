@@ -1,12 +1,20 @@
 use std::sync::{ Arc, Mutex };
 use std::cell::UnsafeCell;
+use std::collections::VecDeque;
 use lazy_static::lazy_static;
 
+#[cfg(feature = "doca_deps")]
 use doca::comm_chan::CommChannel;
+#[cfg(feature = "doca_deps")]
 use doca::device::{ open_device_with_pci, open_device_rep_with_pci };
-use doca_sys::doca_error;
+#[cfg(feature = "doca_deps")]
+use doca::DOCAError;
 
-use super::DocaCommHeaderMeta;
+#[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+use super::emu::EmuCommChannel as CommChannel;
+
+use super::{CommRecvStatus, CommSendStats, CommSendStatus, DocaCommHeaderMeta};
+use crate::common::cluster::{ClusterConfig, NodeRole};
 use crate::MAX_CONN_MSG_SIZE;
 
 use super::comm_buf::{ DocaCommBufAllocator, DocaCommBuf };
@@ -14,9 +22,14 @@ pub struct DocaCommChannel {
     allocator: UnsafeCell<DocaCommBufAllocator>,
     chan: Arc<CommChannel>,
     buf:  UnsafeCell<Option<DocaCommBuf>>,
+    // the full buffers, sent in order once the channel has room
+    sending: UnsafeCell<VecDeque<DocaCommBuf>>,
+    stats: UnsafeCell<CommSendStats>,
+    lost: UnsafeCell<bool>,
 }
 
 impl DocaCommChannel {
+    #[cfg(feature = "doca_deps")]
     pub fn new_server(server_name: &str, pci_addr: &str, pci_addr_rep: &str) -> Self {
         let device = open_device_with_pci(pci_addr).unwrap();
         let device_rep = open_device_rep_with_pci(&device, pci_addr_rep).unwrap();
@@ -27,9 +40,31 @@ impl DocaCommChannel {
             allocator: UnsafeCell::new(allocator),
             chan: CommChannel::create_server(server_name, &device, &device_rep),
             buf: UnsafeCell::new(None),
+            sending: UnsafeCell::new(VecDeque::new()),
+            stats: UnsafeCell::new(CommSendStats::default()),
+            lost: UnsafeCell::new(false),
+        }
+    }
+
+    // the dpu serves the channel of the thread tid, its host connects to it,
+    // None if the node has no channel or its devices are not configured
+    pub fn new_from_cluster(cluster: &ClusterConfig, self_id: u64, tid: usize) -> Option<Self> {
+        let node = cluster.get_node(self_id)?;
+        match node.role {
+            NodeRole::Dpu => Some(Self::new_server(
+                &node.get_comm_name(tid),
+                node.get_pci_addr(tid)?,
+                node.get_pci_addr_rep(tid)?,
+            )),
+            NodeRole::Host => {
+                let dpu = cluster.get_node(node.dpu?)?;
+                Some(Self::new_client(&dpu.get_comm_name(tid), node.get_pci_addr(tid)?))
+            }
+            NodeRole::Client => None,
         }
     }
 
+    #[cfg(feature = "doca_deps")]
     pub fn new_client(server_name: &str, pci_addr: &str) -> Self {
         let device = open_device_with_pci(pci_addr).unwrap();
 
@@ -39,31 +74,82 @@ impl DocaCommChannel {
             allocator: UnsafeCell::new(allocator),
             chan: CommChannel::create_client(server_name, &device),
             buf: UnsafeCell::new(None),
+            sending: UnsafeCell::new(VecDeque::new()),
+            stats: UnsafeCell::new(CommSendStats::default()),
+            lost: UnsafeCell::new(false),
+        }
+    }
+
+    // the pci addresses are unused, the channel is named by the server name
+    #[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+    #[allow(unused_variables)]
+    pub fn new_server(server_name: &str, pci_addr: &str, pci_addr_rep: &str) -> Self {
+        Self {
+            allocator: UnsafeCell::new(DocaCommBufAllocator::new()),
+            chan: CommChannel::create_server(server_name),
+            buf: UnsafeCell::new(None),
+            sending: UnsafeCell::new(VecDeque::new()),
+            stats: UnsafeCell::new(CommSendStats::default()),
+            lost: UnsafeCell::new(false),
+        }
+    }
+
+    #[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+    #[allow(unused_variables)]
+    pub fn new_client(server_name: &str, pci_addr: &str) -> Self {
+        Self {
+            allocator: UnsafeCell::new(DocaCommBufAllocator::new()),
+            chan: CommChannel::create_client(server_name),
+            buf: UnsafeCell::new(None),
+            sending: UnsafeCell::new(VecDeque::new()),
+            stats: UnsafeCell::new(CommSendStats::default()),
+            lost: UnsafeCell::new(false),
+        }
+    }
+
+    // the routines wait in the scheduler for a free buffer before appending,
+    // the replies of the handlers take the reserved ones and spin only if those are out too
+    fn wait_free_buf(&self) -> DocaCommBuf {
+        let allocator = unsafe { self.allocator.get().as_mut().unwrap() };
+        loop {
+            if let Some(mut buf) = allocator.alloc_buf(0) {
+                buf.set_payload(0);
+                return buf;
+            }
+            // a lost channel drops the queued buffers back to the pool
+            self.send_queued();
         }
     }
 
     #[inline]
     fn get_write_buf(&self, total_size: usize) -> &mut DocaCommBuf {
         let buf_opt = unsafe { self.buf.get().as_mut().unwrap() };
-        let allocator = unsafe { self.allocator.get().as_mut().unwrap() };
         if buf_opt.is_none() {
-            let mut alloc_buf = allocator.alloc_buf(0);
-            alloc_buf.set_payload(0);
-            *buf_opt = Some(alloc_buf);
+            *buf_opt = Some(self.wait_free_buf());
         } else {
             let buf = buf_opt.as_mut().unwrap();
             if buf.get_payload() + total_size > MAX_CONN_MSG_SIZE {
-                self.chan.block_send_req(buf.as_raw_pointer());
-                allocator.dealloc_buf(buf_opt.take().unwrap(), 0);
-                let mut alloc_buf = allocator.alloc_buf(0);
-                alloc_buf.set_payload(0);
-                *buf_opt = Some(alloc_buf);
+                self.queue_send(buf_opt.take().unwrap());
+                *buf_opt = Some(self.wait_free_buf());
             }
         }
 
         buf_opt.as_mut().unwrap()
     }
 
+    // a message of total_size fits without taking the buffers kept for the replies
+    pub fn can_append(&self, total_size: usize, reply: bool) -> bool {
+        let buf_opt = unsafe { self.buf.get().as_ref().unwrap() };
+        if let Some(buf) = buf_opt {
+            if buf.get_payload() + total_size <= MAX_CONN_MSG_SIZE {
+                return true;
+            }
+        }
+        self.send_queued();
+        let allocator = unsafe { self.allocator.get().as_ref().unwrap() };
+        allocator.has_free_for(reply) || self.is_lost()
+    }
+
     pub fn append_empty_msg(&self, header: DocaCommHeaderMeta) {
         let total_size = 4;
         assert!(0 == header.info_payload as usize);
@@ -101,20 +187,105 @@ impl DocaCommChannel {
         }
     }
 
+    #[cfg(feature = "doca_deps")]
+    #[inline]
+    fn try_send(&self, buf: &mut DocaCommBuf) -> CommSendStatus {
+        let res = self.chan.send_req(buf.as_raw_pointer());
+        if res == DOCAError::DOCA_SUCCESS {
+            CommSendStatus::Sent
+        } else if res == DOCAError::DOCA_ERROR_AGAIN {
+            CommSendStatus::Again
+        } else {
+            CommSendStatus::Lost
+        }
+    }
+
+    #[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+    #[inline]
+    fn try_send(&self, buf: &mut DocaCommBuf) -> CommSendStatus {
+        self.chan.send_req(buf.as_raw_pointer())
+    }
+
+    fn queue_send(&self, buf: DocaCommBuf) {
+        let sending = unsafe { self.sending.get().as_mut().unwrap() };
+        let stats = unsafe { self.stats.get().as_mut().unwrap() };
+        sending.push_back(buf);
+        stats.max_pending = stats.max_pending.max(sending.len());
+        self.send_queued();
+    }
+
+    // sends the queued buffers until the channel is full, never waits
+    fn send_queued(&self) {
+        let sending = unsafe { self.sending.get().as_mut().unwrap() };
+        let allocator = unsafe { self.allocator.get().as_mut().unwrap() };
+        let stats = unsafe { self.stats.get().as_mut().unwrap() };
+        while let Some(buf) = sending.front_mut() {
+            match self.try_send(buf) {
+                CommSendStatus::Sent => {
+                    stats.sent += 1;
+                    allocator.dealloc_buf(sending.pop_front().unwrap(), 0);
+                }
+                CommSendStatus::Again => {
+                    stats.stalls += 1;
+                    break;
+                }
+                // the receiving side finds the channel lost too
+                CommSendStatus::Lost => {
+                    unsafe { *self.lost.get() = true; }
+                    while let Some(buf) = sending.pop_front() {
+                        allocator.dealloc_buf(buf, 0);
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    // queues the current buffer and sends what the channel takes,
+    // the rest is retried by the next flush
     pub fn flush_pending_msgs(&self) {
         let buf_opt = unsafe { self.buf.get().as_mut().unwrap() };
-        let allocator = unsafe { self.allocator.get().as_mut().unwrap() };
-        if let Some(mut buf) = buf_opt.take() {
-            self.chan.block_send_req(buf.as_raw_pointer());
-            allocator.dealloc_buf(buf, 0);
+        match buf_opt.take() {
+            Some(buf) => self.queue_send(buf),
+            None => self.send_queued(),
         }
     }
 
-    pub fn recv_info(&self, buf: &mut DocaCommBuf) -> doca_error {
+    #[inline]
+    pub fn has_queued_msgs(&self) -> bool {
+        let sending = unsafe { self.sending.get().as_ref().unwrap() };
+        !sending.is_empty()
+    }
+
+    #[inline]
+    pub fn is_lost(&self) -> bool {
+        unsafe { *self.lost.get() }
+    }
+
+    #[inline]
+    pub fn get_send_stats(&self) -> CommSendStats {
+        unsafe { *self.stats.get() }
+    }
+
+    #[cfg(feature = "doca_deps")]
+    pub fn recv_info(&self, buf: &mut DocaCommBuf) -> CommRecvStatus {
+        let res = self.chan.recv_req(buf.as_raw_pointer());
+        if res == DOCAError::DOCA_SUCCESS {
+            CommRecvStatus::Received
+        } else if res == DOCAError::DOCA_ERROR_AGAIN {
+            CommRecvStatus::Empty
+        } else {
+            CommRecvStatus::Lost
+        }
+    }
+
+    #[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+    #[inline]
+    pub fn recv_info(&self, buf: &mut DocaCommBuf) -> CommRecvStatus {
         self.chan.recv_req(buf.as_raw_pointer())
     }
 
-    pub fn alloc_buf(&self, cid: u32) -> DocaCommBuf {
+    pub fn alloc_buf(&self, cid: u32) -> Option<DocaCommBuf> {
         let allocator = unsafe { self.allocator.get().as_mut().unwrap() };
         allocator.alloc_buf(cid)
     }
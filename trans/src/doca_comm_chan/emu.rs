@@ -0,0 +1,96 @@
+use std::fs::remove_file;
+use std::io::ErrorKind;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use super::comm_buf::RawPointer;
+use super::{CommRecvStatus, CommSendStatus};
+
+// what the client sends first, as the real client does
+const HELLO: [u8; 2] = [1u8; 2];
+
+// the socket of the server, the host and the dpu share a machine
+fn server_path(server_name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("trans_comm_{}", server_name.trim_end_matches('\0')))
+}
+
+/// The comm channel without a dpu, over a unix datagram socket. A datagram keeps
+/// the boundary of a message, so a buffer arrives as the real channel delivers it.
+pub struct EmuCommChannel {
+    sock: UnixDatagram,
+    path: PathBuf,
+}
+
+impl EmuCommChannel {
+    pub fn create_server(server_name: &str) -> Arc<Self> {
+        let path = server_path(server_name);
+        let _ = remove_file(&path);
+        let sock = UnixDatagram::bind(&path).expect("Comm Channel server couldn't start listening");
+
+        // the hello names the client
+        let mut temp_buf = [0u8; 10];
+        let (_, peer_addr) = sock.recv_from(&mut temp_buf).unwrap();
+        let peer_path = peer_addr.as_pathname().expect("unnamed comm channel client");
+        sock.connect(peer_path).unwrap();
+        sock.set_nonblocking(true).unwrap();
+
+        Arc::new(Self {
+            sock: sock,
+            path: path,
+        })
+    }
+
+    pub fn create_client(server_name: &str) -> Arc<Self> {
+        let peer_path = server_path(server_name);
+        let path = PathBuf::from(format!("{}.{}", peer_path.display(), std::process::id()));
+        let _ = remove_file(&path);
+        let sock = UnixDatagram::bind(&path).unwrap();
+
+        // until the server listens
+        while sock.connect(&peer_path).is_err() {
+            sleep(Duration::from_millis(1));
+        }
+        sock.send(&HELLO).unwrap();
+        sock.set_nonblocking(true).unwrap();
+
+        Arc::new(Self {
+            sock: sock,
+            path: path,
+        })
+    }
+
+    pub fn send_req(&self, raw: &RawPointer) -> CommSendStatus {
+        let data = unsafe { std::slice::from_raw_parts(raw.inner.as_ptr() as *const u8, raw.payload) };
+        match self.sock.send(data) {
+            Ok(_) => CommSendStatus::Sent,
+            // the receiving queue of the peer is full
+            Err(e) if e.kind() == ErrorKind::WouldBlock => CommSendStatus::Again,
+            Err(_) => CommSendStatus::Lost,
+        }
+    }
+
+    // the payload of the pointer is the capacity before and the length after
+    pub fn recv_req(&self, raw: &mut RawPointer) -> CommRecvStatus {
+        let data = unsafe { std::slice::from_raw_parts_mut(raw.inner.as_ptr() as *mut u8, raw.payload) };
+        match self.sock.recv(data) {
+            // an empty datagram is the goodbye of the peer, a buffer is never empty
+            Ok(0) => CommRecvStatus::Lost,
+            Ok(len) => {
+                raw.payload = len;
+                CommRecvStatus::Received
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => CommRecvStatus::Empty,
+            Err(_) => CommRecvStatus::Lost,
+        }
+    }
+}
+
+impl Drop for EmuCommChannel {
+    fn drop(&mut self) {
+        let _ = self.sock.send(&[]);
+        let _ = remove_file(&self.path);
+    }
+}
@@ -1,10 +1,38 @@
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub mod connection;
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub mod comm_buf;
+// the channel over a unix socket without a dpu, the real one wins if both are on
+#[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+mod emu;
 
 use byte_struct::*;
 
+// a polled receive of the channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommRecvStatus {
+    Received,
+    Empty,
+    Lost,
+}
+
+// a try to send a buffer over the channel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommSendStatus {
+    Sent,
+    // the send queue is full
+    Again,
+    Lost,
+}
+
+// of the sends of a channel, the stalls are the sends refused as the channel is full
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CommSendStats {
+    pub sent: u64,
+    pub stalls: u64,
+    pub max_pending: usize,
+}
+
 pub mod doca_comm_info_type {
     pub type Type = u32;
     pub const REQ: Type = 0;
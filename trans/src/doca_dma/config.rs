@@ -8,7 +8,7 @@
 use doca::{ RawPointer, RawPointerMsg, DOCAMmap };
 use serde_derive::{ Serialize, Deserialize };
 
-use crate::common::connection::ConfigSerialize;
+use crate::common::connection::{ ConfigExchangeError, ConfigSerialize };
 
 pub const DOCA_MAX_CONN_LENGTH: usize = 4096;
 
@@ -56,8 +56,7 @@ impl ConfigSerialize for DocaConnInfoMsg {
         serde_json::to_vec(&data).unwrap()
     }
 
-    fn deserialize(data: &[u8]) -> DocaConnInfoMsg {
-        let msg: DocaConnInfoMsg = serde_json::from_slice(data).unwrap();
-        msg
+    fn deserialize(data: &[u8]) -> Result<DocaConnInfoMsg, ConfigExchangeError> {
+        serde_json::from_slice(data).map_err(|e| ConfigExchangeError::Malformed(e.to_string()))
     }
 }
\ No newline at end of file
@@ -5,37 +5,72 @@ use std::net::SocketAddr;
 use std::sync::{ Arc, Mutex };
 use std::ptr::NonNull;
 
-use doca::{open_device_with_pci, DOCAEvent};
+use doca::{open_device_with_pci, DevContext, DOCAEvent};
 use doca::dma::DOCAContext;
 use doca::{ RawPointer, DOCABuffer, DOCARegisteredMemory, DOCAMmap, BufferInventory, DOCAWorkQueue, DMAEngine };
 use doca_sys::doca_error;
 use doca_sys::doca_access_flags;
 
 use crate::{DOCA_WORKQ_DEPTH, MAX_DMA_BUF_REMOTE, MAX_DMA_BUF_SIZE, MAX_DMA_BUF_PER_ROUTINE};
+use crate::{TransError, TransResult};
+use crate::common::cluster::ClusterConfig;
 use super::process_helpers::{ recv_doca_config, load_doca_config };
 use super::export_helpers::send_doca_config;
+use super::config::DocaConnInfo;
+use super::device::check_doca_device;
 
 use super::dma_shared_buffer::DmaLocalBufAllocator;
 use super::dma_shared_buffer::DmaRemoteBufAllocator;
-use super::{ DmaLocalBuf, DmaRemoteBuf };
+use super::{ BufPoolStats, DmaCompletion, DmaLocalBuf, DmaRemoteBuf };
 
 pub struct DocaDmaControl {
-    conn: Option<Arc<Mutex<DocaDmaConn>>>,
+    // one per worker thread, each on its own work queue and region
+    conns: Vec<Arc<Mutex<DocaDmaConn>>>,
+    // the buffers of the memory the host exports
+    remote_buf_num: usize,
 }
 
 impl DocaDmaControl {
     pub fn new() -> Self {
         Self {
-            conn: None
+            conns: Vec::new(),
+            remote_buf_num: MAX_DMA_BUF_REMOTE,
         }
     }
-    pub fn listen_on(&mut self, pci_addr: &str, listen_addr: SocketAddr, coroutine_num: usize) {
-        let conn_info = recv_doca_config(listen_addr);
-        let config = load_doca_config(0, &conn_info).unwrap();
 
-        assert!(config.remote_addr.payload >= (MAX_DMA_BUF_REMOTE * MAX_DMA_BUF_SIZE));
+    // by the host before it exports, the dpu takes as many as it gets
+    pub fn set_remote_buf_num(&mut self, remote_buf_num: usize) {
+        self.remote_buf_num = remote_buf_num;
+    }
+    pub fn listen_on(&mut self, pci_addr: &str, listen_addr: SocketAddr, coroutine_num: usize) -> TransResult<()> {
+        self.listen_on_threads(pci_addr, listen_addr, 1, coroutine_num)
+    }
+
+    // one handshake brings the regions of all the threads
+    pub fn listen_on_threads(&mut self, pci_addr: &str, listen_addr: SocketAddr, thread_num: usize, coroutine_num: usize) -> TransResult<()> {
+        let conn_info = recv_doca_config(listen_addr)?;
+        if conn_info.buffers.len() < thread_num || conn_info.exports.len() < thread_num {
+            return Err(TransError::TransConfigError);
+        }
+
+        let device = open_device_with_pci(pci_addr).map_err(|_| TransError::TransDocaError)?;
+        self.conns.clear();
+        for tid in 0..thread_num {
+            let conn = Self::new_thread_conn(&device, &conn_info, tid, coroutine_num)?;
+            self.conns.push(conn);
+        }
+        Ok(())
+    }
+
+    // a dma engine and a work queue of the thread, on the region exported for it
+    fn new_thread_conn(device: &Arc<DevContext>, conn_info: &DocaConnInfo, tid: usize, coroutine_num: usize) -> TransResult<Arc<Mutex<DocaDmaConn>>> {
+        let config = load_doca_config(tid, conn_info).map_err(|_| TransError::TransDocaError)?;
+
+        let remote_buf_num = config.remote_addr.payload / MAX_DMA_BUF_SIZE;
+        if remote_buf_num == 0 {
+            return Err(TransError::TransConfigError);
+        }
 
-        let device = open_device_with_pci(pci_addr).unwrap();
         let dma = DMAEngine::new().unwrap();
         let ctx = DOCAContext::new(&dma, vec![device.clone()]).unwrap();
 
@@ -44,11 +79,11 @@ impl DocaDmaControl {
         let mut doca_mmap = Arc::new(DOCAMmap::new().unwrap());
         Arc::get_mut(&mut doca_mmap)
             .unwrap()
-            .add_device(&device)
+            .add_device(device)
             .unwrap();
         // get remote mmap
         let remote_mmap =
-            Arc::new(DOCAMmap::new_from_export(config.export_desc, &device).unwrap());
+            Arc::new(DOCAMmap::new_from_export(config.export_desc, device).unwrap());
 
         let inv = BufferInventory::new(64).unwrap();
         // remote buf
@@ -71,49 +106,118 @@ impl DocaDmaControl {
         
         doca_mmap.start().unwrap();
 
-        let conn = Arc::new(Mutex::new(DocaDmaConn::new(&workq, local_buf, remote_buf, lm, coroutine_num)));
+        let conn = Arc::new(Mutex::new(DocaDmaConn::new(&workq, local_buf, remote_buf, lm, coroutine_num, remote_buf_num)));
+        Ok(conn)
+    }
 
-        self.conn = Some(conn);
+    pub fn get_conn(&self) -> Option<Arc<Mutex<DocaDmaConn>>> {
+        self.conns.first().cloned()
+    }
 
+    pub fn get_thread_conn(&self, tid: usize) -> Option<Arc<Mutex<DocaDmaConn>>> {
+        self.conns.get(tid).cloned()
     }
 
-    pub fn get_conn(&self) -> Option<Arc<Mutex<DocaDmaConn>>> {
-        self.conn.clone()
+    // the dpu listens on its dma port of the thread tid for the memory of its host
+    pub fn listen_cluster(&mut self, cluster: &ClusterConfig, self_id: u64, tid: usize, coroutine_num: usize) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let pci_addr = node.get_pci_addr(tid).ok_or(TransError::TransConfigError)?;
+        let listen_addr: SocketAddr = format!("0.0.0.0:{}", node.dma_port as usize + tid)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        check_doca_device(pci_addr)?;
+        self.listen_on(pci_addr, listen_addr, coroutine_num)
+    }
+
+    // the host exports its memory to the dma port of its dpu
+    pub fn connect_cluster_and_waiting_loop(&mut self, cluster: &ClusterConfig, self_id: u64, tid: usize) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let dpu = node.dpu
+            .and_then(|dpu_id| cluster.get_node(dpu_id))
+            .ok_or(TransError::TransConfigError)?;
+        let pci_addr = node.get_pci_addr(tid).ok_or(TransError::TransConfigError)?;
+        let connect_addr: SocketAddr = dpu.get_dma_addr(tid)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        check_doca_device(pci_addr)?;
+        self.connect_and_waiting_loop(pci_addr, connect_addr)
+    }
+
+    // the dpu of the node takes the regions of all its threads at its first dma port
+    pub fn listen_cluster_threads(&mut self, cluster: &ClusterConfig, self_id: u64, coroutine_num: usize) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let pci_addr = node.get_pci_addr(0).ok_or(TransError::TransConfigError)?;
+        let listen_addr: SocketAddr = format!("0.0.0.0:{}", node.dma_port)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        check_doca_device(pci_addr)?;
+        self.listen_on_threads(pci_addr, listen_addr, node.threads, coroutine_num)
+    }
+
+    pub fn connect_cluster_threads_and_waiting_loop(&mut self, cluster: &ClusterConfig, self_id: u64) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let dpu = node.dpu
+            .and_then(|dpu_id| cluster.get_node(dpu_id))
+            .ok_or(TransError::TransConfigError)?;
+        let pci_addr = node.get_pci_addr(0).ok_or(TransError::TransConfigError)?;
+        let connect_addr: SocketAddr = dpu.get_dma_addr(0)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        check_doca_device(pci_addr)?;
+        self.connect_threads_and_waiting_loop(pci_addr, connect_addr, dpu.threads)
     }
 
     // temporarily, the dpu side just loop until down, becasuse it doesn't need anything
-    pub fn connect_and_waiting_loop(&mut self, pci_addr: &str, connect_addr: SocketAddr) {
+    pub fn connect_and_waiting_loop(&mut self, pci_addr: &str, connect_addr: SocketAddr) -> TransResult<()> {
+        self.connect_threads_and_waiting_loop(pci_addr, connect_addr, 1)
+    }
+
+    // a region for every thread of the dpu, all exported in one handshake
+    pub fn connect_threads_and_waiting_loop(&mut self, pci_addr: &str, connect_addr: SocketAddr, thread_num: usize) -> TransResult<()> {
         let running = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
-        let buffer_length = MAX_DMA_BUF_REMOTE * MAX_DMA_BUF_SIZE;
+        let buffer_length = self.remote_buf_num * MAX_DMA_BUF_SIZE;
+
+        // Open device
+        let device = doca::device::open_device_with_pci(pci_addr).map_err(|_| TransError::TransDocaError)?;
 
-        // first malloc the source buffer
-        let mut src_buffer = vec![0u8; buffer_length].into_boxed_slice();
+        let mut src_buffers = Vec::new();
+        let mut local_mmaps = Vec::new();
+        let mut src_raws = Vec::new();
+        for _ in 0..thread_num {
+            // first malloc the source buffer
+            let mut src_buffer = vec![0u8; buffer_length].into_boxed_slice();
 
-        for i in 0..32 {
-            src_buffer[i] = 'a' as _;
-        }
+            for i in 0..32 {
+                src_buffer[i] = 'a' as _;
+            }
 
-        // Open device
-        let device = doca::device::open_device_with_pci(pci_addr).unwrap();
+            let mut local_mmap = Arc::new(DOCAMmap::new().unwrap());
+            let local_mmap_ref = Arc::get_mut(&mut local_mmap).unwrap();
 
-        let mut local_mmap = Arc::new(DOCAMmap::new().unwrap());
-        let local_mmap_ref = Arc::get_mut(&mut local_mmap).unwrap();
+            let _ = local_mmap_ref.add_device(&device).unwrap();
 
-        let _ = local_mmap_ref.add_device(&device).unwrap();
+            let src_raw = RawPointer {
+                inner: NonNull::new(src_buffer.as_mut_ptr() as *mut _).unwrap(),
+                payload: buffer_length,
+            };
 
-        let src_raw = RawPointer {
-            inner: NonNull::new(src_buffer.as_mut_ptr() as *mut _).unwrap(),
-            payload: buffer_length,
-        };
+            // populate the buffer into the mmap
+            local_mmap_ref.set_memrange(src_raw).unwrap();
 
-        // populate the buffer into the mmap
-        local_mmap_ref.set_memrange(src_raw).unwrap();
+            local_mmap_ref.set_permission(doca_access_flags::DOCA_ACCESS_DPU_READ_WRITE.0 | doca_access_flags::DOCA_ACCESS_LOCAL_READ_WRITE.0).unwrap();
 
-        local_mmap_ref.set_permission(doca_access_flags::DOCA_ACCESS_DPU_READ_WRITE.0 | doca_access_flags::DOCA_ACCESS_LOCAL_READ_WRITE.0).unwrap();
+            local_mmap_ref.start().unwrap();
 
-        local_mmap_ref.start().unwrap();
+            src_buffers.push(src_buffer);
+            local_mmaps.push(local_mmap);
+            src_raws.push(src_raw);
+        }
 
-        send_doca_config(connect_addr, 1, &mut local_mmap, src_raw);
+        send_doca_config(connect_addr, &mut local_mmaps, &src_raws)?;
 
         let r = running.clone();
         ctrlc::set_handler(move || {
@@ -124,11 +228,11 @@ impl DocaDmaControl {
             // Your program's code goes here
             std::thread::sleep(std::time::Duration::from_millis(1000));
 
-            println!("{}", String::from_utf8(src_buffer[0..16].to_vec()).unwrap());
+            println!("{}", String::from_utf8(src_buffers[0][0..16].to_vec()).unwrap());
         }
 
         println!("Server is down!");
-
+        Ok(())
     }
 }
 
@@ -138,17 +242,20 @@ pub struct DocaDmaConn {
     dma_job:       DOCADMAReusableJob,
     local_alloc:   DmaLocalBufAllocator,
     remote_alloc:  DmaRemoteBufAllocator,
+    // the jobs in the work queue
+    inflight:      usize,
 }
 
 impl DocaDmaConn {
-    pub fn new(workq: &Arc<DOCAWorkQueue<DMAEngine>>, local_buf: DOCABuffer, remote_buf: DOCABuffer, lm: *mut u8, coroutine_num: usize) -> Self {
+    pub fn new(workq: &Arc<DOCAWorkQueue<DMAEngine>>, local_buf: DOCABuffer, remote_buf: DOCABuffer, lm: *mut u8, coroutine_num: usize, remote_buf_num: usize) -> Self {
         let dma_job = workq.create_dma_reusable_job(local_buf, remote_buf);
         Self {
             lm: lm,
             workq: workq.clone(),
             dma_job: dma_job,
             local_alloc:  DmaLocalBufAllocator::new(coroutine_num as _, lm as _, coroutine_num as usize * MAX_DMA_BUF_PER_ROUTINE * MAX_DMA_BUF_SIZE),
-            remote_alloc: DmaRemoteBufAllocator::new(),
+            remote_alloc: DmaRemoteBufAllocator::new(remote_buf_num),
+            inflight: 0,
         }
     }
 
@@ -162,6 +269,7 @@ impl DocaDmaConn {
             .unwrap()
             .submit(&self.dma_job)
             .unwrap();
+        self.inflight += 1;
     }
 
     #[inline]
@@ -174,6 +282,13 @@ impl DocaDmaConn {
             .unwrap()
             .submit(&self.dma_job)
             .unwrap();
+        self.inflight += 1;
+    }
+
+    // the work queue takes another job
+    #[inline]
+    pub fn has_room(&self) -> bool {
+        self.inflight < DOCA_WORKQ_DEPTH
     }
 
     #[inline]
@@ -183,13 +298,30 @@ impl DocaDmaConn {
             .progress_retrieve()
     }
 
+    // the completion as the scheduler takes it from either backend
+    #[inline]
+    pub fn poll_dma_comp(&mut self) -> DmaCompletion {
+        let (event, error) = self.poll_completion();
+        match error {
+            doca_error::DOCA_SUCCESS => {
+                self.inflight -= 1;
+                DmaCompletion::Done(event.user_mark())
+            }
+            doca_error::DOCA_ERROR_AGAIN => DmaCompletion::Empty,
+            _ => {
+                self.inflight -= 1;
+                DmaCompletion::Failed(event.user_mark())
+            }
+        }
+    }
+
     #[inline]
     pub fn get_local_buf(&mut self, cid: u32) -> DmaLocalBuf {
         self.local_alloc.get_local_buf(cid)
     }
 
     #[inline]
-    pub fn alloc_remote_buf(&mut self) -> DmaRemoteBuf {
+    pub fn alloc_remote_buf(&mut self) -> Option<DmaRemoteBuf> {
         self.remote_alloc.alloc_remote_buf()
     }
 
@@ -198,6 +330,11 @@ impl DocaDmaConn {
         self.remote_alloc.dealloc_remote_buf(buf);
     }
 
+    #[inline]
+    pub fn get_remote_buf_stats(&self) -> BufPoolStats {
+        self.remote_alloc.get_stats()
+    }
+
 }
 
 impl Drop for DocaDmaConn {
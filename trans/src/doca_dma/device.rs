@@ -0,0 +1,35 @@
+use doca::device::devices;
+
+use crate::{TransError, TransResult};
+
+#[derive(Clone, Debug)]
+pub struct DocaDeviceInfo {
+    // e.g. "af:00.0", as the pci addrs of the cluster config
+    pub pci_addr: String,
+    pub max_buf_size: u64,
+}
+
+// the doca devices of the machine
+pub fn list_doca_devices() -> TransResult<Vec<DocaDeviceInfo>> {
+    let dev_list = devices().map_err(|_| TransError::TransDocaError)?;
+
+    let mut infos = Vec::new();
+    for i in 0..dev_list.num_devices() {
+        let device = dev_list.get(i).ok_or(TransError::TransDocaError)?;
+        infos.push(DocaDeviceInfo {
+            pci_addr: device.name().map_err(|_| TransError::TransDocaError)?,
+            max_buf_size: device.get_max_buf_size().unwrap_or(0),
+        });
+    }
+    Ok(infos)
+}
+
+// the configured device is on the machine, checked as opening a missing one panics
+pub fn check_doca_device(pci_addr: &str) -> TransResult<()> {
+    if list_doca_devices()?.iter().any(|info| info.pci_addr == pci_addr) {
+        Ok(())
+    } else {
+        println!("no doca device at {}", pci_addr);
+        Err(TransError::TransConfigError)
+    }
+}
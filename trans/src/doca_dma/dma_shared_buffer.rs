@@ -1,8 +1,7 @@
 use crate::MAX_DMA_BUF_PER_ROUTINE;
 use crate::MAX_DMA_BUF_SIZE;
-use crate::MAX_DMA_BUF_REMOTE;
 
-use super::{ DmaLocalBuf, DmaRemoteBuf };
+use super::{ BufPoolStats, DmaLocalBuf, DmaRemoteBuf, DmaSegment };
 
 impl DmaLocalBuf {
     pub fn new(saddr: usize, off: usize, len: usize) -> Self {
@@ -83,6 +82,31 @@ impl DmaLocalBufAllocator {
     }
 }
 
+impl DmaSegment {
+    pub fn new(local_offset: usize, remote_offset: usize, payload: usize) -> Self {
+        Self {
+            local_offset: local_offset,
+            remote_offset: remote_offset,
+            payload: payload,
+        }
+    }
+
+    // merges the neighbours contiguous on both sides into one job
+    pub fn coalesce(segs: &[DmaSegment]) -> Vec<DmaSegment> {
+        let mut merged: Vec<DmaSegment> = Vec::with_capacity(segs.len());
+        for seg in segs.iter().filter(|seg| seg.payload > 0) {
+            match merged.last_mut() {
+                Some(last) if last.local_offset + last.payload == seg.local_offset
+                    && last.remote_offset + last.payload == seg.remote_offset => {
+                    last.payload += seg.payload;
+                }
+                _ => merged.push(*seg),
+            }
+        }
+        merged
+    }
+}
+
 impl DmaRemoteBuf {
     pub fn new(off: usize, len: usize) -> Self {
         Self {
@@ -97,25 +121,47 @@ impl DmaRemoteBuf {
     }
 }
 
+// the buffers are carved from the exported memory of the host on demand,
+// as many as it holds, and recycled after
 pub struct DmaRemoteBufAllocator {
     recycled: Vec<DmaRemoteBuf>,
+    carved: usize,
+    stats: BufPoolStats,
 }
 
 impl DmaRemoteBufAllocator {
-    pub fn new() -> Self {
-        let mut recyled = Vec::new();
-        for i in 0..MAX_DMA_BUF_REMOTE {
-            recyled.push(DmaRemoteBuf::new(i * MAX_DMA_BUF_SIZE, MAX_DMA_BUF_SIZE));
-        }
+    pub fn new(capacity: usize) -> Self {
         Self {
-            recycled: recyled
+            recycled: Vec::new(),
+            carved: 0,
+            stats: BufPoolStats::new(capacity),
         }
     }
-    pub fn alloc_remote_buf(&mut self) -> DmaRemoteBuf {
-        self.recycled.pop().unwrap()
+
+    // None when all buffers are in use, the caller waits for one to return
+    pub fn alloc_remote_buf(&mut self) -> Option<DmaRemoteBuf> {
+        let buf = match self.recycled.pop() {
+            Some(buf) => buf,
+            None if self.carved < self.stats.capacity => {
+                self.carved += 1;
+                DmaRemoteBuf::new((self.carved - 1) * MAX_DMA_BUF_SIZE, MAX_DMA_BUF_SIZE)
+            }
+            None => {
+                self.stats.exhausted += 1;
+                return None;
+            }
+        };
+        self.stats.on_alloc();
+        Some(buf)
     }
 
     pub fn dealloc_remote_buf(&mut self, buf: DmaRemoteBuf) {
+        self.stats.on_dealloc();
         self.recycled.push(buf);
     }
+
+    #[inline]
+    pub fn get_stats(&self) -> BufPoolStats {
+        self.stats
+    }
 }
\ No newline at end of file
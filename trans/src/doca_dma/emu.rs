@@ -0,0 +1,390 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use libc::{free, memalign};
+use serde_json::json;
+
+use crate::common::cluster::ClusterConfig;
+use crate::common::connection::{recv_config, send_config, ConfigExchangeError, ConfigSerialize};
+use crate::{TransError, TransResult};
+use crate::{DOCA_WORKQ_DEPTH, MAX_DMA_BUF_PER_ROUTINE, MAX_DMA_BUF_REMOTE, MAX_DMA_BUF_SIZE};
+
+use super::dma_shared_buffer::{DmaLocalBufAllocator, DmaRemoteBufAllocator};
+use super::{BufPoolStats, DmaCompletion, DmaLocalBuf, DmaRemoteBuf};
+
+/// The memory a host exports to its dpu, a file mapped shared by both processes
+/// (or threads), in place of the doca mmap the real dma engine reads and writes.
+pub struct EmuDmaRegion {
+    base: *mut u8,
+    len: usize,
+    path: PathBuf,
+    // the creator removes the file
+    owner: bool,
+}
+
+unsafe impl Send for EmuDmaRegion {}
+unsafe impl Sync for EmuDmaRegion {}
+
+impl EmuDmaRegion {
+    fn map(path: &Path, len: usize, owner: bool) -> TransResult<Self> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(owner)
+            .open(path)
+            .map_err(|_| TransError::TransDocaError)?;
+        if owner {
+            file.set_len(len as _).map_err(|_| TransError::TransDocaError)?;
+        }
+
+        let base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                std::os::unix::io::AsRawFd::as_raw_fd(&file),
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(TransError::TransDocaError);
+        }
+
+        Ok(Self {
+            base: base as _,
+            len: len,
+            path: path.to_path_buf(),
+            owner: owner,
+        })
+    }
+
+    // by the host, zeroed
+    pub fn create(path: &Path, len: usize) -> TransResult<Self> {
+        Self::map(path, len, true)
+    }
+
+    // by the dpu, on the file the host created
+    pub fn open(path: &Path, len: usize) -> TransResult<Self> {
+        Self::map(path, len, false)
+    }
+
+    #[inline]
+    pub fn get_base(&self) -> *mut u8 {
+        self.base
+    }
+
+    #[inline]
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Drop for EmuDmaRegion {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base as _, self.len); }
+        if self.owner {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// what the host sends to the dpu, in place of the doca export descriptors,
+// a region of the same length for every thread
+struct EmuDmaInfo {
+    paths: Vec<String>,
+    len: usize,
+}
+
+impl ConfigSerialize for EmuDmaInfo {
+    fn serialize(data: Self) -> Vec<u8> {
+        json!({ "paths": data.paths, "len": data.len }).to_string().into_bytes()
+    }
+
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError> {
+        let value: serde_json::Value =
+            serde_json::from_slice(data).map_err(|e| ConfigExchangeError::Malformed(e.to_string()))?;
+        let len = value["len"]
+            .as_u64()
+            .ok_or_else(|| ConfigExchangeError::Malformed("no length of the regions".to_string()))?;
+        Ok(Self {
+            paths: value["paths"]
+                .as_array()
+                .map(|paths| paths.iter().filter_map(|p| p.as_str()).map(|p| p.to_string()).collect())
+                .unwrap_or_default(),
+            len: len as _,
+        })
+    }
+}
+
+struct EmuDmaJob {
+    write: bool,
+    local_offset: usize,
+    remote_offset: usize,
+    payload: usize,
+    user_mark: u64,
+    ready_at: Instant,
+}
+
+/// The dma connection without a dpu. A job is copied between the local buffers
+/// and the exported region once its latency has passed, then completes in order.
+pub struct DocaDmaConn {
+    lm: *mut u8,
+    lm_length: usize,
+    region: Arc<EmuDmaRegion>,
+    latency: Duration,
+    jobs: VecDeque<EmuDmaJob>,
+    local_alloc: DmaLocalBufAllocator,
+    remote_alloc: DmaRemoteBufAllocator,
+}
+
+unsafe impl Send for DocaDmaConn {}
+
+impl DocaDmaConn {
+    pub fn new(region: &Arc<EmuDmaRegion>, coroutine_num: usize, latency: Duration) -> Self {
+        let lm_length = coroutine_num * MAX_DMA_BUF_SIZE * MAX_DMA_BUF_PER_ROUTINE;
+        let lm = unsafe { memalign(4096, lm_length) } as *mut u8;
+        Self {
+            lm: lm,
+            lm_length: lm_length,
+            region: region.clone(),
+            latency: latency,
+            jobs: VecDeque::new(),
+            local_alloc: DmaLocalBufAllocator::new(coroutine_num as _, lm as _, lm_length),
+            // as many as the region holds
+            remote_alloc: DmaRemoteBufAllocator::new(region.get_len() / MAX_DMA_BUF_SIZE),
+        }
+    }
+
+    fn post_job(&mut self, write: bool, local_offset: usize, remote_offset: usize, payload: usize, user_mark: u64) {
+        self.jobs.push_back(EmuDmaJob {
+            write: write,
+            local_offset: local_offset,
+            remote_offset: remote_offset,
+            payload: payload,
+            user_mark: user_mark,
+            ready_at: Instant::now() + self.latency,
+        });
+    }
+
+    #[inline]
+    pub fn post_read_dma_reqs(&mut self, local_offset: usize, remote_offset: usize, payload: usize, user_mark: u64) {
+        self.post_job(false, local_offset, remote_offset, payload, user_mark);
+    }
+
+    #[inline]
+    pub fn post_write_dma_reqs(&mut self, local_offset: usize, remote_offset: usize, payload: usize, user_mark: u64) {
+        self.post_job(true, local_offset, remote_offset, payload, user_mark);
+    }
+
+    // the work queue is as deep as the real one
+    #[inline]
+    pub fn has_room(&self) -> bool {
+        self.jobs.len() < DOCA_WORKQ_DEPTH
+    }
+
+    // the jobs out of the local buffers or the region fail as the dma engine does
+    pub fn poll_dma_comp(&mut self) -> DmaCompletion {
+        match self.jobs.front() {
+            Some(job) if job.ready_at <= Instant::now() => {}
+            _ => return DmaCompletion::Empty,
+        }
+
+        let job = self.jobs.pop_front().unwrap();
+        if job.local_offset + job.payload > self.lm_length
+            || job.remote_offset + job.payload > self.region.get_len()
+        {
+            return DmaCompletion::Failed(job.user_mark);
+        }
+
+        unsafe {
+            let local = self.lm.add(job.local_offset);
+            let remote = self.region.get_base().add(job.remote_offset);
+            if job.write {
+                std::ptr::copy_nonoverlapping(local, remote, job.payload);
+            } else {
+                std::ptr::copy_nonoverlapping(remote, local, job.payload);
+            }
+        }
+        DmaCompletion::Done(job.user_mark)
+    }
+
+    #[inline]
+    pub fn get_local_buf(&mut self, cid: u32) -> DmaLocalBuf {
+        self.local_alloc.get_local_buf(cid)
+    }
+
+    #[inline]
+    pub fn alloc_remote_buf(&mut self) -> Option<DmaRemoteBuf> {
+        self.remote_alloc.alloc_remote_buf()
+    }
+
+    #[inline]
+    pub fn dealloc_remote_buf(&mut self, buf: DmaRemoteBuf) {
+        self.remote_alloc.dealloc_remote_buf(buf);
+    }
+
+    #[inline]
+    pub fn get_remote_buf_stats(&self) -> BufPoolStats {
+        self.remote_alloc.get_stats()
+    }
+}
+
+impl Drop for DocaDmaConn {
+    fn drop(&mut self) {
+        unsafe { free(self.lm as _); }
+    }
+}
+
+// the file of the region of a thread of the dma port, the host and the dpu share a machine
+fn region_path(addr: &SocketAddr, tid: usize) -> PathBuf {
+    std::env::temp_dir().join(format!("trans_dma_{}_{}", addr.port(), tid))
+}
+
+pub struct DocaDmaControl {
+    // one per worker thread, each on its own jobs and region
+    conns: Vec<Arc<Mutex<DocaDmaConn>>>,
+    latency: Duration,
+    remote_buf_num: usize,
+}
+
+impl DocaDmaControl {
+    pub fn new() -> Self {
+        Self {
+            conns: Vec::new(),
+            latency: Duration::ZERO,
+            remote_buf_num: MAX_DMA_BUF_REMOTE,
+        }
+    }
+
+    // of every job, before listen_on
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    // by the host before it exports, the dpu takes as many as it gets
+    pub fn set_remote_buf_num(&mut self, remote_buf_num: usize) {
+        self.remote_buf_num = remote_buf_num;
+    }
+
+    // the pci address is unused, the region of the host is named by the dma port
+    #[allow(unused_variables)]
+    pub fn listen_on(&mut self, pci_addr: &str, listen_addr: SocketAddr, coroutine_num: usize) -> TransResult<()> {
+        self.listen_on_threads(pci_addr, listen_addr, 1, coroutine_num)
+    }
+
+    // one handshake brings the regions of all the threads
+    #[allow(unused_variables)]
+    pub fn listen_on_threads(&mut self, pci_addr: &str, listen_addr: SocketAddr, thread_num: usize, coroutine_num: usize) -> TransResult<()> {
+        let info = recv_config::<EmuDmaInfo>(listen_addr)?;
+        if info.len < MAX_DMA_BUF_SIZE || info.paths.len() < thread_num {
+            return Err(TransError::TransConfigError);
+        }
+
+        self.conns.clear();
+        for path in info.paths.iter().take(thread_num) {
+            let region = Arc::new(EmuDmaRegion::open(Path::new(path), info.len)?);
+            let conn = DocaDmaConn::new(&region, coroutine_num, self.latency);
+            self.conns.push(Arc::new(Mutex::new(conn)));
+        }
+        Ok(())
+    }
+
+    pub fn get_conn(&self) -> Option<Arc<Mutex<DocaDmaConn>>> {
+        self.conns.first().cloned()
+    }
+
+    pub fn get_thread_conn(&self, tid: usize) -> Option<Arc<Mutex<DocaDmaConn>>> {
+        self.conns.get(tid).cloned()
+    }
+
+    pub fn listen_cluster(&mut self, cluster: &ClusterConfig, self_id: u64, tid: usize, coroutine_num: usize) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let listen_addr: SocketAddr = format!("0.0.0.0:{}", node.dma_port as usize + tid)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        self.listen_on("", listen_addr, coroutine_num)
+    }
+
+    pub fn connect_cluster_and_waiting_loop(&mut self, cluster: &ClusterConfig, self_id: u64, tid: usize) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let dpu = node.dpu
+            .and_then(|dpu_id| cluster.get_node(dpu_id))
+            .ok_or(TransError::TransConfigError)?;
+        let connect_addr: SocketAddr = dpu.get_dma_addr(tid)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        self.connect_and_waiting_loop("", connect_addr)
+    }
+
+    // the dpu of the node takes the regions of all its threads at its first dma port
+    pub fn listen_cluster_threads(&mut self, cluster: &ClusterConfig, self_id: u64, coroutine_num: usize) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let listen_addr: SocketAddr = format!("0.0.0.0:{}", node.dma_port)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        self.listen_on_threads("", listen_addr, node.threads, coroutine_num)
+    }
+
+    pub fn connect_cluster_threads_and_waiting_loop(&mut self, cluster: &ClusterConfig, self_id: u64) -> TransResult<()> {
+        let node = cluster.get_node(self_id).ok_or(TransError::TransConfigError)?;
+        let dpu = node.dpu
+            .and_then(|dpu_id| cluster.get_node(dpu_id))
+            .ok_or(TransError::TransConfigError)?;
+        let connect_addr: SocketAddr = dpu.get_dma_addr(0)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+
+        self.connect_threads_and_waiting_loop("", connect_addr, dpu.threads)
+    }
+
+    // the host creates a region for every thread of the dpu and sends them in one handshake,
+    // the regions live as long as the returned handles
+    pub fn export_threads(&mut self, connect_addr: SocketAddr, thread_num: usize) -> TransResult<Vec<Arc<EmuDmaRegion>>> {
+        let buffer_length = self.remote_buf_num * MAX_DMA_BUF_SIZE;
+
+        let mut regions = Vec::new();
+        let mut paths = Vec::new();
+        for tid in 0..thread_num {
+            let path = region_path(&connect_addr, tid);
+            regions.push(Arc::new(EmuDmaRegion::create(&path, buffer_length)?));
+            paths.push(path.to_string_lossy().into_owned());
+        }
+
+        send_config(connect_addr, EmuDmaInfo {
+            paths: paths,
+            len: buffer_length,
+        })?;
+        Ok(regions)
+    }
+
+    // the host creates the region and waits as the real one, until ctrl-c
+    pub fn connect_and_waiting_loop(&mut self, pci_addr: &str, connect_addr: SocketAddr) -> TransResult<()> {
+        self.connect_threads_and_waiting_loop(pci_addr, connect_addr, 1)
+    }
+
+    #[allow(unused_variables)]
+    pub fn connect_threads_and_waiting_loop(&mut self, pci_addr: &str, connect_addr: SocketAddr, thread_num: usize) -> TransResult<()> {
+        let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let regions = self.export_threads(connect_addr, thread_num)?;
+
+        let r = running.clone();
+        ctrlc::set_handler(move || {
+            r.store(false, std::sync::atomic::Ordering::SeqCst);
+        }).expect("Error setting Ctrl-C handler");
+
+        while running.load(std::sync::atomic::Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(1000));
+        }
+
+        drop(regions);
+        println!("Server is down!");
+        Ok(())
+    }
+}
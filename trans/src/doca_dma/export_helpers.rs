@@ -12,22 +12,24 @@ use std::sync::Arc;
 use doca::{ DOCAMmap, RawPointer, };
 
 use crate::common::connection::send_config;
+use crate::TransResult;
 use super::config::{ DocaConnInfo, DocaConnInfoMsg };
 
-pub fn send_doca_config(addr: SocketAddr, num_dev: usize, doca_mmap: &mut Arc<DOCAMmap>, src_buf: RawPointer) {
+// the export and the buffer of a mmap for every thread of the dpu, in one message
+pub fn send_doca_config(addr: SocketAddr, doca_mmaps: &mut [Arc<DOCAMmap>], src_bufs: &[RawPointer]) -> TransResult<()> {
     let mut doca_conn: DocaConnInfo = Default::default();
 
-    for i in 0..num_dev {
+    for doca_mmap in doca_mmaps.iter_mut() {
         let export_desc = 
             Arc::get_mut(doca_mmap)
                 .expect("doca map is owned by more than once!")
-                .export_dpu(i)
+                .export_dpu(0)
                 .unwrap();
 
         doca_conn.exports.push(unsafe {
             slice::from_raw_parts_mut(export_desc.inner.as_ptr() as *mut _, export_desc.payload).to_vec()
         });
     }
-    doca_conn.buffers.push(src_buf);
+    doca_conn.buffers.extend_from_slice(src_bufs);
     send_config::<DocaConnInfoMsg>(addr, doca_conn.into())
 }
@@ -3,11 +3,60 @@ pub mod config;
 #[cfg(feature = "doca_deps")]
 pub mod connection;
 #[cfg(feature = "doca_deps")]
+pub mod device;
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 mod dma_shared_buffer;
 #[cfg(feature = "doca_deps")]
 pub mod export_helpers;
 #[cfg(feature = "doca_deps")]
 pub mod process_helpers;
+// the dma over shared memory without a dpu, the real one wins if both are on
+#[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+pub mod emu;
+
+#[cfg(feature = "doca_deps")]
+pub use connection::{DocaDmaConn, DocaDmaControl};
+#[cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+pub use emu::{DocaDmaConn, DocaDmaControl};
+
+// a polled dma job, by its user mark
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DmaCompletion {
+    Done(u64),
+    Failed(u64),
+    Empty,
+}
+
+// the occupancy of a buffer pool, the exhausted are the allocations refused
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BufPoolStats {
+    pub capacity: usize,
+    pub in_use: usize,
+    pub peak_in_use: usize,
+    pub exhausted: u64,
+    // the times the pool was enlarged
+    pub grows: u64,
+}
+
+impl BufPoolStats {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn on_alloc(&mut self) {
+        self.in_use += 1;
+        self.peak_in_use = self.peak_in_use.max(self.in_use);
+    }
+
+    #[inline]
+    pub fn on_dealloc(&mut self) {
+        self.in_use -= 1;
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct DmaLocalBuf {
@@ -16,6 +65,14 @@ pub struct DmaLocalBuf {
     len:   usize,
 }
 
+// a contiguous piece of a batched dma job
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DmaSegment {
+    pub local_offset: usize,
+    pub remote_offset: usize,
+    pub payload: usize,
+}
+
 #[derive(Clone)]
 pub struct DmaRemoteBuf {
     off:   usize,
@@ -11,11 +11,12 @@ use std::net::SocketAddr;
 use doca::{ RawPointer, DOCAResult, LoadedInfo };
 
 use crate::common::connection::recv_config;
+use crate::TransResult;
 
 use super::config::{ DocaConnInfo, DocaConnInfoMsg };
 
-pub fn recv_doca_config(addr: SocketAddr) -> DocaConnInfo {
-    recv_config::<DocaConnInfoMsg>(addr).into()
+pub fn recv_doca_config(addr: SocketAddr) -> TransResult<DocaConnInfo> {
+    Ok(recv_config::<DocaConnInfoMsg>(addr)?.into())
 }
 
 pub fn load_doca_config(thread_id: usize, doca_conn: &DocaConnInfo) -> DOCAResult<LoadedInfo> {
@@ -2,6 +2,7 @@ pub mod rpc;
 mod rpc_shared_buffer;
 pub mod scheduler;
 pub mod worker;
+pub mod quiesce;
 
 use rpc::*;
 pub struct YieldReq {
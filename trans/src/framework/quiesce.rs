@@ -0,0 +1,64 @@
+use std::sync::atomic::{ AtomicBool, AtomicUsize, Ordering };
+use std::thread::sleep;
+use std::time::{ Duration, Instant };
+
+/// Holds the new txns of the workers sharing it back, so that a tool, e.g. the
+/// consistency checker, sees the stores with no txn in flight.
+pub struct QuiesceGate {
+    paused:   AtomicBool,
+    inflight: AtomicUsize,
+}
+
+impl QuiesceGate {
+    pub fn new() -> Self {
+        Self {
+            paused:   AtomicBool::new(false),
+            inflight: AtomicUsize::new(0),
+        }
+    }
+
+    // before a txn, yields while the gate is paused
+    pub async fn enter(&self) {
+        loop {
+            while self.paused.load(Ordering::SeqCst) {
+                tokio::task::yield_now().await;
+            }
+
+            self.inflight.fetch_add(1, Ordering::SeqCst);
+            if !self.paused.load(Ordering::SeqCst) {
+                return;
+            }
+            // paused in between, back off so that the pause drains
+            self.inflight.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // once the txn committed or aborted
+    pub fn leave(&self) {
+        self.inflight.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    // blocks until no txn is in flight, so never on a worker thread,
+    // false on timeout with the gate still paused
+    pub fn pause(&self, timeout: Duration) -> bool {
+        self.paused.store(true, Ordering::SeqCst);
+
+        let deadline = Instant::now() + timeout;
+        while self.inflight.load(Ordering::SeqCst) != 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+}
@@ -107,6 +107,10 @@ pub trait RpcHandler {
         size: u32,
         meta: RpcProcessMeta,
     );
+
+    // the connection to the peer broke, the requests it sent will not finish
+    #[allow(unused_variables)]
+    fn peer_broken_handler(&self, peer_id: u64) {}
 }
 
 pub struct DefaultRpcHandler;
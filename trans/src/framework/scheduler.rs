@@ -1,35 +1,41 @@
+use std::alloc::Layout;
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::fmt;
 use std::sync::{Arc, Weak};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use crate::common::cluster::ClusterConfig;
 use crate::doca_dma::{DmaLocalBuf, DmaRemoteBuf};
+use crate::rdma::control::RdmaControl;
 use crate::rdma::RdmaBaseAllocator;
 use crate::rdma::rcconn::RdmaRcConn;
+use crate::rdma::srq::RdmaSharedRecvQueue;
+use crate::rdma::udadapter::{RdmaUdAdapter, RdmaUdAddr, UD_SEQ_MASK};
 use crate::rdma::RdmaRecvCallback;
-// use crate::rdma::one_side::OneSideComm;
+use crate::rdma::one_side::OneSideComm;
 use crate::rdma::two_sides::TwoSidesComm;
 use crate::rdma::RdmaSendCallback;
 use crate::MAX_CONN_MSG_SIZE;
+use crate::WRID_RESERVE_BITS;
+use crate::TransResult;
+use crate::{UD_MAX_RETRANSMITS, UD_RETRANSMIT_MS};
 
-#[cfg(feature = "doca_deps")]
-use doca::DOCAError;
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+use crate::doca_dma::{BufPoolStats, DmaCompletion, DmaSegment, DocaDmaConn};
 
-#[cfg(feature = "doca_deps")]
-use crate::doca_dma::connection::DocaDmaConn;
-
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 use crate::doca_comm_chan::connection::DocaCommChannel;
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 use crate::doca_comm_chan::connection::{ DocaCommHandler, DEFAULT_DOCA_CONN_HANDLER };
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 use crate::doca_comm_chan::comm_buf::{ DocaCommBuf, DocaCommReply };
 
-#[cfg(feature = "doca_deps")]
-use crate::doca_comm_chan::{ doca_comm_info_type, DocaCommHeaderMeta };
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+use crate::doca_comm_chan::{ doca_comm_info_type, CommRecvStatus, CommSendStats, DocaCommHeaderMeta };
 
 use super::rpc_shared_buffer::RpcBufAllocator;
 
@@ -45,6 +51,10 @@ use super::rpc::{AsyncRpc, RpcProcessMeta};
 struct ReplyMeta {
     reply_bufs: Vec<*mut u8>,
     reply_counts: Vec<u32>,
+    // the replies left will not come, the buffers are not filled
+    reply_failed: Vec<bool>,
+    // the peers the requests went to, failed if one of them breaks
+    reply_peers: Vec<Vec<u64>>,
 }
 
 impl ReplyMeta {
@@ -59,11 +69,32 @@ impl ReplyMeta {
         Self {
             reply_bufs: bufs,
             reply_counts: counts,
+            reply_failed: vec![false; routine_num as usize],
+            reply_peers: (0..routine_num).map(|_| Vec::new()).collect(),
         }
     }
 }
 
-#[cfg(feature = "doca_deps")]
+// a datagram request of a routine, sent again until its reply comes
+struct UdInflight {
+    peer_id: u64,
+    peer_tid: u64,
+    rpc_id: u32,
+    seq: u32,
+    // the header and the payload, the req buf is kept by the routine until the replies
+    msg: *mut u8,
+    len: u32,
+    sent: Instant,
+    retries: usize,
+}
+
+// the last datagram request of a remote routine and the reply sent for it, empty until then
+struct UdServed {
+    seq: u32,
+    reply: Vec<u8>,
+}
+
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 #[derive(Clone, Copy)]
 enum DmaStatus {
     DmaIdle,
@@ -71,13 +102,74 @@ enum DmaStatus {
     DmaError,
 }
 
-#[cfg(feature = "doca_deps")]
-struct DmaMeta(DmaStatus);
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+struct DmaMeta {
+    status: DmaStatus,
+    // the jobs of the routine in the work queue
+    inflight: usize,
+    failed: bool,
+    // the segments of a batch waiting for room in the work queue, true to write
+    queued: std::collections::VecDeque<(DmaSegment, bool)>,
+}
+
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+impl DmaMeta {
+    fn new() -> Self {
+        Self {
+            status: DmaStatus::DmaIdle,
+            inflight: 0,
+            failed: false,
+            queued: std::collections::VecDeque::new(),
+        }
+    }
+
+    #[inline]
+    fn is_done(&self) -> bool {
+        self.inflight == 0 && self.queued.is_empty()
+    }
+
+    // a new request of the routine, the error of the last one is forgotten
+    fn start(&mut self) {
+        if self.is_done() {
+            self.failed = false;
+        }
+        self.status = DmaStatus::DmaWaiting;
+    }
+
+    // the routine is woken by the last job
+    fn finish_job(&mut self, success: bool) {
+        self.inflight = self.inflight.saturating_sub(1);
+        if !success {
+            self.failed = true;
+            self.queued.clear();
+        }
+        if self.is_done() {
+            self.status = if self.failed { DmaStatus::DmaError } else { DmaStatus::DmaIdle };
+        }
+    }
+}
 
 pub struct AsyncScheduler {
     tid: usize,
     allocator: Mutex<RpcBufAllocator>,
+    mr_allocator: Arc<RdmaBaseAllocator>,
     conns: HashMap<u64, Arc<Mutex<RdmaRcConn>>>,
+    // the recvs of the shared connections, demultiplexed by the local qp
+    srq: Option<Arc<Mutex<RdmaSharedRecvQueue>>>,
+    // refreshed when a shared connection is re-established on a new qp
+    qp_conns: UnsafeCell<HashMap<u32, Arc<Mutex<RdmaRcConn>>>>,
+    // datagram rpcs to the peers without a rc connection, by (peer_id, tid)
+    ud: Option<Arc<Mutex<RdmaUdAdapter>>>,
+    ud_conns: HashMap<(u64, u64), Arc<Mutex<RdmaRcConn>>>,
+    // by routine, the requests sent again until their replies come
+    ud_inflight: UnsafeCell<Vec<Vec<UdInflight>>>,
+    // the seq of the last request to (peer_id, tid, cid), and the one served from there
+    ud_seqs: UnsafeCell<HashMap<(u64, u64, u32), u32>>,
+    ud_served: UnsafeCell<HashMap<(u64, u64, u32), UdServed>>,
+    // the peers whose connections are seen broken, until they are re-established
+    broken: UnsafeCell<Vec<u64>>,
+    // dials or accepts the broken peers again from poll_recvs
+    reconnect: Option<Mutex<(RdmaControl, ClusterConfig)>>,
     vers: UnsafeCell<Vec<u32>>,
     //  read / write (one-side primitives)
     // pending for coroutines
@@ -89,16 +181,19 @@ pub struct AsyncScheduler {
     // callbacks
     callback: Weak<dyn RpcHandler + Send + Sync + 'static>,
 
-    #[cfg(feature = "doca_deps")]
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
     dma_conn: Option<Arc<Mutex<DocaDmaConn>>>,
-    #[cfg(feature = "doca_deps")]
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
     dma_meta: UnsafeCell<Vec<DmaMeta>>,
-    #[cfg(feature = "doca_deps")]
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
     comm_chan: Option<DocaCommChannel>,
-    #[cfg(feature = "doca_deps")]
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
     comm_handler: Weak<dyn DocaCommHandler + Send + Sync + 'static>,
-    #[cfg(feature = "doca_deps")]
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
     comm_replys: UnsafeCell<Vec<DocaCommReply>>,
+    // the peer of the comm channel is gone, it is no longer polled
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+    comm_lost: UnsafeCell<bool>,
 }
 
 // 手动标记 Send + Sync
@@ -108,7 +203,7 @@ unsafe impl Sync for AsyncScheduler {}
 impl AsyncScheduler {
     pub fn new(tid: usize, routine_num: u32, allocator: &Arc<RdmaBaseAllocator>) -> Self {
         let mut pendings = Vec::new();
-        #[cfg(feature = "doca_deps")]
+        #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
         let dma_meta = Vec::new();
 
         let mut vers = Vec::new();
@@ -119,7 +214,17 @@ impl AsyncScheduler {
         Self {
             tid: tid,
             allocator: Mutex::new(RpcBufAllocator::new(routine_num, allocator)),
+            mr_allocator: allocator.clone(),
             conns: HashMap::new(),
+            srq: None,
+            qp_conns: UnsafeCell::new(HashMap::new()),
+            ud: None,
+            ud_conns: HashMap::new(),
+            ud_inflight: UnsafeCell::new((0..routine_num).map(|_| Vec::new()).collect()),
+            ud_seqs: UnsafeCell::new(HashMap::new()),
+            ud_served: UnsafeCell::new(HashMap::new()),
+            broken: UnsafeCell::new(Vec::new()),
+            reconnect: None,
             vers: UnsafeCell::new(vers),
             pendings: UnsafeCell::new(pendings),
             reply_metas: UnsafeCell::new(ReplyMeta::new(routine_num)),
@@ -127,23 +232,195 @@ impl AsyncScheduler {
             // callbacks
             callback: Arc::downgrade(&DEFAULT_RPC_HANDLER) as _,
 
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             dma_conn: None,
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             dma_meta: UnsafeCell::new(dma_meta),
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             comm_chan: None,
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             comm_handler: Arc::downgrade(&DEFAULT_DOCA_CONN_HANDLER) as _,
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             comm_replys: UnsafeCell::new(Vec::new()),
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+            comm_lost: UnsafeCell::new(false),
         }
     }
 
     pub fn append_conn(&mut self, id: u64, conn: &Arc<Mutex<RdmaRcConn>>) {
+        {
+            let locked = conn.lock().unwrap();
+            if locked.is_shared_recv() {
+                self.qp_conns.get_mut().insert(locked.get_qp_num(), conn.clone());
+            }
+        }
         self.conns.insert(id, conn.clone());
     }
 
+    // the srq of the shared connections, its recvs are polled in poll_recvs
+    pub fn set_srq(&mut self, srq: &Arc<Mutex<RdmaSharedRecvQueue>>) {
+        self.srq = Some(srq.clone());
+    }
+
+    // the ud qp of this thread, its recvs are polled in poll_recvs
+    pub fn set_ud_adapter(&mut self, ud: &Arc<Mutex<RdmaUdAdapter>>) {
+        self.ud = Some(ud.clone());
+    }
+
+    // the control that set up the connections of this thread, it re-establishes them
+    // with the peers of the cluster once they break
+    pub fn set_reconnect(&mut self, rdma: RdmaControl, cluster: &ClusterConfig) {
+        self.reconnect = Some(Mutex::new((rdma, cluster.clone())));
+    }
+
+    // the peer thread is then reached by datagrams unless the peer has a rc connection
+    pub fn append_ud_peer(&mut self, addr: &RdmaUdAddr) -> TransResult<()> {
+        let ud = self.ud.as_ref().unwrap();
+        ud.lock().unwrap().add_peer(addr)?;
+
+        let conn = RdmaRcConn::new_ud(addr.peer_id, addr.tid, ud, &self.mr_allocator);
+        self.ud_conns.insert((addr.peer_id, addr.tid), Arc::new(Mutex::new(conn)));
+        Ok(())
+    }
+
+    // the shared connection of a local qp, a re-established one is found on a miss
+    fn get_qp_conn(&self, qp_num: u32) -> Option<Arc<Mutex<RdmaRcConn>>> {
+        let qp_conns = unsafe { self.qp_conns.get().as_mut().unwrap() };
+        if let Some(conn) = qp_conns.get(&qp_num) {
+            return Some(conn.clone());
+        }
+
+        for (_, conn) in self.conns.iter() {
+            let locked = conn.lock().unwrap();
+            if locked.is_shared_recv() && locked.get_qp_num() == qp_num {
+                qp_conns.insert(qp_num, conn.clone());
+                return Some(conn.clone());
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn get_conn(&self, peer_id: u64, peer_tid: u64) -> Option<&Arc<Mutex<RdmaRcConn>>> {
+        self.conns.get(&peer_id).or_else(|| self.ud_conns.get(&(peer_id, peer_tid)))
+    }
+
+    #[inline]
+    fn is_ud_peer(&self, peer_id: u64, peer_tid: u64) -> bool {
+        !self.conns.contains_key(&peer_id) && self.ud_conns.contains_key(&(peer_id, peer_tid))
+    }
+
+    // a request takes the next seq of its routine, a reply the one of the request it
+    // answers and is kept to be sent again if the request comes again
+    fn ud_seq_of(&self, msg: *mut u8, len: u32, rpc_type: u32, cid: u32, peer_id: u64, peer_tid: u64) -> u32 {
+        if rpc_type == rpc_msg_type::REQ {
+            let seqs = unsafe { self.ud_seqs.get().as_mut().unwrap() };
+            let seq = seqs.entry((peer_id, peer_tid, cid)).or_insert(0);
+            *seq = (*seq + 1) & UD_SEQ_MASK;
+            return *seq;
+        }
+
+        let served = unsafe { self.ud_served.get().as_mut().unwrap() };
+        match served.get_mut(&(peer_id, peer_tid, cid)) {
+            Some(req) => {
+                req.reply = unsafe { std::slice::from_raw_parts(msg, len as _) }.to_vec();
+                req.seq
+            }
+            None => 0,
+        }
+    }
+
+    // a request whose first copy ran here gets the same reply again instead of running twice,
+    // a late copy of an older one is dropped
+    fn serve_ud_req(&self, peer_id: u64, peer_tid: u64, seq: u32, msg: *mut u8) -> bool {
+        let meta = RpcHeaderMeta::from_header(unsafe { *(msg as *mut u32) });
+        if meta.rpc_type != rpc_msg_type::REQ {
+            return true;
+        }
+
+        let served = unsafe { self.ud_served.get().as_mut().unwrap() };
+        let key = (peer_id, peer_tid, meta.rpc_cid);
+        match served.get(&key) {
+            Some(req) if req.seq.wrapping_sub(seq) & UD_SEQ_MASK < UD_SEQ_MASK / 2 => {
+                // not replied yet, the reply goes out when the handler is done
+                if req.seq == seq && !req.reply.is_empty() {
+                    let buf = unsafe { self.get_reply_buf(0).sub(4) };
+                    unsafe { std::ptr::copy_nonoverlapping(req.reply.as_ptr(), buf, req.reply.len()) };
+                    let sent = self.ud.as_ref().unwrap().lock().unwrap().send_pending(peer_id, peer_tid, seq, buf, req.reply.len() as _);
+                    if sent.is_err() {
+                        println!("the reply to {}:{} is lost", peer_id, peer_tid);
+                    }
+                }
+                false
+            }
+            _ => {
+                served.insert(key, UdServed { seq: seq, reply: Vec::new() });
+                true
+            }
+        }
+    }
+
+    fn track_ud_req(&self, msg: *mut u8, len: u32, rpc_id: u32, cid: u32, peer_id: u64, peer_tid: u64) {
+        let seq = unsafe { self.ud_seqs.get().as_ref().unwrap()[&(peer_id, peer_tid, cid)] };
+        let inflight = unsafe { self.ud_inflight.get().as_mut().unwrap() };
+        inflight[cid as usize].push(UdInflight {
+            peer_id: peer_id,
+            peer_tid: peer_tid,
+            rpc_id: rpc_id,
+            seq: seq,
+            msg: msg,
+            len: len,
+            sent: Instant::now(),
+            retries: 0,
+        });
+    }
+
+    // the first reply of a datagram request is taken, a duplicate or a late one is dropped
+    fn take_ud_reply(&self, peer_id: u64, peer_tid: u64, seq: u32, msg: *mut u8) -> bool {
+        let meta = RpcHeaderMeta::from_header(unsafe { *(msg as *mut u32) });
+        if meta.rpc_type != rpc_msg_type::RESP {
+            return true;
+        }
+
+        let inflight = unsafe { self.ud_inflight.get().as_mut().unwrap() };
+        let reqs = &mut inflight[meta.rpc_cid as usize];
+        match reqs.iter().position(|req| {
+            req.peer_id == peer_id && req.peer_tid == peer_tid && req.rpc_id == meta.rpc_id && req.seq == seq
+        }) {
+            Some(idx) => {
+                reqs.swap_remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // the requests not replied in time are sent again, the replies of a routine
+    // fail once one of them ran out of retries
+    fn retransmit_ud(&self, ud: &mut RdmaUdAdapter) {
+        let inflight = unsafe { self.ud_inflight.get().as_mut().unwrap() };
+        let timeout = Duration::from_millis(UD_RETRANSMIT_MS);
+        for cid in 0..inflight.len() {
+            let mut failed = false;
+            for req in inflight[cid].iter_mut() {
+                if req.sent.elapsed() < timeout {
+                    continue;
+                }
+                if req.retries == UD_MAX_RETRANSMITS || ud.send_pending(req.peer_id, req.peer_tid, req.seq, req.msg, req.len).is_err() {
+                    failed = true;
+                    break;
+                }
+                req.retries += 1;
+                req.sent = Instant::now();
+            }
+
+            if failed {
+                println!("the datagram requests of {} are not replied", cid);
+                self.fail_replies(cid as u32);
+            }
+        }
+    }
+
     pub fn register_callback(&mut self, callback: &Arc<impl RpcHandler + Send + Sync + 'static>) {
         self.callback = Arc::downgrade(callback) as _;
     }
@@ -152,14 +429,93 @@ impl AsyncScheduler {
         for (_, conn) in self.conns.iter() {
             conn.lock().unwrap().poll_send();
         }
+
+        if let Some(ud) = self.ud.as_ref() {
+            ud.lock().unwrap().poll_send();
+        }
     }
 }
 
+// completions of one-sided primitives, the low bits of wr_id are the cid
 impl RdmaSendCallback for AsyncScheduler {
-    #[allow(unused)]
     fn rdma_send_handler(&self, wr_id: u64) {
-        todo!();
-        // update pending and signal
+        let cid = (wr_id & ((1 << WRID_RESERVE_BITS) - 1)) as usize;
+        let pendings = unsafe { self.pendings.get().as_mut().unwrap() };
+        pendings[cid] -= 1;
+    }
+}
+
+// one-sided primitives, await them with yield_until_ready
+// remote_off is the offset in the registered region of the peer
+impl AsyncScheduler {
+    // local buffers of the one-sided primitives, in the registered region
+    pub fn alloc_mr(&self, layout: Layout) -> *mut u8 {
+        unsafe { self.mr_allocator.alloc(layout) }
+    }
+
+    pub fn post_read(&self, peer_id: u64, local_buf: *mut u8, len: u32, remote_off: u64, cid: u32) -> TransResult<()> {
+        let pendings = unsafe { self.pendings.get().as_mut().unwrap() };
+        pendings[cid as usize] += 1;
+
+        let ret = self.conns.get(&peer_id)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .post_read(local_buf, len, remote_off, cid as _);
+
+        if ret.is_err() {
+            pendings[cid as usize] -= 1;
+        }
+        ret
+    }
+
+    pub fn post_write(&self, peer_id: u64, local_buf: *const u8, len: u32, remote_off: u64, cid: u32) -> TransResult<()> {
+        let pendings = unsafe { self.pendings.get().as_mut().unwrap() };
+        pendings[cid as usize] += 1;
+
+        let ret = self.conns.get(&peer_id)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .post_write(local_buf, len, remote_off, cid as _);
+
+        if ret.is_err() {
+            pendings[cid as usize] -= 1;
+        }
+        ret
+    }
+
+    // the old word is in local_buf once ready
+    pub fn post_cas(&self, peer_id: u64, local_buf: *mut u8, remote_off: u64, compare: u64, swap: u64, cid: u32) -> TransResult<()> {
+        let pendings = unsafe { self.pendings.get().as_mut().unwrap() };
+        pendings[cid as usize] += 1;
+
+        let ret = self.conns.get(&peer_id)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .post_cas(local_buf, remote_off, compare, swap, cid as _);
+
+        if ret.is_err() {
+            pendings[cid as usize] -= 1;
+        }
+        ret
+    }
+
+    pub fn post_faa(&self, peer_id: u64, local_buf: *mut u8, remote_off: u64, add: u64, cid: u32) -> TransResult<()> {
+        let pendings = unsafe { self.pendings.get().as_mut().unwrap() };
+        pendings[cid as usize] += 1;
+
+        let ret = self.conns.get(&peer_id)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .post_faa(local_buf, remote_off, add, cid as _);
+
+        if ret.is_err() {
+            pendings[cid as usize] -= 1;
+        }
+        ret
     }
 }
 
@@ -171,8 +527,107 @@ impl AsyncScheduler {
             conn.lock().unwrap().poll_recvs();
         }
 
+        if let Some(srq) = self.srq.as_ref() {
+            let mut srq = srq.lock().unwrap();
+            let poll_result = srq.poll_recvs();
+            for i in 0..poll_result {
+                let (qp_num, msg) = match srq.recv(i) {
+                    Some(recv) => recv,
+                    None => continue,
+                };
+                match self.get_qp_conn(qp_num) {
+                    Some(conn) => self.rdma_recv_handler(&mut conn.lock().unwrap(), msg),
+                    None => println!("recv from an unknown qp {}", qp_num),
+                }
+            }
+            srq.post_recvs(poll_result as _).unwrap();
+        }
+
+        // not locked by the handlers, which send through it
+        if let Some(ud) = self.ud.as_ref() {
+            let poll_result = ud.lock().unwrap().poll_recvs();
+            for i in 0..poll_result {
+                let recv = ud.lock().unwrap().recv(i);
+                if let Some((peer_id, peer_tid, seq, msg)) = recv {
+                    if !self.take_ud_reply(peer_id, peer_tid, seq, msg) || !self.serve_ud_req(peer_id, peer_tid, seq, msg) {
+                        continue;
+                    }
+                    match self.ud_conns.get(&(peer_id, peer_tid)) {
+                        Some(conn) => self.rdma_recv_handler(&mut conn.lock().unwrap(), msg),
+                        None => println!("datagram from an unknown peer {}:{}", peer_id, peer_tid),
+                    }
+                }
+            }
+            ud.lock().unwrap().post_recvs(poll_result as _).unwrap();
+            self.retransmit_ud(&mut ud.lock().unwrap());
+            ud.lock().unwrap().flush_pending().unwrap();
+        }
+
         for (_, conn) in self.conns.iter() {
-            conn.lock().unwrap().flush_pending().unwrap();
+            Self::flush_conn(&mut conn.lock().unwrap());
+        }
+        self.poll_broken();
+    }
+
+    // a newly broken peer fails the routines waiting for it and releases what it held
+    // here, then it is re-established without blocking the poll loop
+    fn poll_broken(&self) {
+        let broken = unsafe { self.broken.get().as_mut().unwrap() };
+        for (peer_id, conn) in self.conns.iter() {
+            let is_broken = conn.lock().unwrap().is_broken();
+            let seen = broken.contains(peer_id);
+            if is_broken && !seen {
+                println!("the connection to {} is broken", peer_id);
+                broken.push(*peer_id);
+                self.fail_peer_replies(*peer_id);
+                self.callback.upgrade().unwrap().peer_broken_handler(*peer_id);
+            } else if !is_broken && seen {
+                println!("the connection to {} is re-established", peer_id);
+                broken.retain(|broken_id| broken_id != peer_id);
+            }
+        }
+
+        if let Some(reconnect) = self.reconnect.as_ref() {
+            let mut reconnect = reconnect.lock().unwrap();
+            let (rdma, cluster) = &mut *reconnect;
+            for peer_id in broken.iter() {
+                if let Err(err) = rdma.reconnect_cluster(cluster, self.tid, *peer_id) {
+                    println!("reconnecting {} failed: {:?}", peer_id, err);
+                }
+            }
+        }
+    }
+
+    fn fail_peer_replies(&self, peer_id: u64) {
+        let reply_metas = unsafe { self.reply_metas.get().as_mut().unwrap() };
+        for cid in 0..reply_metas.reply_counts.len() {
+            if reply_metas.reply_counts[cid] > 0 && reply_metas.reply_peers[cid].contains(&peer_id) {
+                self.fail_replies(cid as u32);
+            }
+        }
+    }
+
+    // a request that is not sent fails the replies of its routine
+    fn track_req(&self, sent: TransResult<()>, msg: *mut u8, len: u32, rpc_id: u32, rpc_cid: u32, peer_id: u64, peer_tid: u64) {
+        if sent.is_err() {
+            self.fail_replies(rpc_cid);
+            return;
+        }
+
+        let reply_metas = unsafe { self.reply_metas.get().as_mut().unwrap() };
+        reply_metas.reply_peers[rpc_cid as usize].push(peer_id);
+        if self.is_ud_peer(peer_id, peer_tid) {
+            self.track_ud_req(msg, len, rpc_id, rpc_cid, peer_id, peer_tid);
+        }
+    }
+
+    // the msgs to a broken connection fail until the peer is reconnected
+    fn flush_conn(conn: &mut RdmaRcConn) {
+        if conn.is_broken() {
+            return;
+        }
+        if conn.flush_pending().is_err() {
+            println!("the connection to {} is broken", conn.get_conn_id());
         }
     }
 
@@ -191,11 +646,31 @@ impl AsyncScheduler {
         if let Some(mut_buf) = reply_metas.reply_bufs.get_mut::<usize>(cid as _) {
             *mut_buf = reply_buf;
         }
+        reply_metas.reply_failed[cid as usize] = false;
+        reply_metas.reply_peers[cid as usize].clear();
+        unsafe { self.ud_inflight.get().as_mut().unwrap()[cid as usize].clear() };
+    }
+
+    // the routine stops waiting for the replies left
+    pub fn fail_replies(&self, cid: u32) {
+        let reply_metas = unsafe { self.reply_metas.get().as_mut().unwrap() };
+        reply_metas.reply_counts[cid as usize] = 0;
+        reply_metas.reply_failed[cid as usize] = true;
+        unsafe { self.ud_inflight.get().as_mut().unwrap()[cid as usize].clear() };
+    }
+
+    #[inline]
+    pub fn is_reply_failed(&self, cid: u32) -> bool {
+        unsafe { self.reply_metas.get().as_ref().unwrap().reply_failed[cid as usize] }
     }
 
     pub fn flush_pending(&self) {
         for (_, conn) in self.conns.iter() {
-            conn.lock().unwrap().flush_pending().unwrap();
+            Self::flush_conn(&mut conn.lock().unwrap());
+        }
+
+        if let Some(ud) = self.ud.as_ref() {
+            ud.lock().unwrap().flush_pending().unwrap();
         }
     }
 }
@@ -223,6 +698,10 @@ impl RdmaRecvCallback for AsyncScheduler {
             rpc_msg_type::RESP => {
                 let index = meta.rpc_cid as usize;
                 let reply_metas = unsafe { self.reply_metas.get().as_mut().unwrap() };
+                // the routine stopped waiting, a late reply is dropped
+                if reply_metas.reply_failed[index] {
+                    return;
+                }
 
                 let buf = *reply_metas.reply_bufs.get::<usize>(index).unwrap();
                 unsafe {
@@ -272,9 +751,14 @@ impl AsyncRpc for AsyncScheduler {
         peer_tid: u64,
     ) {
         Self::prepare_msg_header(msg, rpc_id, rpc_size, rpc_cid, rpc_msg_type::RESP);
-        src_conn
-            .send_pending(unsafe { msg.sub(4) as _ }, rpc_size + 4)
-            .unwrap();
+        if let Some(tid) = src_conn.get_ud_tid() {
+            let seq = self.ud_seq_of(unsafe { msg.sub(4) }, rpc_size + 4, rpc_msg_type::RESP, rpc_cid, src_conn.get_conn_id(), tid);
+            src_conn.set_ud_seq(seq);
+        }
+        // the peer is gone, what it held is released once the connection is seen broken
+        if src_conn.send_pending(unsafe { msg.sub(4) as _ }, rpc_size + 4).is_err() {
+            println!("the reply to {} is lost", src_conn.get_conn_id());
+        }
     }
 
     #[allow(unused_variables)]
@@ -291,16 +775,19 @@ impl AsyncRpc for AsyncScheduler {
         // todo!();
         Self::prepare_msg_header(msg, rpc_id, rpc_size, rpc_cid, rpc_type);
 
-        let conn = self.conns.get(&peer_id);
+        let conn = self.get_conn(peer_id, peer_tid);
         if conn.is_none() {
             panic!("{}", peer_id);
         }
-        conn.as_ref()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .send_pending(unsafe { msg.sub(4) as _ }, rpc_size + 4)
-            .unwrap();
+        let mut conn = conn.unwrap().lock().unwrap();
+        if self.is_ud_peer(peer_id, peer_tid) {
+            conn.set_ud_seq(self.ud_seq_of(unsafe { msg.sub(4) }, rpc_size + 4, rpc_type, rpc_cid, peer_id, peer_tid));
+        }
+        let sent = conn.send_pending(unsafe { msg.sub(4) as _ }, rpc_size + 4);
+        drop(conn);
+        if rpc_type == rpc_msg_type::REQ {
+            self.track_req(sent, unsafe { msg.sub(4) }, rpc_size + 4, rpc_id, rpc_cid, peer_id, peer_tid);
+        }
     }
 
     #[allow(unused_variables)]
@@ -317,48 +804,77 @@ impl AsyncRpc for AsyncScheduler {
         // todo!();
         Self::prepare_msg_header(msg, rpc_id, rpc_size, rpc_cid, rpc_type);
 
-        let conn =  self.conns.get(&peer_id).unwrap();
-        conn.lock()
-            .unwrap()
-            .send_one(unsafe { msg.sub(4) as _ }, rpc_size + 4);
+        let mut conn = self.get_conn(peer_id, peer_tid).unwrap().lock().unwrap();
+        if self.is_ud_peer(peer_id, peer_tid) {
+            conn.set_ud_seq(self.ud_seq_of(unsafe { msg.sub(4) }, rpc_size + 4, rpc_type, rpc_cid, peer_id, peer_tid));
+        }
+        let sent = conn.send_one(unsafe { msg.sub(4) as _ }, rpc_size + 4);
+        drop(conn);
+        if rpc_type == rpc_msg_type::REQ {
+            self.track_req(sent, unsafe { msg.sub(4) }, rpc_size + 4, rpc_id, rpc_cid, peer_id, peer_tid);
+        }
     }
 }
 
 // for doca dma
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 impl AsyncScheduler {
     pub fn set_dma_conn(&mut self, dma_conn: &Arc<Mutex<DocaDmaConn>>) {
         let routine_num = self.pendings.get_mut().len();
         for _ in 0..routine_num {
-            self.dma_meta.get_mut().push(DmaMeta(DmaStatus::DmaIdle));
+            self.dma_meta.get_mut().push(DmaMeta::new());
         }
         self.dma_conn = Some(dma_conn.clone());
     }
 
+    // a batch of one, it waits its turn when the work queue is full
     #[inline]
     pub fn post_read_dma_req(&self, local_offset: usize, remote_offset: usize, payload: usize, cid: u32) {
-        self.dma_conn
-            .as_ref()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .post_read_dma_reqs(local_offset, remote_offset, payload, cid as _);
-
-        let dma_meta = unsafe { self.dma_meta.get().as_mut().unwrap() };
-        dma_meta[cid as usize].0 = DmaStatus::DmaWaiting;
+        self.post_dma_batch(&[DmaSegment::new(local_offset, remote_offset, payload)], false, cid);
     }
 
     #[inline]
     pub fn post_write_dma_req(&self, local_offset: usize, remote_offset: usize, payload: usize, cid: u32) {
-        self.dma_conn
-            .as_ref()
-            .unwrap()
-            .lock()
-            .unwrap()
-            .post_write_dma_reqs(local_offset, remote_offset, payload, cid as _);
+        self.post_dma_batch(&[DmaSegment::new(local_offset, remote_offset, payload)], true, cid);
+    }
 
+    // the segments complete as one event of the routine, they are coalesced
+    // and submitted as the work queue has room
+    pub fn post_dma_batch(&self, segs: &[DmaSegment], write: bool, cid: u32) {
         let dma_meta = unsafe { self.dma_meta.get().as_mut().unwrap() };
-        dma_meta[cid as usize].0 = DmaStatus::DmaWaiting;
+        let meta = &mut dma_meta[cid as usize];
+        meta.start();
+        meta.queued.extend(DmaSegment::coalesce(segs).into_iter().map(|seg| (seg, write)));
+        if meta.is_done() {
+            meta.status = DmaStatus::DmaIdle;
+            return;
+        }
+
+        let mut dma_conn = self.dma_conn.as_ref().unwrap().lock().unwrap();
+        self.submit_queued_dma(&mut dma_conn);
+    }
+
+    pub async fn dma_batch(&self, segs: &[DmaSegment], write: bool, cid: u32) -> Result<(), ()> {
+        self.post_dma_batch(segs, write, cid);
+        self.yield_until_dma_ready(cid).await
+    }
+
+    fn submit_queued_dma(&self, dma_conn: &mut DocaDmaConn) {
+        let dma_meta = unsafe { self.dma_meta.get().as_mut().unwrap() };
+        for (cid, meta) in dma_meta.iter_mut().enumerate() {
+            while dma_conn.has_room() {
+                let (seg, write) = match meta.queued.pop_front() {
+                    Some(job) => job,
+                    None => break,
+                };
+                if write {
+                    dma_conn.post_write_dma_reqs(seg.local_offset, seg.remote_offset, seg.payload, cid as _);
+                } else {
+                    dma_conn.post_read_dma_reqs(seg.local_offset, seg.remote_offset, seg.payload, cid as _);
+                }
+                meta.inflight += 1;
+            }
+        }
     }
 
     #[inline]
@@ -370,28 +886,29 @@ impl AsyncScheduler {
             .unwrap();
 
         loop {
-            let (event, error) = dma_conn.poll_completion();
-            match error {
-                DOCAError::DOCA_SUCCESS => {
-                    let cid: usize = event.user_mark() as _;
+            match dma_conn.poll_dma_comp() {
+                DmaCompletion::Done(user_mark) => {
+                    let cid: usize = user_mark as _;
                     let dma_meta = unsafe { self.dma_meta.get().as_mut().unwrap() };
-                    dma_meta[cid as usize].0 = DmaStatus::DmaIdle;
+                    dma_meta[cid as usize].finish_job(true);
                 }
-                DOCAError::DOCA_ERROR_AGAIN => {
+                DmaCompletion::Empty => {
                     break;
                 }
-                _ => {
-                    let cid: usize = event.user_mark() as _;
+                DmaCompletion::Failed(user_mark) => {
+                    let cid: usize = user_mark as _;
                     let dma_meta = unsafe { self.dma_meta.get().as_mut().unwrap() };
-                    dma_meta[cid as usize].0 = DmaStatus::DmaError;
+                    dma_meta[cid as usize].finish_job(false);
                 }
             }
         }
+        self.submit_queued_dma(&mut dma_conn);
     }
 
+    // nothing else runs meanwhile, the completions and the queued jobs are polled here
     pub fn busy_until_dma_ready(&self, cid: u32) -> Result<(), ()> {
         loop {
-            let status = unsafe{ self.dma_meta.get().as_mut().unwrap().get::<usize>(cid as _).unwrap().0 };
+            let status = unsafe{ self.dma_meta.get().as_mut().unwrap().get::<usize>(cid as _).unwrap().status };
             match status {
                 DmaStatus::DmaIdle => {
                     return Ok(());
@@ -400,7 +917,7 @@ impl AsyncScheduler {
                     return Err(());
                 }
                 DmaStatus::DmaWaiting => {
-                    continue;
+                    self.poll_dma_comps();
                 }
             }
         }
@@ -408,7 +925,7 @@ impl AsyncScheduler {
 
     pub async fn yield_until_dma_ready(&self, cid: u32) -> Result<(), ()> {
         loop {
-            let status = unsafe{ self.dma_meta.get().as_mut().unwrap().get::<usize>(cid as _).unwrap().0 };
+            let status = unsafe{ self.dma_meta.get().as_mut().unwrap().get::<usize>(cid as _).unwrap().status };
             match status {
                 DmaStatus::DmaIdle => {
                     return Ok(());
@@ -428,18 +945,39 @@ impl AsyncScheduler {
         self.dma_conn.as_ref().unwrap().lock().unwrap().get_local_buf(cid)
     }
 
+    // None if all the remote buffers are in use
     #[inline]
-    pub fn dma_alloc_remote_buf(&self) -> DmaRemoteBuf {
+    pub fn dma_alloc_remote_buf(&self) -> Option<DmaRemoteBuf> {
         self.dma_conn.as_ref().unwrap().lock().unwrap().alloc_remote_buf()
     }
 
+    // others run until a remote buffer is returned
+    pub async fn yield_until_dma_remote_buf(&self, cid: u32) -> DmaRemoteBuf {
+        loop {
+            if let Some(buf) = self.dma_alloc_remote_buf() {
+                return buf;
+            }
+            self.yield_now(cid).await;
+        }
+    }
+
+    #[inline]
+    pub fn has_dma_conn(&self) -> bool {
+        self.dma_conn.is_some()
+    }
+
+    #[inline]
+    pub fn dma_remote_buf_stats(&self) -> BufPoolStats {
+        self.dma_conn.as_ref().unwrap().lock().unwrap().get_remote_buf_stats()
+    }
+
     #[inline]
     pub fn dma_dealloc_remote_buf(&self, buf: DmaRemoteBuf) {
         self.dma_conn.as_ref().unwrap().lock().unwrap().dealloc_remote_buf(buf);
     }
 }
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 impl AsyncScheduler {
     pub fn set_comm_chan(&mut self, chan: DocaCommChannel) {
         let routine_num = self.pendings.get_mut().len();
@@ -464,7 +1002,7 @@ impl AsyncScheduler {
     }
 
     #[inline]
-    pub fn comm_chan_alloc_buf(&self, cid: u32) -> DocaCommBuf {
+    pub fn comm_chan_alloc_buf(&self, cid: u32) -> Option<DocaCommBuf> {
         // println!("({}){} alloc buf", self.tid,cid);
         self.comm_chan.as_ref().unwrap().alloc_buf(cid)
     }
@@ -487,28 +1025,77 @@ impl AsyncScheduler {
         self.comm_chan.as_ref().unwrap().append_slice_msg(header, items);
     }
 
+    // the sends of the channel, the stalls count how often it was full
+    #[inline]
+    pub fn comm_chan_send_stats(&self) -> CommSendStats {
+        self.comm_chan.as_ref().unwrap().get_send_stats()
+    }
+
+    // a request of total_size can be appended, the buffers kept for the replies are left alone
+    pub async fn yield_until_comm_buf(&self, cid: u32, total_size: usize) {
+        let chan = self.comm_chan.as_ref().unwrap();
+        while !chan.can_append(total_size, false) {
+            self.yield_now(cid).await;
+        }
+    }
+
+    // the messages appended so far are handed to the channel, others run meanwhile
+    pub async fn yield_until_comm_sent(&self, cid: u32) {
+        let chan = self.comm_chan.as_ref().unwrap();
+        chan.flush_pending_msgs();
+        while chan.has_queued_msgs() {
+            self.yield_now(cid).await;
+            chan.flush_pending_msgs();
+        }
+    }
+
     pub fn prepare_comm_replys(&self, cid: u32, count: usize) {
         let comm_replys = unsafe { self.comm_replys.get().as_mut().unwrap() };
         comm_replys[cid as usize].reset(count);
+        if self.is_comm_chan_lost() {
+            comm_replys[cid as usize].fail();
+        }
+    }
+
+    #[inline]
+    pub fn is_comm_chan_lost(&self) -> bool {
+        unsafe { *self.comm_lost.get() }
+    }
+
+    // the routines waiting on the channel see their replies failed
+    fn set_comm_chan_lost(&self) {
+        println!("the comm channel is lost {}", self.tid);
+        unsafe { *self.comm_lost.get() = true; }
+        let comm_replys = unsafe { self.comm_replys.get().as_mut().unwrap() };
+        for reply in comm_replys.iter_mut() {
+            reply.fail();
+        }
     }
 
     pub fn poll_comm_chan(&self) {
-        let mut recv_bufs: Vec<DocaCommBuf> = Vec::with_capacity(10);
-        loop {
-            let mut recv_buf = self.comm_chan_alloc_buf(0);
+        if self.is_comm_chan_lost() {
+            return;
+        }
+        if self.comm_chan.as_ref().unwrap().is_lost() {
+            self.set_comm_chan_lost();
+            return;
+        }
+
+        // each buffer is handled before the next is received, the pool may be out meanwhile
+        while let Some(mut recv_buf) = self.comm_chan_alloc_buf(0) {
             recv_buf.set_payload(MAX_CONN_MSG_SIZE);
-            let res = self.comm_chan.as_ref().unwrap().recv_info(&mut recv_buf);
-            if res == DOCAError::DOCA_ERROR_AGAIN {
-                self.comm_chan_dealloc_buf(recv_buf, 0);
-                break;
-            }
-            if res != DOCAError::DOCA_SUCCESS {
-                panic!("the connection is lost {}", self.tid);
+            match self.comm_chan.as_ref().unwrap().recv_info(&mut recv_buf) {
+                CommRecvStatus::Received => {}
+                CommRecvStatus::Empty => {
+                    self.comm_chan_dealloc_buf(recv_buf, 0);
+                    break;
+                }
+                CommRecvStatus::Lost => {
+                    self.comm_chan_dealloc_buf(recv_buf, 0);
+                    self.set_comm_chan_lost();
+                    return;
+                }
             }
-            recv_bufs.push(recv_buf);
-        }
-        for i in 0..recv_bufs.len() {
-            let recv_buf: &mut _ = recv_bufs.get_mut(i).unwrap();
 
             recv_buf.start_read();
 
@@ -519,7 +1106,7 @@ impl AsyncScheduler {
                     doca_comm_info_type::REQ => {
                         // println!("REQ({})", header.info_payload);
                         self.comm_handler.upgrade().unwrap().comm_handler(
-                            recv_buf,
+                            &recv_buf,
                             header.info_id as _,
                             header.info_payload as _,
                             header.info_pid as _,
@@ -555,9 +1142,7 @@ impl AsyncScheduler {
 
                 recv_buf.shift_to_next_msg(header.info_payload);
             }
-        }
 
-        while let Some(recv_buf) = recv_bufs.pop() {
             self.comm_chan_dealloc_buf(recv_buf, 0);
         }
 
@@ -10,10 +10,14 @@ where
 
     fn has_stopped(&self) -> bool;
 
+    // housekeeping between polls, e.g. version gc
+    fn poll_background(&self, _round: usize) {}
+
     // routine 0
     fn main_routine(self: Arc<Self>) -> impl std::future::Future<Output = ()> + Send {
         async move {
             let scheduler = self.get_scheduler();
+            let mut round = 0;
             loop {
                 if self.has_stopped() {
                     break;
@@ -22,6 +26,9 @@ where
                 scheduler.poll_recvs();
                 scheduler.poll_sends();
 
+                self.poll_background(round);
+                round = round.wrapping_add(1);
+
                 scheduler.yield_now(0).await;
             }
         }
@@ -16,12 +16,18 @@ pub enum TransError {
     TransRdmaError,
     TransDocaError,
     TransSyncError,
+    TransConfigError,
+    // which setup call failed, its errno and the address
+    TransSetupError(rdma::control::RdmaSetupError),
+    // the config handshake of a bootstrap
+    TransExchangeError(common::connection::ConfigExchangeError),
 }
 
 type TransResult<T> = Result<T, TransError>;
 
 // connection info
-const PEERNUMS: u64 = 2;
+// the dpu of a partition is part_id + DPU_PEER_ID_BASE unless a cluster config says otherwise
+const DPU_PEER_ID_BASE: u64 = 100;
 
 // mem info
 const NPAGES: u64 = 512;
@@ -31,6 +37,18 @@ const MAX_PACKET_SIZE: usize = 1024;
 const MAX_SEND_SIZE: usize = 128;
 const MAX_RECV_SIZE: usize = 64;
 const MAX_DOORBELL_SEND_SIZE: usize = 8;
+// recv buffers of a shared receive queue, for all the peers of a worker
+const MAX_SRQ_RECV_SIZE: usize = 128;
+// ud datagrams, a recv buffer holds the grh and a packet
+const MAX_UD_RECV_SIZE: usize = 256;
+const UD_GRH_SIZE: usize = 40;
+const UD_QKEY: u32 = 0x11111111;
+// a datagram request not replied in time is sent again, the reply fails after the retries
+const UD_RETRANSMIT_MS: u64 = 10;
+const UD_MAX_RETRANSMITS: usize = 8;
+// the port and the gid of a device unless configured
+const DEFAULT_PORT_NUM: u8 = 1;
+const DEFAULT_GID_INDEX: i32 = 0;
 // const MAX_IDLE_RECV_NUM: usize = 1;
 
 const MAX_SIGNAL_PENDINGS: usize = MAX_SEND_SIZE - MAX_DOORBELL_SEND_SIZE;
@@ -45,23 +63,52 @@ const MAX_RESP_SIZE: usize = 4096;
 /////////////////// MemStore //////////////////////////
 const ROBINHOOD_SIZE:    usize = 131072;
 const ROBINHOOD_DIB_MAX: usize = 8;
+const MVCC_MAX_VERSIONS: usize = 4;
+// polls of the main routine between version collections
+const MVCC_GC_POLLS: usize = 1024;
 
 
 /////////////////// DOCA DMA //////////////////////////
 const DOCA_WORKQ_DEPTH: usize = 8;
 const MAX_DMA_BUF_SIZE: usize = 1024;
+// unless the host exports more
 const MAX_DMA_BUF_REMOTE: usize = 128;
 const MAX_DMA_BUF_PER_ROUTINE: usize = 16;
 
 /////////////////// DOCA CONN /////////////////////////
 const MAX_CONN_INFO_BUFS: usize = 32;
+// kept for the replies of the handlers, the routines wait for a buffer beyond them
+const COMM_REPLY_BUFS: usize = 16;
 const MAX_CONN_MSG_SIZE: usize = 2048;
 
 /////////////////// CACHE /////////////////////////////
+// the initial, the pool doubles when it runs out
 const MAX_LOCAL_CACHE_BUF_COUNT: usize = 64;
 
+/////////////////// OFFLOAD ///////////////////////////
+// keys of a range, what the offload controller tracks and moves
+const OFFLOAD_RANGE_SIZE: usize = 100;
+// how long a move waits for the txns on its range
+const OFFLOAD_DRAIN_TIMEOUT_MS: u64 = 10;
+// lock owner of the records being moved, no peer has it
+const OFFLOAD_LOCK_CONTENT: u64 = (1 << 63) - 1;
+
 /////////////////// WORKER ////////////////////////////
 const MAIN_ROUTINE_ID: u32 = 0;
+// lock owner of the stored procedures, never taken by a routine
+const PROC_ROUTINE_ID: u32 = 1023;
+// length of an epoch of the group commit
+const GROUP_COMMIT_EPOCH_US: u64 = 100;
+// remote locks taken by RDMA CAS in one txn, the others go by rpc
+const MAX_CAS_LOCKS_PER_ROUTINE: usize = 16;
+// remote nodes read by RDMA READ in one round, the others go by rpc
+const MAX_ONE_SIDE_READS_PER_ROUTINE: usize = 16;
+// remote keys located from the layouts of their owners in one round, the others go by rpc
+const MAX_PROBES_PER_ROUTINE: usize = 16;
+// the buckets a key may be in, read at once
+const MAX_PROBE_WINDOW: usize = 1024;
+// polls of the dpu main routine between the reports of its buffer pools
+const BUF_STATS_POLLS: usize = 1 << 24;
 
 /////////////////// Small Bank Wokeloads //////////////
 pub const SMALL_BANK_NROUTINES: usize = 8;
@@ -76,6 +123,14 @@ const SMALL_BANK_MIN_BALANCE: f64 = 10000.0;
 const SMALL_BANK_MAX_BALANCE: f64 = 50000.0;
 
 const SMALL_BANK_PART_OFFLOAD_RATIO: usize = 30;
+// serve balance with multi-version snapshot reads
+const SMALL_BANK_SNAPSHOT_BALANCE: bool = false;
+// run transact savings as a stored procedure on the owner
+const SMALL_BANK_STORED_PROC: bool = false;
+// read the remote accounts of balance by RDMA READ if the owner registers them
+const SMALL_BANK_ONE_SIDE_READ: bool = false;
+// unless the cluster config sets cc_protocol
+const SMALL_BANK_CC_PROTOCOL: occ::CcProtocol = occ::CcProtocol::Occ;
 
 /////////////////// SMALL TPCC ////////////////////////
 pub const TPCC_NROUTINES: usize = 8;
@@ -85,3 +140,8 @@ const TPCC_REMOTE_THRESHHOOD: usize = 50;
 const TPCC_SCALE: usize = 1;
 
 const TPCC_PART_OFFLOAD_RATIO: usize = 50;
+// commit new order in epochs instead of one by one
+const TPCC_GROUP_COMMIT: bool = false;
+// lock the stocks by RDMA CAS if the owner registers them
+const TPCC_CAS_LOCK: bool = false;
+const TPCC_CC_PROTOCOL: occ::CcProtocol = occ::CcProtocol::Occ;
@@ -0,0 +1,34 @@
+// merge a delta into the value in place, executed on the owner of the item
+pub type DeltaMergeFn = fn(value: *mut u8, delta: *const u8, len: u32);
+
+// merge id 0 is a plain overwrite
+pub const NO_MERGE: u32 = 0;
+
+// built-in merges, the delta is a single number added to the field at OFFSET
+pub fn delta_add_f64<const OFFSET: usize>(value: *mut u8, delta: *const u8, _len: u32) {
+    unsafe {
+        let field = (value.byte_add(OFFSET) as *mut f64).as_mut().unwrap();
+        *field += *(delta as *const f64);
+    }
+}
+
+pub fn delta_add_i64<const OFFSET: usize>(value: *mut u8, delta: *const u8, _len: u32) {
+    unsafe {
+        let field = (value.byte_add(OFFSET) as *mut i64).as_mut().unwrap();
+        *field = field.wrapping_add(*(delta as *const i64));
+    }
+}
+
+pub fn delta_add_u64<const OFFSET: usize>(value: *mut u8, delta: *const u8, _len: u32) {
+    unsafe {
+        let field = (value.byte_add(OFFSET) as *mut u64).as_mut().unwrap();
+        *field = field.wrapping_add(*(delta as *const u64));
+    }
+}
+
+pub fn delta_sub_u64<const OFFSET: usize>(value: *mut u8, delta: *const u8, _len: u32) {
+    unsafe {
+        let field = (value.byte_add(OFFSET) as *mut u64).as_mut().unwrap();
+        *field = field.wrapping_sub(*(delta as *const u64));
+    }
+}
@@ -1,6 +1,10 @@
 use core::panic;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-use super::memstore::{MemNodeMeta, MemStore};
+use super::memstore::{is_delta_lock, MemNodeMeta, MemStore, MemStoreLayout, MEMNODE_VALUE_OFF};
+use super::mvcc_memstore::MvccClock;
+use super::delta_merge::{DeltaMergeFn, NO_MERGE};
 use super::valuestore::ValueStore;
 
 #[allow(unused)]
@@ -8,6 +12,10 @@ pub struct TableSchema {
     k_len: u32,
     v_len: u32,
     meta_len: u32,
+    // remote peers lock the rows with RDMA CAS instead of LOCK_RPC
+    cas_lock: bool,
+    // remote peers read and validate the rows with RDMA READ
+    one_side_read: bool,
 }
 
 impl Default for TableSchema {
@@ -15,7 +23,9 @@ impl Default for TableSchema {
         Self {
             k_len: 0,
             v_len: 0,
-            meta_len: 0
+            meta_len: 0,
+            cas_lock: false,
+            one_side_read: false,
         }
     }
 }
@@ -26,8 +36,47 @@ impl TableSchema {
             k_len,
             v_len,
             meta_len,
+            cas_lock: false,
+            one_side_read: false,
         }
     }
+
+    pub fn with_cas_lock(mut self) -> Self {
+        self.cas_lock = true;
+        self
+    }
+
+    pub fn with_one_side_read(mut self) -> Self {
+        self.one_side_read = true;
+        self
+    }
+}
+
+// a local txn shipped to where the data lives, tid is the executing thread,
+// returns the output length, or None if it is aborted
+pub type StoredProcFn = fn(memdb: &Arc<MemDB>, tid: u32, args: &[u8], out: &mut [u8]) -> Option<usize>;
+
+// of a value-less dpu, the rows the procedure writes, it locks them before its host runs it,
+// false if the args are not rational
+pub type ProcKeysFn = fn(args: &[u8], keys: &mut Vec<(usize, u64)>) -> bool;
+
+// the procedure on the values of a host, under the locks its dpu took
+pub type ValueProcFn = fn(valuedb: &ValueDB, args: &[u8], out: &mut [u8]) -> Option<usize>;
+
+// marks a forwarded call the dpu could not lock
+pub const NO_PROC: u32 = 0;
+
+// ids are hashed from the names, so all the nodes agree on them
+pub fn proc_id(name: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in name.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    if hash == NO_PROC {
+        panic!("not rational procedure name {}", name);
+    }
+    hash
 }
 
 pub enum MemStoreType {
@@ -39,6 +88,14 @@ pub enum MemStoreType {
 pub struct MemDB {
     metas:  Vec<TableSchema>,
     tables: Vec<Box<dyn MemStore + Send + Sync + 'static>>,
+    clock:  Arc<MvccClock>,
+    merges: Vec<Option<DeltaMergeFn>>,
+    procs:  HashMap<u32, StoredProcFn>,
+    proc_keys: HashMap<u32, ProcKeysFn>,
+    // the registered region peers address with one-sided primitives, (base, len, rkey)
+    region: (u64, u64, u32),
+    // the delta locks each owner holds, their words do not name it
+    deltas: Mutex<HashMap<u64, Vec<(usize, u64)>>>,
 }
 
 impl MemDB
@@ -46,8 +103,78 @@ impl MemDB
     pub fn new() -> Self {
         Self {
             metas: Vec::new(),
-            tables: Vec::new()
+            tables: Vec::new(),
+            clock: Arc::new(MvccClock::new()),
+            merges: Vec::new(),
+            procs: HashMap::new(),
+            proc_keys: HashMap::new(),
+            region: (0, 0, 0),
+            deltas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // the registered region of the node, the tables in it are allocated by the caller
+    pub fn set_region(&mut self, base: *const u8, len: usize, rkey: u32) {
+        self.region = (base as u64, len as u64, rkey);
+    }
+
+    // the layout with base as the offset in the region, None if the table is not in it
+    pub fn export_layout(&self, table_id: usize) -> Option<MemStoreLayout> {
+        if !self.is_cas_lock(table_id) && !self.is_one_side_read(table_id) {
+            return None;
+        }
+
+        let (base, len, rkey) = self.region;
+        let mut layout = self.tables[table_id].get_layout()?;
+        if layout.base < base || layout.base + layout.capacity * layout.unit_size > base + len {
+            return None;
         }
+
+        layout.base -= base;
+        layout.rkey = rkey;
+        Some(layout)
+    }
+
+    // shared with the multi-version tables
+    pub fn get_clock(&self) -> Arc<MvccClock> {
+        self.clock.clone()
+    }
+
+    // merge ids are shared by all the peers
+    pub fn add_merge(&mut self, merge_id: u32, merge: DeltaMergeFn) {
+        let merge_id = merge_id as usize;
+        if merge_id == NO_MERGE as usize {
+            panic!("not rational add merge");
+        }
+
+        if self.merges.len() <= merge_id {
+            self.merges.resize(merge_id + 1, None);
+        }
+        self.merges[merge_id] = Some(merge);
+    }
+
+    pub fn add_procedure(&mut self, name: &str, procedure: StoredProcFn) -> u32 {
+        let id = proc_id(name);
+        if self.procs.insert(id, procedure).is_some() {
+            panic!("not rational add procedure {}", name);
+        }
+        id
+    }
+
+    pub fn get_procedure(&self, proc_id: u32) -> Option<StoredProcFn> {
+        self.procs.get(&proc_id).copied()
+    }
+
+    pub fn add_proc_keys(&mut self, name: &str, keys: ProcKeysFn) -> u32 {
+        let id = proc_id(name);
+        if self.proc_keys.insert(id, keys).is_some() {
+            panic!("not rational add procedure {}", name);
+        }
+        id
+    }
+
+    pub fn get_proc_keys(&self, proc_id: u32) -> Option<ProcKeysFn> {
+        self.proc_keys.get(&proc_id).copied()
     }
 
     pub fn add_schema(&mut self, table_id: usize, schema: TableSchema, table: impl MemStore + Send + Sync + 'static) {
@@ -60,6 +187,11 @@ impl MemDB
         self.tables.push(Box::new(table) as _);
     }
 
+    #[inline]
+    pub fn get_table_num(&self) -> usize {
+        self.metas.len()
+    }
+
     // local
     pub fn get_item_length(&self, table_id: usize) -> usize {
         if table_id >= self.metas.len() {
@@ -128,6 +260,10 @@ impl MemDB
             return;
         }
 
+        // the peers lock the rows of a cas-lock table by RDMA CAS, only our own lock is cleared
+        if self.metas[table_id].cas_lock {
+            return self.tables[table_id].local_try_unlock(key, lock_content);
+        }
         self.tables[table_id].local_unlock(key, lock_content)
     }
 
@@ -150,12 +286,280 @@ impl MemDB
 
         self.tables[table_id].local_erase(key)
     }
+
+    // 2pl
+    pub fn local_get_shared(&self, table_id: usize, key: u64, ptr: *mut u8, len: u32) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        self.tables[table_id].local_get_shared(key, ptr, len)
+    }
+
+    pub fn local_unlock_shared(&self, table_id: usize, key: u64)
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return;
+        }
+
+        self.tables[table_id].local_unlock_shared(key)
+    }
+
+    // lock is lock_content after it if the caller was the only reader
+    pub fn local_upgrade_shared(&self, table_id: usize, key: u64, lock_content: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        self.tables[table_id].local_upgrade_shared(key, lock_content)
+    }
+
+    // deltas, owner is the lock content of the txn, only the locks it holds are released
+    pub fn local_lock_delta(&self, table_id: usize, key: u64, owner: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        let meta = self.tables[table_id].local_lock_delta(key)?;
+        if is_delta_lock(meta.lock) {
+            self.deltas.lock().unwrap().entry(owner).or_default().push((table_id, key));
+        }
+        Some(meta)
+    }
+
+    pub fn local_unlock_delta(&self, table_id: usize, key: u64, owner: u64)
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return;
+        }
+
+        let mut deltas = self.deltas.lock().unwrap();
+        let held = match deltas.get_mut(&owner) {
+            Some(held) => held,
+            None => return,
+        };
+        if let Some(pos) = held.iter().position(|lock| *lock == (table_id, key)) {
+            held.swap_remove(pos);
+            if held.is_empty() {
+                deltas.remove(&owner);
+            }
+            self.tables[table_id].local_unlock_delta(key);
+        }
+    }
+
+    // the delta locks of the owners peer_of decodes to peer_id
+    pub fn release_peer_deltas(&self, peer_id: u64, peer_of: fn(u64) -> Option<u64>)
+    {
+        let mut deltas = self.deltas.lock().unwrap();
+        deltas.retain(|owner, held| {
+            if peer_of(*owner) != Some(peer_id) {
+                return true;
+            }
+            for (table_id, key) in held.iter() {
+                self.tables[*table_id].local_unlock_delta(*key);
+            }
+            false
+        });
+    }
+
+    // timestamp ordering
+    pub fn local_cas_seq(&self, table_id: usize, key: u64, old_seq: u64, new_seq: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        self.tables[table_id].local_cas_seq(key, old_seq, new_seq)
+    }
+
+    pub fn local_upd_val_set_seq(&self, table_id: usize, key: u64, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        self.tables[table_id].local_upd_val_set_seq(key, ptr, len, seq)
+    }
+
+    pub fn local_apply_delta(&self, table_id: usize, key: u64, merge_id: u32, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        match self.merge_of(table_id, merge_id) {
+            Some(merge) => self.tables[table_id].local_apply_delta(key, merge, ptr, len, commit_ts),
+            None => None
+        }
+    }
+
+    pub fn local_apply_delta_set_seq(&self, table_id: usize, key: u64, merge_id: u32, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        match self.merge_of(table_id, merge_id) {
+            Some(merge) => self.tables[table_id].local_apply_delta_set_seq(key, merge, ptr, len, seq),
+            None => None
+        }
+    }
+
+    fn merge_of(&self, table_id: usize, merge_id: u32) -> Option<DeltaMergeFn> {
+        // a meta-only store has no value to merge into
+        if self.tables[table_id].get_item_length() == 0 {
+            println!("the table has no values to merge!");
+            return None;
+        }
+
+        match self.merges.get(merge_id as usize) {
+            Some(Some(merge)) => Some(*merge),
+            _ => {
+                println!("the merge does not exists!");
+                None
+            }
+        }
+    }
+
+    // value and seq of the same version, lock != 0 if it is being written
+    pub fn local_get_stable(&self, table_id: usize, key: u64, ptr: *mut u8, len: u32) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        loop {
+            let before = self.tables[table_id].local_get_meta(key)?;
+            if before.lock != 0 {
+                return Some(before);
+            }
+
+            let after = self.tables[table_id].local_get_readonly(key, ptr, len)?;
+            if after.lock == 0 && after.seq == before.seq {
+                return Some(after);
+            }
+        }
+    }
+
+    // multi-version
+    #[inline]
+    pub fn alloc_commit_ts(&self) -> u64 {
+        self.clock.alloc_commit_ts()
+    }
+
+    #[inline]
+    pub fn join_commit_ts(&self, commit_ts: u64) {
+        self.clock.join_commit_ts(commit_ts);
+    }
+
+    #[inline]
+    pub fn finish_commit_ts(&self, commit_ts: u64) {
+        self.clock.finish_commit_ts(commit_ts);
+    }
+
+    #[inline]
+    pub fn begin_snapshot(&self) -> u64 {
+        self.clock.begin_snapshot()
+    }
+
+    #[inline]
+    pub fn join_snapshot(&self, read_ts: u64) -> bool {
+        self.clock.join_snapshot(read_ts)
+    }
+
+    #[inline]
+    pub fn end_snapshot(&self, read_ts: u64) {
+        self.clock.end_snapshot(read_ts);
+    }
+
+    pub fn local_upd_val_seq_at(&self, table_id: usize, key: u64, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        self.tables[table_id].local_upd_val_seq_at(key, ptr, len, commit_ts)
+    }
+
+    pub fn local_get_snapshot(&self, table_id: usize, key: u64, ptr: *mut u8, len: u32, read_ts: u64) -> Option<MemNodeMeta>
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return None;
+        }
+
+        self.tables[table_id].local_get_snapshot(key, ptr, len, read_ts)
+    }
+
+    #[inline]
+    pub fn is_cas_lock(&self, table_id: usize) -> bool {
+        self.metas.get(table_id).map_or(false, |meta| meta.cas_lock)
+    }
+
+    #[inline]
+    pub fn is_one_side_read(&self, table_id: usize) -> bool {
+        self.metas.get(table_id).map_or(false, |meta| meta.one_side_read)
+    }
+
+    // offset of the node in the registered region,
+    // None if the row is not there, then it is accessed by rpc
+    pub fn local_get_node_offset(&self, table_id: usize, key: u64) -> Option<u64>
+    {
+        if !self.is_cas_lock(table_id) && !self.is_one_side_read(table_id) {
+            return None;
+        }
+
+        let (base, len, _) = self.region;
+        let addr = self.tables[table_id].local_get_node_addr(key)?;
+        let node_len = (MEMNODE_VALUE_OFF + self.tables[table_id].get_item_length()) as u64;
+        if addr < base || addr + node_len > base + len {
+            return None;
+        }
+
+        Some(addr - base)
+    }
+
+    // the meta of every key of the table, false if it cannot be scanned
+    pub fn local_scan_meta(&self, table_id: usize, f: &mut dyn FnMut(u64, MemNodeMeta)) -> bool
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return false;
+        }
+
+        self.tables[table_id].local_scan_meta(f)
+    }
+
+    // drop the versions no snapshot can see
+    pub fn collect_versions(&self) {
+        let watermark = self.clock.gc_watermark();
+
+        for table in self.tables.iter() {
+            table.local_gc(watermark);
+        }
+    }
 }
 
 
 pub struct ValueDB {
     metas:  Vec<TableSchema>,
     tables: Vec<Box<dyn ValueStore + Send + Sync + 'static>>,
+    merges: Vec<Option<DeltaMergeFn>>,
+    procs:  HashMap<u32, ValueProcFn>,
 }
 
 impl ValueDB
@@ -163,8 +567,34 @@ impl ValueDB
     pub fn new() -> Self {
         Self {
             metas: Vec::new(),
-            tables: Vec::new()
+            tables: Vec::new(),
+            merges: Vec::new(),
+            procs: HashMap::new(),
+        }
+    }
+
+    pub fn add_procedure(&mut self, name: &str, procedure: ValueProcFn) -> u32 {
+        let id = proc_id(name);
+        if self.procs.insert(id, procedure).is_some() {
+            panic!("not rational add procedure {}", name);
+        }
+        id
+    }
+
+    pub fn get_procedure(&self, proc_id: u32) -> Option<ValueProcFn> {
+        self.procs.get(&proc_id).copied()
+    }
+
+    pub fn add_merge(&mut self, merge_id: u32, merge: DeltaMergeFn) {
+        let merge_id = merge_id as usize;
+        if merge_id == NO_MERGE as usize {
+            panic!("not rational add merge");
+        }
+
+        if self.merges.len() <= merge_id {
+            self.merges.resize(merge_id + 1, None);
         }
+        self.merges[merge_id] = Some(merge);
     }
 
     pub fn add_schema(&mut self, table_id: usize, schema: TableSchema, table: impl ValueStore + Send + Sync + 'static) {
@@ -226,4 +656,42 @@ impl ValueDB
 
         self.tables[table_id].local_erase_value(key);
     }
+
+    pub fn local_scan_keys(&self, table_id: usize, f: &mut dyn FnMut(u64)) -> bool
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return false;
+        }
+
+        self.tables[table_id].local_scan_keys(f);
+        true
+    }
+
+    // the caller holds the row lock on the dpu, so get then set is atomic
+    pub fn local_apply_delta(&self, table_id: usize, key: u64, merge_id: u32, ptr: *const u8, len: u32) -> bool
+    {
+        if table_id >= self.metas.len() {
+            println!("the table does not exists!");
+            return false;
+        }
+
+        let merge = match self.merges.get(merge_id as usize) {
+            Some(Some(merge)) => *merge,
+            _ => {
+                println!("the merge does not exists!");
+                return false;
+            }
+        };
+
+        let item_len = self.tables[table_id].get_item_length();
+        let mut value = vec![0u64; (item_len + 7) / 8];
+        let value_ptr = value.as_mut_ptr() as *mut u8;
+        if !self.tables[table_id].local_get_value(key, value_ptr, item_len as u32) {
+            return false;
+        }
+
+        merge(value_ptr, ptr, len);
+        self.tables[table_id].local_set_value(key, value_ptr, item_len as u32)
+    }
 }
@@ -2,6 +2,91 @@ use std::cell::UnsafeCell;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 
+use super::delta_merge::DeltaMergeFn;
+use super::robinhood::robinhood::robinhood_hash;
+
+// readers count in the lower bits, never set by an exclusive lock content
+pub const SHARED_LOCK_FLAG: u64 = 1 << 63;
+// the lock of a node left behind by an erase, never released
+pub const ERASED_LOCK: u64 = !SHARED_LOCK_FLAG;
+// the writers of deltas count in the lower bits as well, they only exclude the others
+pub const DELTA_LOCK_FLAG: u64 = SHARED_LOCK_FLAG | 1 << 62;
+
+#[inline]
+pub fn is_shared_lock(lock: u64) -> bool {
+    lock & DELTA_LOCK_FLAG == SHARED_LOCK_FLAG
+}
+
+#[inline]
+pub fn is_delta_lock(lock: u64) -> bool {
+    lock & DELTA_LOCK_FLAG == DELTA_LOCK_FLAG
+}
+
+// layout of the node seen by the one-sided primitives
+pub const MEMNODE_LOCK_OFF:  usize = 0;
+pub const MEMNODE_SEQ_OFF:   usize = 8;
+pub const MEMNODE_VALUE_OFF: usize = 16;
+
+/// Where the buckets of a store are, so that the peers and the DPU address the rows directly.
+/// The node of a key is in one of the dib_max buckets from robinhood_hash(key) % capacity,
+/// in the one which is valid and holds the key, the nodes there never move.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemStoreLayout {
+    // local address, or the offset in the registered region once exported
+    pub base:      u64,
+    pub rkey:      u32,
+    pub capacity:  u64,
+    pub unit_size: u64,
+    pub valid_off: u64,
+    pub key_off:   u64,
+    pub dib_off:   u64,
+    // the MemNode in a unit
+    pub node_off:  u64,
+    pub dib_max:   u64,
+    // the dib of a unit left by an erase
+    pub tomb_dib:  u64,
+}
+
+impl MemStoreLayout {
+    // the probe-th bucket from the home bucket of key
+    pub fn bucket_off(&self, key: u64, probe: u64) -> u64 {
+        let ind = (robinhood_hash(&key) % self.capacity + probe) % self.capacity;
+        self.base + ind * self.unit_size
+    }
+
+    // the buckets a key may be in, None if they wrap around the end
+    pub fn window_of(&self, key: u64) -> Option<(u64, u64)> {
+        let home = robinhood_hash(&key) % self.capacity;
+        if home + self.dib_max > self.capacity {
+            return None;
+        }
+        Some((self.base + home * self.unit_size, self.dib_max * self.unit_size))
+    }
+
+    // the offset of the node of key in a window read from window_of
+    pub fn node_in_window(&self, window: *const u8, key: u64) -> Option<u64> {
+        let (base, _) = self.window_of(key)?;
+        for probe in 0..self.dib_max {
+            let unit = unsafe { window.add((probe * self.unit_size) as usize) };
+            let (valid, unit_key, dib) = unsafe {(
+                *(unit.add(self.valid_off as usize) as *const bool),
+                *(unit.add(self.key_off as usize) as *const u64),
+                *(unit.add(self.dib_off as usize) as *const u64),
+            )};
+
+            // the keys are never past a free bucket
+            if !valid {
+                return None;
+            }
+            if unit_key == key && dib != self.tomb_dib {
+                return Some(base + probe * self.unit_size + self.node_off);
+            }
+        }
+        None
+    }
+}
+
 // just marker trait
 pub trait MemStoreValue: Clone + Send + Sync + Default {}
 
@@ -98,15 +183,131 @@ where
         }
     }
 
+    // the peers holding the address of an erased node see it locked and go by rpc
+    pub fn lock_erased(&self) {
+        self.lock.store(ERASED_LOCK, Ordering::Release);
+    }
+
     pub fn unlock(&self, lock_sig: u64) -> bool {
         self.lock.store(0, Ordering::Release);
         true
     }
 
+    pub fn try_lock_shared(&self) -> bool {
+        let mut cur = self.lock.load(Ordering::Acquire);
+        loop {
+            if cur != 0 && !is_shared_lock(cur) {
+                return false;
+            }
+
+            let new = if cur == 0 { SHARED_LOCK_FLAG | 1 } else { cur + 1 };
+            match self
+                .lock
+                .compare_exchange(cur, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    return true;
+                }
+                Err(now) => {
+                    cur = now;
+                }
+            }
+        }
+    }
+
+    pub fn unlock_shared(&self) {
+        let mut cur = self.lock.load(Ordering::Acquire);
+        loop {
+            if !is_shared_lock(cur) {
+                return;
+            }
+
+            let new = if cur & !SHARED_LOCK_FLAG == 1 { 0 } else { cur - 1 };
+            match self
+                .lock
+                .compare_exchange(cur, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    return;
+                }
+                Err(now) => {
+                    cur = now;
+                }
+            }
+        }
+    }
+
+    // the only reader takes the exclusive lock
+    pub fn try_upgrade_shared(&self, lock_sig: u64) -> bool {
+        self.lock
+            .compare_exchange(SHARED_LOCK_FLAG | 1, lock_sig, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    // Ok with the new word, or Err with the word of the holder
+    pub fn try_lock_delta(&self) -> Result<u64, u64> {
+        let mut cur = self.lock.load(Ordering::Acquire);
+        loop {
+            if cur != 0 && !is_delta_lock(cur) {
+                return Err(cur);
+            }
+
+            let new = if cur == 0 { DELTA_LOCK_FLAG | 1 } else { cur + 1 };
+            match self
+                .lock
+                .compare_exchange(cur, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    return Ok(new);
+                }
+                Err(now) => {
+                    cur = now;
+                }
+            }
+        }
+    }
+
+    pub fn unlock_delta(&self) {
+        let mut cur = self.lock.load(Ordering::Acquire);
+        loop {
+            if !is_delta_lock(cur) {
+                return;
+            }
+
+            let new = if cur & !DELTA_LOCK_FLAG == 1 { 0 } else { cur - 1 };
+            match self
+                .lock
+                .compare_exchange(cur, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    return;
+                }
+                Err(now) => {
+                    cur = now;
+                }
+            }
+        }
+    }
+
+    pub fn cas_seq(&self, old: u64, new: u64) -> bool {
+        self.seq
+            .compare_exchange(old, new, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    pub fn set_seq(&self, seq: u64) {
+        self.seq.store(seq, Ordering::Release);
+    }
+
     pub fn advance_seq(&self) {
         self.seq.fetch_add(2, Ordering::AcqRel);
     }
 
+    // the holders of a delta lock set it in any order
+    pub fn raise_seq(&self, seq: u64) {
+        self.seq.fetch_max(seq, Ordering::AcqRel);
+    }
+
     pub fn set_value(&self, value: &T) {
         unsafe {
             *self.value.get() = value.clone();
@@ -159,4 +360,48 @@ pub trait MemStore {
     // update or insert
     fn local_upd_val_seq(&self, key: u64, ptr: *const u8, len: u32) -> Option<MemNodeMeta>;
     fn local_erase(&self, key: u64) -> Option<MemNodeMeta>;
+
+    // 2pl, shared lock and read if it is acquired
+    fn local_get_shared(&self, key: u64, ptr: *mut u8, len: u32) -> Option<MemNodeMeta>;
+    fn local_unlock_shared(&self, key: u64);
+    fn local_upgrade_shared(&self, key: u64, lock_content: u64) -> Option<MemNodeMeta>;
+    // deltas, lock is the delta word if it is acquired
+    fn local_lock_delta(&self, key: u64) -> Option<MemNodeMeta>;
+    fn local_unlock_delta(&self, key: u64);
+    // timestamp based, seq is the timestamp word
+    fn local_cas_seq(&self, key: u64, old_seq: u64, new_seq: u64) -> Option<MemNodeMeta>;
+    fn local_upd_val_set_seq(&self, key: u64, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta>;
+    // commutative update, merge the delta without reading it out,
+    // the holders of a delta lock merge one at a time and never move the seq back
+    fn local_apply_delta(&self, key: u64, merge: DeltaMergeFn, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta>;
+    fn local_apply_delta_set_seq(&self, key: u64, merge: DeltaMergeFn, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta>;
+
+    // multi-version, single-version stores only keep the newest one
+    #[allow(unused_variables)]
+    fn local_upd_val_seq_at(&self, key: u64, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta> {
+        self.local_upd_val_seq(key, ptr, len)
+    }
+    // None if the version visible at read_ts is not kept any more
+    #[allow(unused_variables)]
+    fn local_get_snapshot(&self, key: u64, ptr: *mut u8, len: u32, read_ts: u64) -> Option<MemNodeMeta> {
+        self.local_get_readonly(key, ptr, len)
+    }
+    #[allow(unused_variables)]
+    fn local_gc(&self, watermark: u64) {}
+
+    // address of the node, only for stores whose nodes never move,
+    // so that peers may access it with the one-sided primitives
+    #[allow(unused_variables)]
+    fn local_get_node_addr(&self, key: u64) -> Option<u64> {
+        None
+    }
+    // for the stores whose buckets may be addressed directly
+    fn get_layout(&self) -> Option<MemStoreLayout> {
+        None
+    }
+    // visit the meta of every key, false if the store cannot be scanned
+    #[allow(unused_variables)]
+    fn local_scan_meta(&self, f: &mut dyn FnMut(u64, MemNodeMeta)) -> bool {
+        false
+    }
 }
@@ -2,16 +2,24 @@ mod bplustree_memstore;
 
 mod robinhood;
 mod robinhood_memstore;
+mod mvcc_memstore;
 
 mod cluster_chain;
 
 mod memstore;
 mod valuestore;
+mod delta_merge;
 
 pub mod memdb;
 
 pub use memstore::MemStoreValue;
 pub use memstore::MemNodeMeta;
+pub use memstore::{ERASED_LOCK, SHARED_LOCK_FLAG, DELTA_LOCK_FLAG, is_shared_lock, is_delta_lock};
+pub use memstore::{MEMNODE_LOCK_OFF, MEMNODE_SEQ_OFF, MEMNODE_VALUE_OFF};
+pub use memstore::MemStoreLayout;
+pub use robinhood::robinhood::robinhood_hash;
+pub use delta_merge::*;
 
 pub use robinhood_memstore::RobinhoodMemStore;
+pub use mvcc_memstore::{MvccClock, MvccMemStore, NO_COMMIT_TS};
 pub use valuestore::RobinhoodValueStore;
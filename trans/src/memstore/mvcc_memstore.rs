@@ -0,0 +1,612 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+use super::robinhood::robinhood::RobinHood;
+use super::memstore::{MemNode, MemNodeMeta, MemStore, MemStoreValue};
+use super::delta_merge::DeltaMergeFn;
+
+use crate::{ROBINHOOD_SIZE, ROBINHOOD_DIB_MAX, MVCC_MAX_VERSIONS};
+
+// the clock starts after it, a coordinator without a clock sends it
+pub const NO_COMMIT_TS: u64 = 0;
+
+/// Commit timestamps of one partition.
+/// A snapshot timestamp never covers an in-flight commit,
+/// so a snapshot reader sees either all or none writes of a txn.
+pub struct MvccClock {
+    next_ts:   AtomicU64,
+    // a ts chosen by remote coordinators may be installed by several at once
+    inflight:  Mutex<BTreeMap<u64, usize>>,
+    snapshots: Mutex<BTreeMap<u64, usize>>,
+}
+
+impl MvccClock {
+    pub fn new() -> Self {
+        Self {
+            next_ts:   AtomicU64::new(1),
+            inflight:  Mutex::new(BTreeMap::new()),
+            snapshots: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    #[inline]
+    fn stable_ts(&self, inflight: &BTreeMap<u64, usize>) -> u64 {
+        match inflight.first_key_value() {
+            Some((ts, _)) => *ts - 1,
+            None => self.next_ts.load(Ordering::Acquire) - 1,
+        }
+    }
+
+    pub fn alloc_commit_ts(&self) -> u64 {
+        let mut inflight = self.inflight.lock().unwrap();
+        let ts = self.next_ts.fetch_add(1, Ordering::AcqRel);
+        *inflight.entry(ts).or_insert(0) += 1;
+        ts
+    }
+
+    // commit ts chosen by a remote coordinator, the commits from now on here are after it
+    pub fn join_commit_ts(&self, ts: u64) {
+        let mut inflight = self.inflight.lock().unwrap();
+        self.next_ts.fetch_max(ts + 1, Ordering::AcqRel);
+        *inflight.entry(ts).or_insert(0) += 1;
+    }
+
+    pub fn finish_commit_ts(&self, ts: u64) {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(count) = inflight.get_mut(&ts) {
+            *count -= 1;
+            if *count == 0 {
+                inflight.remove(&ts);
+            }
+        }
+    }
+
+    // largest ts whose commits (and all before) are installed
+    pub fn snapshot_ts(&self) -> u64 {
+        let inflight = self.inflight.lock().unwrap();
+        self.stable_ts(&inflight)
+    }
+
+    // snapshot registered, versions it needs will not be collected
+    pub fn begin_snapshot(&self) -> u64 {
+        let inflight = self.inflight.lock().unwrap();
+        let ts = self.stable_ts(&inflight);
+
+        let mut snapshots = self.snapshots.lock().unwrap();
+        *snapshots.entry(ts).or_insert(0) += 1;
+        ts
+    }
+
+    // snapshot chosen by a remote coordinator, commits from now on are after it,
+    // false when a commit at or before it is still installing
+    pub fn join_snapshot(&self, ts: u64) -> bool {
+        let inflight = self.inflight.lock().unwrap();
+        self.next_ts.fetch_max(ts + 1, Ordering::AcqRel);
+        if self.stable_ts(&inflight) < ts {
+            return false;
+        }
+
+        let mut snapshots = self.snapshots.lock().unwrap();
+        *snapshots.entry(ts).or_insert(0) += 1;
+        true
+    }
+
+    pub fn end_snapshot(&self, ts: u64) {
+        let mut snapshots = self.snapshots.lock().unwrap();
+        if let Some(count) = snapshots.get_mut(&ts) {
+            *count -= 1;
+            if *count == 0 {
+                snapshots.remove(&ts);
+            }
+        }
+    }
+
+    // versions older than the watermark are invisible to all snapshots
+    pub fn gc_watermark(&self) -> u64 {
+        let inflight = self.inflight.lock().unwrap();
+        let ts = self.stable_ts(&inflight);
+
+        let snapshots = self.snapshots.lock().unwrap();
+        match snapshots.keys().next() {
+            Some(oldest) if *oldest < ts => *oldest,
+            _ => ts,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct MvccVersion<T: MemStoreValue> {
+    commit_ts: u64,
+    value:     T,
+}
+
+/// MemNode with a bounded chain of committed versions (newest first)
+struct MvccNode<T: MemStoreValue> {
+    node:     MemNode<T>,
+    versions: Mutex<VecDeque<MvccVersion<T>>>,
+}
+
+impl<T> Clone for MvccNode<T>
+where
+    T: MemStoreValue,
+{
+    fn clone(&self) -> Self {
+        Self {
+            node:     self.node.clone(),
+            versions: Mutex::new(self.versions.lock().unwrap().clone()),
+        }
+    }
+}
+
+impl<T> Default for MvccNode<T>
+where
+    T: MemStoreValue,
+{
+    fn default() -> Self {
+        Self {
+            node:     MemNode::default(),
+            versions: Mutex::new(VecDeque::new()),
+        }
+    }
+}
+
+impl<T> MvccNode<T>
+where
+    T: MemStoreValue,
+{
+    fn new_zero(lock: u64, seq: u64) -> Self {
+        Self {
+            node:     MemNode::new_zero(lock, seq),
+            versions: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn install(&self, value: &T, commit_ts: u64, watermark: u64) {
+        let mut versions = self.versions.lock().unwrap();
+
+        versions.push_front(MvccVersion {
+            commit_ts: commit_ts,
+            value:     value.clone(),
+        });
+        self.node.set_value(value);
+        self.node.advance_seq();
+
+        Self::prune(&mut versions, watermark);
+    }
+
+    // the holders of a delta lock install in any order, so the delta goes into
+    // the versions after commit_ts as well, under the lock of the chain
+    fn install_delta(&self, merge: DeltaMergeFn, ptr: *const u8, len: u32, commit_ts: u64, watermark: u64) {
+        let mut versions = self.versions.lock().unwrap();
+
+        let pos = versions.iter().position(|v| v.commit_ts <= commit_ts).unwrap_or(versions.len());
+        for version in versions.iter_mut().take(pos) {
+            merge(&mut version.value as *mut T as *mut u8, ptr, len);
+        }
+
+        let base = match versions.get(pos) {
+            Some(version) => Some(version.value.clone()),
+            None if versions.is_empty() => Some(self.node.get_value().clone()),
+            // older than all the versions kept, no snapshot sees it alone
+            None => None,
+        };
+        if let Some(mut value) = base {
+            merge(&mut value as *mut T as *mut u8, ptr, len);
+            versions.insert(pos, MvccVersion {
+                commit_ts: commit_ts,
+                value:     value,
+            });
+        }
+
+        self.node.set_value(&versions.front().unwrap().value);
+        self.node.advance_seq();
+
+        Self::prune(&mut versions, watermark);
+    }
+
+    // keep the newest version visible at watermark and all later ones
+    fn prune(versions: &mut VecDeque<MvccVersion<T>>, watermark: u64) {
+        if let Some(pos) = versions.iter().position(|v| v.commit_ts <= watermark) {
+            versions.truncate(pos + 1);
+        }
+
+        versions.truncate(MVCC_MAX_VERSIONS);
+    }
+
+    // Ok(None): never committed, Err(()): the version has been collected
+    fn read_at(&self, read_ts: u64) -> Result<Option<T>, ()> {
+        let versions = self.versions.lock().unwrap();
+
+        if versions.is_empty() {
+            return Ok(None);
+        }
+
+        for version in versions.iter() {
+            if version.commit_ts <= read_ts {
+                return Ok(Some(version.value.clone()));
+            }
+        }
+
+        Err(())
+    }
+}
+
+unsafe impl<T> Send for MvccNode<T> where T: MemStoreValue {}
+unsafe impl<T> Sync for MvccNode<T> where T: MemStoreValue {}
+
+pub struct MvccMemStore<T>
+where
+    T: MemStoreValue,
+{
+    table: RwLock<RobinHood<u64, MvccNode<T>, ROBINHOOD_SIZE>>,
+    clock: Arc<MvccClock>,
+}
+
+impl<T> MvccMemStore<T>
+where
+    T: MemStoreValue,
+{
+    pub fn new(clock: &Arc<MvccClock>) -> Self {
+        Self {
+            table: RwLock::new(RobinHood::new(ROBINHOOD_DIB_MAX)),
+            clock: clock.clone(),
+        }
+    }
+}
+
+impl<T> MemStore for MvccMemStore<T>
+where
+    T: MemStoreValue,
+{
+    #[inline]
+    fn get_item_length(&self) -> usize {
+        std::mem::size_of::<T>()
+    }
+
+    fn local_get_meta(&self, key: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_get_readonly(&self, key: u64, ptr: *mut u8, len: u32) -> Option<MemNodeMeta> {
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("get length is not rational!");
+        }
+
+        let value = unsafe { (ptr as *mut T).as_mut().unwrap() };
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                *value = mv.node.get_value().clone();
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_get_for_upd(
+        &self,
+        key: u64,
+        ptr: *mut u8,
+        len: u32,
+        lock_content: u64,
+    ) -> Option<MemNodeMeta> {
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("get length is not rational!");
+        }
+
+        let value = unsafe { (ptr as *mut T).as_mut().unwrap() };
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                if mv.node.try_lock(lock_content) {
+                    *value = mv.node.get_value().clone();
+                }
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_lock(&self, key: u64, lock_content: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let mut need_insert = false;
+
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.try_lock(lock_content);
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {
+                need_insert = true;
+            }
+        }
+
+        drop(table);
+
+        if !need_insert {
+            return ret;
+        }
+
+        let mut table = self.table.write().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.try_lock(lock_content);
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {
+                let mv = MvccNode::new_zero(lock_content, 2);
+                table.put(&key, &mv);
+                ret = Some(MemNodeMeta::new(lock_content, 2))
+            }
+        }
+
+        ret
+    }
+
+    fn local_try_unlock(&self, key: u64, lock_content: u64) {
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.try_unlock(lock_content);
+            }
+            None => {}
+        }
+    }
+
+    fn local_unlock(&self, key: u64, lock_content: u64) {
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.unlock(lock_content);
+            }
+            None => {}
+        }
+    }
+
+    // single-item commit, e.g. loaders
+    fn local_upd_val_seq(&self, key: u64, ptr: *const u8, len: u32) -> Option<MemNodeMeta> {
+        let commit_ts = self.clock.alloc_commit_ts();
+        let ret = self.local_upd_val_seq_at(key, ptr, len, commit_ts);
+        self.clock.finish_commit_ts(commit_ts);
+
+        ret
+    }
+
+    // erase drops the whole version chain
+    fn local_erase(&self, key: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+        let mut table = self.table.write().unwrap();
+
+        match table.erase(&key) {
+            Some(mv) => {
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_get_shared(&self, key: u64, ptr: *mut u8, len: u32) -> Option<MemNodeMeta> {
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("get length is not rational!");
+        }
+
+        let value = unsafe { (ptr as *mut T).as_mut().unwrap() };
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                if mv.node.try_lock_shared() {
+                    *value = mv.node.get_value().clone();
+                }
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_unlock_shared(&self, key: u64) {
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.unlock_shared();
+            }
+            None => {}
+        }
+    }
+
+    fn local_upgrade_shared(&self, key: u64, lock_content: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.try_upgrade_shared(lock_content);
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_lock_delta(&self, key: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                let lock = mv.node.try_lock_delta().unwrap_or_else(|holder| holder);
+                ret = Some(MemNodeMeta::new(lock, mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_unlock_delta(&self, key: u64) {
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.unlock_delta();
+            }
+            None => {}
+        }
+    }
+
+    fn local_cas_seq(&self, key: u64, old_seq: u64, new_seq: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.cas_seq(old_seq, new_seq);
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    // installs a new version as well, the seq is left to the caller
+    fn local_upd_val_set_seq(&self, key: u64, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta> {
+        let commit_ts = self.clock.alloc_commit_ts();
+        let ret = self.local_upd_val_seq_at(key, ptr, len, commit_ts);
+        self.clock.finish_commit_ts(commit_ts);
+
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.set_seq(seq);
+                Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()))
+            }
+            None => ret
+        }
+    }
+
+    fn local_apply_delta(&self, key: u64, merge: DeltaMergeFn, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+        let watermark = self.clock.gc_watermark();
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                mv.install_delta(merge, ptr, len, commit_ts, watermark);
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_apply_delta_set_seq(&self, key: u64, merge: DeltaMergeFn, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta> {
+        let commit_ts = self.clock.alloc_commit_ts();
+        let ret = self.local_apply_delta(key, merge, ptr, len, commit_ts);
+        self.clock.finish_commit_ts(commit_ts);
+
+        let table = self.table.read().unwrap();
+        match table.get(&key) {
+            Some(mv) => {
+                mv.node.raise_seq(seq);
+                Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()))
+            }
+            None => ret
+        }
+    }
+
+    fn local_upd_val_seq_at(&self, key: u64, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("upd length is not rational!");
+        }
+        let watermark = self.clock.gc_watermark();
+        let table = self.table.read().unwrap();
+
+        let value = unsafe { (ptr as *const T).as_ref().unwrap() };
+
+        match table.get(&key) {
+            Some(mv) => {
+                mv.install(value, commit_ts, watermark);
+                ret = Some(MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_get_snapshot(&self, key: u64, ptr: *mut u8, len: u32, read_ts: u64) -> Option<MemNodeMeta> {
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("get length is not rational!");
+        }
+
+        let value = unsafe { (ptr as *mut T).as_mut().unwrap() };
+
+        let table = self.table.read().unwrap();
+
+        match table.get(&key) {
+            Some(mv) => {
+                match mv.read_at(read_ts) {
+                    Ok(Some(v)) => {
+                        *value = v;
+                        Some(MemNodeMeta::new(0, mv.node.get_seq()))
+                    }
+                    Ok(None) => Some(MemNodeMeta::new(0, 0)),
+                    Err(_) => None
+                }
+            }
+            None => Some(MemNodeMeta::new(0, 0))
+        }
+    }
+
+    fn local_gc(&self, watermark: u64) {
+        let table = self.table.read().unwrap();
+
+        table.for_each(|_, mv| {
+            let mut versions = mv.versions.lock().unwrap();
+            MvccNode::prune(&mut versions, watermark);
+        });
+    }
+
+    fn local_scan_meta(&self, f: &mut dyn FnMut(u64, MemNodeMeta)) -> bool {
+        let table = self.table.read().unwrap();
+
+        table.for_each(|key, mv| f(*key, MemNodeMeta::new(mv.node.get_lock(), mv.node.get_seq())));
+        true
+    }
+}
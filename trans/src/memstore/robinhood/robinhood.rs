@@ -1,11 +1,51 @@
 use std::collections::HashMap;
 use std::hash::Hash;
-use std::hash::RandomState;
+use std::hash::BuildHasherDefault;
 use std::hash::{BuildHasher, Hasher};
 
 use rand::prelude::*;
 
 use crate::common::region::RawArrayRegion;
+use crate::memstore::memstore::MemStoreLayout;
+
+// the dib of an erased unit of a pinned table
+const TOMB_DIB: usize = usize::MAX;
+
+// FNV-1a with a final mix, the same on every node
+pub struct RobinHoodHasher {
+    hash: u64,
+}
+
+impl Default for RobinHoodHasher {
+    fn default() -> Self {
+        Self {
+            hash: 0xcbf29ce484222325,
+        }
+    }
+}
+
+impl Hasher for RobinHoodHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let mut hash = self.hash;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+        hash ^= hash >> 33;
+        hash
+    }
+}
+
+pub fn robinhood_hash<K: Hash>(key: &K) -> u64 {
+    let mut hasher = RobinHoodHasher::default();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
 
 #[repr(C)]
 #[derive(Clone)]
@@ -152,10 +192,14 @@ where
     inbuf_size: usize,
     // TODO: simple linked buckets
     of_buckets: HashMap<K, V>,
-    hash_builder: RandomState,
+    hash_builder: BuildHasherDefault<RobinHoodHasher>,
+    // the units never move: inserts take the first free bucket without displacing,
+    // erases leave a tomb, which only the same key takes again
+    pinned: bool,
 }
 
-// (TODO:) expose memory to support one-side rdma primitives and dma functions
+// the units are exposed to one-side rdma primitives and dma functions if they are in a registered region,
+// the overflow buckets are not
 impl<K, V, const INBUF_CAP: usize> RobinHood<K, V, INBUF_CAP>
 where
     K: Default + Eq + PartialEq + Hash + Copy + Clone + Send + Sync,
@@ -167,10 +211,54 @@ where
             dib_max: dib_max,
             inbuf_size: 0,
             of_buckets: HashMap::<K, V>::new(),
-            hash_builder: RandomState::new(),
+            hash_builder: BuildHasherDefault::default(),
+            pinned: false,
         }
     }
 
+    // the units in the caller's memory, len must be region_len(),
+    // the peers keep the addresses of the values, so they are pinned
+    pub fn new_from_raw(dib_max: usize, ptr: *mut u8, len: usize) -> Option<Self> {
+        Some(Self {
+            units: RawArrayRegion::new_from_raw(ptr, len)?,
+            dib_max: dib_max,
+            inbuf_size: 0,
+            of_buckets: HashMap::<K, V>::new(),
+            hash_builder: BuildHasherDefault::default(),
+            pinned: true,
+        })
+    }
+
+    pub const fn region_len() -> usize {
+        std::mem::size_of::<RobinHoodUnit<K, V>>() * INBUF_CAP
+    }
+
+    #[inline]
+    pub fn is_pinned(&self) -> bool {
+        self.pinned
+    }
+
+    // node_off is the offset of the value in a unit
+    pub fn get_layout(&self) -> MemStoreLayout {
+        MemStoreLayout {
+            base:      &self.units[0] as *const _ as u64,
+            rkey:      0,
+            capacity:  INBUF_CAP as u64,
+            unit_size: std::mem::size_of::<RobinHoodUnit<K, V>>() as u64,
+            valid_off: std::mem::offset_of!(RobinHoodUnit<K, V>, valid) as u64,
+            key_off:   std::mem::offset_of!(RobinHoodUnit<K, V>, key) as u64,
+            dib_off:   std::mem::offset_of!(RobinHoodUnit<K, V>, dib) as u64,
+            node_off:  std::mem::offset_of!(RobinHoodUnit<K, V>, value) as u64,
+            dib_max:   self.dib_max as u64,
+            tomb_dib:  TOMB_DIB as u64,
+        }
+    }
+
+    #[inline]
+    fn holds(&self, ind: usize, key: &K) -> bool {
+        self.units[ind].dib != TOMB_DIB && self.units[ind].key.eq(key)
+    }
+
     fn hash(&self, key: &K) -> usize {
         let mut hasher = self.hash_builder.build_hasher();
         key.hash(&mut hasher);
@@ -193,7 +281,7 @@ where
                 return None;
             }
 
-            if self.units[ind].key.eq(key) {
+            if self.holds(ind, key) {
                 return Some(&self.units[ind].value);
             }
 
@@ -224,6 +312,10 @@ where
     pub fn put(&mut self, key: &K, value: &V) {
         let capacity = INBUF_CAP;
 
+        if self.pinned {
+            return self.put_pinned(key, value);
+        }
+
         if self.inbuf_size >= capacity {
             self.of_buckets.insert(*key, value.clone());
             return;
@@ -274,6 +366,29 @@ where
         }
     }
 
+    // the tombs are never freed, so a key finds its own tomb before the first free bucket
+    fn put_pinned(&mut self, key: &K, value: &V) {
+        let mut ind = self.hash(key);
+
+        for dib in 0..self.dib_max {
+            let unit = &self.units[ind];
+            if !unit.valid || (unit.dib == TOMB_DIB && unit.key.eq(key)) {
+                if !unit.valid {
+                    self.inbuf_size += 1;
+                }
+                self.units[ind] = RobinHoodUnit::new(true, *key, dib, value.clone());
+                return;
+            }
+
+            ind += 1;
+            if ind >= INBUF_CAP {
+                ind = 0;
+            }
+        }
+
+        self.of_buckets.insert(*key, value.clone());
+    }
+
     #[inline]
     fn get_index_inbuf(&self, key: &K) -> Option<usize> {
         let capacity = INBUF_CAP;
@@ -284,7 +399,7 @@ where
             if !self.units[ind].valid {
                 return None;
             }
-            if self.units[ind].key.eq(key) {
+            if self.holds(ind, key) {
                 return Some(ind);
             }
 
@@ -308,6 +423,11 @@ where
         let capacity = INBUF_CAP;
         if let Some(mut ind) = self.get_index_inbuf(key) {
             let old_value = self.units[ind].value.clone();
+            if self.pinned {
+                self.units[ind].dib = TOMB_DIB;
+                return Some(old_value);
+            }
+
             self.units[ind] = RobinHoodUnit::default();
             // back shift
             loop {
@@ -329,6 +449,19 @@ where
         }
         return None;
     }
+
+    // visit all the valid entries, both inbuf and overflow
+    pub fn for_each<F: FnMut(&K, &V)>(&self, mut f: F) {
+        for i in 0..INBUF_CAP {
+            if self.units[i].valid && self.units[i].dib != TOMB_DIB {
+                f(&self.units[i].key, &self.units[i].value);
+            }
+        }
+
+        for (key, value) in self.of_buckets.iter() {
+            f(key, value);
+        }
+    }
 }
 
 impl<V, const INBUF_CAP: usize> RobinHood<usize, V, INBUF_CAP>
@@ -5,7 +5,7 @@ use std::sync::RwLock;
 use std::time::SystemTime;
 use std::time::Duration;
 
-use super::super::memstore::{MemNode, MemStoreValue};
+use super::super::memstore::{MemNode, MemStoreLayout, MemStoreValue};
 use super::robinhood::RobinHood;
 
 use crate::{ROBINHOOD_SIZE, ROBINHOOD_DIB_MAX};
@@ -21,15 +21,15 @@ bitfields!(
 /// | 31 ... 10 | 9 | 8 ... 0 |
 /// TODO: using htm to ensure consistence
 /// UnsafeCell is in need
-pub struct RobinHoodTableCell<T>
+pub struct RobinHoodTableCell<T, const CAP: usize = ROBINHOOD_SIZE>
 where
     T: MemStoreValue,
 {
-    table: UnsafeCell<RobinHood<u64, MemNode<T>, ROBINHOOD_SIZE>>,
+    table: UnsafeCell<RobinHood<u64, MemNode<T>, CAP>>,
 }
 
-unsafe impl<T> Send for RobinHoodTableCell<T> where T: MemStoreValue {}
-unsafe impl<T> Sync for RobinHoodTableCell<T> where T: MemStoreValue {}
+unsafe impl<T, const CAP: usize> Send for RobinHoodTableCell<T, CAP> where T: MemStoreValue {}
+unsafe impl<T, const CAP: usize> Sync for RobinHoodTableCell<T, CAP> where T: MemStoreValue {}
 
 impl<T> RobinHoodTableCell<T>
 where
@@ -40,6 +40,28 @@ where
             table:  UnsafeCell::new(RobinHood::new(ROBINHOOD_DIB_MAX))
         }
     }
+}
+
+impl<T, const CAP: usize> RobinHoodTableCell<T, CAP>
+where
+    T: MemStoreValue,
+{
+    pub fn new_from_raw(ptr: *mut u8, len: usize) -> Option<Self> {
+        Some(Self {
+            table:  UnsafeCell::new(RobinHood::new_from_raw(ROBINHOOD_DIB_MAX, ptr, len)?)
+        })
+    }
+
+    pub const fn region_len() -> usize {
+        RobinHood::<u64, MemNode<T>, CAP>::region_len()
+    }
+
+    // None if the nodes may move
+    pub fn get_layout(&self) -> Option<MemStoreLayout> {
+        let ref_table = unsafe { self.table.get().as_ref().unwrap() };
+
+        ref_table.is_pinned().then(|| ref_table.get_layout())
+    }
 
     pub fn get(&self, key: u64) -> Option<&MemNode<T>> {
         let ref_table = unsafe { self.table.get().as_ref().unwrap() };
@@ -56,6 +78,19 @@ where
     pub fn erase(&self, key: u64) -> Option<MemNode<T>> {
         let refmut_table = unsafe { self.table.get().as_mut().unwrap() };
 
+        if refmut_table.is_pinned() {
+            let node = refmut_table.get(&key)?;
+            let old = node.clone();
+            node.lock_erased();
+            refmut_table.erase(&key);
+            return Some(old);
+        }
         refmut_table.erase(&key)
     }
+
+    pub fn for_each<F: FnMut(u64, &MemNode<T>)>(&self, mut f: F) {
+        let ref_table = unsafe { self.table.get().as_ref().unwrap() };
+
+        ref_table.for_each(|key, node| f(*key, node));
+    }
 }
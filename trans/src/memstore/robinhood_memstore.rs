@@ -1,13 +1,19 @@
-use std::sync::RwLock;
+use std::alloc::Layout;
+use std::sync::{Mutex, RwLock};
 
 use super::robinhood::robinhoodcell::RobinHoodTableCell;
-use super::memstore::{MemNode, MemNodeMeta, MemStore, MemStoreValue};
+use super::memstore::{MemNode, MemNodeMeta, MemStore, MemStoreLayout, MemStoreValue};
+use crate::ROBINHOOD_SIZE;
+use crate::rdma::control::RdmaBaseAllocator;
+use super::delta_merge::DeltaMergeFn;
 
-pub struct RobinhoodMemStore<T>
+pub struct RobinhoodMemStore<T, const CAP: usize = ROBINHOOD_SIZE>
 where
     T: MemStoreValue,
 {
-    table: RwLock<RobinHoodTableCell<T>>,
+    table: RwLock<RobinHoodTableCell<T, CAP>>,
+    // the holders of a delta lock merge under it
+    merging: Mutex<()>,
 }
 
 impl<T> RobinhoodMemStore<T> 
@@ -16,12 +22,38 @@ where
 {
     pub fn new() -> Self {
         Self {
-            table: RwLock::new(RobinHoodTableCell::<T>::new())
+            table: RwLock::new(RobinHoodTableCell::<T>::new()),
+            merging: Mutex::new(()),
         }
     }
 }
 
-impl<T> MemStore for RobinhoodMemStore<T>
+impl<T, const CAP: usize> RobinhoodMemStore<T, CAP>
+where
+    T: MemStoreValue
+{
+    // the buckets in a registered region, so that the peers access the rows directly,
+    // the rows there never move
+    pub fn new_from_raw(ptr: *mut u8, len: usize) -> Option<Self> {
+        Some(Self {
+            table: RwLock::new(RobinHoodTableCell::<T, CAP>::new_from_raw(ptr, len)?),
+            merging: Mutex::new(()),
+        })
+    }
+
+    pub const fn region_len() -> usize {
+        RobinHoodTableCell::<T, CAP>::region_len()
+    }
+
+    // allocated from the region, None if it has no room left
+    pub fn new_in(region: &RdmaBaseAllocator) -> Option<Self> {
+        let len = Self::region_len();
+        let ptr = unsafe { region.alloc(Layout::from_size_align(len, 4096).unwrap()) };
+        Self::new_from_raw(ptr, len)
+    }
+}
+
+impl<T, const CAP: usize> MemStore for RobinhoodMemStore<T, CAP>
 where
     T: MemStoreValue,
 {
@@ -181,6 +213,156 @@ where
         ret
     }
 
+    fn local_get_shared(&self, key: u64, ptr: *mut u8, len: u32) -> Option<MemNodeMeta> {
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("get length is not rational!");
+        }
+
+        let value = unsafe { (ptr as *mut T).as_mut().unwrap() };
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+
+        match table.get(key) {
+            Some(node) => {
+                if node.try_lock_shared() {
+                    *value = node.get_value().clone();
+                }
+                ret = Some(MemNodeMeta::new(node.get_lock(), node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_unlock_shared(&self, key: u64) {
+        let table = self.table.read().unwrap();
+        match table.get(key) {
+            Some(node) => {
+                node.unlock_shared();
+            }
+            None => {}
+        }
+    }
+
+    fn local_upgrade_shared(&self, key: u64, lock_content: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+        match table.get(key) {
+            Some(node) => {
+                node.try_upgrade_shared(lock_content);
+                ret = Some(MemNodeMeta::new(node.get_lock(), node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    // a delta needs the row, it is not inserted
+    fn local_lock_delta(&self, key: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+        match table.get(key) {
+            Some(node) => {
+                let lock = node.try_lock_delta().unwrap_or_else(|holder| holder);
+                ret = Some(MemNodeMeta::new(lock, node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_unlock_delta(&self, key: u64) {
+        let table = self.table.read().unwrap();
+        match table.get(key) {
+            Some(node) => {
+                node.unlock_delta();
+            }
+            None => {}
+        }
+    }
+
+    fn local_cas_seq(&self, key: u64, old_seq: u64, new_seq: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        let table = self.table.read().unwrap();
+        match table.get(key) {
+            Some(node) => {
+                node.cas_seq(old_seq, new_seq);
+                ret = Some(MemNodeMeta::new(node.get_lock(), node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_upd_val_set_seq(&self, key: u64, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+
+        if std::mem::size_of::<T>() > len as usize {
+            panic!("upd length is not rational!");
+        }
+        let table = self.table.read().unwrap();
+
+        let value = unsafe { (ptr as *const T).as_ref().unwrap() };
+
+        match table.get(key) {
+            Some(node) => {
+                node.set_value(value);
+                node.set_seq(seq);
+                ret = Some(MemNodeMeta::new(node.get_lock(), node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    #[allow(unused_variables)]
+    fn local_apply_delta(&self, key: u64, merge: DeltaMergeFn, ptr: *const u8, len: u32, commit_ts: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+        let table = self.table.read().unwrap();
+        let _merging = self.merging.lock().unwrap();
+
+        match table.get(key) {
+            Some(node) => {
+                let mut value = node.get_value().clone();
+                merge(&mut value as *mut T as *mut u8, ptr, len);
+                node.set_value(&value);
+                node.advance_seq();
+                ret = Some(MemNodeMeta::new(node.get_lock(), node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
+    fn local_apply_delta_set_seq(&self, key: u64, merge: DeltaMergeFn, ptr: *const u8, len: u32, seq: u64) -> Option<MemNodeMeta> {
+        let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
+        let table = self.table.read().unwrap();
+        let _merging = self.merging.lock().unwrap();
+
+        match table.get(key) {
+            Some(node) => {
+                let mut value = node.get_value().clone();
+                merge(&mut value as *mut T as *mut u8, ptr, len);
+                node.set_value(&value);
+                node.raise_seq(seq);
+                ret = Some(MemNodeMeta::new(node.get_lock(), node.get_seq()));
+            }
+            None => {}
+        }
+
+        ret
+    }
+
     fn local_erase(&self, key: u64) -> Option<MemNodeMeta> {
         let mut ret: Option<MemNodeMeta> = Some(MemNodeMeta::new(0, 0));
         let table = self.table.write().unwrap();
@@ -194,4 +376,22 @@ where
 
         ret
     }
+
+    // overflow nodes are off the region, MemDB filters them out
+    fn local_get_node_addr(&self, key: u64) -> Option<u64> {
+        let table = self.table.read().unwrap();
+
+        table.get(key).map(|node| node as *const MemNode<T> as u64)
+    }
+
+    fn get_layout(&self) -> Option<MemStoreLayout> {
+        self.table.read().unwrap().get_layout()
+    }
+
+    fn local_scan_meta(&self, f: &mut dyn FnMut(u64, MemNodeMeta)) -> bool {
+        let table = self.table.read().unwrap();
+
+        table.for_each(|key, node| f(key, MemNodeMeta::new(node.get_lock(), node.get_seq())));
+        true
+    }
 }
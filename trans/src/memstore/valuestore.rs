@@ -12,6 +12,8 @@ pub trait ValueStore {
     fn local_set_value(&self, key: u64, ptr: *const u8, len: u32) -> bool;
     fn local_put_value(&self, key: u64, ptr: *const u8, len: u32);
     fn local_erase_value(&self, key: u64);
+    // visit every key
+    fn local_scan_keys(&self, f: &mut dyn FnMut(u64));
 }
 
 struct UnsafeValue<T: MemStoreValue>(UnsafeCell<T>);
@@ -150,4 +152,10 @@ where
             None => {}
         }
     }
+
+    fn local_scan_keys(&self, f: &mut dyn FnMut(u64)) {
+        let table = self.table.read().unwrap();
+
+        table.for_each(|key, _| f(*key));
+    }
 }
\ No newline at end of file
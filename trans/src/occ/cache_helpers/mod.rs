@@ -15,4 +15,5 @@ pub struct CacheWriteSetItem {
     pub(crate) table_id: usize,
     pub(crate) key:      u64,
     pub(crate) insert:   bool,
+    pub(crate) delta:    bool,
 }
\ No newline at end of file
@@ -9,7 +9,9 @@ use crate::MAX_LOCAL_CACHE_BUF_COUNT;
 use crate::framework::rpc::RpcProcessMeta;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::occ::occ::LockContent;
-use crate::doca_dma::{ DmaLocalBuf, DmaRemoteBuf };
+use crate::doca_dma::{ BufPoolStats, DmaLocalBuf, DmaRemoteBuf };
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+use crate::doca_dma::DmaSegment;
 
 use super::{ CacheReadSetItem, CacheWriteSetItem };
 
@@ -53,31 +55,57 @@ impl LocalCacheBuf {
     }
 }
 
-// local cache allocator
+// local cache allocator, the capacity doubles when it runs out until the limit
 struct LocalCacheBufAllocator {
     free_bufs: Vec<LocalCacheBuf>,
     alloc_num: usize,
+    limit: usize,
+    stats: BufPoolStats,
 }
 
 impl LocalCacheBufAllocator {
-    pub fn new() -> Self {
+    pub fn new(capacity: usize, limit: usize) -> Self {
         Self {
             free_bufs: Vec::new(),
             alloc_num: 0,
+            limit: limit,
+            stats: BufPoolStats::new(capacity.min(limit)),
         }
     }
 
     pub fn alloc_buf(&mut self) -> Option<LocalCacheBuf> {
-        if !self.free_bufs.is_empty() {
-            return self.free_bufs.pop();
-        } else if self.alloc_num < MAX_LOCAL_CACHE_BUF_COUNT {
-            return Some(LocalCacheBuf(Arc::pin([0u8; MAX_DMA_BUF_SIZE])));
-        } else {
-            return None;
+        if let Some(buf) = self.free_bufs.pop() {
+            self.stats.on_alloc();
+            return Some(buf);
         }
+
+        if self.alloc_num == self.stats.capacity {
+            if self.stats.capacity >= self.limit {
+                self.stats.exhausted += 1;
+                return None;
+            }
+            self.stats.capacity = (self.stats.capacity * 2).max(1).min(self.limit);
+            self.stats.grows += 1;
+        }
+
+        self.alloc_num += 1;
+        self.stats.on_alloc();
+        Some(LocalCacheBuf(Arc::pin([0u8; MAX_DMA_BUF_SIZE])))
+    }
+
+    // the handlers cannot wait, past the limit they take a buffer dropped on its return
+    pub fn alloc_overflow_buf(&mut self) -> LocalCacheBuf {
+        self.alloc_num += 1;
+        self.stats.on_alloc();
+        LocalCacheBuf(Arc::pin([0u8; MAX_DMA_BUF_SIZE]))
     }
 
     pub fn dealloc_buf(&mut self, buf: LocalCacheBuf) {
+        self.stats.on_dealloc();
+        if self.alloc_num > self.stats.capacity {
+            self.alloc_num -= 1;
+            return;
+        }
         self.free_bufs.push(buf);
     }
 }
@@ -131,20 +159,30 @@ pub struct TransCacheView {
 impl TransCacheView {
     pub fn new(scheduler: &Arc<AsyncScheduler>) -> Self {
         Self {
-            local_alloc: LocalCacheBufAllocator::new(),
+            local_alloc: LocalCacheBufAllocator::new(MAX_LOCAL_CACHE_BUF_COUNT, usize::MAX),
             trans_read_map: HashMap::new(),
             trans_write_map: HashMap::new(),
             scheduler: scheduler.clone(),
         }
     }
 
+    // before the first transaction, a limit bounds the memory of the cache
+    pub fn set_local_buf_capacity(&mut self, capacity: usize, limit: usize) {
+        self.local_alloc = LocalCacheBufAllocator::new(capacity, limit);
+    }
+
+    #[inline]
+    pub fn local_buf_stats(&self) -> BufPoolStats {
+        self.local_alloc.stats
+    }
+
     #[inline]
     pub fn start_read_trans(&mut self, key: &TransKey) {
         if self.trans_read_map.contains_key(key) {
             return;
         }
         self.trans_read_map.insert(key.clone(), Vec::new());
-        self.alloc_read_buf(key);
+        self.block_alloc_read_buf(key);
     }
 
     #[inline]
@@ -153,7 +191,7 @@ impl TransCacheView {
             return;
         }
         self.trans_write_map.insert(key.clone(), Vec::new());
-        self.alloc_write_buf(key);
+        self.block_alloc_write_buf(key);
     }
 
     #[inline]
@@ -161,19 +199,11 @@ impl TransCacheView {
         let read_map = &mut self.trans_read_map;
         let read_metas = read_map.get_mut(key).unwrap();
         read_metas[0].count = 0;
-       
-        // while let Some(meta) = read_metas.pop() {
-        //     match meta.buf {
-        //         CacheBuf::LocalBuf(buf) => {
-        //             self.local_alloc.dealloc_buf(buf);
-        //         }
-        //         CacheBuf::RemoteBuf(buf) => {
-        //             #[cfg(feature = "doca_deps")]
-        //             self.scheduler.dma_dealloc_remote_buf(buf);
-        //         }
-        //     }
-        // }
-        // read_map.remove(key);
+        // the first buffer stays with the key for its next trans
+        while read_metas.len() > 1 {
+            let meta = read_metas.pop().unwrap();
+            Self::dealloc_cache_buf(&mut self.local_alloc, &self.scheduler, meta.buf);
+        }
     }
 
     #[inline]
@@ -182,22 +212,44 @@ impl TransCacheView {
 
         let write_metas =write_map.get_mut(key).unwrap();
         write_metas[0].count = 0;
-        // while let Some(meta) = write_metas.pop() {
-        //     match meta.buf {
-        //         CacheBuf::LocalBuf(buf) => {
-        //             self.local_alloc.dealloc_buf(buf);
-        //         }
-        //         CacheBuf::RemoteBuf(buf) => {
-        //             #[cfg(feature = "doca_deps")]
-        //             self.scheduler.dma_dealloc_remote_buf(buf);
-        //         }
-        //     }
-        // }
-
-        // write_map.remove(key);
-    }
-
-    #[cfg(feature = "doca_deps")]
+        while write_metas.len() > 1 {
+            let meta = write_metas.pop().unwrap();
+            Self::dealloc_cache_buf(&mut self.local_alloc, &self.scheduler, meta.buf);
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn dealloc_cache_buf(local_alloc: &mut LocalCacheBufAllocator, scheduler: &AsyncScheduler, buf: CacheBuf) {
+        match buf {
+            CacheBuf::LocalBuf(buf) => {
+                local_alloc.dealloc_buf(buf);
+            }
+            CacheBuf::RemoteBuf(buf) => {
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+                scheduler.dma_dealloc_remote_buf(buf);
+            }
+        }
+    }
+
+    // others run until a trans ends and returns a buffer
+    async fn yield_until_local_buf(&mut self, cid: u32) -> LocalCacheBuf {
+        loop {
+            if let Some(buf) = self.local_alloc.alloc_buf() {
+                return buf;
+            }
+            self.scheduler.yield_now(cid).await;
+        }
+    }
+
+    #[inline]
+    fn block_alloc_local_buf(&mut self) -> LocalCacheBuf {
+        match self.local_alloc.alloc_buf() {
+            Some(buf) => buf,
+            None => self.local_alloc.alloc_overflow_buf(),
+        }
+    }
+
+    #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
     fn get_local_dma_buf(&self, cid: u32) -> DmaLocalBuf {
         self.scheduler.dma_get_local_buf(cid)
     }
@@ -211,23 +263,14 @@ impl TransCacheView {
                 }
             }
             CacheBuf::RemoteBuf(buf) => {
-                #[cfg(feature = "doca_deps")]
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
                 if true {
                     let remote_off = buf.get_off();
                     let payload = std::mem::size_of::<ITEM>() * meta.count;
                     let dma_local_buf = self.scheduler.dma_get_local_buf(cid);
+                    let segs = [DmaSegment::new(dma_local_buf.get_off(), remote_off, payload)];
 
-                    loop {
-                        // sync functions can be more useful ???
-                        self.scheduler.post_read_dma_req(dma_local_buf.get_off(), remote_off, payload, cid);
-
-                        match self.scheduler.yield_until_dma_ready(cid).await {
-                            Ok(_) => {
-                                break;
-                            }
-                            Err(_) => {}
-                        }
-                    }
+                    while self.scheduler.dma_batch(&segs, false, cid).await.is_err() {}
 
                     return unsafe { dma_local_buf.get_const_slice(meta.count) };
                 }
@@ -247,7 +290,7 @@ impl TransCacheView {
                 }
             }
             CacheBuf::RemoteBuf(buf) => {
-                #[cfg(feature = "doca_deps")]
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
                 if true {
                     let remote_off = buf.get_off();
                     let payload = std::mem::size_of::<ITEM>() * meta.count;
@@ -279,22 +322,13 @@ impl TransCacheView {
         match &meta.buf {
             CacheBuf::LocalBuf(_) => {}
             CacheBuf::RemoteBuf(buf) => {
-                #[cfg(feature = "doca_deps")]
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
                 if true {
                     let remote_off = buf.get_off() + meta.count * std::mem::size_of::<ITEM>();
                     let payload = dirty_count * std::mem::size_of::<ITEM>();
+                    let segs = [DmaSegment::new(dma_local_buf.unwrap().get_off(), remote_off, payload)];
 
-                    loop {
-                        // sync functions can be more useful ???
-                        self.scheduler.post_write_dma_req(dma_local_buf.unwrap().get_off(), remote_off, payload, cid);
-
-                        match self.scheduler.yield_until_dma_ready(cid).await {
-                            Ok(_) => {
-                                break;
-                            }
-                            Err(_) => {}
-                        }
-                    }
+                    while self.scheduler.dma_batch(&segs, true, cid).await.is_err() {}
 
                     return;
                 }
@@ -307,7 +341,7 @@ impl TransCacheView {
         match &meta.buf {
             CacheBuf::LocalBuf(_) => {}
             CacheBuf::RemoteBuf(buf) => {
-                #[cfg(feature = "doca_deps")]
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
                 if true {
                     let remote_off = buf.get_off() + meta.count * std::mem::size_of::<ITEM>();
                     let payload = dirty_count * std::mem::size_of::<ITEM>();
@@ -356,9 +390,17 @@ impl TransCacheView {
         self.block_get_buf_slice(meta, cid)
     }
 
+    pub async fn alloc_read_buf(&mut self, key: &TransKey, cid: u32) {
+        let new_buf = self.yield_until_local_buf(cid).await;
+
+        let read_map = &mut self.trans_read_map;
+        let read_vec = read_map.get_mut(key).unwrap();
+        read_vec.push(CacheMeta::new(CacheBuf::LocalBuf(new_buf), 0));
+    }
+
     #[inline]
-    pub fn alloc_read_buf(&mut self, key: &TransKey) {
-        let new_buf = self.local_alloc.alloc_buf().unwrap();
+    pub fn block_alloc_read_buf(&mut self, key: &TransKey) {
+        let new_buf = self.block_alloc_local_buf();
 
         let read_map = &mut self.trans_read_map;
         let read_vec = read_map.get_mut(key).unwrap();
@@ -428,8 +470,15 @@ impl TransCacheView {
         self.block_get_buf_slice(meta, cid)
     }
 
-    pub fn alloc_write_buf(&mut self, key: &TransKey) {
-        let new_buf = self.local_alloc.alloc_buf().unwrap();
+    pub async fn alloc_write_buf(&mut self, key: &TransKey, cid: u32) {
+        let new_buf = self.yield_until_local_buf(cid).await;
+        let write_map = &mut self.trans_write_map;
+        let metas = write_map.get_mut(key).unwrap();
+        metas.push(CacheMeta::new(CacheBuf::LocalBuf(new_buf), 0));
+    }
+
+    pub fn block_alloc_write_buf(&mut self, key: &TransKey) {
+        let new_buf = self.block_alloc_local_buf();
         let write_map = &mut self.trans_write_map;
         let metas = write_map.get_mut(key).unwrap();
         metas.push(CacheMeta::new(CacheBuf::LocalBuf(new_buf), 0));
@@ -506,7 +555,7 @@ impl<'worker> ReadCacheMetaWriter {
                 }
             }
             CacheBuf::RemoteBuf(_) => {
-                #[cfg(feature = "doca_deps")]
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
                 if true {
                     let idx = self.dirty_count;
                     if let Some(dma_buf) = &mut self.dma_local_buf {
@@ -527,11 +576,11 @@ impl<'worker> ReadCacheMetaWriter {
     pub async fn append_item(&mut self, view: &mut TransCacheView, item: CacheReadSetItem) {
         if self.meta.count + self.dirty_count >= max_read_item_count_in_buf() {
             view.sync_read_buf(&self.trans_key, self.cid, &self.dma_local_buf, self.dirty_count).await;
-            view.alloc_read_buf(&self.trans_key);
+            view.alloc_read_buf(&self.trans_key, self.cid).await;
 
             self.meta = view.get_last_read_meta(&self.trans_key);
 
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             if self.meta.buf.is_remote_buf() && self.dma_local_buf.is_none() {
                 self.dma_local_buf = Some(view.get_local_dma_buf(self.cid));
             }
@@ -549,11 +598,11 @@ impl<'worker> ReadCacheMetaWriter {
     pub fn block_append_item(&mut self, view: &mut TransCacheView, item: CacheReadSetItem) {
         if self.meta.count + self.dirty_count >= max_read_item_count_in_buf() {
             view.block_sync_read_buf(&self.trans_key, self.cid, &self.dma_local_buf, self.dirty_count);
-            view.alloc_read_buf(&self.trans_key);
+            view.block_alloc_read_buf(&self.trans_key);
 
             self.meta = view.get_last_read_meta(&self.trans_key);
 
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             if self.meta.buf.is_remote_buf() && self.dma_local_buf.is_none() {
                 self.dma_local_buf = Some(view.get_local_dma_buf(self.cid));
             }
@@ -597,7 +646,7 @@ impl<'worker> WriteCacheMetaWriter {
                 }
             }
             CacheBuf::RemoteBuf(_) => {
-                #[cfg(feature = "doca_deps")]
+                #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
                 if true {
                     let idx = self.dirty_count;
                     if let Some(dma_buf) = &mut self.dma_local_buf {
@@ -618,11 +667,11 @@ impl<'worker> WriteCacheMetaWriter {
     pub async fn append_item(&mut self, view: &mut TransCacheView, item: CacheWriteSetItem) {
         if self.meta.count + self.dirty_count >= max_write_item_count_in_buf() {
             view.sync_write_buf(&self.trans_key, self.cid, &self.dma_local_buf, self.dirty_count).await;
-            view.alloc_write_buf(&self.trans_key);
+            view.alloc_write_buf(&self.trans_key, self.cid).await;
 
             self.meta = view.get_last_write_meta(&self.trans_key);
 
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             if self.meta.buf.is_remote_buf() && self.dma_local_buf.is_none() {
                 self.dma_local_buf = Some(view.get_local_dma_buf(self.cid));
             }
@@ -641,11 +690,11 @@ impl<'worker> WriteCacheMetaWriter {
     pub fn block_append_item(&mut self, view: &mut TransCacheView, item: CacheWriteSetItem) {
         if self.meta.count + self.dirty_count >= max_write_item_count_in_buf() {
             view.block_sync_write_buf(&self.trans_key, self.cid, &self.dma_local_buf, self.dirty_count);
-            view.alloc_write_buf(&self.trans_key);
+            view.block_alloc_write_buf(&self.trans_key);
 
             self.meta = view.get_last_write_meta(&self.trans_key);
 
-            #[cfg(feature = "doca_deps")]
+            #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
             if self.meta.buf.is_remote_buf() && self.dma_local_buf.is_none() {
                 self.dma_local_buf = Some(view.get_local_dma_buf(self.cid));
             }
@@ -0,0 +1,218 @@
+use std::sync::Arc;
+
+use crate::memstore::memdb::MemDB;
+use crate::memstore::MemStoreValue;
+use crate::framework::scheduler::AsyncScheduler;
+use crate::common::partition::Partitioner;
+use crate::common::cluster::get_cluster;
+
+use super::rwset::RwType;
+use super::remote_helpers::group_commit_ctrl::GroupCommitCtrl;
+use super::remote_helpers::cas_lock_ctrl::CasLockCtrl;
+use super::remote_helpers::one_side_req_ctrl::OneSideReqCtrl;
+use super::occ_remote::OccRemote;
+use super::occ_trans_cache::OccTransCache;
+use super::two_pl::{TwoPl, TplPolicy};
+use super::tictoc::TicToc;
+
+/// Concurrency control of a worker, all the peers must use the same one
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CcProtocol {
+    Occ,
+    TwoPlNoWait,
+    TwoPlWaitDie,
+    TicToc,
+}
+
+impl CcProtocol {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "occ" => Some(Self::Occ),
+            "2pl_no_wait" => Some(Self::TwoPlNoWait),
+            "2pl_wait_die" => Some(Self::TwoPlWaitDie),
+            "tictoc" => Some(Self::TicToc),
+            _ => None,
+        }
+    }
+
+    // the one of the cluster config, else the default of the app
+    pub fn configured(default: Self) -> Self {
+        get_cluster().and_then(|cluster| cluster.get_cc_protocol()).unwrap_or(default)
+    }
+}
+
+// polymorphic manually
+pub enum CcTxn<const MAX_ITEM_SIZE: usize> {
+    Occ(OccRemote<MAX_ITEM_SIZE>),
+    OccCache(OccTransCache<MAX_ITEM_SIZE>),
+    TwoPl(TwoPl<MAX_ITEM_SIZE>),
+    TicToc(TicToc<MAX_ITEM_SIZE>),
+}
+
+impl<const MAX_ITEM_SIZE: usize> CcTxn<MAX_ITEM_SIZE>
+{
+    pub fn new(protocol: CcProtocol, part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        match protocol {
+            CcProtocol::Occ => Self::Occ(OccRemote::new(part_id, tid, cid, memdb, scheduler)),
+            CcProtocol::TwoPlNoWait => Self::TwoPl(TwoPl::new(TplPolicy::NoWait, part_id, tid, cid, memdb, scheduler)),
+            CcProtocol::TwoPlWaitDie => Self::TwoPl(TwoPl::new(TplPolicy::WaitDie, part_id, tid, cid, memdb, scheduler)),
+            CcProtocol::TicToc => Self::TicToc(TicToc::new(part_id, tid, cid, memdb, scheduler)),
+        }
+    }
+
+    // occ with the cached rpc handlers on the remote side
+    pub fn new_cached(protocol: CcProtocol, part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        match protocol {
+            CcProtocol::Occ => Self::OccCache(OccTransCache::new(part_id, tid, cid, memdb, scheduler)),
+            _ => Self::new(protocol, part_id, tid, cid, memdb, scheduler),
+        }
+    }
+
+    // occ commits in the epochs of the group, the others commit one by one
+    pub fn new_grouped(protocol: CcProtocol, part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>, group: &Arc<GroupCommitCtrl>) -> Self {
+        match protocol {
+            CcProtocol::Occ => Self::Occ(OccRemote::new_grouped(part_id, tid, cid, memdb, scheduler, group)),
+            _ => Self::new(protocol, part_id, tid, cid, memdb, scheduler),
+        }
+    }
+
+    // only occ locks by RDMA CAS
+    pub fn set_cas_lock(&mut self, cas_locks: &Arc<CasLockCtrl>) {
+        if let Self::Occ(txn) = self {
+            txn.set_cas_lock(cas_locks);
+        }
+    }
+
+    // only an aborted 2pl txn is run again, with the lock word it aborted with
+    pub fn should_retry(&self) -> bool {
+        match self {
+            Self::TwoPl(txn) => txn.is_aborted(),
+            _ => false,
+        }
+    }
+
+    // only occ reads by RDMA READ
+    pub fn set_one_side_read(&mut self, one_side: &Arc<OneSideReqCtrl>) {
+        if let Self::Occ(txn) = self {
+            txn.set_one_side_read(one_side);
+        }
+    }
+
+    pub fn start(&mut self) {
+        match self {
+            Self::Occ(txn) => txn.start(),
+            Self::OccCache(txn) => txn.start(),
+            Self::TwoPl(txn) => txn.start(),
+            Self::TicToc(txn) => txn.start(),
+        }
+    }
+
+    pub fn read<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        match self {
+            Self::Occ(txn) => txn.read::<T>(table_id, part_id, key),
+            Self::OccCache(txn) => txn.read::<T>(table_id, part_id, key),
+            Self::TwoPl(txn) => txn.read::<T>(table_id, part_id, key),
+            Self::TicToc(txn) => txn.read::<T>(table_id, part_id, key),
+        }
+    }
+
+    pub fn fetch_write<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        match self {
+            Self::Occ(txn) => txn.fetch_write::<T>(table_id, part_id, key),
+            Self::OccCache(txn) => txn.fetch_write::<T>(table_id, part_id, key),
+            Self::TwoPl(txn) => txn.fetch_write::<T>(table_id, part_id, key),
+            Self::TicToc(txn) => txn.fetch_write::<T>(table_id, part_id, key),
+        }
+    }
+
+    pub fn write<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, rwtype: RwType) -> usize {
+        match self {
+            Self::Occ(txn) => txn.write::<T>(table_id, part_id, key, rwtype),
+            Self::OccCache(txn) => txn.write::<T>(table_id, part_id, key, rwtype),
+            Self::TwoPl(txn) => txn.write::<T>(table_id, part_id, key, rwtype),
+            Self::TicToc(txn) => txn.write::<T>(table_id, part_id, key, rwtype),
+        }
+    }
+
+    fn place(&mut self, placement: &dyn Partitioner, key: u64) -> u64 {
+        match self {
+            Self::Occ(txn) => txn.place(placement, key),
+            Self::OccCache(txn) => txn.place(placement, key),
+            Self::TwoPl(txn) => txn.place(placement, key),
+            Self::TicToc(txn) => txn.place(placement, key),
+        }
+    }
+
+    // the partition of the key by the placement
+    #[inline]
+    pub fn read_placed<T: MemStoreValue>(&mut self, table_id: usize, placement: &dyn Partitioner, key: u64) -> usize {
+        let part_id = self.place(placement, key);
+        self.read::<T>(table_id, part_id, key)
+    }
+
+    #[inline]
+    pub fn fetch_write_placed<T: MemStoreValue>(&mut self, table_id: usize, placement: &dyn Partitioner, key: u64) -> usize {
+        let part_id = self.place(placement, key);
+        self.fetch_write::<T>(table_id, part_id, key)
+    }
+
+    #[inline]
+    pub fn write_placed<T: MemStoreValue>(&mut self, table_id: usize, placement: &dyn Partitioner, key: u64, rwtype: RwType) -> usize {
+        let part_id = self.place(placement, key);
+        self.write::<T>(table_id, part_id, key, rwtype)
+    }
+
+    pub fn apply_delta<D: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, merge_id: u32, delta: &D) -> usize {
+        match self {
+            Self::Occ(txn) => txn.apply_delta(table_id, part_id, key, merge_id, delta),
+            Self::OccCache(txn) => txn.apply_delta(table_id, part_id, key, merge_id, delta),
+            Self::TwoPl(txn) => txn.apply_delta(table_id, part_id, key, merge_id, delta),
+            Self::TicToc(txn) => txn.apply_delta(table_id, part_id, key, merge_id, delta),
+        }
+    }
+
+    pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, update: bool, idx: usize) -> &'trans T {
+        match self {
+            Self::Occ(txn) => txn.get_value::<T>(update, idx).await,
+            Self::OccCache(txn) => txn.get_value::<T>(update, idx).await,
+            Self::TwoPl(txn) => txn.get_value::<T>(update, idx).await,
+            Self::TicToc(txn) => txn.get_value::<T>(update, idx).await,
+        }
+    }
+
+    pub fn set_value<T: MemStoreValue>(&mut self, update: bool, idx: usize, value: &T) {
+        match self {
+            Self::Occ(txn) => txn.set_value(update, idx, value),
+            Self::OccCache(txn) => txn.set_value(update, idx, value),
+            Self::TwoPl(txn) => txn.set_value(update, idx, value),
+            Self::TicToc(txn) => txn.set_value(update, idx, value),
+        }
+    }
+
+    pub async fn commit(&mut self) {
+        match self {
+            Self::Occ(txn) => txn.commit().await,
+            Self::OccCache(txn) => txn.commit().await,
+            Self::TwoPl(txn) => txn.commit().await,
+            Self::TicToc(txn) => txn.commit().await,
+        }
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        match self {
+            Self::Occ(txn) => txn.is_aborted(),
+            Self::OccCache(txn) => txn.is_aborted(),
+            Self::TwoPl(txn) => txn.is_aborted(),
+            Self::TicToc(txn) => txn.is_aborted(),
+        }
+    }
+
+    pub fn is_commited(&self) -> bool {
+        match self {
+            Self::Occ(txn) => txn.is_commited(),
+            Self::OccCache(txn) => txn.is_commited(),
+            Self::TwoPl(txn) => txn.is_commited(),
+            Self::TicToc(txn) => txn.is_commited(),
+        }
+    }
+}
@@ -53,7 +53,8 @@ impl CommChanCtrl {
         self.success = true;
     }
 
-    pub fn send_comm_info(&mut self) {
+    // each info waits for room in the channel, the dpu handlers keep replying meanwhile
+    pub async fn send_comm_info(&mut self) {
         let mut info_num = self.msg_set.len();
         if !self.read_infos.is_empty() {
             info_num += 1;
@@ -74,6 +75,7 @@ impl CommChanCtrl {
                 info_cid:     self.cid,
             };
 
+            self.scheduler.yield_until_comm_buf(self.cid, payload + 4).await;
             self.scheduler.comm_chan_append_slice_info(
                 header, 
                 self.read_infos.as_slice(),
@@ -91,6 +93,7 @@ impl CommChanCtrl {
                 info_cid:     self.cid,
             };
 
+            self.scheduler.yield_until_comm_buf(self.cid, payload + 4).await;
             self.scheduler.comm_chan_append_slice_info(
                 header, 
                 self.lock_infos.as_slice(),
@@ -98,6 +101,7 @@ impl CommChanCtrl {
         }
         
         for info_id in &self.msg_set {
+            self.scheduler.yield_until_comm_buf(self.cid, 4).await;
             self.scheduler.comm_chan_append_empty_info(DocaCommHeaderMeta{
                 info_type:    doca_comm_info_type::REQ,
                 info_id:      *info_id,
@@ -9,11 +9,11 @@ use crate::doca_comm_chan::DocaCommHeaderMeta;
 use crate::framework::scheduler;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::framework::rpc::*;
-use crate::memstore::memdb::MemDB;
+use crate::memstore::memdb::{ MemDB, NO_PROC };
 use crate::occ::cache_helpers::trans_cache_view::TransCacheView;
 use crate::occ::cache_helpers::trans_cache_view::TransKey;
 use crate::occ::cache_helpers::{ CacheReadSetItem, CacheWriteSetItem };
-use crate::occ::occ::LockContent;
+use crate::occ::occ::{ release_peer_locks, LockContent };
 use crate::rdma::rcconn::RdmaRcConn;
 use crate::MAIN_ROUTINE_ID;
 use crate::MAX_RESP_SIZE;
@@ -41,6 +41,21 @@ impl DpuRpcProc {
             trans_view: UnsafeCell::new(TransCacheView::new(scheduler)),
         }
     }
+
+    pub fn release_peer_locks(&self, peer_id: u64) {
+        release_peer_locks(&self.memdb, peer_id, LockContent::peer_of);
+    }
+
+    // the occupancy of the cache buffers, the exhausted ones were taken past the limit
+    pub fn report_buf_stats(&self) {
+        let trans_view = unsafe { self.trans_view.get().as_ref().unwrap() };
+        println!("({}) local cache bufs: {:?}", self.tid, trans_view.local_buf_stats());
+
+        #[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
+        if self.scheduler.has_dma_conn() {
+            println!("({}) remote dma bufs: {:?}", self.tid, self.scheduler.dma_remote_buf_stats());
+        }
+    }
 }
 
 // dpu comm conn handler
@@ -132,6 +147,7 @@ impl DpuRpcProc {
                 table_id: item.table_id, 
                 key:      item.key,
                 insert:   (meta.seq == 2),
+                delta:    false,
             })
         }
 
@@ -287,6 +303,51 @@ impl DpuRpcProc {
 
         self.scheduler.comm_chan_append_empty_info(header);
     }
+
+    // from the host once it ran the forwarded procedures, the rows they wrote get a new seq,
+    // the tables of the dpu keep no value
+    pub fn remote_proc_done_info_handler(
+        &self,
+        buf: &DocaCommBuf,
+        info_payload: u32,
+        info_pid: u32,
+        info_tid: u32,
+        info_cid: u32,
+    ) {
+        if info_tid != self.tid {
+            panic!("what the fuck {} , {} ", info_tid, self.tid);
+        }
+
+        let mut forward = copy_info_msg(buf, info_payload);
+        let mut req_wrapper = BatchRpcReqWrapper::new(forward.as_mut_ptr() as _, info_payload as _);
+        let req_header = req_wrapper.get_header();
+        let lock_content = LockContent::new(info_pid as _, self.tid as _, info_cid).to_content();
+
+        let mut keys = Vec::new();
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<ProcReqItem>().clone();
+            let args = unsafe {
+                std::slice::from_raw_parts(
+                    req_wrapper.get_extra_data_const_ptr::<ProcReqItem>(),
+                    req_item.length as _,
+                )
+            };
+
+            keys.clear();
+            if let Some(proc_keys) = self.memdb.get_proc_keys(req_item.proc_id) {
+                proc_keys(args, &mut keys);
+            }
+            // a row shared by two calls is released by the first
+            for (table_id, key) in keys.iter() {
+                if self.memdb.local_get_meta(*table_id, *key).map_or(false, |node| node.lock == lock_content) {
+                    self.memdb.local_upd_val_seq(*table_id, *key, std::ptr::NonNull::<u64>::dangling().as_ptr() as _, 0);
+                    self.memdb.local_unlock(*table_id, *key, lock_content);
+                }
+            }
+
+            req_wrapper.shift_to_next_item::<ProcReqItem>(req_item.length as _);
+        }
+    }
 }
 
 impl DpuRpcProc {
@@ -387,6 +448,7 @@ impl DpuRpcProc {
                     table_id: req_item.table_id, 
                     key:      req_item.key,
                     insert:   false,
+                    delta:    false,
                 });
             }
 
@@ -496,6 +558,7 @@ impl DpuRpcProc {
                 table_id: req_item.table_id, 
                 key:      req_item.key,
                 insert:   (meta.seq == 2),
+                delta:    false,
             });
 
             req_wrapper.shift_to_next_item::<LockReqItem>(0);
@@ -607,6 +670,81 @@ impl DpuRpcProc {
         );
     }
 
+    // the dpu keeps no values, it locks the rows of the procedures and forwards them to its
+    // host, which runs them, replies to the peer and hands the locks back
+    pub fn proc_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        // aligned, the calls not locked are marked in the copy
+        let mut forward = vec![0u64; (size as usize + 7) / 8];
+        unsafe { std::ptr::copy_nonoverlapping(msg, forward.as_mut_ptr() as *mut u8, size as _) };
+
+        let mut req_wrapper = BatchRpcReqWrapper::new(forward.as_mut_ptr() as _, size as _);
+        let req_header = req_wrapper.get_header();
+        let lock_content = LockContent::new(meta.peer_id, self.tid as _, meta.rpc_cid).to_content();
+
+        let mut keys = Vec::new();
+        for _ in 0..req_header.num {
+            let mut req_item = req_wrapper.get_item::<ProcReqItem>().clone();
+            let args = unsafe {
+                std::slice::from_raw_parts(
+                    req_wrapper.get_extra_data_const_ptr::<ProcReqItem>(),
+                    req_item.length as _,
+                )
+            };
+
+            keys.clear();
+            let locked = match self.memdb.get_proc_keys(req_item.proc_id) {
+                Some(proc_keys) => proc_keys(args, &mut keys) && self.lock_proc_keys(&keys, lock_content),
+                None => false,
+            };
+            if !locked {
+                req_item.proc_id = NO_PROC;
+                req_wrapper.set_item(req_item.clone());
+            }
+
+            req_wrapper.shift_to_next_item::<ProcReqItem>(req_item.length as _);
+        }
+
+        let header = DocaCommHeaderMeta{
+            info_type: doca_comm_info_type::REQ as _,
+            info_id:   doca_comm_info_id::REMOTE_PROC_INFO as _,
+            info_payload: size as _,
+            info_pid: meta.peer_id as _,
+            info_tid: self.tid as _,
+            info_cid: meta.rpc_cid as _,
+        };
+
+        let forward = unsafe { std::slice::from_raw_parts(forward.as_ptr() as *const u8, size as _) };
+        self.scheduler.comm_chan_append_slice_info(header, forward);
+    }
+
+    // all or none, the rows an earlier call of the batch holds are shared with it
+    fn lock_proc_keys(&self, keys: &[(usize, u64)], lock_content: u64) -> bool {
+        let mut taken = Vec::new();
+        for (table_id, key) in keys.iter() {
+            let held = self.memdb.local_get_meta(*table_id, *key).map_or(false, |node| node.lock == lock_content);
+            if held {
+                continue;
+            }
+
+            let locked = self.memdb.local_lock(*table_id, *key, lock_content)
+                .map_or(false, |node| node.lock == lock_content);
+            if !locked {
+                for (table_id, key) in taken.iter() {
+                    self.memdb.local_unlock(*table_id, *key, lock_content);
+                }
+                return false;
+            }
+            taken.push((*table_id, *key));
+        }
+        true
+    }
+
     pub fn abort_cache_rpc_handler(
         &self,
         src_conn: &mut RdmaRcConn,
@@ -653,4 +791,4 @@ impl DpuRpcProc {
             meta.peer_tid
         );
     }
-}
\ No newline at end of file
+}
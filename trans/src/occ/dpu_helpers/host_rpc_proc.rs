@@ -6,12 +6,13 @@ use crate::doca_comm_chan::DocaCommHeaderMeta;
 use crate::framework::scheduler;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::framework::rpc::*;
-use crate::memstore::memdb::ValueDB;
+use crate::memstore::memdb::{ ValueDB, NO_PROC };
 use crate::occ::occ::LockContent;
 use crate::rdma::rcconn::RdmaRcConn;
 use crate::MAIN_ROUTINE_ID;
 use crate::MAX_RESP_SIZE;
 
+use super::{ copy_info_msg, doca_comm_info_id };
 use super::super::remote_helpers::*;
 use super::super::remote_helpers::batch_rpc_msg_wrapper::{ BatchRpcReqWrapper, BatchRpcRespWrapper };
 
@@ -136,6 +137,90 @@ impl HostRpcProc {
             self.tid as _,
         );
     }
+
+    // the procedures of a peer, under the locks the dpu took, the calls it could not lock
+    // are aborted, then the dpu gets the calls back to release the locks
+    pub fn remote_proc_info_handler(
+        &self,
+        buf: &DocaCommBuf,
+        info_payload: u32,
+        info_pid: u32,
+        info_tid: u32,
+        info_cid: u32,
+    ) {
+        if info_tid != self.tid {
+            panic!("what the fuck {} , {} ", info_tid, self.tid);
+        }
+
+        let mut forward = copy_info_msg(buf, info_payload);
+        let mut req_wrapper = BatchRpcReqWrapper::new(forward.as_mut_ptr() as _, info_payload as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<ProcReqItem>().clone();
+
+            let args = unsafe {
+                std::slice::from_raw_parts(
+                    req_wrapper.get_extra_data_const_ptr::<ProcReqItem>(),
+                    req_item.length as _,
+                )
+            };
+
+            // the output can take up the rest of the reply
+            let out_cap = (MAX_RESP_SIZE - 4).saturating_sub(resp_wrapper.get_off() + std::mem::size_of::<ProcRespItem>());
+            let out = unsafe {
+                std::slice::from_raw_parts_mut(
+                    resp_wrapper.get_extra_data_raw_ptr::<ProcRespItem>(),
+                    out_cap,
+                )
+            };
+
+            let ret = match self.valuedb.get_procedure(req_item.proc_id) {
+                Some(procedure) if req_item.proc_id != NO_PROC => procedure(&self.valuedb, args, out),
+                _ => None,
+            };
+            let data_len = ret.unwrap_or(0);
+
+            resp_wrapper.set_item(ProcRespItem{
+                idx:     req_item.idx,
+                success: ret.is_some(),
+                length:  data_len as _,
+            });
+
+            req_wrapper.shift_to_next_item::<ProcReqItem>(req_item.length as _);
+            resp_wrapper.shift_to_next_item::<ProcRespItem>(data_len);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: info_cid,
+            num: req_header.num,
+        });
+
+        let header = DocaCommHeaderMeta{
+            info_type: doca_comm_info_type::REQ as _,
+            info_id:   doca_comm_info_id::REMOTE_PROC_DONE_INFO as _,
+            info_payload: info_payload as _,
+            info_pid: info_pid as _,
+            info_tid: self.tid as _,
+            info_cid: info_cid as _,
+        };
+        let forward = unsafe { std::slice::from_raw_parts(forward.as_ptr() as *const u8, info_payload as _) };
+        self.scheduler.comm_chan_append_slice_info(header, forward);
+
+        self.scheduler.send_req(
+            resp_buf, 
+            occ_rpc_id::PROC_RPC, 
+            resp_wrapper.get_off() as _, 
+            info_cid, 
+            rpc_msg_type::RESP, 
+            info_pid as _, 
+            self.tid as _,
+        );
+    }
 }
 
 impl HostRpcProc {
@@ -191,4 +276,42 @@ impl HostRpcProc {
             meta.peer_tid
         );
     }
-}
\ No newline at end of file
+
+    pub fn delta_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<DeltaReqItem>();
+            let data_len = req_item.length;
+
+            self.valuedb.local_apply_delta(
+                req_item.table_id, 
+                req_item.key, 
+                req_item.merge_id, 
+                req_wrapper.get_extra_data_const_ptr::<DeltaReqItem>(), 
+                data_len,
+            );
+
+            req_wrapper.shift_to_next_item::<DeltaReqItem>(data_len as _);
+        }
+
+        self.scheduler.send_reply(
+            src_conn, 
+            resp_buf, 
+            occ_rpc_id::DELTA_RPC, 
+            0, 
+            meta.rpc_cid, 
+            meta.peer_id, 
+            meta.peer_tid
+        );
+    }
+}
@@ -3,6 +3,8 @@ pub mod comm_chan_ctrl;
 pub mod dpu_rpc_proc;
 pub mod host_rpc_proc;
 
+use crate::doca_comm_chan::comm_buf::DocaCommBuf;
+
 #[repr(C)]
 #[derive(Clone)]
 pub struct DocaCommReply {
@@ -19,6 +21,17 @@ pub mod doca_comm_info_id {
     pub const LOCAL_ABORT_INFO:       Type = 4;
     pub const REMOTE_READ_INFO:       Type = 5;
     pub const REMOTE_FETCHWRITE_INFO: Type = 6;
+    pub const REMOTE_PROC_INFO:       Type = 7;
+    pub const REMOTE_PROC_DONE_INFO:  Type = 8;
+}
+
+// the raw msg of an info, copied out aligned
+pub(crate) fn copy_info_msg(buf: &DocaCommBuf, info_payload: u32) -> Vec<u64> {
+    let mut msg = vec![0u64; (info_payload as usize + 7) / 8];
+    unsafe {
+        std::ptr::copy_nonoverlapping(buf.get_item::<u8>(0) as *const u8, msg.as_mut_ptr() as *mut u8, info_payload as _);
+    }
+    msg
 }
 
 #[repr(C)]
@@ -3,24 +3,35 @@ mod occ;
 mod remote_helpers;
 mod cache_helpers;
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 mod dpu_helpers;
 
 pub mod occ_local;
 pub mod occ_remote;
 pub mod occ_trans_cache;
 pub mod occ_hybrid;
+pub mod occ_readonly;
+pub mod occ_proc;
+pub mod two_pl;
+pub mod tictoc;
+pub mod cc_txn;
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub mod occ_host;
 
 pub use remote_helpers::batch_rpc_proc::BatchRpcProc;
 pub use remote_helpers::occ_rpc_id;
+pub use remote_helpers::group_commit_ctrl::GroupCommitCtrl;
+pub use remote_helpers::remote_addr_cache::RemoteAddrCache;
+pub use remote_helpers::cas_lock_ctrl::CasLockCtrl;
+pub use remote_helpers::one_side_req_ctrl::OneSideReqCtrl;
+pub use remote_helpers::offload_rpc_ctrl::RpcMigrator;
 pub use rwset::RwType;
+pub use cc_txn::{CcProtocol, CcTxn};
 
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub use dpu_helpers::dpu_rpc_proc::DpuRpcProc;
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub use dpu_helpers::host_rpc_proc::HostRpcProc;
-#[cfg(feature = "doca_deps")]
+#[cfg(any(feature = "doca_deps", feature = "doca_emu"))]
 pub use dpu_helpers::doca_comm_info_id;
\ No newline at end of file
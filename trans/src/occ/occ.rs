@@ -1,6 +1,7 @@
 use byte_struct::*;
 
-use crate::memstore::MemStoreValue;
+use crate::memstore::memdb::MemDB;
+use crate::memstore::{is_delta_lock, MemNodeMeta, MemStoreValue, ERASED_LOCK};
 
 bitfields!(
     pub LockContent: u64 {
@@ -27,6 +28,31 @@ impl LockContent {
     pub fn from_content(content: u64) -> Self {
         Self::from_raw(content)
     }
+
+    pub fn peer_of(content: u64) -> Option<u64> {
+        Some(Self::from_content(content).peer_id)
+    }
+}
+
+// the rows still locked by the coordinators of a peer whose connection broke,
+// its txns will neither commit nor abort them.
+// peer_of decodes the lock words of the protocol, None for a word of no one peer,
+// the delta words count their holders and are released from the ledger of the memdb
+pub fn release_peer_locks(memdb: &MemDB, peer_id: u64, peer_of: fn(u64) -> Option<u64>) {
+    for table_id in 0..memdb.get_table_num() {
+        let mut held = Vec::new();
+        memdb.local_scan_meta(table_id, &mut |key: u64, meta: MemNodeMeta| {
+            if meta.lock != 0 && meta.lock != ERASED_LOCK && !is_delta_lock(meta.lock) && peer_of(meta.lock) == Some(peer_id) {
+                held.push((key, meta.lock));
+            }
+        });
+
+        for (key, lock) in held {
+            memdb.local_try_unlock(table_id, key, lock);
+        }
+    }
+
+    memdb.release_peer_deltas(peer_id, peer_of);
 }
 
 // How to fix this type of dynamic dispatch elegently?
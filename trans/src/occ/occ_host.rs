@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::doca_comm_chan::comm_buf::DocaCommBuf;
 use crate::memstore::memdb::ValueDB;
 use crate::memstore::MemStoreValue;
+use crate::memstore::{NO_COMMIT_TS, NO_MERGE};
 use crate::framework::scheduler::AsyncScheduler;
 use crate::MAX_RESP_SIZE;
 
@@ -178,8 +179,11 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
                 // remote
                 match item.rwtype {
                     RwType::ERASE => {
+                        // the host keeps no clock, the participant picks the ts
                         let remote_req = CommitCacheReqItem{
-                            length:   0,
+                            commit_ts: NO_COMMIT_TS,
+                            length:    0,
+                            merge_id:  NO_MERGE,
                         };
 
                         self.batch_rpc.append_req(
@@ -192,7 +196,9 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
                     RwType::INSERT | RwType::UPDATE => {
                         let length = item.value.get_length();
                         let remote_req = CommitCacheReqItem{
-                            length:   item.value.get_length(),
+                            commit_ts: NO_COMMIT_TS,
+                            length:    item.value.get_length(),
+                            merge_id:  NO_MERGE,
                         };
 
                         self.batch_rpc.append_req_with_data(
@@ -307,7 +313,13 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
     }
 
     fn process_batch_rpc_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
 
         for i in 0..resp_num {
             let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
@@ -328,7 +340,13 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
     }
     
     fn process_batch_rpc_reduce_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
         for _ in 0..resp_num {
             let reduce_resp = unsafe { (resp_buf as *const BatchRpcReduceResp).as_ref().unwrap() };
             if !reduce_resp.success {
@@ -394,6 +412,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
                 let remote_req = LockReqItem{
                     table_id: item.table_id,
                     key:      item.key,
+                    delta:    false,
                 };
         
                 self.batch_rpc.append_req::<LockReqItem>(
@@ -408,7 +427,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
         // send remote
         self.batch_rpc.send_batch_reqs();
         // send dpu
-        self.comm_chan.send_comm_info();
+        self.comm_chan.send_comm_info().await;
 
         // wait dpu
         self.comm_chan.wait_until_done().await;
@@ -446,7 +465,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
         // send remote
         self.batch_rpc.send_batch_reqs();
         // send dpu
-        self.comm_chan.send_comm_info();
+        self.comm_chan.send_comm_info().await;
 
         // wait dpu
         self.comm_chan.wait_until_done().await;
@@ -479,7 +498,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
         self.release_on(false);
 
         self.batch_rpc.send_batch_reqs();
-        self.comm_chan.send_comm_info();
+        self.comm_chan.send_comm_info().await;
         self.comm_chan.wait_until_done().await;
         self.batch_rpc.wait_until_done().await;
     }
@@ -491,7 +510,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHost<MAX_ITEM_SIZE>
         self.abort_on(false);
 
         self.batch_rpc.send_batch_reqs();
-        self.comm_chan.send_comm_info();
+        self.comm_chan.send_comm_info().await;
         self.comm_chan.wait_until_done().await;
         self.batch_rpc.wait_until_done().await;
     }
@@ -547,7 +566,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHost< MAX_ITEM_SIZE>
     pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, update: bool, idx: usize) -> &'trans T {
         // TODO: more careful check
         self.batch_rpc.send_batch_reqs();
-        self.comm_chan.send_comm_info();
+        self.comm_chan.send_comm_info().await;
 
         self.comm_chan.wait_until_done().await;
         self.process_comm_chan_resp();
@@ -4,6 +4,7 @@ use crate::memstore::memdb::MemDB;
 use crate::memstore::MemStoreValue;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::MAX_RESP_SIZE;
+use crate::common::cluster::dpu_peer_id;
 
 use super::occ::{LockContent, MemStoreItemEnum, OccStatus};
 use super::rwset::{RwSet, RwItem, RwType};
@@ -18,7 +19,7 @@ fn remote_host_peer_id(part_id: u64) -> u64 {
 
 #[inline]
 fn remote_dpu_peer_id(part_id: u64) -> u64 {
-    part_id + 100
+    dpu_peer_id(part_id).expect("the cluster config gives the partition no dpu")
 }
 
 pub struct OccHybrid<const MAX_ITEM_SIZE: usize>
@@ -164,7 +165,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
 impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
 {
     #[inline]
-    fn commit_writes_on(&mut self, update: bool) {
+    fn commit_writes_on(&mut self, update: bool, commit_ts: u64) {
         let ref_set = if update {
             &mut self.updateset
         } else {
@@ -182,7 +183,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
                     }
                     RwType::INSERT | RwType::UPDATE => {
                         let raw = item.value.get_raw_ptr();
-                        self.memdb.local_upd_val_seq(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32);
+                        self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
                     }
                     _ => {}
                 }
@@ -191,9 +192,10 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
                 match item.rwtype {
                     RwType::ERASE => {
                         let remote_req = CommitReqItem{
-                            table_id: item.table_id,
-                            key:      item.key,
-                            length:   0,
+                            table_id:  item.table_id,
+                            key:       item.key,
+                            commit_ts: commit_ts,
+                            length:    0,
                         };
 
                         self.batch_rpc.append_req(
@@ -206,9 +208,10 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
                     RwType::INSERT | RwType::UPDATE => {
                         let length = item.value.get_length();
                         let remote_req = CommitReqItem{
-                            table_id: item.table_id,
-                            key:      item.key,
-                            length:   item.value.get_length(),
+                            table_id:  item.table_id,
+                            key:       item.key,
+                            commit_ts: commit_ts,
+                            length:    item.value.get_length(),
                         };
 
                         self.batch_rpc.append_req_with_data(
@@ -331,7 +334,13 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
     }
 
     fn process_batch_rpc_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
 
         for i in 0..resp_num {
             let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
@@ -350,7 +359,13 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
     }
     
     fn process_batch_rpc_reduce_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
         for _ in 0..resp_num {
             let reduce_resp = unsafe { (resp_buf as *const BatchRpcReduceResp).as_ref().unwrap() };
             if !reduce_resp.success {
@@ -383,6 +398,7 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
                 let remote_req = LockReqItem{
                     table_id: item.table_id,
                     key:      item.key,
+                    delta:    false,
                 };
         
                 self.batch_rpc.append_req::<LockReqItem>(
@@ -436,8 +452,10 @@ impl<const MAX_ITEM_SIZE: usize> OccHybrid<MAX_ITEM_SIZE>
     
     async fn commit_writes(&mut self) {
         self.batch_rpc.restart_batch();
-        self.commit_writes_on(true);
-        self.commit_writes_on(false);
+        let commit_ts = self.memdb.alloc_commit_ts();
+        self.commit_writes_on(true, commit_ts);
+        self.commit_writes_on(false, commit_ts);
+        self.memdb.finish_commit_ts(commit_ts);
 
         self.batch_rpc.send_batch_reqs();
         self.batch_rpc.wait_until_done().await;
@@ -3,6 +3,7 @@ use std::sync::Arc;
 use crate::memstore::memdb::MemDB;
 use crate::memstore::MemStoreValue;
 use crate::memstore::MemNodeMeta;
+use crate::memstore::is_delta_lock;
 
 use super::occ::{OccStatus, MemStoreItemEnum, LockContent};
 use super::rwset::{RwType, RwItem, RwSet};
@@ -10,6 +11,7 @@ use super::rwset::{RwType, RwItem, RwSet};
 pub struct OccLocal<const MAX_ITEM_SIZE: usize> 
 {
     status:    OccStatus,
+    tid:       u32,
     cid:       u32,
     memdb:     Arc<MemDB>,
     readset:   RwSet<MAX_ITEM_SIZE>,
@@ -20,8 +22,14 @@ pub struct OccLocal<const MAX_ITEM_SIZE: usize>
 impl<const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE> 
 {
     pub fn new(cid: u32, memdb: &Arc<MemDB>) -> Self {
+        Self::new_on(0, cid, memdb)
+    }
+
+    // txns of different threads on the same node must not share the lock content
+    pub fn new_on(tid: u32, cid: u32, memdb: &Arc<MemDB>) -> Self {
         Self {
             status:    OccStatus::OccUnint,
+            tid:       tid,
             cid:       cid,
             memdb:     memdb.clone(),
             readset:   RwSet::new(),
@@ -38,14 +46,24 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
             return;
         }
 
-        let lock_content = LockContent::new(0, 0, self.cid);
+        let lock_content = LockContent::new(0, self.tid, self.cid);
 
         for i in 0..self.writeset.get_len() {
             let item = self.writeset.bucket(i);
 
-            let meta = self.memdb.local_lock(item.table_id, item.key, lock_content.to_content()).unwrap();
+            let locked = match item.rwtype {
+                // the deltas of the txns share the lock
+                RwType::DELTA(_) => {
+                    let meta = self.memdb.local_lock_delta(item.table_id, item.key, lock_content.to_content()).unwrap();
+                    is_delta_lock(meta.lock)
+                }
+                _ => {
+                    let meta = self.memdb.local_lock(item.table_id, item.key, lock_content.to_content()).unwrap();
+                    meta.lock == lock_content.to_content()
+                }
+            };
 
-            if meta.lock != lock_content.to_content() {
+            if !locked {
                 self.status = OccStatus::OccMustabort;
                 break;
             }
@@ -77,6 +95,8 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
             return;
         }
 
+        let commit_ts = self.memdb.alloc_commit_ts();
+
         for i in 0..self.updateset.get_len() {
             let item = self.updateset.bucket(i);
 
@@ -86,7 +106,7 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
                 }
                 RwType::INSERT | RwType::UPDATE => {
                     let raw = item.value.get_raw_ptr();
-                    self.memdb.local_upd_val_seq(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32);
+                    self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
                 }
                 _ => {}
             }
@@ -102,11 +122,17 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
                 }
                 RwType::INSERT | RwType::UPDATE => {
                     let raw = item.value.get_raw_ptr();
-                    self.memdb.local_upd_val_seq(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32);
+                    self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
+                }
+                RwType::DELTA(merge_id) => {
+                    let raw = item.value.get_raw_ptr();
+                    self.memdb.local_apply_delta(item.table_id, item.key, merge_id, raw, item.value.get_length(), commit_ts);
                 }
                 _ => {}
             }
         }
+
+        self.memdb.finish_commit_ts(commit_ts);
     }
 
     fn release(&mut self) {
@@ -114,7 +140,7 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
             return;
         }
 
-        let lock_content = LockContent::new(0, 0, self.cid);
+        let lock_content = LockContent::new(0, self.tid, self.cid);
 
         for i in 0..self.updateset.get_len() {
             let item = self.updateset.bucket(i);
@@ -123,7 +149,10 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
 
         for i in 0..self.writeset.get_len() {
             let item = self.writeset.bucket(i);
-            self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+            match item.rwtype {
+                RwType::DELTA(_) => self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content()),
+                _ => self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content()),
+            }
         }
     }
 
@@ -132,7 +161,7 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
             return;
         }
 
-        let lock_content = LockContent::new(0, 0, self.cid);
+        let lock_content = LockContent::new(0, self.tid, self.cid);
 
         for i in 0..self.updateset.get_len() {
             let item = self.updateset.bucket(i);
@@ -146,6 +175,9 @@ impl<'trans, const MAX_ITEM_SIZE: usize> OccLocal<MAX_ITEM_SIZE>
                 RwType::ERASE | RwType::UPDATE => {
                     self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
                 }
+                RwType::DELTA(_) => {
+                    self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                }
                 RwType::INSERT => {
                     self.memdb.local_erase(item.table_id, item.key);
                 }
@@ -187,7 +219,7 @@ impl< const MAX_ITEM_SIZE: usize> OccLocal< MAX_ITEM_SIZE>
         // local
         assert_eq!(part_id, 0);
 
-        let lock_content = LockContent::new(0, 0, self.cid);
+        let lock_content = LockContent::new(0, self.tid, self.cid);
 
         let mut value = T::default();
         let ptr = &mut value as *mut T as *mut u8;
@@ -215,7 +247,7 @@ impl< const MAX_ITEM_SIZE: usize> OccLocal< MAX_ITEM_SIZE>
         // local
         assert_eq!(part_id, 0);
 
-        let lock_content = LockContent::new(0, 0, self.cid);
+        let lock_content = LockContent::new(0, self.tid, self.cid);
 
         let value = T::default();
 
@@ -233,6 +265,14 @@ impl< const MAX_ITEM_SIZE: usize> OccLocal< MAX_ITEM_SIZE>
         return self.writeset.get_len() - 1;
     }
 
+    // blind commutative update, the delta is merged at commit
+    pub fn apply_delta<D: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, merge_id: u32, delta: &D) -> usize {
+        let write_idx = self.write::<D>(table_id, part_id, key, RwType::DELTA(merge_id));
+        self.set_value(false, write_idx, delta);
+
+        write_idx
+    }
+
     pub fn get_value<T: MemStoreValue>(&mut self, update: bool, idx: usize) -> &T {
         if update {
             return self.updateset.bucket(idx).value.get_inner();
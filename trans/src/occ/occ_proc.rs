@@ -0,0 +1,195 @@
+use std::sync::Arc;
+
+use crate::memstore::memdb::MemDB;
+use crate::memstore::MemStoreValue;
+use crate::framework::scheduler::AsyncScheduler;
+use crate::MAX_RESP_SIZE;
+
+use super::occ::{MemStoreItemEnum, OccStatus};
+use super::remote_helpers::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+use super::remote_helpers::batch_rpc_ctrl::BatchRpcCtrl;
+use super::remote_helpers::*;
+
+struct ProcCall<const MAX_OUT_SIZE: usize> {
+    status: OccStatus,
+    output: MemStoreItemEnum<MAX_OUT_SIZE>,
+}
+
+/// Invokes stored procedures registered in the memdb of the owners.
+/// Each call is a whole single-partition txn, so the coordinator never sees
+/// the values, calls to remote partitions are batched into one PROC_RPC per peer.
+pub struct OccProc<const MAX_OUT_SIZE: usize>
+{
+    part_id:   u64,
+    tid:       u32,
+    cid:       u32,
+    memdb:     Arc<MemDB>,
+    batch_rpc: BatchRpcCtrl,
+    calls:     Vec<ProcCall<MAX_OUT_SIZE>>,
+}
+
+impl<const MAX_OUT_SIZE: usize> OccProc<MAX_OUT_SIZE>
+{
+    pub fn new(part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        Self {
+            part_id:   part_id,
+            tid:       tid,
+            cid:       cid,
+            memdb:     memdb.clone(),
+            batch_rpc: BatchRpcCtrl::new(scheduler, cid),
+            calls:     Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn local_call(&mut self, proc_id: u32, args: &[u8]) -> usize {
+        let call_idx = self.calls.len();
+        let mut out = [0u8; MAX_OUT_SIZE];
+
+        // runs to the end without yielding, as the remote ones do in the handler
+        let ret = match self.memdb.get_procedure(proc_id) {
+            Some(procedure) => procedure(&self.memdb, self.tid, args, &mut out),
+            None => {
+                println!("the procedure does not exists!");
+                None
+            }
+        };
+
+        let mut output = MemStoreItemEnum::default();
+        let status = match ret {
+            Some(length) => {
+                output.set_raw_data(out.as_ptr(), length as _);
+                OccStatus::OccCommited
+            }
+            None => OccStatus::OccAborted,
+        };
+
+        self.calls.push(ProcCall {
+            status: status,
+            output: output,
+        });
+
+        call_idx
+    }
+
+    #[inline]
+    fn remote_call_rpc(&mut self, part_id: u64, proc_id: u32, args: &[u8]) -> usize {
+        let call_idx = self.calls.len();
+        let remote_req = ProcReqItem{
+            proc_id: proc_id,
+            length:  args.len() as _,
+            idx:     call_idx,
+        };
+
+        self.batch_rpc.append_req_with_data(
+            &remote_req,
+            args.as_ptr(),
+            args.len(),
+            part_id,
+            0,
+            occ_rpc_id::PROC_RPC,
+        );
+
+        // pending
+        self.calls.push(ProcCall {
+            status: OccStatus::OccInprogress,
+            output: MemStoreItemEnum::default(),
+        });
+
+        call_idx
+    }
+
+    fn abort_pending(&mut self) {
+        for call in self.calls.iter_mut().filter(|call| call.status == OccStatus::OccInprogress) {
+            call.status = OccStatus::OccAborted;
+        }
+    }
+
+    fn process_batch_rpc_resp(&mut self) {
+        // the calls not replied are aborted
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.abort_pending();
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            if header.cid != self.cid {
+                panic!("got strange resp ! me:{}, get:{}", self.cid, header.cid);
+            }
+
+            for _ in 0..header.num {
+                let item = wrapper.get_item::<ProcRespItem>().clone();
+                let raw_data = wrapper.get_extra_data_const_ptr::<ProcRespItem>();
+
+                // a malformed reply, the items after it can not be read either
+                if item.length as usize > MAX_OUT_SIZE || item.idx >= self.calls.len() {
+                    self.abort_pending();
+                    return;
+                }
+
+                let call = &mut self.calls[item.idx];
+                if item.success {
+                    call.output.set_raw_data(raw_data, item.length);
+                    call.status = OccStatus::OccCommited;
+                } else {
+                    call.status = OccStatus::OccAborted;
+                }
+
+                wrapper.shift_to_next_item::<ProcRespItem>(item.length as _);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+}
+
+impl<const MAX_OUT_SIZE: usize> OccProc<MAX_OUT_SIZE>
+{
+    pub fn start(&mut self) {
+        self.batch_rpc.restart_batch();
+        self.calls.clear();
+    }
+
+    // args are shipped as raw bytes, the procedure reads them unaligned
+    pub fn call<A: MemStoreValue>(&mut self, part_id: u64, proc_id: u32, args: &A) -> usize {
+        let args = unsafe {
+            std::slice::from_raw_parts(args as *const A as *const u8, std::mem::size_of::<A>())
+        };
+
+        if part_id == self.part_id {
+            // local
+            self.local_call(proc_id, args)
+        } else {
+            // remote
+            self.remote_call_rpc(part_id, proc_id, args)
+        }
+    }
+
+    pub async fn wait(&mut self) {
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+
+        self.process_batch_rpc_resp();
+        self.batch_rpc.restart_batch();
+    }
+
+    pub fn get_output<'trans, T: MemStoreValue + 'trans>(&self, idx: usize) -> &'trans T {
+        self.calls[idx].output.get_inner()
+    }
+
+    #[inline]
+    pub fn is_aborted(&self, idx: usize) -> bool {
+        self.calls[idx].status.eq(&OccStatus::OccAborted)
+    }
+
+    #[inline]
+    pub fn is_commited(&self, idx: usize) -> bool {
+        self.calls[idx].status.eq(&OccStatus::OccCommited)
+    }
+}
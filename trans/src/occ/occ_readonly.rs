@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use crate::memstore::memdb::MemDB;
+use crate::memstore::MemStoreValue;
+use crate::framework::scheduler::AsyncScheduler;
+use crate::MAX_RESP_SIZE;
+
+use super::occ::{MemStoreItemEnum, OccStatus};
+use super::rwset::{RwSet, RwItem, RwType};
+use super::remote_helpers::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+use super::remote_helpers::batch_rpc_ctrl::BatchRpcCtrl;
+use super::remote_helpers::*;
+
+/// Read-only txn served by multi-version tables.
+/// No locks and no validation, it only aborts when a needed version has been collected.
+/// The snapshot is taken once at start and carried in every remote read.
+pub struct OccReadOnly<const MAX_ITEM_SIZE: usize>
+{
+    status:    OccStatus,
+    part_id:   u64,
+    cid:       u32,
+    memdb:     Arc<MemDB>,
+    batch_rpc: BatchRpcCtrl,
+    readset:   RwSet<MAX_ITEM_SIZE>,
+    read_ts:   u64,
+}
+
+impl<const MAX_ITEM_SIZE: usize> OccReadOnly<MAX_ITEM_SIZE>
+{
+    pub fn new(part_id: u64, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        Self {
+            status:    OccStatus::OccUnint,
+            part_id:   part_id,
+            cid:       cid,
+            memdb:     memdb.clone(),
+            batch_rpc: BatchRpcCtrl::new(scheduler, cid),
+            readset:   RwSet::new(),
+            read_ts:   0,
+        }
+    }
+
+    #[inline]
+    fn local_read<T: MemStoreValue>(&mut self, table_id: usize, key: u64) -> usize {
+        let read_idx = self.readset.get_len();
+        let mut value = T::default();
+        let ptr = &mut value as *mut T as *mut u8;
+        let len = std::mem::size_of::<T>();
+
+        let seq = match self.memdb.local_get_snapshot(table_id, key, ptr, len as _, self.read_ts) {
+            Some(meta) => meta.seq,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                0
+            }
+        };
+
+        let item = RwItem::new(
+            table_id,
+            self.part_id,
+            RwType::READ,
+            key,
+            MemStoreItemEnum::from_raw(value),
+            seq
+        );
+
+        self.readset.push(item);
+        return read_idx;
+    }
+
+    #[inline]
+    fn remote_read_rpc(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        let read_idx = self.readset.get_len();
+        let remote_req = SnapshotReadReqItem{
+            table_id: table_id,
+            key:      key,
+            read_idx: read_idx,
+            read_ts:  self.read_ts,
+        };
+        self.batch_rpc.append_req::<SnapshotReadReqItem>(
+            &remote_req,
+            part_id,
+            0,
+            occ_rpc_id::SNAPSHOT_READ_RPC
+        );
+
+        // pending
+        let item = RwItem::new(
+            table_id,
+            part_id,
+            RwType::READ,
+            key,
+            MemStoreItemEnum::default(),
+            0
+        );
+        self.readset.push(item);
+
+        read_idx
+    }
+
+    fn process_batch_rpc_resp(&mut self) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            for _ in 0..header.num {
+                let item = wrapper.get_item::<ReadRespItem>();
+                let raw_data = wrapper.get_extra_data_const_ptr::<ReadRespItem>();
+
+                if item.length == 0 {
+                    self.status = OccStatus::OccMustabort;
+                }
+
+                let bucket = self.readset.bucket(item.read_idx);
+                bucket.seq = item.seq;
+                bucket.value.set_raw_data(raw_data, item.length as _);
+
+                wrapper.shift_to_next_item::<ReadRespItem>(item.length);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+}
+
+impl<const MAX_ITEM_SIZE: usize> OccReadOnly<MAX_ITEM_SIZE>
+{
+    pub fn start(&mut self) {
+        self.batch_rpc.restart_batch();
+        self.read_ts = self.memdb.begin_snapshot();
+        self.status = OccStatus::OccInprogress;
+    }
+
+    pub fn read<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        if part_id == self.part_id {
+            // local
+            self.local_read::<T>(table_id, key)
+        } else {
+            // remote
+            self.remote_read_rpc(table_id, part_id, key)
+        }
+    }
+
+    pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, idx: usize) -> &'trans T {
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+
+        self.process_batch_rpc_resp();
+        self.batch_rpc.restart_batch();
+
+        return self.readset.bucket(idx).value.get_inner();
+    }
+
+    pub async fn commit(&mut self) {
+        self.memdb.end_snapshot(self.read_ts);
+
+        if self.status.eq(&OccStatus::OccMustabort) {
+            self.status = OccStatus::OccAborted;
+        } else {
+            self.status = OccStatus::OccCommited;
+        }
+    }
+
+    pub async fn abort(&mut self) {
+        self.memdb.end_snapshot(self.read_ts);
+
+        self.status = OccStatus::OccAborted;
+    }
+
+    #[inline]
+    pub fn is_aborted(&self) -> bool {
+        self.status.eq(&OccStatus::OccAborted)
+    }
+
+    #[inline]
+    pub fn is_commited(&self) -> bool {
+        self.status.eq(&OccStatus::OccCommited)
+    }
+}
@@ -1,14 +1,22 @@
 use std::sync::Arc;
+use std::collections::HashMap;
 
 use crate::memstore::memdb::MemDB;
 use crate::memstore::MemStoreValue;
+use crate::memstore::{is_delta_lock, NO_MERGE};
 use crate::framework::scheduler::AsyncScheduler;
+use crate::common::partition::Partitioner;
+use crate::common::offload::OffloadPin;
 use crate::MAX_RESP_SIZE;
 
 use super::occ::{LockContent, MemStoreItemEnum, OccStatus};
 use super::rwset::{RwSet, RwItem, RwType};
 use super::remote_helpers::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
 use super::remote_helpers::batch_rpc_ctrl::BatchRpcCtrl;
+use super::remote_helpers::group_commit_ctrl::GroupCommitCtrl;
+use super::remote_helpers::cas_lock_ctrl::CasLockCtrl;
+use super::remote_helpers::one_side_req_ctrl::OneSideReqCtrl;
+use super::remote_helpers::remote_addr_cache::RemoteAddrCache;
 use super::remote_helpers::*;
 
 pub struct OccRemote<const MAX_ITEM_SIZE: usize>
@@ -19,9 +27,17 @@ pub struct OccRemote<const MAX_ITEM_SIZE: usize>
     cid:       u32,
     memdb:     Arc<MemDB>,
     batch_rpc: BatchRpcCtrl,
+    group:     Option<Arc<GroupCommitCtrl>>,
+    cas_locks: Option<Arc<CasLockCtrl>>,
+    one_side:  Option<Arc<OneSideReqCtrl>>,
+    addrs:     Option<Arc<RemoteAddrCache>>,
+    // (read_idx, slot) of the one-sided reads in flight
+    one_side_reads: Vec<(usize, usize)>,
     readset:   RwSet<MAX_ITEM_SIZE>,
     updateset: RwSet<MAX_ITEM_SIZE>,
-    writeset:  RwSet<MAX_ITEM_SIZE>
+    writeset:  RwSet<MAX_ITEM_SIZE>,
+    // the ranges of the offload map the txn routed to
+    pins:      Vec<OffloadPin>,
 }
 
 // local operations
@@ -35,9 +51,42 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
             cid:       cid,
             memdb:     memdb.clone(),
             batch_rpc: BatchRpcCtrl::new(scheduler, cid),
+            group:     None,
+            cas_locks: None,
+            one_side:  None,
+            addrs:     None,
+            one_side_reads: Vec::new(),
             readset:   RwSet::new(),
             updateset: RwSet::new(),
             writeset:  RwSet::new(),
+            pins:      Vec::new(),
+        }
+    }
+
+    // commit and release remotely in the epochs of the group
+    pub fn new_grouped(part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>, group: &Arc<GroupCommitCtrl>) -> Self {
+        let mut txn = Self::new(part_id, tid, cid, memdb, scheduler);
+        txn.group = Some(group.clone());
+        txn
+    }
+
+    // lock the writes of the cas-lock tables by RDMA CAS
+    pub fn set_cas_lock(&mut self, cas_locks: &Arc<CasLockCtrl>) {
+        self.set_addrs(cas_locks.get_addrs());
+        self.cas_locks = Some(cas_locks.clone());
+    }
+
+    // read and validate the one-side-read tables by RDMA READ, still commit by rpc
+    pub fn set_one_side_read(&mut self, one_side: &Arc<OneSideReqCtrl>) {
+        self.set_addrs(one_side.get_addrs());
+        self.one_side = Some(one_side.clone());
+    }
+
+    // the one-sided controllers of a worker share its cache, the offsets resolved for one serve both
+    fn set_addrs(&mut self, addrs: &Arc<RemoteAddrCache>) {
+        match self.addrs.as_ref() {
+            Some(old) => assert!(Arc::ptr_eq(old, addrs), "the one-sided controllers must share one cache"),
+            None => self.addrs = Some(addrs.clone()),
         }
     }
 
@@ -121,6 +170,37 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
 
         read_idx
     }
+    // None if it must go by rpc
+    #[inline]
+    fn remote_read_one_side<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> Option<usize> {
+        let one_side = self.one_side.as_ref()?;
+        if !self.memdb.is_one_side_read(table_id) {
+            return None;
+        }
+
+        let offset = one_side.get_addrs().get_offset(part_id, table_id, key)??;
+        let slot = self.one_side_reads.len();
+        if !one_side.post_read_node(part_id, offset, std::mem::size_of::<T>(), self.cid, slot) {
+            return None;
+        }
+
+        let read_idx = self.readset.get_len();
+        self.one_side_reads.push((read_idx, slot));
+
+        // pending
+        let item = RwItem::new(
+            table_id, 
+            part_id,
+            RwType::READ, 
+            key, 
+            MemStoreItemEnum::default(),
+            0
+        );
+        self.readset.push(item);
+
+        Some(read_idx)
+    }
+
     // fetch write
     #[inline]
     fn remote_fetch_write_rpc<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
@@ -154,7 +234,7 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
 impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
 {
     #[inline]
-    fn commit_writes_on(&mut self, update: bool) {
+    fn commit_writes_on(&mut self, update: bool, commit_ts: u64) {
         let ref_set = if update {
             &mut self.updateset
         } else {
@@ -172,7 +252,11 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                     }
                     RwType::INSERT | RwType::UPDATE => {
                         let raw = item.value.get_raw_ptr();
-                        self.memdb.local_upd_val_seq(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32);
+                        self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
+                    }
+                    RwType::DELTA(merge_id) => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_apply_delta(item.table_id, item.key, merge_id, raw, item.value.get_length(), commit_ts);
                     }
                     _ => {}
                 }
@@ -181,9 +265,10 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                 match item.rwtype {
                     RwType::ERASE => {
                         let remote_req = CommitReqItem{
-                            table_id: item.table_id,
-                            key:      item.key,
-                            length:   0,
+                            table_id:  item.table_id,
+                            key:       item.key,
+                            commit_ts: commit_ts,
+                            length:    0,
                         };
 
                         self.batch_rpc.append_req(
@@ -196,9 +281,10 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                     RwType::INSERT | RwType::UPDATE => {
                         let length = item.value.get_length();
                         let remote_req = CommitReqItem{
-                            table_id: item.table_id,
-                            key:      item.key,
-                            length:   item.value.get_length(),
+                            table_id:  item.table_id,
+                            key:       item.key,
+                            commit_ts: commit_ts,
+                            length:    item.value.get_length(),
                         };
 
                         self.batch_rpc.append_req_with_data(
@@ -210,6 +296,25 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                             occ_rpc_id::COMMIT_RPC,
                         );
                     }
+                    RwType::DELTA(merge_id) => {
+                        let length = item.value.get_length();
+                        let remote_req = DeltaReqItem{
+                            table_id:  item.table_id,
+                            key:       item.key,
+                            commit_ts: commit_ts,
+                            merge_id:  merge_id,
+                            length:    length,
+                        };
+
+                        self.batch_rpc.append_req_with_data(
+                            &remote_req, 
+                            item.value.get_raw_ptr(), 
+                            length as usize, 
+                            item.part_id, 
+                            0, 
+                            occ_rpc_id::DELTA_RPC,
+                        );
+                    }
                     _ => {}
                 }
             }
@@ -229,12 +334,19 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
         for i in 0..ref_set.get_len() {
             let item = ref_set.bucket(i);
 
+            let delta = matches!(item.rwtype, RwType::DELTA(_));
+
             if item.part_id == self.part_id {
-                self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+                if delta {
+                    self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                } else {
+                    self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+                }
             } else {
                 let remote_req = ReleaseReqItem{
                     table_id: item.table_id,
-                    key:      item.key
+                    key:      item.key,
+                    delta:    delta,
                 };
     
                 self.batch_rpc.append_req(
@@ -247,6 +359,95 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
         }
     }
 
+    #[inline]
+    fn group_needs_on(&mut self, update: bool, needs: &mut HashMap<u64, usize>) {
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+
+            if item.part_id != self.part_id {
+                let length = match item.rwtype {
+                    RwType::INSERT | RwType::UPDATE | RwType::DELTA(_) => item.value.get_length() as usize,
+                    _ => 0,
+                };
+                *needs.entry(item.part_id).or_insert(0) += std::mem::size_of::<GroupCommitReqItem>() + length;
+            }
+        }
+    }
+
+    #[inline]
+    fn group_install_on(&mut self, update: bool, commit_ts: u64) {
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+
+        let lock_content =  LockContent::new(self.part_id, self.tid, self.cid);
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+
+            if item.part_id == self.part_id {
+                match item.rwtype {
+                    RwType::ERASE => {
+                        self.memdb.local_erase(item.table_id, item.key);
+                    }
+                    RwType::INSERT | RwType::UPDATE => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
+                    }
+                    RwType::DELTA(merge_id) => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_apply_delta(item.table_id, item.key, merge_id, raw, item.value.get_length(), commit_ts);
+                        self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+            }
+        }
+    }
+
+    #[inline]
+    fn group_append_on(&mut self, update: bool, group: &GroupCommitCtrl, commit_ts: u64) {
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+
+            if item.part_id != self.part_id {
+                let (merge_id, length) = match item.rwtype {
+                    RwType::INSERT | RwType::UPDATE => (NO_MERGE, item.value.get_length()),
+                    RwType::DELTA(merge_id) => (merge_id, item.value.get_length()),
+                    _ => (NO_MERGE, 0),
+                };
+
+                let remote_req = GroupCommitReqItem{
+                    table_id:  item.table_id,
+                    key:       item.key,
+                    commit_ts: commit_ts,
+                    merge_id:  merge_id,
+                    length:    length,
+                    cid:       self.cid,
+                };
+
+                group.append_write(&remote_req, item.value.get_raw_ptr(), item.part_id);
+            }
+        }
+    }
+
     #[inline]
     fn abort_on(&mut self, update: bool) {
         let ref_set = if update {
@@ -265,6 +466,9 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                     RwType::ERASE | RwType::UPDATE => {
                         self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
                     }
+                    RwType::DELTA(_) => {
+                        self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                    }
                     RwType::INSERT => {
                         self.memdb.local_erase(item.table_id, item.key);
                     }
@@ -275,6 +479,7 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                     table_id: item.table_id,
                     key:      item.key,
                     insert:   item.rwtype == RwType::INSERT,
+                    delta:    matches!(item.rwtype, RwType::DELTA(_)),
                 };
     
                 self.batch_rpc.append_req(
@@ -329,7 +534,13 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
     }
 
     fn process_batch_rpc_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
 
         for i in 0..resp_num {
             let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
@@ -348,8 +559,88 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
         }
     }
     
+    // a torn node fails the validation later, as its seq changes
+    fn process_one_side_reads(&mut self) {
+        let one_side = match self.one_side.clone() {
+            Some(one_side) => one_side,
+            None => return,
+        };
+
+        for (read_idx, slot) in self.one_side_reads.drain(..) {
+            let bucket = self.readset.bucket(read_idx);
+            let length = self.memdb.get_item_length(bucket.table_id);
+
+            if one_side.get_lock(self.cid, slot) != 0 {
+                self.status = OccStatus::OccMustabort;
+            }
+
+            bucket.seq = one_side.get_seq(self.cid, slot);
+            bucket.value.set_raw_data(one_side.get_value_ptr(self.cid, slot), length as _);
+        }
+    }
+
+    fn process_node_addr_resp(&mut self, addrs: &RemoteAddrCache, keys: &[(u64, usize, u64)]) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            for _ in 0..header.num {
+                let resp_item = wrapper.get_item::<NodeAddrRespItem>().clone();
+                let (part_id, table_id, key) = keys[resp_item.idx];
+
+                let offset = if resp_item.valid { Some(resp_item.offset) } else { None };
+                addrs.set_offset(part_id, table_id, key, offset);
+
+                wrapper.shift_to_next_item::<NodeAddrRespItem>(0);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+
+    fn process_table_layout_resp(&mut self, addrs: &RemoteAddrCache, tables: &[(u64, usize)]) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            for _ in 0..header.num {
+                let resp_item = wrapper.get_item::<TableLayoutRespItem>().clone();
+                let (part_id, table_id) = tables[resp_item.idx];
+
+                let layout = if resp_item.valid { Some(resp_item.layout) } else { None };
+                addrs.set_layout(part_id, table_id, layout);
+
+                wrapper.shift_to_next_item::<TableLayoutRespItem>(0);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+
     fn process_batch_rpc_reduce_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
         for _ in 0..resp_num {
             let reduce_resp = unsafe { (resp_buf as *const BatchRpcReduceResp).as_ref().unwrap() };
             if !reduce_resp.success {
@@ -364,24 +655,162 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
 
 impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
 {
+    // the offsets of the nodes not cached yet, used from the next access on
+    async fn resolve_node_addrs(&mut self, addrs: &RemoteAddrCache) {
+        let mut keys = Vec::new();
+
+        if self.cas_locks.is_some() {
+            for i in 0..self.writeset.get_len() {
+                let item = self.writeset.bucket(i);
+                if item.part_id != self.part_id
+                    && item.rwtype != RwType::INSERT
+                    && self.memdb.is_cas_lock(item.table_id)
+                {
+                    keys.push((item.part_id, item.table_id, item.key));
+                }
+            }
+        }
+
+        if self.one_side.is_some() {
+            for i in 0..self.readset.get_len() {
+                let item = self.readset.bucket(i);
+                if item.part_id != self.part_id && self.memdb.is_one_side_read(item.table_id) {
+                    keys.push((item.part_id, item.table_id, item.key));
+                }
+            }
+        }
+
+        keys.retain(|(part_id, table_id, key)| addrs.get_offset(*part_id, *table_id, *key).is_none());
+        if keys.is_empty() {
+            return;
+        }
+
+        // the layout of a table is fetched once from its owner
+        let mut tables: Vec<(u64, usize)> = keys.iter()
+            .map(|(part_id, table_id, _)| (*part_id, *table_id))
+            .filter(|(part_id, table_id)| addrs.get_layout(*part_id, *table_id).is_none())
+            .collect();
+        tables.sort();
+        tables.dedup();
+        if !tables.is_empty() {
+            self.batch_rpc.restart_batch();
+            for (idx, (part_id, table_id)) in tables.iter().enumerate() {
+                let remote_req = TableLayoutReqItem{
+                    table_id: *table_id,
+                    idx:      idx,
+                };
+
+                self.batch_rpc.append_req::<TableLayoutReqItem>(
+                    &remote_req, 
+                    *part_id, 
+                    0, 
+                    occ_rpc_id::TABLE_LAYOUT_RPC
+                );
+            }
+
+            self.batch_rpc.send_batch_reqs();
+            self.batch_rpc.wait_until_done().await;
+            self.process_table_layout_resp(addrs, &tables);
+        }
+
+        // the nodes are found by reading their buckets, without the owner
+        self.batch_rpc.restart_batch();
+        let mut probes = Vec::new();
+        for (part_id, table_id, key) in keys.iter() {
+            if let Some(Some(layout)) = addrs.get_layout(*part_id, *table_id) {
+                if addrs.post_probe(*part_id, &layout, *key, self.cid, probes.len()) {
+                    probes.push((*part_id, *table_id, *key, layout));
+                }
+            }
+        }
+        if !probes.is_empty() {
+            self.batch_rpc.send_batch_reqs();
+            self.batch_rpc.wait_until_done().await;
+
+            for (slot, (part_id, table_id, key, layout)) in probes.iter().enumerate() {
+                if let Some(offset) = addrs.probed_offset(layout, *key, self.cid, slot) {
+                    addrs.set_offset(*part_id, *table_id, *key, Some(offset));
+                }
+            }
+            keys.retain(|(part_id, table_id, key)| addrs.get_offset(*part_id, *table_id, *key).is_none());
+            if keys.is_empty() {
+                return;
+            }
+        }
+
+        // the others by their owners
+        self.batch_rpc.restart_batch();
+        for (idx, (part_id, table_id, key)) in keys.iter().enumerate() {
+            let remote_req = NodeAddrReqItem{
+                table_id: *table_id,
+                key:      *key,
+                idx:      idx,
+            };
+
+            self.batch_rpc.append_req::<NodeAddrReqItem>(
+                &remote_req, 
+                *part_id, 
+                0, 
+                occ_rpc_id::NODE_ADDR_RPC
+            );
+        }
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+
+        self.process_node_addr_resp(addrs, &keys);
+    }
+
     async fn lock_writes(&mut self) {
+        if let Some(addrs) = self.addrs.clone() {
+            self.resolve_node_addrs(&addrs).await;
+        }
+
+        let cas_locks = self.cas_locks.clone();
+
         self.batch_rpc.restart_batch();
 
         let lock_content = LockContent::new(self.part_id, self.tid,  self.cid);
+        let mut cas_num = 0;
         for i in 0..self.writeset.get_len() {
             let item = self.writeset.bucket(i);
+            // the deltas only exclude the reads and the other writes
+            let delta = matches!(item.rwtype, RwType::DELTA(_));
+
             if item.part_id == self.part_id {
                 // local
+                if delta {
+                    let meta = self.memdb.local_lock_delta(item.table_id, item.key, lock_content.to_content()).unwrap();
+
+                    if !is_delta_lock(meta.lock) {
+                        self.status = OccStatus::OccMustabort;
+                    }
+                    continue;
+                }
+
                 let meta = self.memdb.local_lock(item.table_id, item.key, lock_content.to_content()).unwrap();
 
                 if meta.lock != lock_content.to_content() {
                     self.status = OccStatus::OccMustabort;
                 }
             } else {
-                // remote
+                // remote, by RDMA CAS if the owner registers the lock word,
+                // a delta lock counts its holders so it goes by rpc
+                if let Some(cas_locks) = cas_locks.as_ref() {
+                    if item.rwtype != RwType::INSERT && !delta {
+                        if let Some(Some(offset)) = cas_locks.get_addrs().get_offset(item.part_id, item.table_id, item.key) {
+                            if cas_locks.post_lock(item.part_id, offset, lock_content.to_content(), self.cid, cas_num) {
+                                cas_num += 1;
+                                continue;
+                            }
+                        }
+                    }
+                }
+
                 let remote_req = LockReqItem{
                     table_id: item.table_id,
                     key:      item.key,
+                    delta:    delta,
                 };
         
                 self.batch_rpc.append_req::<LockReqItem>(
@@ -393,14 +822,30 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
             }
         }
 
+        // waits for the CAS as well
         self.batch_rpc.send_batch_reqs();
         self.batch_rpc.wait_until_done().await;
 
         self.process_batch_rpc_reduce_resp();
+
+        // the ones not acquired are left to ABORT_RPC, which only unlocks our own
+        if let Some(cas_locks) = cas_locks.as_ref() {
+            for slot in 0..cas_num {
+                if !cas_locks.is_locked(self.cid, slot) {
+                    self.status = OccStatus::OccMustabort;
+                }
+            }
+        }
     }
 
     async fn validate(&mut self) {
+        // the reads not taken out by get_value, completed while locking
+        self.process_one_side_reads();
+
         self.batch_rpc.restart_batch();
+
+        let one_side = self.one_side.clone();
+        let mut one_side_checks = Vec::new();
         for i in 0..self.readset.get_len() {
             let item = self.readset.bucket(i);
             if item.part_id == self.part_id {
@@ -411,7 +856,19 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
                     self.status = OccStatus::OccMustabort;
                 }
             } else {
-                // remote
+                // remote, re-read the lock and seq if the node is registered
+                if let Some(one_side) = one_side.as_ref() {
+                    if self.memdb.is_one_side_read(item.table_id) {
+                        if let Some(Some(offset)) = one_side.get_addrs().get_offset(item.part_id, item.table_id, item.key) {
+                            let slot = one_side_checks.len();
+                            if one_side.post_read_meta(item.part_id, offset, self.cid, slot) {
+                                one_side_checks.push((i, slot));
+                                continue;
+                            }
+                        }
+                    }
+                }
+
                 let remote_req = ValidateReqItem{
                     table_id: item.table_id,
                     key:      item.key,
@@ -427,10 +884,20 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
             }
         }
 
+        // waits for the reads as well
         self.batch_rpc.send_batch_reqs();
         self.batch_rpc.wait_until_done().await;
 
         self.process_batch_rpc_reduce_resp();
+
+        if let Some(one_side) = one_side.as_ref() {
+            for (read_idx, slot) in one_side_checks {
+                let item = self.readset.bucket(read_idx);
+                if one_side.get_lock(self.cid, slot) != 0 || one_side.get_seq(self.cid, slot) != item.seq {
+                    self.status = OccStatus::OccMustabort;
+                }
+            }
+        }
     }
 
     async fn log_writes(&mut self) {
@@ -439,8 +906,10 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
     
     async fn commit_writes(&mut self) {
         self.batch_rpc.restart_batch();
-        self.commit_writes_on(true);
-        self.commit_writes_on(false);
+        let commit_ts = self.memdb.alloc_commit_ts();
+        self.commit_writes_on(true, commit_ts);
+        self.commit_writes_on(false, commit_ts);
+        self.memdb.finish_commit_ts(commit_ts);
 
         self.batch_rpc.send_batch_reqs();
         self.batch_rpc.wait_until_done().await;
@@ -455,6 +924,30 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
         self.batch_rpc.wait_until_done().await;
     }
 
+    async fn group_commit(&mut self, group: &GroupCommitCtrl) {
+        let mut needs = HashMap::new();
+        self.group_needs_on(true, &mut needs);
+        self.group_needs_on(false, &mut needs);
+
+        // the participants install the writes at the same ts as the local ones
+        let commit_ts = self.memdb.alloc_commit_ts();
+
+        // a local-only txn has nothing to wait for
+        if !needs.is_empty() {
+            let epoch = group.join(self.cid, &needs).await;
+            self.group_append_on(true, group, commit_ts);
+            self.group_append_on(false, group, commit_ts);
+
+            // the local writes stay locked until the epoch is acknowledged, no log
+            // record is written for it, as log_writes is a stub on every path
+            group.wait(self.cid, epoch).await;
+        }
+
+        self.group_install_on(true, commit_ts);
+        self.group_install_on(false, commit_ts);
+        self.memdb.finish_commit_ts(commit_ts);
+    }
+
     async fn recover_on_aborted(&mut self) {
         self.batch_rpc.restart_batch();
         self.abort_on(true);
@@ -468,17 +961,30 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote<MAX_ITEM_SIZE>
 impl<const MAX_ITEM_SIZE: usize> OccRemote< MAX_ITEM_SIZE>
 {
     pub fn start(&mut self) {
+        self.pins.clear();
         self.batch_rpc.restart_batch();
+        self.one_side_reads.clear();
         self.status = OccStatus::OccInprogress;
     }
 
+    // pins the key where the placement puts it, a range on the move aborts the txn
+    pub fn place(&mut self, placement: &dyn Partitioner, key: u64) -> u64 {
+        if !placement.pin(key as _, &mut self.pins) {
+            self.status = OccStatus::OccMustabort;
+        }
+        placement.part_of(key as _) as _
+    }
+
     pub fn read<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
         if part_id == self.part_id {
             // local
             self.local_read::<T>(table_id, key)
         } else {
-            // remote
-            self.remote_read_rpc::<T>(table_id, part_id, key)
+            // remote, by RDMA READ if the node is registered
+            match self.remote_read_one_side::<T>(table_id, part_id, key) {
+                Some(read_idx) => read_idx,
+                None => self.remote_read_rpc::<T>(table_id, part_id, key),
+            }
         }
     }
 
@@ -511,12 +1017,21 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote< MAX_ITEM_SIZE>
         write_idx
     }
 
+    // blind commutative update, the delta is merged by the owner at commit
+    pub fn apply_delta<D: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, merge_id: u32, delta: &D) -> usize {
+        let write_idx = self.write::<D>(table_id, part_id, key, RwType::DELTA(merge_id));
+        self.set_value(false, write_idx, delta);
+
+        write_idx
+    }
+
     pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, update: bool, idx: usize) -> &'trans T {
         // TODO: more careful check
         self.batch_rpc.send_batch_reqs();
         self.batch_rpc.wait_until_done().await;
 
         self.process_batch_rpc_resp();
+        self.process_one_side_reads();
         self.batch_rpc.restart_batch();
 
         if update {
@@ -547,9 +1062,13 @@ impl<const MAX_ITEM_SIZE: usize> OccRemote< MAX_ITEM_SIZE>
 
         self.log_writes().await;
 
-        self.commit_writes().await;
+        if let Some(group) = self.group.clone() {
+            self.group_commit(&group).await;
+        } else {
+            self.commit_writes().await;
 
-        self.release().await;
+            self.release().await;
+        }
 
         self.status = OccStatus::OccCommited;
     }
@@ -2,7 +2,10 @@ use std::sync::Arc;
 
 use crate::memstore::memdb::MemDB;
 use crate::memstore::MemStoreValue;
+use crate::memstore::{is_delta_lock, NO_MERGE};
 use crate::framework::scheduler::AsyncScheduler;
+use crate::common::partition::Partitioner;
+use crate::common::offload::OffloadPin;
 use crate::MAX_RESP_SIZE;
 
 use super::occ::{LockContent, MemStoreItemEnum, OccStatus};
@@ -21,7 +24,8 @@ pub struct OccTransCache<const MAX_ITEM_SIZE: usize>
     batch_rpc: BatchRpcCtrl,
     readset:   RwSet<MAX_ITEM_SIZE>,
     updateset: RwSet<MAX_ITEM_SIZE>,
-    writeset:  RwSet<MAX_ITEM_SIZE>
+    writeset:  RwSet<MAX_ITEM_SIZE>,
+    pins:      Vec<OffloadPin>,
 }
 
 // local operations
@@ -38,6 +42,7 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
             readset:   RwSet::new(),
             updateset: RwSet::new(),
             writeset:  RwSet::new(),
+            pins:      Vec::new(),
         }
     }
 
@@ -154,7 +159,7 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
 impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
 {
     #[inline]
-    fn commit_writes_on(&mut self, update: bool) {
+    fn commit_writes_on(&mut self, update: bool, commit_ts: u64) {
         let ref_set = if update {
             &mut self.updateset
         } else {
@@ -172,7 +177,11 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
                     }
                     RwType::INSERT | RwType::UPDATE => {
                         let raw = item.value.get_raw_ptr();
-                        self.memdb.local_upd_val_seq(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32);
+                        self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
+                    }
+                    RwType::DELTA(merge_id) => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_apply_delta(item.table_id, item.key, merge_id, raw, item.value.get_length(), commit_ts);
                     }
                     _ => {}
                 }
@@ -181,7 +190,9 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
                 match item.rwtype {
                     RwType::ERASE => {
                         let remote_req = CommitCacheReqItem{
-                            length:   0,
+                            commit_ts: commit_ts,
+                            length:    0,
+                            merge_id:  NO_MERGE,
                         };
 
                         self.batch_rpc.append_req(
@@ -194,7 +205,27 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
                     RwType::INSERT | RwType::UPDATE => {
                         let length = item.value.get_length();
                         let remote_req = CommitCacheReqItem{
-                            length:   item.value.get_length(),
+                            commit_ts: commit_ts,
+                            length:    item.value.get_length(),
+                            merge_id:  NO_MERGE,
+                        };
+
+                        self.batch_rpc.append_req_with_data(
+                            &remote_req, 
+                            item.value.get_raw_ptr(), 
+                            length as usize, 
+                            item.part_id, 
+                            0, 
+                            occ_rpc_id::COMMIT_RPC,
+                        );
+                    }
+                    // the cached lock keeps the order, so it goes with the commits
+                    RwType::DELTA(merge_id) => {
+                        let length = item.value.get_length();
+                        let remote_req = CommitCacheReqItem{
+                            commit_ts: commit_ts,
+                            length:    length,
+                            merge_id:  merge_id,
                         };
 
                         self.batch_rpc.append_req_with_data(
@@ -226,7 +257,10 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
             let item = ref_set.bucket(i);
 
             if item.part_id == self.part_id {
-                self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+                match item.rwtype {
+                    RwType::DELTA(_) => self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content()),
+                    _ => self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content()),
+                }
             } else {
                 let remote_req = DummyReqItem{};
     
@@ -258,6 +292,9 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
                     RwType::ERASE | RwType::UPDATE => {
                         self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
                     }
+                    RwType::DELTA(_) => {
+                        self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                    }
                     RwType::INSERT => {
                         self.memdb.local_erase(item.table_id, item.key);
                     }
@@ -317,7 +354,13 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
     }
 
     fn process_batch_rpc_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
 
         for i in 0..resp_num {
             let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
@@ -337,7 +380,13 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
     }
     
     fn process_batch_rpc_reduce_resp(&mut self) {
-        let (mut resp_buf, resp_num) = self.batch_rpc.get_resp_buf_num().unwrap();
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
         for _ in 0..resp_num {
             let reduce_resp = unsafe { (resp_buf as *const BatchRpcReduceResp).as_ref().unwrap() };
             if !reduce_resp.success {
@@ -358,11 +407,19 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
         let lock_content = LockContent::new(self.part_id, self.tid,  self.cid);
         for i in 0..self.writeset.get_len() {
             let item = self.writeset.bucket(i);
+            let delta = matches!(item.rwtype, RwType::DELTA(_));
+
             if item.part_id == self.part_id {
-                // local
-                let meta = self.memdb.local_lock(item.table_id, item.key, lock_content.to_content()).unwrap();
+                // local, the deltas of the txns share the lock
+                let locked = if delta {
+                    let meta = self.memdb.local_lock_delta(item.table_id, item.key, lock_content.to_content()).unwrap();
+                    is_delta_lock(meta.lock)
+                } else {
+                    let meta = self.memdb.local_lock(item.table_id, item.key, lock_content.to_content()).unwrap();
+                    meta.lock == lock_content.to_content()
+                };
 
-                if meta.lock != lock_content.to_content() {
+                if !locked {
                     self.status = OccStatus::OccMustabort;
                 }
             } else {
@@ -370,6 +427,7 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
                 let remote_req = LockReqItem{
                     table_id: item.table_id,
                     key:      item.key,
+                    delta:    delta,
                 };
         
                 self.batch_rpc.append_req::<LockReqItem>(
@@ -423,8 +481,10 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
     
     async fn commit_writes(&mut self) {
         self.batch_rpc.restart_batch();
-        self.commit_writes_on(true);
-        self.commit_writes_on(false);
+        let commit_ts = self.memdb.alloc_commit_ts();
+        self.commit_writes_on(true, commit_ts);
+        self.commit_writes_on(false, commit_ts);
+        self.memdb.finish_commit_ts(commit_ts);
 
         self.batch_rpc.send_batch_reqs();
         self.batch_rpc.wait_until_done().await;
@@ -452,10 +512,19 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache<MAX_ITEM_SIZE>
 impl<const MAX_ITEM_SIZE: usize> OccTransCache< MAX_ITEM_SIZE>
 {
     pub fn start(&mut self) {
+        self.pins.clear();
         self.batch_rpc.restart_batch();
         self.status = OccStatus::OccInprogress;
     }
 
+    // as OccRemote::place
+    pub fn place(&mut self, placement: &dyn Partitioner, key: u64) -> u64 {
+        if !placement.pin(key as _, &mut self.pins) {
+            self.status = OccStatus::OccMustabort;
+        }
+        placement.part_of(key as _) as _
+    }
+
     pub fn read<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
         if part_id == self.part_id {
             // local
@@ -495,6 +564,33 @@ impl<const MAX_ITEM_SIZE: usize> OccTransCache< MAX_ITEM_SIZE>
         write_idx
     }
 
+    // the partition of the key by the placement
+    #[inline]
+    pub fn read_placed<T: MemStoreValue>(&mut self, table_id: usize, placement: &dyn Partitioner, key: u64) -> usize {
+        let part_id = self.place(placement, key);
+        self.read::<T>(table_id, part_id, key)
+    }
+
+    #[inline]
+    pub fn fetch_write_placed<T: MemStoreValue>(&mut self, table_id: usize, placement: &dyn Partitioner, key: u64) -> usize {
+        let part_id = self.place(placement, key);
+        self.fetch_write::<T>(table_id, part_id, key)
+    }
+
+    #[inline]
+    pub fn write_placed<T: MemStoreValue>(&mut self, table_id: usize, placement: &dyn Partitioner, key: u64, rwtype: RwType) -> usize {
+        let part_id = self.place(placement, key);
+        self.write::<T>(table_id, part_id, key, rwtype)
+    }
+
+    // blind commutative update, the delta is merged by the owner at commit
+    pub fn apply_delta<D: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, merge_id: u32, delta: &D) -> usize {
+        let write_idx = self.write::<D>(table_id, part_id, key, RwType::DELTA(merge_id));
+        self.set_value(false, write_idx, delta);
+
+        write_idx
+    }
+
     pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, update: bool, idx: usize) -> &'trans T {
         // TODO: more careful check
         self.batch_rpc.send_batch_reqs();
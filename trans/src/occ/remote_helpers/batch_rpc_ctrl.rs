@@ -188,7 +188,16 @@ impl BatchRpcCtrl
         self.scheduler.yield_until_ready(self.cid).await;
     }
 
+    #[inline]
+    pub fn is_failed(&self) -> bool {
+        self.resp_buf.is_some() && self.scheduler.is_reply_failed(self.cid)
+    }
+
+    // none if the replies failed, e.g. the peer is gone
     pub fn get_resp_buf_num(&mut self) -> Option<(*mut u8, usize)> {
+        if self.is_failed() {
+            return None;
+        }
         if let Some(buf) = self.resp_buf {
             return Some((buf as *mut u8, self.req_msgs.len()));
         }
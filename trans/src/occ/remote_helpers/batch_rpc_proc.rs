@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use std::cell::UnsafeCell;
+use std::collections::HashMap;
 
 use tokio::sync::mpsc;
 
@@ -7,6 +8,7 @@ use crate::framework::YieldReq;
 use crate::framework::scheduler::AsyncScheduler;
 use crate::framework::rpc::*;
 use crate::memstore::memdb::MemDB;
+use crate::memstore::{is_delta_lock, NO_COMMIT_TS, NO_MERGE};
 use crate::occ::cache_helpers::trans_cache_view::TransCacheView;
 use crate::occ::cache_helpers::trans_cache_view::TransKey;
 use crate::occ::cache_helpers::CacheWriteSetItem;
@@ -18,7 +20,9 @@ use super::*;
 
 use super::batch_rpc_msg_wrapper::BatchRpcReqWrapper;
 use super::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
-use super::super::occ::LockContent;
+use super::super::occ::{ release_peer_locks, LockContent };
+use super::super::two_pl::TplTimestamp;
+use super::super::cc_txn::CcProtocol;
 use super::super::cache_helpers::CacheReadSetItem;
 
 pub struct BatchRpcProc {
@@ -26,6 +30,8 @@ pub struct BatchRpcProc {
     pub memdb:      Arc<MemDB>,
     pub scheduler:  Arc<AsyncScheduler>,
     pub trans_view: UnsafeCell<TransCacheView>,
+    // the 2pl shared locks granted to each peer, their words do not name it
+    pub tpl_shared: UnsafeCell<HashMap<u64, Vec<(usize, u64)>>>,
 }
 
 unsafe impl Send for BatchRpcProc {}
@@ -38,6 +44,24 @@ impl BatchRpcProc {
             memdb: memdb.clone(),
             scheduler: scheduler.clone(),
             trans_view: UnsafeCell::new(TransCacheView::new(scheduler)),
+            tpl_shared: UnsafeCell::new(HashMap::new()),
+        }
+    }
+
+    // the lock words are decoded by the protocol the peers run
+    pub fn release_peer_locks(&self, cc: CcProtocol, peer_id: u64) {
+        match cc {
+            CcProtocol::TwoPlNoWait | CcProtocol::TwoPlWaitDie => {
+                release_peer_locks(&self.memdb, peer_id, TplTimestamp::peer_of);
+
+                let tpl_shared = unsafe { self.tpl_shared.get().as_mut().unwrap() };
+                for (table_id, key) in tpl_shared.remove(&peer_id).unwrap_or_default() {
+                    self.memdb.local_unlock_shared(table_id, key);
+                }
+            }
+            CcProtocol::Occ | CcProtocol::TicToc => {
+                release_peer_locks(&self.memdb, peer_id, LockContent::peer_of);
+            }
         }
     }
 }
@@ -95,6 +119,74 @@ impl BatchRpcProc {
         
     }
 
+    // multi-version read at the snapshot of the coordinator, no validation later
+    pub fn snapshot_read_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<SnapshotReadReqItem>();
+            let read_ts = req_item.read_ts;
+            let mut data_len = self.memdb.get_item_length(req_item.table_id);
+
+            // zero length means abort, when a commit before the snapshot
+            // is still installing or the version is collected
+            let mut node_meta = None;
+            if self.memdb.join_snapshot(read_ts) {
+                node_meta = self.memdb.local_get_snapshot(
+                    req_item.table_id, 
+                    req_item.key, 
+                    resp_wrapper.get_extra_data_raw_ptr::<ReadRespItem>(), 
+                    data_len as u32,
+                    read_ts,
+                );
+                self.memdb.end_snapshot(read_ts);
+            }
+
+            let seq = match node_meta {
+                Some(node_meta) => node_meta.seq,
+                None => {
+                    data_len = 0;
+                    0
+                }
+            };
+
+            resp_wrapper.set_item(ReadRespItem{
+                read_idx: req_item.read_idx,
+                seq:      seq,
+                length:   data_len,
+            });
+
+            req_wrapper.shift_to_next_item::<SnapshotReadReqItem>(0);
+            resp_wrapper.shift_to_next_item::<ReadRespItem>(data_len);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn, 
+            resp_buf, 
+            occ_rpc_id::SNAPSHOT_READ_RPC, 
+            resp_wrapper.get_off() as _, 
+            meta.rpc_cid, 
+            meta.peer_id, 
+            meta.peer_tid
+        );
+    }
+
     pub fn fetch_write_rpc_handler(
         &self,
         src_conn: &mut RdmaRcConn,
@@ -177,15 +269,28 @@ impl BatchRpcProc {
         for _ in 0..req_header.num {
             let req_item = req_wrapper.get_item::<LockReqItem>();
 
-            let meta = self.memdb.local_lock(
-                req_item.table_id, 
-                req_item.key, 
-                lock_content.to_content(),
-            ).unwrap();
+            if req_item.delta {
+                let meta = self.memdb.local_lock_delta(
+                    req_item.table_id, 
+                    req_item.key, 
+                    lock_content.to_content(),
+                ).unwrap();
 
-            if meta.lock != lock_content.to_content() {
-                success = false;
-                break;
+                if !is_delta_lock(meta.lock) {
+                    success = false;
+                    break;
+                }
+            } else {
+                let meta = self.memdb.local_lock(
+                    req_item.table_id, 
+                    req_item.key, 
+                    lock_content.to_content(),
+                ).unwrap();
+
+                if meta.lock != lock_content.to_content() {
+                    success = false;
+                    break;
+                }
             }
 
             req_wrapper.shift_to_next_item::<LockReqItem>(0);
@@ -265,6 +370,10 @@ impl BatchRpcProc {
 
         let req_header = req_wrapper.get_header();
 
+        // the items of a txn carry the ts its coordinator picked
+        let commit_ts = req_wrapper.get_item::<CommitReqItem>().commit_ts;
+        self.memdb.join_commit_ts(commit_ts);
+
         // modify
         for _ in 0..req_header.num {
             let req_item = req_wrapper.get_item::<CommitReqItem>();
@@ -273,17 +382,20 @@ impl BatchRpcProc {
             if data_len == 0 {
                 self.memdb.local_erase(req_item.table_id, req_item.key);
             } else {
-                self.memdb.local_upd_val_seq(
+                self.memdb.local_upd_val_seq_at(
                     req_item.table_id, 
                     req_item.key, 
                     req_wrapper.get_extra_data_const_ptr::<CommitReqItem>(), 
                     data_len,
+                    commit_ts,
                 );
             }
 
             req_wrapper.shift_to_next_item::<CommitReqItem>(data_len as _);
         }
 
+        self.memdb.finish_commit_ts(commit_ts);
+
         // TODO: mark unlock and no neef release rpc
 
         self.scheduler.send_reply(
@@ -298,6 +410,218 @@ impl BatchRpcProc {
 
     }
 
+    // all the writes of an epoch, installed at one commit ts and then released
+    pub fn group_commit_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let req_header = req_wrapper.get_header();
+
+        // the txns of an epoch are in flight here until all their writes are installed
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<GroupCommitReqItem>();
+            let data_len = req_item.length;
+            self.memdb.join_commit_ts(req_item.commit_ts);
+            req_wrapper.shift_to_next_item::<GroupCommitReqItem>(data_len as _);
+        }
+
+        // modify
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<GroupCommitReqItem>();
+            let data_len = req_item.length;
+            let commit_ts = req_item.commit_ts;
+
+            if req_item.merge_id != NO_MERGE {
+                self.memdb.local_apply_delta(
+                    req_item.table_id, 
+                    req_item.key, 
+                    req_item.merge_id, 
+                    req_wrapper.get_extra_data_const_ptr::<GroupCommitReqItem>(), 
+                    data_len,
+                    commit_ts,
+                );
+            } else if data_len == 0 {
+                self.memdb.local_erase(req_item.table_id, req_item.key);
+            } else {
+                self.memdb.local_upd_val_seq_at(
+                    req_item.table_id, 
+                    req_item.key, 
+                    req_wrapper.get_extra_data_const_ptr::<GroupCommitReqItem>(), 
+                    data_len,
+                    commit_ts,
+                );
+            }
+
+            req_wrapper.shift_to_next_item::<GroupCommitReqItem>(data_len as _);
+        }
+
+        // unlock
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<GroupCommitReqItem>();
+            let data_len = req_item.length;
+
+            let lock_content = LockContent::new(meta.peer_id, self.tid as _, req_item.cid);
+            if req_item.merge_id != NO_MERGE {
+                self.memdb.local_unlock_delta(req_item.table_id, req_item.key, lock_content.to_content());
+            } else if data_len != 0 {
+                self.memdb.local_unlock(req_item.table_id, req_item.key, lock_content.to_content());
+            }
+            self.memdb.finish_commit_ts(req_item.commit_ts);
+
+            req_wrapper.shift_to_next_item::<GroupCommitReqItem>(data_len as _);
+        }
+
+        self.scheduler.send_reply(
+            src_conn, 
+            resp_buf, 
+            occ_rpc_id::GROUP_COMMIT_RPC, 
+            0, 
+            meta.rpc_cid, 
+            meta.peer_id, 
+            meta.peer_tid
+        );
+    }
+
+    // deltas are merged here without being read by the coordinator
+    pub fn delta_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let req_header = req_wrapper.get_header();
+        let commit_ts = req_wrapper.get_item::<DeltaReqItem>().commit_ts;
+        self.memdb.join_commit_ts(commit_ts);
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<DeltaReqItem>();
+            let data_len = req_item.length;
+
+            self.memdb.local_apply_delta(
+                req_item.table_id, 
+                req_item.key, 
+                req_item.merge_id, 
+                req_wrapper.get_extra_data_const_ptr::<DeltaReqItem>(), 
+                data_len,
+                commit_ts,
+            );
+
+            req_wrapper.shift_to_next_item::<DeltaReqItem>(data_len as _);
+        }
+
+        self.memdb.finish_commit_ts(commit_ts);
+
+        self.scheduler.send_reply(
+            src_conn, 
+            resp_buf, 
+            occ_rpc_id::DELTA_RPC, 
+            0, 
+            meta.rpc_cid, 
+            meta.peer_id, 
+            meta.peer_tid
+        );
+    }
+
+    pub fn node_addr_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<NodeAddrReqItem>();
+            let offset = self.memdb.local_get_node_offset(req_item.table_id, req_item.key);
+
+            resp_wrapper.set_item(NodeAddrRespItem{
+                idx:    req_item.idx,
+                offset: offset.unwrap_or(0),
+                valid:  offset.is_some(),
+            });
+
+            req_wrapper.shift_to_next_item::<NodeAddrReqItem>(0);
+            resp_wrapper.shift_to_next_item::<NodeAddrRespItem>(0);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn, 
+            resp_buf, 
+            occ_rpc_id::NODE_ADDR_RPC, 
+            resp_wrapper.get_off() as _, 
+            meta.rpc_cid, 
+            meta.peer_id, 
+            meta.peer_tid
+        );
+    }
+
+    pub fn table_layout_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TableLayoutReqItem>();
+            let layout = self.memdb.export_layout(req_item.table_id);
+
+            resp_wrapper.set_item(TableLayoutRespItem{
+                idx:    req_item.idx,
+                layout: layout.unwrap_or_default(),
+                valid:  layout.is_some(),
+            });
+
+            req_wrapper.shift_to_next_item::<TableLayoutReqItem>(0);
+            resp_wrapper.shift_to_next_item::<TableLayoutRespItem>(0);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn, 
+            resp_buf, 
+            occ_rpc_id::TABLE_LAYOUT_RPC, 
+            resp_wrapper.get_off() as _, 
+            meta.rpc_cid, 
+            meta.peer_id, 
+            meta.peer_tid
+        );
+    }
+
     pub fn release_rpc_handler(
         &self,
         src_conn: &mut RdmaRcConn,
@@ -315,7 +639,11 @@ impl BatchRpcProc {
         // unlock
         for _ in 0..req_header.num {
             let req_item = req_wrapper.get_item::<ReleaseReqItem>();
-            self.memdb.local_unlock(req_item.table_id, req_item.key, lock_content.to_content());
+            if req_item.delta {
+                self.memdb.local_unlock_delta(req_item.table_id, req_item.key, lock_content.to_content());
+            } else {
+                self.memdb.local_unlock(req_item.table_id, req_item.key, lock_content.to_content());
+            }
         
             req_wrapper.shift_to_next_item::<ReleaseReqItem>(0);
         }
@@ -350,6 +678,8 @@ impl BatchRpcProc {
 
             if req_item.insert {
                 self.memdb.local_erase(req_item.table_id, req_item.key);
+            } else if req_item.delta {
+                self.memdb.local_unlock_delta(req_item.table_id, req_item.key, lock_content.to_content());
             } else {
                 self.memdb.local_try_unlock(
                     req_item.table_id, 
@@ -488,6 +818,7 @@ impl BatchRpcProc {
                     table_id: req_item.table_id, 
                     key:      req_item.key,
                     insert:   false,
+                    delta:    false,
                 });
             }
 
@@ -539,13 +870,26 @@ impl BatchRpcProc {
         for _ in 0..req_header.num {
             let req_item = req_wrapper.get_item::<LockReqItem>();
 
-            let meta = self.memdb.local_lock(
-                req_item.table_id, 
-                req_item.key, 
-                lock_content.to_content(),
-            ).unwrap();
+            let meta = if req_item.delta {
+                self.memdb.local_lock_delta(
+                    req_item.table_id, 
+                    req_item.key, 
+                    lock_content.to_content(),
+                ).unwrap()
+            } else {
+                self.memdb.local_lock(
+                    req_item.table_id, 
+                    req_item.key, 
+                    lock_content.to_content(),
+                ).unwrap()
+            };
 
-            if meta.lock != lock_content.to_content() {
+            let locked = if req_item.delta {
+                is_delta_lock(meta.lock)
+            } else {
+                meta.lock == lock_content.to_content()
+            };
+            if !locked {
                 success = false;
                 break;
             }
@@ -554,7 +898,8 @@ impl BatchRpcProc {
             write_cache_writer.block_append_item(trans_view, CacheWriteSetItem{
                 table_id: req_item.table_id, 
                 key:      req_item.key,
-                insert:   (meta.seq == 2),
+                insert:   !req_item.delta && (meta.seq == 2),
+                delta:    req_item.delta,
             });
 
             req_wrapper.shift_to_next_item::<LockReqItem>(0);
@@ -646,6 +991,15 @@ impl BatchRpcProc {
         // println!("trasn commit: {:?}", trans_key);
         let trans_view = unsafe { self.trans_view.get().as_mut().unwrap() };
         let buf_count = trans_view.get_write_range_num(&trans_key);
+
+        // a host coordinator keeps no clock and leaves the ts to here
+        let commit_ts = match req_wrapper.get_item::<CommitCacheReqItem>().commit_ts {
+            NO_COMMIT_TS => self.memdb.alloc_commit_ts(),
+            commit_ts => {
+                self.memdb.join_commit_ts(commit_ts);
+                commit_ts
+            }
+        };
     
         for i in 0..buf_count {
             let write_buf = trans_view.block_get_write_buf(&trans_key, i, 0);
@@ -656,12 +1010,22 @@ impl BatchRpcProc {
 
                 if data_len == 0 {
                     self.memdb.local_erase(item.table_id, item.key);
+                } else if req_item.merge_id != NO_MERGE {
+                    self.memdb.local_apply_delta(
+                        item.table_id, 
+                        item.key, 
+                        req_item.merge_id, 
+                        req_wrapper.get_extra_data_const_ptr::<CommitCacheReqItem>(), 
+                        data_len,
+                        commit_ts,
+                    );
                 } else {
-                    self.memdb.local_upd_val_seq(
+                    self.memdb.local_upd_val_seq_at(
                         item.table_id, 
                         item.key, 
                         req_wrapper.get_extra_data_const_ptr::<CommitCacheReqItem>(), 
                         data_len,
+                        commit_ts,
                     );
                 }
 
@@ -669,6 +1033,8 @@ impl BatchRpcProc {
             }
         }
 
+        self.memdb.finish_commit_ts(commit_ts);
+
         self.scheduler.send_reply(
             src_conn, 
             resp_buf, 
@@ -702,7 +1068,11 @@ impl BatchRpcProc {
             let write_buf = trans_view.block_get_write_buf(&trans_key, i, 0);
 
             for item in write_buf.iter() {
-                self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+                if item.delta {
+                    self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                } else {
+                    self.memdb.local_unlock(item.table_id, item.key, lock_content.to_content());
+                }
             }
         }
 
@@ -742,6 +1112,8 @@ impl BatchRpcProc {
             for item in write_buf.iter() {
                 if item.insert {
                     self.memdb.local_erase(item.table_id, item.key);
+                } else if item.delta {
+                    self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
                 } else {
                     self.memdb.local_try_unlock(
                         item.table_id, 
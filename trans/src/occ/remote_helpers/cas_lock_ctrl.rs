@@ -0,0 +1,66 @@
+use std::alloc::Layout;
+use std::sync::Arc;
+
+use crate::framework::scheduler::AsyncScheduler;
+use crate::MAX_CAS_LOCKS_PER_ROUTINE;
+
+use super::remote_addr_cache::RemoteAddrCache;
+
+/// Locks the rows of the peers with RDMA CAS on their lock words, bypassing the remote CPU.
+/// The lock word is the head of the node, rows without a registered node are still locked by LOCK_RPC.
+pub struct CasLockCtrl {
+    scheduler: Arc<AsyncScheduler>,
+    // the old lock words of each routine, in the registered region
+    olds:      *mut u64,
+    addrs:     Arc<RemoteAddrCache>,
+}
+
+// only touched by the coroutines of the same thread
+unsafe impl Send for CasLockCtrl {}
+unsafe impl Sync for CasLockCtrl {}
+
+impl CasLockCtrl {
+    pub fn new(scheduler: &Arc<AsyncScheduler>, addrs: &Arc<RemoteAddrCache>, routine_num: u32) -> Self {
+        let layout = Layout::from_size_align(
+            routine_num as usize * MAX_CAS_LOCKS_PER_ROUTINE * std::mem::size_of::<u64>(),
+            std::mem::align_of::<u64>(),
+        ).unwrap();
+
+        Self {
+            scheduler: scheduler.clone(),
+            olds:      scheduler.alloc_mr(layout) as _,
+            addrs:     addrs.clone(),
+        }
+    }
+
+    #[inline]
+    pub fn get_addrs(&self) -> &Arc<RemoteAddrCache> {
+        &self.addrs
+    }
+
+    #[inline]
+    fn old_word(&self, cid: u32, slot: usize) -> *mut u64 {
+        unsafe { self.olds.add(cid as usize * MAX_CAS_LOCKS_PER_ROUTINE + slot) }
+    }
+
+    // CAS 0 -> lock_content into the slot-th old word of cid,
+    // false if it is not posted, then lock it by rpc
+    pub fn post_lock(&self, part_id: u64, offset: u64, lock_content: u64, cid: u32, slot: usize) -> bool {
+        if slot >= MAX_CAS_LOCKS_PER_ROUTINE {
+            return false;
+        }
+
+        let old = self.old_word(cid, slot);
+        unsafe { *old = u64::MAX; }
+
+        self.scheduler
+            .post_cas(part_id, old as _, offset, 0, lock_content, cid)
+            .is_ok()
+    }
+
+    // after the CAS of the slot completes
+    #[inline]
+    pub fn is_locked(&self, cid: u32, slot: usize) -> bool {
+        unsafe { *self.old_word(cid, slot) == 0 }
+    }
+}
@@ -0,0 +1,167 @@
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::framework::scheduler::AsyncScheduler;
+use crate::{GROUP_COMMIT_EPOCH_US, MAX_REQ_SIZE};
+
+use super::*;
+use super::batch_rpc_ctrl::BatchRpcCtrl;
+
+struct GroupEpoch {
+    leader:    u32,
+    members:   usize,
+    full:      bool,
+    opened_at: Instant,
+    batch:     BatchRpcCtrl,
+    peer_used: HashMap<u64, usize>,
+}
+
+struct GroupCommitInner {
+    // id of the open epoch, or of the next one
+    next_epoch: u64,
+    // all the epochs up to it are acknowledged
+    done_epoch: u64,
+    open:       Option<GroupEpoch>,
+}
+
+/// Silo-style group commit, shared by the coroutines of a thread.
+/// The remote writes of the txns committing within an epoch are batched into one
+/// GROUP_COMMIT_RPC per peer, which installs and releases them together.
+/// The first txn of an epoch leads it, and the epochs are acknowledged in order.
+pub struct GroupCommitCtrl {
+    scheduler:   Arc<AsyncScheduler>,
+    max_members: usize,
+    inner:       UnsafeCell<GroupCommitInner>,
+}
+
+// only touched by the coroutines of the same thread
+unsafe impl Send for GroupCommitCtrl {}
+unsafe impl Sync for GroupCommitCtrl {}
+
+impl GroupCommitCtrl {
+    pub fn new(scheduler: &Arc<AsyncScheduler>, max_members: usize) -> Self {
+        Self {
+            scheduler:   scheduler.clone(),
+            max_members: max_members,
+            inner:       UnsafeCell::new(GroupCommitInner {
+                next_epoch: 1,
+                done_epoch: 0,
+                open:       None,
+            }),
+        }
+    }
+
+    #[inline]
+    fn inner(&self) -> &mut GroupCommitInner {
+        unsafe { self.inner.get().as_mut().unwrap() }
+    }
+
+    #[inline]
+    fn has_room(epoch: &GroupEpoch, needs: &HashMap<u64, usize>) -> bool {
+        needs.iter().all(|(peer_id, need)| {
+            let used = epoch.peer_used.get(peer_id).copied().unwrap_or(0);
+            std::mem::size_of::<BatchRpcReqHeader>() + used + need + 4 < MAX_REQ_SIZE
+        })
+    }
+
+    // needs are the bytes of the items to each peer,
+    // joins the open epoch, or opens a new one led by cid
+    pub async fn join(&self, cid: u32, needs: &HashMap<u64, usize>) -> u64 {
+        loop {
+            let inner = self.inner();
+            match inner.open.as_mut() {
+                Some(epoch) => {
+                    if !epoch.full && Self::has_room(epoch, needs) {
+                        epoch.members += 1;
+                        for (peer_id, need) in needs.iter() {
+                            *epoch.peer_used.entry(*peer_id).or_insert(0) += need;
+                        }
+                        if epoch.members >= self.max_members {
+                            epoch.full = true;
+                        }
+                        return inner.next_epoch;
+                    }
+
+                    // flushed by its leader right away
+                    epoch.full = true;
+                }
+                None => {
+                    let mut batch = BatchRpcCtrl::new(&self.scheduler, cid);
+                    batch.restart_batch();
+
+                    let epoch = GroupEpoch {
+                        leader:    cid,
+                        members:   0,
+                        full:      false,
+                        opened_at: Instant::now(),
+                        batch:     batch,
+                        peer_used: HashMap::new(),
+                    };
+
+                    if !Self::has_room(&epoch, needs) {
+                        panic!("unsupport! the txn does not fit in an epoch");
+                    }
+
+                    inner.open = Some(epoch);
+                    continue;
+                }
+            }
+
+            self.scheduler.yield_now(cid).await;
+        }
+    }
+
+    // must follow join without yielding
+    pub fn append_write(&self, item: &GroupCommitReqItem, data_ptr: *const u8, peer_id: u64) {
+        let epoch = self.inner().open.as_mut().unwrap();
+        epoch.batch.append_req_with_data(
+            item,
+            data_ptr,
+            item.length as usize,
+            peer_id,
+            0,
+            occ_rpc_id::GROUP_COMMIT_RPC,
+        );
+    }
+
+    // returns when the epoch is acknowledged by all the peers
+    pub async fn wait(&self, cid: u32, epoch_id: u64) {
+        let inner = self.inner();
+        let is_leader = inner.next_epoch == epoch_id
+            && inner.open.as_ref().map_or(false, |epoch| epoch.leader == cid);
+
+        if !is_leader {
+            while self.inner().done_epoch < epoch_id {
+                self.scheduler.yield_now(cid).await;
+            }
+            return;
+        }
+
+        // wait for the others to join
+        let epoch_len = Duration::from_micros(GROUP_COMMIT_EPOCH_US);
+        loop {
+            let epoch = self.inner().open.as_ref().unwrap();
+            if epoch.full || epoch.opened_at.elapsed() >= epoch_len {
+                break;
+            }
+            self.scheduler.yield_now(cid).await;
+        }
+
+        // close it, the later txns go to the next epoch
+        let inner = self.inner();
+        let mut epoch = inner.open.take().unwrap();
+        inner.next_epoch += 1;
+
+        // acknowledged in order
+        while self.inner().done_epoch + 1 < epoch_id {
+            self.scheduler.yield_now(cid).await;
+        }
+
+        epoch.batch.send_batch_reqs();
+        epoch.batch.wait_until_done().await;
+
+        self.inner().done_epoch = epoch_id;
+    }
+}
@@ -2,9 +2,18 @@ pub mod batch_rpc_msg_wrapper;
 
 pub mod batch_rpc_ctrl;
 pub mod batch_rpc_proc;
+pub mod two_pl_rpc_proc;
+pub mod tictoc_rpc_proc;
+pub mod proc_rpc_proc;
+pub mod group_commit_ctrl;
+pub mod remote_addr_cache;
+pub mod cas_lock_ctrl;
 pub mod one_side_req_ctrl;
+pub mod offload_rpc_proc;
+pub mod offload_rpc_ctrl;
 
 use crate::framework::rpc::*;
+use crate::memstore::MemStoreLayout;
 
 // peer_id and cid are used by server to store info when encounter YReq
 #[repr(C)]
@@ -40,6 +49,31 @@ pub mod occ_rpc_id {
     pub const COMMIT_RPC:      Type = 5;
     pub const RELEASE_RPC:     Type = 6;
     pub const ABORT_RPC:       Type = 7;
+    pub const SNAPSHOT_READ_RPC: Type = 8;
+    // 2pl
+    pub const TPL_READ_RPC:        Type = 9;
+    pub const TPL_LOCK_RPC:        Type = 10;
+    pub const TPL_RELEASE_RPC:     Type = 11;
+    // tictoc
+    pub const TICTOC_READ_RPC:     Type = 12;
+    pub const TICTOC_LOCK_RPC:     Type = 13;
+    pub const TICTOC_VALIDATE_RPC: Type = 14;
+    pub const TICTOC_COMMIT_RPC:   Type = 15;
+    // commutative updates
+    pub const DELTA_RPC:           Type = 16;
+    // stored procedures
+    pub const PROC_RPC:            Type = 17;
+    // epoch-based group commit
+    pub const GROUP_COMMIT_RPC:    Type = 18;
+    // nodes for the one-sided primitives
+    pub const NODE_ADDR_RPC:       Type = 19;
+    pub const TABLE_LAYOUT_RPC:    Type = 25;
+    // offload, the maps of the routing peers and the records between a host and its dpu
+    pub const OFFLOAD_FREEZE_RPC:  Type = 20;
+    pub const OFFLOAD_PUBLISH_RPC: Type = 21;
+    pub const OFFLOAD_INSTALL_RPC: Type = 22;
+    pub const OFFLOAD_EXTRACT_RPC: Type = 23;
+    pub const OFFLOAD_RELEASE_RPC: Type = 24;
 }
 
 #[repr(C)]
@@ -50,6 +84,16 @@ pub struct ReadReqItem {
     pub(crate) read_idx: usize,
 }
 
+// carries the snapshot of the coordinator
+#[repr(C)]
+#[derive(Clone)]
+pub struct SnapshotReadReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) read_idx: usize,
+    pub(crate) read_ts:  u64,
+}
+
 #[repr(C)]
 #[derive(Clone)]
 pub struct ReadRespItem {
@@ -93,6 +137,40 @@ pub struct FetchWriteCacheRespItem {
 pub struct LockReqItem {
     pub(crate) table_id: usize,
     pub(crate) key:      u64,
+    pub(crate) delta:    bool, // the delta lock, shared with the other deltas
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct NodeAddrReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) idx:      usize,
+}
+
+// offset of the node in the registered region of the owner, the lock word is its head
+#[repr(C)]
+#[derive(Clone)]
+pub struct NodeAddrRespItem {
+    pub(crate) idx:    usize,
+    pub(crate) offset: u64,
+    pub(crate) valid:  bool,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TableLayoutReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) idx:      usize,
+}
+
+// the buckets of the table in the registered region of the owner
+#[repr(C)]
+#[derive(Clone)]
+pub struct TableLayoutRespItem {
+    pub(crate) idx:    usize,
+    pub(crate) layout: MemStoreLayout,
+    pub(crate) valid:  bool,
 }
 
 #[repr(C)]
@@ -106,15 +184,56 @@ pub struct ValidateReqItem {
 #[repr(C)]
 #[derive(Clone)]
 pub struct CommitReqItem {
-    pub(crate) table_id: usize,
-    pub(crate) key:      u64,
-    pub(crate) length:   u32, // flexible length, zero means erase
+    pub(crate) table_id:  usize,
+    pub(crate) key:       u64,
+    pub(crate) commit_ts: u64, // chosen by the coordinator
+    pub(crate) length:    u32, // flexible length, zero means erase
 }
 
 #[repr(C)]
 #[derive(Clone)]
 pub struct CommitCacheReqItem {
-    pub(crate) length:   u32, // flexible length, zero means erase
+    pub(crate) commit_ts: u64,
+    pub(crate) length:    u32, // flexible length, zero means erase
+    pub(crate) merge_id:  u32, // the data is a delta if it is not NO_MERGE
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct DeltaReqItem {
+    pub(crate) table_id:  usize,
+    pub(crate) key:       u64,
+    pub(crate) commit_ts: u64,
+    pub(crate) merge_id:  u32,
+    pub(crate) length:    u32, // flexible length
+}
+
+// commit and release in one, the txns of an epoch share a message
+#[repr(C)]
+#[derive(Clone)]
+pub struct GroupCommitReqItem {
+    pub(crate) table_id:  usize,
+    pub(crate) key:       u64,
+    pub(crate) commit_ts: u64, // of the txn, the ones of an epoch differ
+    pub(crate) merge_id:  u32,
+    pub(crate) length:    u32, // flexible length, zero means erase
+    pub(crate) cid:       u32, // the coordinator holding the lock
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct ProcReqItem {
+    pub(crate) proc_id: u32,
+    pub(crate) length:  u32, // flexible length of the args
+    pub(crate) idx:     usize,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct ProcRespItem {
+    pub(crate) idx:     usize,
+    pub(crate) success: bool,
+    pub(crate) length:  u32, // flexible length of the output
 }
 
 #[repr(C)]
@@ -122,6 +241,7 @@ pub struct CommitCacheReqItem {
 pub struct ReleaseReqItem {
     pub(crate) table_id: usize,
     pub(crate) key:      u64,
+    pub(crate) delta:    bool,
 }
 
 #[repr(C)]
@@ -130,6 +250,130 @@ pub struct AbortReqItem {
     pub(crate) table_id: usize,
     pub(crate) key:      u64,
     pub(crate) insert:   bool,
+    pub(crate) delta:    bool,
+}
+
+// 2pl, exclusive lock with the lock word of the txn
+#[repr(C)]
+#[derive(Clone)]
+pub struct TplLockReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) idx:      usize,
+    pub(crate) lock:     u64,
+    pub(crate) update:   bool,
+    // the shared lock of the coordinator becomes exclusive
+    pub(crate) upgrade:  bool,
+    // the delta lock instead, lock names its holder in the ledger
+    pub(crate) delta:    bool,
+}
+
+// lock is the word after locking, the value follows if acquired
+#[repr(C)]
+#[derive(Clone)]
+pub struct TplLockRespItem {
+    pub(crate) idx:    usize,
+    pub(crate) lock:   u64,
+    pub(crate) update: bool,
+    pub(crate) length: usize,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TplReleaseReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) lock:     u64,
+    pub(crate) shared:   bool,
+    pub(crate) delta:    bool,
+}
+
+// tictoc, seq is the timestamp word
+#[repr(C)]
+#[derive(Clone)]
+pub struct TicTocReadReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) idx:      usize,
+    pub(crate) update:   bool,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TicTocReadRespItem {
+    pub(crate) idx:     usize,
+    pub(crate) update:  bool,
+    pub(crate) success: bool,
+    pub(crate) seq:     u64,
+    pub(crate) length:  usize,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TicTocLockReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) idx:      usize,
+    pub(crate) update:   bool,
+    pub(crate) delta:    bool,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TicTocLockRespItem {
+    pub(crate) idx:     usize,
+    pub(crate) update:  bool,
+    pub(crate) success: bool,
+    pub(crate) seq:     u64,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TicTocValidateReqItem {
+    pub(crate) table_id:  usize,
+    pub(crate) key:       u64,
+    pub(crate) old_seq:   u64,
+    pub(crate) commit_ts: u64,
+}
+
+#[repr(C)]
+#[derive(Clone)]
+pub struct TicTocCommitReqItem {
+    pub(crate) table_id:  usize,
+    pub(crate) key:       u64,
+    pub(crate) commit_ts: u64,
+    pub(crate) length:    u32, // flexible length, zero means erase
+    pub(crate) merge_id:  u32,
+}
+
+// offload, a range of the map of a routing peer, the owner and version only to publish
+#[repr(C)]
+#[derive(Clone)]
+pub struct OffloadRangeReqItem {
+    pub(crate) range:   usize,
+    pub(crate) owner:   u8,
+    pub(crate) version: u64,
+}
+
+// a record handed over, the value follows
+#[repr(C)]
+#[derive(Clone)]
+pub struct OffloadRecordItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) seq:      u64,
+    pub(crate) length:   u32, // flexible length
+    // held by a txn, no value follows
+    pub(crate) locked:   bool,
+}
+
+// unlock the record extracted, or erase it once the other side installed it
+#[repr(C)]
+#[derive(Clone)]
+pub struct OffloadReleaseReqItem {
+    pub(crate) table_id: usize,
+    pub(crate) key:      u64,
+    pub(crate) erase:    bool,
 }
 
 #[repr(C)]
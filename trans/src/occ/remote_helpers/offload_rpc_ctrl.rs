@@ -0,0 +1,294 @@
+use std::sync::Arc;
+
+use crate::common::offload::{ offload_owner, OffloadMigrator };
+use crate::framework::scheduler::AsyncScheduler;
+use crate::memstore::memdb::MemDB;
+use crate::{ MAX_PACKET_SIZE, MAX_REQ_SIZE, MAX_RESP_SIZE, OFFLOAD_LOCK_CONTENT };
+
+use super::*;
+
+use super::batch_rpc_ctrl::BatchRpcCtrl;
+use super::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+
+// a record the host took from its dpu
+struct ExtractedRecord {
+    table_id: usize,
+    key:      u64,
+    seq:      u64,
+    value:    Vec<u64>,
+}
+
+/// Moves the records of some tables between a host and its dpu by rpcs, on a routine of
+/// a host worker, and keeps the maps of the peers routing to the partition in step with
+/// the one of the host.
+pub struct RpcMigrator {
+    memdb:     Arc<MemDB>,
+    scheduler: Arc<AsyncScheduler>,
+    cid:       u32,
+    dpu_id:    u64,
+    peers:     Vec<u64>,
+    tables:    Vec<usize>,
+}
+
+impl RpcMigrator {
+    pub fn new(memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>, cid: u32, dpu_id: u64, peers: &[u64], tables: &[usize]) -> Self {
+        Self {
+            memdb:     memdb.clone(),
+            scheduler: scheduler.clone(),
+            cid:       cid,
+            dpu_id:    dpu_id,
+            peers:     peers.to_vec(),
+            tables:    tables.to_vec(),
+        }
+    }
+
+    // of a table, what a msg takes, a reply to an extract holds as many
+    fn records_per_msg(&self, table_id: usize) -> usize {
+        let record_len = std::mem::size_of::<OffloadRecordItem>() + self.memdb.get_item_length(table_id);
+        let cap = MAX_REQ_SIZE - 8 - std::mem::size_of::<BatchRpcReqHeader>();
+        std::cmp::max(cap / record_len, 1)
+    }
+
+    async fn send_and_wait(&self, batch_rpc: &mut BatchRpcCtrl) -> Option<(*mut u8, usize)> {
+        batch_rpc.send_batch_reqs();
+        batch_rpc.wait_until_done().await;
+        batch_rpc.get_resp_buf_num()
+    }
+
+    async fn all_success(&self, batch_rpc: &mut BatchRpcCtrl) -> bool {
+        let (mut resp_buf, resp_num) = match self.send_and_wait(batch_rpc).await {
+            Some(resp) => resp,
+            None => return false,
+        };
+
+        for _ in 0..resp_num {
+            let reduce_resp = unsafe { (resp_buf as *const BatchRpcReduceResp).as_ref().unwrap() };
+            if !reduce_resp.success {
+                return false;
+            }
+            resp_buf = unsafe { resp_buf.byte_add(MAX_PACKET_SIZE) };
+        }
+        true
+    }
+
+    async fn range_to_peers(&self, rpc_id: u32, item: OffloadRangeReqItem) -> bool {
+        let mut batch_rpc = BatchRpcCtrl::new(&self.scheduler, self.cid);
+        batch_rpc.restart_batch();
+        for peer_id in self.peers.iter() {
+            batch_rpc.append_req(&item, *peer_id, 0, rpc_id);
+        }
+        self.all_success(&mut batch_rpc).await
+    }
+
+    // the dpu unlocks the records, or drops them the host installed,
+    // returns the ones of the chunks it did not ack
+    async fn release_on_dpu(&self, records: &[(usize, u64)], erase: bool) -> Vec<(usize, u64)> {
+        let mut unreleased = Vec::new();
+        for chunk in records.chunks((MAX_REQ_SIZE - 8 - std::mem::size_of::<BatchRpcReqHeader>()) / std::mem::size_of::<OffloadReleaseReqItem>()) {
+            let mut batch_rpc = BatchRpcCtrl::new(&self.scheduler, self.cid);
+            batch_rpc.restart_batch();
+            for (table_id, key) in chunk {
+                batch_rpc.append_req(
+                    &OffloadReleaseReqItem{ table_id: *table_id, key: *key, erase: erase },
+                    self.dpu_id,
+                    0,
+                    occ_rpc_id::OFFLOAD_RELEASE_RPC,
+                );
+            }
+            if !self.all_success(&mut batch_rpc).await {
+                unreleased.extend_from_slice(chunk);
+            }
+        }
+        unreleased
+    }
+
+    fn unlock_all(&self, locked: &[(usize, u64)]) {
+        for (table_id, key) in locked {
+            self.memdb.local_unlock(*table_id, *key, OFFLOAD_LOCK_CONTENT);
+        }
+    }
+
+    // lock here, install on the dpu, then erase here
+    async fn push(&self, keys: &[usize]) -> bool {
+        let mut locked = Vec::new();
+        for table_id in self.tables.iter() {
+            for key in keys {
+                let key = *key as u64;
+                // seq 0 is a record never written
+                let meta = match self.memdb.local_get_meta(*table_id, key) {
+                    Some(meta) if meta.seq != 0 => meta,
+                    _ => continue,
+                };
+                if meta.lock != 0 {
+                    self.unlock_all(&locked);
+                    return false;
+                }
+
+                match self.memdb.local_lock(*table_id, key, OFFLOAD_LOCK_CONTENT) {
+                    Some(meta) if meta.lock == OFFLOAD_LOCK_CONTENT => locked.push((*table_id, key)),
+                    _ => {
+                        self.unlock_all(&locked);
+                        return false;
+                    }
+                }
+            }
+        }
+
+        let mut installed = 0;
+        for table_id in self.tables.iter() {
+            let len = self.memdb.get_item_length(*table_id);
+            let mut value = vec![0u64; (len + 7) / 8];
+
+            let records: Vec<u64> = locked.iter().filter(|(t, _)| t == table_id).map(|(_, key)| *key).collect();
+            for chunk in records.chunks(self.records_per_msg(*table_id)) {
+                let mut batch_rpc = BatchRpcCtrl::new(&self.scheduler, self.cid);
+                batch_rpc.restart_batch();
+                for key in chunk {
+                    let ptr = value.as_mut_ptr() as *mut u8;
+                    let meta = self.memdb.local_get_readonly(*table_id, *key, ptr, len as _).unwrap();
+                    batch_rpc.append_req_with_data(
+                        &OffloadRecordItem{
+                            table_id: *table_id,
+                            key:      *key,
+                            seq:      meta.seq,
+                            length:   len as _,
+                            locked:   false,
+                        },
+                        ptr,
+                        len,
+                        self.dpu_id,
+                        0,
+                        occ_rpc_id::OFFLOAD_INSTALL_RPC,
+                    );
+                }
+
+                installed += chunk.len();
+                if !self.all_success(&mut batch_rpc).await {
+                    // the copies the dpu took are not routed to, they go if it is still there
+                    self.release_on_dpu(&locked[..installed], true).await;
+                    self.unlock_all(&locked);
+                    return false;
+                }
+            }
+        }
+
+        for (table_id, key) in locked.iter() {
+            self.memdb.local_erase(*table_id, *key);
+        }
+        true
+    }
+
+    // the dpu locks and sends the records, install here, then the dpu erases them
+    async fn pull(&self, keys: &[usize]) -> bool {
+        let mut extracted = Vec::new();
+        let mut failed = false;
+        for table_id in self.tables.iter() {
+            for chunk in keys.chunks(self.records_per_msg(*table_id)) {
+                let mut batch_rpc = BatchRpcCtrl::new(&self.scheduler, self.cid);
+                batch_rpc.restart_batch();
+                for key in chunk {
+                    batch_rpc.append_req(
+                        &LockReqItem{ table_id: *table_id, key: *key as u64, delta: false },
+                        self.dpu_id,
+                        0,
+                        occ_rpc_id::OFFLOAD_EXTRACT_RPC,
+                    );
+                }
+
+                let (resp_buf, resp_num) = match self.send_and_wait(&mut batch_rpc).await {
+                    Some(resp) => resp,
+                    None => {
+                        failed = true;
+                        break;
+                    }
+                };
+                if resp_num == 0 {
+                    continue;
+                }
+
+                let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+                let header = wrapper.get_header();
+                for _ in 0..header.num {
+                    let item = wrapper.get_item::<OffloadRecordItem>().clone();
+                    if item.locked {
+                        failed = true;
+                    } else {
+                        let mut value = vec![0u64; (item.length as usize + 7) / 8];
+                        unsafe {
+                            std::ptr::copy_nonoverlapping(
+                                wrapper.get_extra_data_const_ptr::<OffloadRecordItem>(),
+                                value.as_mut_ptr() as *mut u8,
+                                item.length as _,
+                            );
+                        }
+                        extracted.push(ExtractedRecord {
+                            table_id: item.table_id,
+                            key:      item.key,
+                            seq:      item.seq,
+                            value:    value,
+                        });
+                    }
+                    wrapper.shift_to_next_item::<OffloadRecordItem>(item.length as _);
+                }
+
+                if failed {
+                    break;
+                }
+            }
+            if failed {
+                break;
+            }
+        }
+
+        let records: Vec<(usize, u64)> = extracted.iter().map(|record| (record.table_id, record.key)).collect();
+        if failed {
+            self.release_on_dpu(&records, false).await;
+            return false;
+        }
+
+        for record in extracted.iter() {
+            let len = self.memdb.get_item_length(record.table_id);
+            self.memdb.local_lock(record.table_id, record.key, 0);
+            self.memdb.local_upd_val_set_seq(record.table_id, record.key, record.value.as_ptr() as _, len as _, record.seq);
+        }
+
+        // the copies here are the ones served from now on, a dpu copy a release missed stays
+        // locked by the extract, nothing routes to it and a later push overwrites it
+        let unreleased = self.release_on_dpu(&records, true).await;
+        if !unreleased.is_empty() {
+            self.release_on_dpu(&unreleased, true).await;
+        }
+        true
+    }
+}
+
+impl OffloadMigrator for RpcMigrator {
+    async fn freeze_peers(&self, range: usize) -> bool {
+        self.range_to_peers(occ_rpc_id::OFFLOAD_FREEZE_RPC, OffloadRangeReqItem{
+            range:   range,
+            owner:   offload_owner::STATIC,
+            version: 0,
+        }).await
+    }
+
+    async fn migrate(&self, keys: &[usize], to_dpu: bool) -> bool {
+        if to_dpu {
+            self.push(keys).await
+        } else {
+            self.pull(keys).await
+        }
+    }
+
+    // a peer missing it keeps the range frozen, its txns on it abort until the range moves again
+    async fn publish_peers(&self, range: usize, owner: offload_owner::Type, version: u64) {
+        self.range_to_peers(occ_rpc_id::OFFLOAD_PUBLISH_RPC, OffloadRangeReqItem{
+            range:   range,
+            owner:   owner,
+            version: version,
+        }).await;
+    }
+
+    async fn pause(&self) {
+        self.scheduler.yield_now(self.cid).await;
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use crate::common::offload::OffloadMap;
+use crate::framework::rpc::*;
+use crate::rdma::rcconn::RdmaRcConn;
+use crate::{ MAX_RESP_SIZE, OFFLOAD_LOCK_CONTENT };
+
+use super::*;
+
+use super::batch_rpc_proc::BatchRpcProc;
+use super::batch_rpc_msg_wrapper::BatchRpcReqWrapper;
+use super::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+
+impl BatchRpcProc {
+    fn send_reduce_reply(&self, src_conn: &mut RdmaRcConn, rpc_id: u32, success: bool, meta: RpcProcessMeta) {
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let reduce_resp = unsafe { (resp_buf as *mut BatchRpcReduceResp).as_mut().unwrap() };
+        *reduce_resp = BatchRpcReduceResp{
+            success: success
+        };
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            rpc_id,
+            std::mem::size_of::<BatchRpcReduceResp>() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    // a peer without the map routes none of the keys by it
+    pub fn offload_freeze_rpc_handler(
+        &self,
+        map: Option<&Arc<OffloadMap>>,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let req_header = req_wrapper.get_header();
+
+        let mut drained = true;
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<OffloadRangeReqItem>();
+            if let Some(map) = map {
+                drained &= map.freeze(req_item.range);
+            }
+            req_wrapper.shift_to_next_item::<OffloadRangeReqItem>(0);
+        }
+
+        self.send_reduce_reply(src_conn, occ_rpc_id::OFFLOAD_FREEZE_RPC, drained, meta);
+    }
+
+    pub fn offload_publish_rpc_handler(
+        &self,
+        map: Option<&Arc<OffloadMap>>,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<OffloadRangeReqItem>();
+            if let Some(map) = map {
+                map.publish(req_item.range, req_item.owner, req_item.version);
+            }
+            req_wrapper.shift_to_next_item::<OffloadRangeReqItem>(0);
+        }
+
+        self.send_reduce_reply(src_conn, occ_rpc_id::OFFLOAD_PUBLISH_RPC, true, meta);
+    }
+
+    // the seqs come along, so a later validation sees no change
+    pub fn offload_install_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<OffloadRecordItem>();
+            let data_len = req_item.length;
+
+            self.memdb.local_lock(req_item.table_id, req_item.key, 0);
+            self.memdb.local_upd_val_set_seq(
+                req_item.table_id,
+                req_item.key,
+                req_wrapper.get_extra_data_const_ptr::<OffloadRecordItem>(),
+                data_len,
+                req_item.seq,
+            );
+
+            req_wrapper.shift_to_next_item::<OffloadRecordItem>(data_len as _);
+        }
+
+        self.send_reduce_reply(src_conn, occ_rpc_id::OFFLOAD_INSTALL_RPC, true, meta);
+    }
+
+    // locks the records and sends them, the ones never written are left out
+    pub fn offload_extract_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        let mut resp_num = 0;
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<LockReqItem>().clone();
+            req_wrapper.shift_to_next_item::<LockReqItem>(0);
+
+            match self.memdb.local_get_meta(req_item.table_id, req_item.key) {
+                Some(node) if node.seq != 0 => {}
+                _ => continue,
+            }
+
+            let locked = self.memdb.local_lock(req_item.table_id, req_item.key, OFFLOAD_LOCK_CONTENT)
+                .map_or(true, |node| node.lock != OFFLOAD_LOCK_CONTENT);
+
+            let mut data_len = 0;
+            let mut seq = 0;
+            if !locked {
+                data_len = self.memdb.get_item_length(req_item.table_id);
+                seq = self.memdb.local_get_readonly(
+                    req_item.table_id,
+                    req_item.key,
+                    resp_wrapper.get_extra_data_raw_ptr::<OffloadRecordItem>(),
+                    data_len as _,
+                ).unwrap().seq;
+            }
+
+            resp_wrapper.set_item(OffloadRecordItem{
+                table_id: req_item.table_id,
+                key:      req_item.key,
+                seq:      seq,
+                length:   data_len as _,
+                locked:   locked,
+            });
+            resp_wrapper.shift_to_next_item::<OffloadRecordItem>(data_len);
+            resp_num += 1;
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: meta.rpc_cid,
+            num: resp_num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::OFFLOAD_EXTRACT_RPC,
+            resp_wrapper.get_off() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    pub fn offload_release_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<OffloadReleaseReqItem>();
+            if req_item.erase {
+                self.memdb.local_erase(req_item.table_id, req_item.key);
+            } else {
+                self.memdb.local_unlock(req_item.table_id, req_item.key, OFFLOAD_LOCK_CONTENT);
+            }
+            req_wrapper.shift_to_next_item::<OffloadReleaseReqItem>(0);
+        }
+
+        self.send_reduce_reply(src_conn, occ_rpc_id::OFFLOAD_RELEASE_RPC, true, meta);
+    }
+}
@@ -1,7 +1,89 @@
-struct OneSideLockReq {
+use std::alloc::Layout;
+use std::sync::Arc;
 
+use crate::framework::scheduler::AsyncScheduler;
+use crate::memstore::{MEMNODE_LOCK_OFF, MEMNODE_SEQ_OFF, MEMNODE_VALUE_OFF};
+use crate::MAX_ONE_SIDE_READS_PER_ROUTINE;
+
+use super::remote_addr_cache::RemoteAddrCache;
+
+/// Reads the nodes of the peers with RDMA READ, bypassing the remote CPU.
+/// A node is read whole, lock, seq and value, and may be torn by a concurrent commit,
+/// so the reads are validated by reading the lock and seq again after the writes are locked.
+pub struct OneSideReqCtrl {
+    scheduler: Arc<AsyncScheduler>,
+    node_size: usize,
+    // the read nodes of each routine, in the registered region
+    bufs:      *mut u8,
+    addrs:     Arc<RemoteAddrCache>,
 }
 
-struct OneSideWriteReq {
-    
-}
\ No newline at end of file
+// only touched by the coroutines of the same thread
+unsafe impl Send for OneSideReqCtrl {}
+unsafe impl Sync for OneSideReqCtrl {}
+
+impl OneSideReqCtrl {
+    pub fn new(scheduler: &Arc<AsyncScheduler>, addrs: &Arc<RemoteAddrCache>, routine_num: u32, max_item_size: usize) -> Self {
+        let node_size = (MEMNODE_VALUE_OFF + max_item_size + 7) & !7;
+        let layout = Layout::from_size_align(
+            routine_num as usize * MAX_ONE_SIDE_READS_PER_ROUTINE * node_size,
+            std::mem::align_of::<u64>(),
+        ).unwrap();
+
+        Self {
+            scheduler: scheduler.clone(),
+            node_size: node_size,
+            bufs:      scheduler.alloc_mr(layout),
+            addrs:     addrs.clone(),
+        }
+    }
+
+    #[inline]
+    pub fn get_addrs(&self) -> &Arc<RemoteAddrCache> {
+        &self.addrs
+    }
+
+    #[inline]
+    fn node_buf(&self, cid: u32, slot: usize) -> *mut u8 {
+        unsafe { self.bufs.add((cid as usize * MAX_ONE_SIDE_READS_PER_ROUTINE + slot) * self.node_size) }
+    }
+
+    // the whole node into the slot-th buffer of cid,
+    // false if it is not posted, then read it by rpc
+    pub fn post_read_node(&self, part_id: u64, offset: u64, item_len: usize, cid: u32, slot: usize) -> bool {
+        if slot >= MAX_ONE_SIDE_READS_PER_ROUTINE || MEMNODE_VALUE_OFF + item_len > self.node_size {
+            return false;
+        }
+
+        self.scheduler
+            .post_read(part_id, self.node_buf(cid, slot), (MEMNODE_VALUE_OFF + item_len) as _, offset, cid)
+            .is_ok()
+    }
+
+    // lock and seq only, for validation
+    pub fn post_read_meta(&self, part_id: u64, offset: u64, cid: u32, slot: usize) -> bool {
+        if slot >= MAX_ONE_SIDE_READS_PER_ROUTINE {
+            return false;
+        }
+
+        self.scheduler
+            .post_read(part_id, self.node_buf(cid, slot), MEMNODE_VALUE_OFF as _, offset, cid)
+            .is_ok()
+    }
+
+    // after the read of the slot completes
+    #[inline]
+    pub fn get_lock(&self, cid: u32, slot: usize) -> u64 {
+        unsafe { *(self.node_buf(cid, slot).add(MEMNODE_LOCK_OFF) as *const u64) }
+    }
+
+    #[inline]
+    pub fn get_seq(&self, cid: u32, slot: usize) -> u64 {
+        unsafe { *(self.node_buf(cid, slot).add(MEMNODE_SEQ_OFF) as *const u64) }
+    }
+
+    #[inline]
+    pub fn get_value_ptr(&self, cid: u32, slot: usize) -> *const u8 {
+        unsafe { self.node_buf(cid, slot).add(MEMNODE_VALUE_OFF) }
+    }
+}
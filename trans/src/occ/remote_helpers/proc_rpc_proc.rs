@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use crate::framework::rpc::*;
+use crate::memstore::memdb::MemDB;
+use crate::rdma::rcconn::RdmaRcConn;
+use crate::MAX_RESP_SIZE;
+
+use super::*;
+
+use super::batch_rpc_proc::BatchRpcProc;
+use super::batch_rpc_msg_wrapper::BatchRpcReqWrapper;
+use super::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+
+// run the batched procedures on the memdb, a value-less dpu forwards them to its host instead
+pub(crate) fn exec_procs(memdb: &Arc<MemDB>, tid: u32, msg: *mut u8, size: u32, resp_buf: *mut u8, cid: u32) -> usize {
+    let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+    let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+    let req_header = req_wrapper.get_header();
+
+    for _ in 0..req_header.num {
+        let req_item = req_wrapper.get_item::<ProcReqItem>().clone();
+
+        let args = unsafe {
+            std::slice::from_raw_parts(
+                req_wrapper.get_extra_data_const_ptr::<ProcReqItem>(),
+                req_item.length as _,
+            )
+        };
+
+        // the output can take up the rest of the reply
+        let out_cap = (MAX_RESP_SIZE - 4).saturating_sub(resp_wrapper.get_off() + std::mem::size_of::<ProcRespItem>());
+        let out = unsafe {
+            std::slice::from_raw_parts_mut(
+                resp_wrapper.get_extra_data_raw_ptr::<ProcRespItem>(),
+                out_cap,
+            )
+        };
+
+        let ret = match memdb.get_procedure(req_item.proc_id) {
+            Some(procedure) => procedure(memdb, tid, args, out),
+            None => {
+                println!("the procedure does not exists!");
+                None
+            }
+        };
+        let data_len = ret.unwrap_or(0);
+
+        resp_wrapper.set_item(ProcRespItem{
+            idx:     req_item.idx,
+            success: ret.is_some(),
+            length:  data_len as _,
+        });
+
+        req_wrapper.shift_to_next_item::<ProcReqItem>(req_item.length as _);
+        resp_wrapper.shift_to_next_item::<ProcRespItem>(data_len);
+    }
+
+    resp_wrapper.set_header(BatchRpcRespHeader {
+        write: false,
+        cid: cid,
+        num: req_header.num,
+    });
+
+    resp_wrapper.get_off()
+}
+
+impl BatchRpcProc {
+    pub fn proc_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let resp_len = exec_procs(&self.memdb, self.tid, msg, size, resp_buf, meta.rpc_cid);
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::PROC_RPC,
+            resp_len as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+}
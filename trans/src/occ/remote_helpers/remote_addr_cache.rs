@@ -0,0 +1,95 @@
+use std::alloc::Layout;
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::framework::scheduler::AsyncScheduler;
+use crate::memstore::MemStoreLayout;
+use crate::{MAX_PROBES_PER_ROUTINE, MAX_PROBE_WINDOW};
+
+/// Offsets of the remote nodes in the registered regions of their owners, found by
+/// reading the buckets of the exported layouts, or resolved by NODE_ADDR_RPC otherwise,
+/// and shared by the one-sided controllers of a thread.
+pub struct RemoteAddrCache {
+    scheduler: Arc<AsyncScheduler>,
+    // (part_id, table_id, key) -> offset, None if it is only accessed by rpc
+    offsets: UnsafeCell<HashMap<(u64, usize, u64), Option<u64>>>,
+    // (part_id, table_id) -> layout, None if the owner does not export it
+    layouts: UnsafeCell<HashMap<(u64, usize), Option<MemStoreLayout>>>,
+    // the bucket windows read by each routine, in the registered region
+    windows: *mut u8,
+}
+
+// only touched by the coroutines of the same thread
+unsafe impl Send for RemoteAddrCache {}
+unsafe impl Sync for RemoteAddrCache {}
+
+impl RemoteAddrCache {
+    pub fn new(scheduler: &Arc<AsyncScheduler>, routine_num: u32) -> Self {
+        let layout = Layout::from_size_align(
+            routine_num as usize * MAX_PROBES_PER_ROUTINE * MAX_PROBE_WINDOW,
+            std::mem::align_of::<u64>(),
+        ).unwrap();
+
+        Self {
+            scheduler: scheduler.clone(),
+            offsets: UnsafeCell::new(HashMap::new()),
+            layouts: UnsafeCell::new(HashMap::new()),
+            windows: scheduler.alloc_mr(layout),
+        }
+    }
+
+    #[inline]
+    fn offsets(&self) -> &mut HashMap<(u64, usize, u64), Option<u64>> {
+        unsafe { self.offsets.get().as_mut().unwrap() }
+    }
+
+    // None if it is not resolved yet
+    #[inline]
+    pub fn get_offset(&self, part_id: u64, table_id: usize, key: u64) -> Option<Option<u64>> {
+        self.offsets().get(&(part_id, table_id, key)).copied()
+    }
+
+    #[inline]
+    pub fn set_offset(&self, part_id: u64, table_id: usize, key: u64, offset: Option<u64>) {
+        self.offsets().insert((part_id, table_id, key), offset);
+    }
+
+    // None if it is not fetched yet
+    #[inline]
+    pub fn get_layout(&self, part_id: u64, table_id: usize) -> Option<Option<MemStoreLayout>> {
+        unsafe { self.layouts.get().as_ref().unwrap().get(&(part_id, table_id)).copied() }
+    }
+
+    #[inline]
+    pub fn set_layout(&self, part_id: u64, table_id: usize, layout: Option<MemStoreLayout>) {
+        unsafe { self.layouts.get().as_mut().unwrap().insert((part_id, table_id), layout) };
+    }
+
+    #[inline]
+    fn window_buf(&self, cid: u32, slot: usize) -> *mut u8 {
+        unsafe { self.windows.add((cid as usize * MAX_PROBES_PER_ROUTINE + slot) * MAX_PROBE_WINDOW) }
+    }
+
+    // the buckets key may be in into the slot-th window of cid,
+    // false if it is not posted, then resolve it by rpc
+    pub fn post_probe(&self, part_id: u64, layout: &MemStoreLayout, key: u64, cid: u32, slot: usize) -> bool {
+        let (offset, len) = match layout.window_of(key) {
+            Some(window) => window,
+            None => return false,
+        };
+        if slot >= MAX_PROBES_PER_ROUTINE || len as usize > MAX_PROBE_WINDOW {
+            return false;
+        }
+
+        self.scheduler
+            .post_read(part_id, self.window_buf(cid, slot), len as _, offset, cid)
+            .is_ok()
+    }
+
+    // after the read of the slot completes, None if the key is not in the buckets,
+    // e.g. it overflowed, then resolve it by rpc
+    pub fn probed_offset(&self, layout: &MemStoreLayout, key: u64, cid: u32, slot: usize) -> Option<u64> {
+        layout.node_in_window(self.window_buf(cid, slot), key)
+    }
+}
@@ -0,0 +1,240 @@
+use crate::framework::rpc::*;
+use crate::memstore::{is_delta_lock, NO_MERGE};
+use crate::rdma::rcconn::RdmaRcConn;
+use crate::MAX_RESP_SIZE;
+
+use super::*;
+
+use super::batch_rpc_proc::BatchRpcProc;
+use super::batch_rpc_msg_wrapper::BatchRpcReqWrapper;
+use super::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+use super::super::occ::LockContent;
+use super::super::tictoc::TicTocWord;
+
+impl BatchRpcProc {
+    pub fn tictoc_read_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TicTocReadReqItem>();
+            let mut data_len = self.memdb.get_item_length(req_item.table_id);
+
+            let meta = self.memdb.local_get_stable(
+                req_item.table_id,
+                req_item.key,
+                resp_wrapper.get_extra_data_raw_ptr::<TicTocReadRespItem>(),
+                data_len as u32,
+            ).unwrap();
+
+            let success = meta.lock == 0;
+            if !success {
+                data_len = 0;
+            }
+
+            resp_wrapper.set_item(TicTocReadRespItem{
+                idx:     req_item.idx,
+                update:  req_item.update,
+                success: success,
+                seq:     meta.seq,
+                length:  data_len,
+            });
+
+            req_wrapper.shift_to_next_item::<TicTocReadReqItem>(0);
+            resp_wrapper.shift_to_next_item::<TicTocReadRespItem>(data_len);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TICTOC_READ_RPC,
+            resp_wrapper.get_off() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    // lock and return the timestamp word under the lock
+    pub fn tictoc_lock_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+
+        let lock_content = LockContent::new(meta.peer_id, self.tid as _, meta.rpc_cid);
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TicTocLockReqItem>();
+
+            let (meta, success) = if req_item.delta {
+                let meta = self.memdb.local_lock_delta(
+                    req_item.table_id,
+                    req_item.key,
+                    lock_content.to_content(),
+                ).unwrap();
+                let success = is_delta_lock(meta.lock);
+                (meta, success)
+            } else {
+                let meta = self.memdb.local_lock(
+                    req_item.table_id,
+                    req_item.key,
+                    lock_content.to_content(),
+                ).unwrap();
+                let success = meta.lock == lock_content.to_content();
+                (meta, success)
+            };
+
+            resp_wrapper.set_item(TicTocLockRespItem{
+                idx:     req_item.idx,
+                update:  req_item.update,
+                success: success,
+                seq:     meta.seq,
+            });
+
+            req_wrapper.shift_to_next_item::<TicTocLockReqItem>(0);
+            resp_wrapper.shift_to_next_item::<TicTocLockRespItem>(0);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: true,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TICTOC_LOCK_RPC,
+            resp_wrapper.get_off() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    // extend rts of the read versions to commit_ts
+    pub fn tictoc_validate_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let req_header = req_wrapper.get_header();
+
+        // the keys the txn locked through the lock rpc of this thread
+        let lock_content = LockContent::new(meta.peer_id, self.tid as _, meta.rpc_cid);
+
+        let mut success = true;
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TicTocValidateReqItem>();
+
+            if !TicTocWord::validate_extend(
+                &self.memdb,
+                req_item.table_id,
+                req_item.key,
+                req_item.old_seq,
+                req_item.commit_ts,
+                lock_content.to_content(),
+            ) {
+                success = false;
+                break;
+            }
+
+            req_wrapper.shift_to_next_item::<TicTocValidateReqItem>(0);
+        }
+
+        let reduce_resp = unsafe { (resp_buf as *mut BatchRpcReduceResp).as_mut().unwrap() };
+        *reduce_resp = BatchRpcReduceResp{
+            success: success
+        };
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TICTOC_VALIDATE_RPC,
+            std::mem::size_of::<BatchRpcReduceResp>() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    pub fn tictoc_commit_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TicTocCommitReqItem>();
+            let data_len = req_item.length;
+
+            if data_len == 0 {
+                self.memdb.local_erase(req_item.table_id, req_item.key);
+            } else if req_item.merge_id != NO_MERGE {
+                TicTocWord::apply_delta(
+                    &self.memdb,
+                    req_item.table_id,
+                    req_item.key,
+                    req_item.merge_id,
+                    req_wrapper.get_extra_data_const_ptr::<TicTocCommitReqItem>(),
+                    data_len,
+                    req_item.commit_ts,
+                );
+            } else {
+                self.memdb.local_upd_val_set_seq(
+                    req_item.table_id,
+                    req_item.key,
+                    req_wrapper.get_extra_data_const_ptr::<TicTocCommitReqItem>(),
+                    data_len,
+                    TicTocWord::new(req_item.commit_ts, 0).to_raw(),
+                );
+            }
+
+            req_wrapper.shift_to_next_item::<TicTocCommitReqItem>(data_len as _);
+        }
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TICTOC_COMMIT_RPC,
+            0,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::framework::rpc::*;
+use crate::memstore::is_shared_lock;
+use crate::rdma::rcconn::RdmaRcConn;
+use crate::MAX_RESP_SIZE;
+
+use super::*;
+
+use super::batch_rpc_proc::BatchRpcProc;
+use super::batch_rpc_msg_wrapper::BatchRpcReqWrapper;
+use super::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+
+// 2pl handlers never block, the coordinator decides to wait or die
+impl BatchRpcProc {
+    #[inline]
+    fn keep_tpl_shared(&self, peer_id: u64, table_id: usize, key: u64) {
+        let tpl_shared = unsafe { self.tpl_shared.get().as_mut().unwrap() };
+        tpl_shared.entry(peer_id).or_default().push((table_id, key));
+    }
+
+    // one shared lock of the peer on the row is gone, released or upgraded
+    fn forget_tpl_shared(&self, peer_id: u64, table_id: usize, key: u64) {
+        let tpl_shared = unsafe { self.tpl_shared.get().as_mut().unwrap() };
+        if let Some(shared) = tpl_shared.get_mut(&peer_id) {
+            if let Some(pos) = shared.iter().position(|held| *held == (table_id, key)) {
+                shared.swap_remove(pos);
+            }
+        }
+    }
+
+    pub fn tpl_read_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+        // the row meta below shadows the one of the rpc
+        let peer_id = meta.peer_id;
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<ReadReqItem>();
+            let mut data_len = self.memdb.get_item_length(req_item.table_id);
+
+            let meta = self.memdb.local_get_shared(
+                req_item.table_id,
+                req_item.key,
+                resp_wrapper.get_extra_data_raw_ptr::<TplLockRespItem>(),
+                data_len as u32,
+            ).unwrap();
+
+            if meta.lock != 0 && !is_shared_lock(meta.lock) {
+                data_len = 0;
+            } else {
+                self.keep_tpl_shared(peer_id, req_item.table_id, req_item.key);
+            }
+
+            resp_wrapper.set_item(TplLockRespItem{
+                idx:    req_item.read_idx,
+                lock:   meta.lock,
+                update: false,
+                length: data_len,
+            });
+
+            req_wrapper.shift_to_next_item::<ReadReqItem>(0);
+            resp_wrapper.shift_to_next_item::<TplLockRespItem>(data_len);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: false,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TPL_READ_RPC,
+            resp_wrapper.get_off() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    pub fn tpl_lock_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+        let mut resp_wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE - 4);
+
+        let req_header = req_wrapper.get_header();
+        let peer_id = meta.peer_id;
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TplLockReqItem>();
+            let mut data_len = self.memdb.get_item_length(req_item.table_id);
+
+            // insert if absent, as blind writes lock here too
+            let meta = if req_item.delta {
+                self.memdb.local_lock_delta(
+                    req_item.table_id,
+                    req_item.key,
+                    req_item.lock,
+                ).unwrap()
+            } else if req_item.upgrade {
+                self.memdb.local_upgrade_shared(
+                    req_item.table_id,
+                    req_item.key,
+                    req_item.lock,
+                ).unwrap()
+            } else {
+                self.memdb.local_lock(
+                    req_item.table_id,
+                    req_item.key,
+                    req_item.lock,
+                ).unwrap()
+            };
+
+            if req_item.delta {
+                // a blind delta reads nothing, the word tells if it is acquired
+                data_len = 0;
+            } else if meta.lock == req_item.lock {
+                if req_item.upgrade {
+                    self.forget_tpl_shared(peer_id, req_item.table_id, req_item.key);
+                }
+                self.memdb.local_get_readonly(
+                    req_item.table_id,
+                    req_item.key,
+                    resp_wrapper.get_extra_data_raw_ptr::<TplLockRespItem>(),
+                    data_len as u32,
+                );
+            } else {
+                data_len = 0;
+            }
+
+            resp_wrapper.set_item(TplLockRespItem{
+                idx:    req_item.idx,
+                lock:   meta.lock,
+                update: req_item.update,
+                length: data_len,
+            });
+
+            req_wrapper.shift_to_next_item::<TplLockReqItem>(0);
+            resp_wrapper.shift_to_next_item::<TplLockRespItem>(data_len);
+        }
+
+        resp_wrapper.set_header(BatchRpcRespHeader {
+            write: true,
+            cid: meta.rpc_cid,
+            num: req_header.num,
+        });
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TPL_LOCK_RPC,
+            resp_wrapper.get_off() as _,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+
+    pub fn tpl_release_rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta
+    ) {
+        let mut req_wrapper = BatchRpcReqWrapper::new(msg, size as _);
+        let resp_buf = self.scheduler.get_reply_buf(0);
+
+        let req_header = req_wrapper.get_header();
+
+        for _ in 0..req_header.num {
+            let req_item = req_wrapper.get_item::<TplReleaseReqItem>();
+
+            if req_item.shared {
+                self.memdb.local_unlock_shared(req_item.table_id, req_item.key);
+                self.forget_tpl_shared(meta.peer_id, req_item.table_id, req_item.key);
+            } else if req_item.delta {
+                self.memdb.local_unlock_delta(req_item.table_id, req_item.key, req_item.lock);
+            } else {
+                self.memdb.local_unlock(req_item.table_id, req_item.key, 0);
+            }
+
+            req_wrapper.shift_to_next_item::<TplReleaseReqItem>(0);
+        }
+
+        self.scheduler.send_reply(
+            src_conn,
+            resp_buf,
+            occ_rpc_id::TPL_RELEASE_RPC,
+            0,
+            meta.rpc_cid,
+            meta.peer_id,
+            meta.peer_tid
+        );
+    }
+}
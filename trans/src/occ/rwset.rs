@@ -5,6 +5,8 @@ pub enum RwType {
     INSERT,
     UPDATE,
     ERASE,
+    // commutative update, merged on the owner with the merge id
+    DELTA(u32),
 }
 
 // seq = { local_seq, node_id }
@@ -69,4 +71,9 @@ impl<const ITEM_MAX_SIZE: usize> RwSet<ITEM_MAX_SIZE>
     pub fn get_len(&self) -> usize {
         self.items.len()
     }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,649 @@
+use std::sync::Arc;
+
+use byte_struct::*;
+
+use crate::memstore::memdb::MemDB;
+use crate::memstore::MemStoreValue;
+use crate::memstore::{is_delta_lock, NO_MERGE};
+use crate::framework::scheduler::AsyncScheduler;
+use crate::common::partition::Partitioner;
+use crate::common::offload::OffloadPin;
+use crate::MAX_RESP_SIZE;
+
+use super::occ::{LockContent, MemStoreItemEnum, OccStatus};
+use super::rwset::{RwSet, RwItem, RwType};
+use super::remote_helpers::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+use super::remote_helpers::batch_rpc_ctrl::BatchRpcCtrl;
+use super::remote_helpers::*;
+
+// the seq of a node, rts = wts + delta
+bitfields!(
+    pub TicTocWord: u64 {
+        pub delta: 16,
+        pub wts:   48,
+    }
+);
+
+const TICTOC_MAX_DELTA: u64 = 0xffff;
+
+impl TicTocWord {
+    pub fn new(wts: u64, delta: u64) -> Self {
+        Self {
+            delta: delta,
+            wts:   wts,
+        }
+    }
+
+    #[inline]
+    pub fn rts(&self) -> u64 {
+        self.wts + self.delta
+    }
+
+    // wts moves forward if the delta overflows
+    pub fn extend(&self, commit_ts: u64) -> Self {
+        if commit_ts - self.wts > TICTOC_MAX_DELTA {
+            Self::new(commit_ts - TICTOC_MAX_DELTA, TICTOC_MAX_DELTA)
+        } else {
+            Self::new(self.wts, commit_ts - self.wts)
+        }
+    }
+
+    // the read version stays valid until commit_ts, own_lock is the lock of the validating txn,
+    // a key it also writes needs no extension as the txn holds it until commit_ts
+    pub(crate) fn validate_extend(memdb: &MemDB, table_id: usize, key: u64, old_seq: u64, commit_ts: u64, own_lock: u64) -> bool {
+        let old = TicTocWord::from_raw(old_seq);
+
+        loop {
+            let meta = memdb.local_get_meta(table_id, key).unwrap();
+            let now = TicTocWord::from_raw(meta.seq);
+
+            if now.wts != old.wts {
+                return false;
+            }
+            if now.rts() >= commit_ts || meta.lock == own_lock {
+                return true;
+            }
+            if meta.lock != 0 {
+                return false;
+            }
+
+            let new_seq = now.extend(commit_ts).to_raw();
+            let after = memdb.local_cas_seq(table_id, key, meta.seq, new_seq).unwrap();
+            if after.seq == new_seq {
+                // a writer may have taken the old rts under its lock
+                return after.lock == 0;
+            }
+        }
+    }
+
+    // merge the delta under the delta lock, then move wts up to commit_ts,
+    // the other holders of the lock may have moved it past already
+    pub(crate) fn apply_delta(memdb: &MemDB, table_id: usize, key: u64, merge_id: u32, ptr: *const u8, len: u32, commit_ts: u64) {
+        memdb.local_apply_delta_set_seq(table_id, key, merge_id, ptr, len, TicTocWord::new(commit_ts, 0).to_raw());
+    }
+}
+
+/// TicTok-style timestamp ordering, the commit timestamp is computed from
+/// the read and write sets instead of a global counter.
+pub struct TicToc<const MAX_ITEM_SIZE: usize>
+{
+    status:    OccStatus,
+    part_id:   u64,
+    tid:       u32,
+    cid:       u32,
+    memdb:     Arc<MemDB>,
+    batch_rpc: BatchRpcCtrl,
+    readset:   RwSet<MAX_ITEM_SIZE>,
+    updateset: RwSet<MAX_ITEM_SIZE>,
+    writeset:  RwSet<MAX_ITEM_SIZE>,
+    commit_ts: u64,
+    pins:      Vec<OffloadPin>,
+}
+
+impl<const MAX_ITEM_SIZE: usize> TicToc<MAX_ITEM_SIZE>
+{
+    pub fn new(part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        Self {
+            status:    OccStatus::OccUnint,
+            part_id:   part_id,
+            tid:       tid,
+            cid:       cid,
+            memdb:     memdb.clone(),
+            batch_rpc: BatchRpcCtrl::new(scheduler, cid),
+            readset:   RwSet::new(),
+            updateset: RwSet::new(),
+            writeset:  RwSet::new(),
+            commit_ts: 0,
+            pins:      Vec::new(),
+        }
+    }
+
+    fn read_on<T: MemStoreValue>(&mut self, update: bool, table_id: usize, part_id: u64, key: u64) -> usize {
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.readset
+        };
+        let idx = ref_set.get_len();
+
+        if part_id == self.part_id {
+            // local
+            let mut value = T::default();
+            let ptr = &mut value as *mut T as *mut u8;
+            let len = std::mem::size_of::<T>();
+            let meta = self.memdb.local_get_stable(table_id, key, ptr, len as _).unwrap();
+
+            if meta.lock != 0 {
+                self.status = OccStatus::OccMustabort;
+            }
+
+            ref_set.push(RwItem::new(
+                table_id,
+                part_id,
+                if update { RwType::UPDATE } else { RwType::READ },
+                key,
+                MemStoreItemEnum::from_raw(value),
+                meta.seq
+            ));
+        } else {
+            // remote
+            let remote_req = TicTocReadReqItem{
+                table_id: table_id,
+                key:      key,
+                idx:      idx,
+                update:   update,
+            };
+            self.batch_rpc.append_req::<TicTocReadReqItem>(
+                &remote_req,
+                part_id,
+                0,
+                occ_rpc_id::TICTOC_READ_RPC
+            );
+
+            // pending
+            ref_set.push(RwItem::new(
+                table_id,
+                part_id,
+                if update { RwType::UPDATE } else { RwType::READ },
+                key,
+                MemStoreItemEnum::default(),
+                0
+            ));
+        }
+
+        idx
+    }
+
+    fn process_read_resp(&mut self) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            for _ in 0..header.num {
+                let item = wrapper.get_item::<TicTocReadRespItem>();
+                let raw_data = wrapper.get_extra_data_const_ptr::<TicTocReadRespItem>();
+
+                if !item.success {
+                    self.status = OccStatus::OccMustabort;
+                }
+
+                let bucket = if item.update {
+                    self.updateset.bucket(item.idx)
+                } else {
+                    self.readset.bucket(item.idx)
+                };
+                bucket.seq = item.seq;
+                bucket.value.set_raw_data(raw_data, item.length as _);
+
+                wrapper.shift_to_next_item::<TicTocReadRespItem>(item.length);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+
+    fn process_lock_resp(&mut self) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            for _ in 0..header.num {
+                let item = wrapper.get_item::<TicTocLockRespItem>();
+
+                if !item.success {
+                    self.status = OccStatus::OccMustabort;
+                } else if item.update {
+                    let bucket = self.updateset.bucket(item.idx);
+                    // the version read must be the locked one
+                    if TicTocWord::from_raw(bucket.seq).wts != TicTocWord::from_raw(item.seq).wts {
+                        self.status = OccStatus::OccMustabort;
+                    }
+                    bucket.seq = item.seq;
+                } else {
+                    self.writeset.bucket(item.idx).seq = item.seq;
+                }
+
+                wrapper.shift_to_next_item::<TicTocLockRespItem>(0);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+
+    fn process_batch_rpc_reduce_resp(&mut self) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+        for _ in 0..resp_num {
+            let reduce_resp = unsafe { (resp_buf as *const BatchRpcReduceResp).as_ref().unwrap() };
+            if !reduce_resp.success {
+                self.status = OccStatus::OccMustabort;
+                break;
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+}
+
+impl<const MAX_ITEM_SIZE: usize> TicToc<MAX_ITEM_SIZE>
+{
+    fn lock_on(&mut self, update: bool) {
+        let lock_content = LockContent::new(self.part_id, self.tid, self.cid);
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+            let delta = matches!(item.rwtype, RwType::DELTA(_));
+
+            if item.part_id == self.part_id {
+                // local, the deltas of the txns share the lock
+                let meta = if delta {
+                    self.memdb.local_lock_delta(item.table_id, item.key, lock_content.to_content()).unwrap()
+                } else {
+                    self.memdb.local_lock(item.table_id, item.key, lock_content.to_content()).unwrap()
+                };
+                let locked = if delta {
+                    is_delta_lock(meta.lock)
+                } else {
+                    meta.lock == lock_content.to_content()
+                };
+
+                if !locked {
+                    self.status = OccStatus::OccMustabort;
+                    continue;
+                }
+                if update && TicTocWord::from_raw(item.seq).wts != TicTocWord::from_raw(meta.seq).wts {
+                    self.status = OccStatus::OccMustabort;
+                }
+                item.seq = meta.seq;
+            } else {
+                // remote
+                let remote_req = TicTocLockReqItem{
+                    table_id: item.table_id,
+                    key:      item.key,
+                    idx:      i,
+                    update:   update,
+                    delta:    delta,
+                };
+
+                self.batch_rpc.append_req::<TicTocLockReqItem>(
+                    &remote_req,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::TICTOC_LOCK_RPC
+                );
+            }
+        }
+    }
+
+    async fn lock_writes(&mut self) {
+        self.batch_rpc.restart_batch();
+        self.lock_on(true);
+        self.lock_on(false);
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+        self.process_lock_resp();
+    }
+
+    // after all locks are held, the rts of the writes are stable
+    fn compute_commit_ts(&mut self) {
+        let mut commit_ts = 0;
+
+        for i in 0..self.readset.get_len() {
+            let word = TicTocWord::from_raw(self.readset.bucket(i).seq);
+            commit_ts = commit_ts.max(word.wts);
+        }
+
+        for i in 0..self.updateset.get_len() {
+            let word = TicTocWord::from_raw(self.updateset.bucket(i).seq);
+            commit_ts = commit_ts.max(word.rts() + 1);
+        }
+
+        for i in 0..self.writeset.get_len() {
+            let word = TicTocWord::from_raw(self.writeset.bucket(i).seq);
+            commit_ts = commit_ts.max(word.rts() + 1);
+        }
+
+        self.commit_ts = commit_ts;
+    }
+
+    async fn validate(&mut self) {
+        self.batch_rpc.restart_batch();
+
+        for i in 0..self.readset.get_len() {
+            let item = self.readset.bucket(i);
+            if TicTocWord::from_raw(item.seq).rts() >= self.commit_ts {
+                continue;
+            }
+
+            if item.part_id == self.part_id {
+                // local
+                let own_lock = LockContent::new(self.part_id, self.tid, self.cid).to_content();
+                if !TicTocWord::validate_extend(&self.memdb, item.table_id, item.key, item.seq, self.commit_ts, own_lock) {
+                    self.status = OccStatus::OccMustabort;
+                    break;
+                }
+            } else {
+                // remote
+                let remote_req = TicTocValidateReqItem{
+                    table_id:  item.table_id,
+                    key:       item.key,
+                    old_seq:   item.seq,
+                    commit_ts: self.commit_ts,
+                };
+
+                self.batch_rpc.append_req::<TicTocValidateReqItem>(
+                    &remote_req,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::TICTOC_VALIDATE_RPC
+                );
+            }
+        }
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+        self.process_batch_rpc_reduce_resp();
+    }
+
+    fn commit_writes_on(&mut self, update: bool) {
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+        let new_seq = TicTocWord::new(self.commit_ts, 0).to_raw();
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+
+            if item.part_id == self.part_id {
+                // local
+                match item.rwtype {
+                    RwType::ERASE => {
+                        self.memdb.local_erase(item.table_id, item.key);
+                    }
+                    RwType::INSERT | RwType::UPDATE => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_upd_val_set_seq(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, new_seq);
+                    }
+                    RwType::DELTA(merge_id) => {
+                        TicTocWord::apply_delta(
+                            &self.memdb,
+                            item.table_id,
+                            item.key,
+                            merge_id,
+                            item.value.get_raw_ptr(),
+                            item.value.get_length(),
+                            self.commit_ts,
+                        );
+                    }
+                    _ => {}
+                }
+            } else {
+                // remote
+                let length = match item.rwtype {
+                    RwType::ERASE => 0,
+                    _ => item.value.get_length(),
+                };
+                let merge_id = match item.rwtype {
+                    RwType::DELTA(merge_id) => merge_id,
+                    _ => NO_MERGE,
+                };
+                let remote_req = TicTocCommitReqItem{
+                    table_id:  item.table_id,
+                    key:       item.key,
+                    commit_ts: self.commit_ts,
+                    length:    length,
+                    merge_id:  merge_id,
+                };
+
+                self.batch_rpc.append_req_with_data(
+                    &remote_req,
+                    item.value.get_raw_ptr(),
+                    length as usize,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::TICTOC_COMMIT_RPC,
+                );
+            }
+        }
+    }
+
+    async fn commit_writes(&mut self) {
+        self.batch_rpc.restart_batch();
+        self.commit_writes_on(true);
+        self.commit_writes_on(false);
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+    }
+
+    fn release_on(&mut self, update: bool, aborted: bool) {
+        let lock_content = LockContent::new(self.part_id, self.tid, self.cid);
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+            let insert = aborted && item.rwtype == RwType::INSERT;
+            let delta = matches!(item.rwtype, RwType::DELTA(_));
+
+            if item.part_id == self.part_id {
+                // local
+                if insert {
+                    self.memdb.local_erase(item.table_id, item.key);
+                } else if delta {
+                    self.memdb.local_unlock_delta(item.table_id, item.key, lock_content.to_content());
+                } else {
+                    self.memdb.local_try_unlock(item.table_id, item.key, lock_content.to_content());
+                }
+            } else if aborted {
+                let remote_req = AbortReqItem{
+                    table_id: item.table_id,
+                    key:      item.key,
+                    insert:   insert,
+                    delta:    delta,
+                };
+
+                self.batch_rpc.append_req::<AbortReqItem>(
+                    &remote_req,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::ABORT_RPC
+                );
+            } else {
+                let remote_req = ReleaseReqItem{
+                    table_id: item.table_id,
+                    key:      item.key,
+                    delta:    delta,
+                };
+
+                self.batch_rpc.append_req::<ReleaseReqItem>(
+                    &remote_req,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::RELEASE_RPC
+                );
+            }
+        }
+    }
+
+    async fn release(&mut self, aborted: bool) {
+        self.batch_rpc.restart_batch();
+        self.release_on(true, aborted);
+        self.release_on(false, aborted);
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+    }
+}
+
+impl<const MAX_ITEM_SIZE: usize> TicToc<MAX_ITEM_SIZE>
+{
+    pub fn start(&mut self) {
+        self.pins.clear();
+        self.batch_rpc.restart_batch();
+        self.status = OccStatus::OccInprogress;
+    }
+
+    // as OccRemote::place
+    pub fn place(&mut self, placement: &dyn Partitioner, key: u64) -> u64 {
+        if !placement.pin(key as _, &mut self.pins) {
+            self.status = OccStatus::OccMustabort;
+        }
+        placement.part_of(key as _) as _
+    }
+
+    pub fn read<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        self.read_on::<T>(false, table_id, part_id, key)
+    }
+
+    // read now, lock at commit
+    pub fn fetch_write<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        self.read_on::<T>(true, table_id, part_id, key)
+    }
+
+    pub fn write<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, rwtype: RwType) -> usize {
+        let write_idx = self.writeset.get_len();
+
+        // lock later
+        let item = RwItem::new(
+            table_id,
+            part_id,
+            rwtype,
+            key,
+            MemStoreItemEnum::default(),
+            0
+        );
+
+        self.writeset.push(item);
+
+        write_idx
+    }
+
+    // locked with the other deltas, only the read is saved
+    pub fn apply_delta<D: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, merge_id: u32, delta: &D) -> usize {
+        let write_idx = self.write::<D>(table_id, part_id, key, RwType::DELTA(merge_id));
+        self.set_value(false, write_idx, delta);
+
+        write_idx
+    }
+
+    pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, update: bool, idx: usize) -> &'trans T {
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+
+        self.process_read_resp();
+        self.batch_rpc.restart_batch();
+
+        if update {
+            return self.updateset.bucket(idx).value.get_inner();
+        } else {
+            return self.readset.bucket(idx).value.get_inner();
+        }
+    }
+
+    pub fn set_value<T: MemStoreValue>(&mut self, update: bool, idx: usize, value: &T) {
+        if update {
+            self.updateset.bucket(idx).value.set_inner(value);
+        } else {
+            self.writeset.bucket(idx).value.set_inner(value);
+        }
+    }
+
+    pub async fn commit(&mut self) {
+        // reads never waited by get_value
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+        self.process_read_resp();
+
+        if self.status.eq(&OccStatus::OccMustabort) {
+            self.status = OccStatus::OccAborted;
+            return;
+        }
+
+        self.lock_writes().await;
+        if self.status.eq(&OccStatus::OccMustabort) {
+            return self.abort().await;
+        }
+
+        self.compute_commit_ts();
+
+        self.validate().await;
+        if self.status.eq(&OccStatus::OccMustabort) {
+            return self.abort().await;
+        }
+
+        self.commit_writes().await;
+
+        self.release(false).await;
+
+        self.status = OccStatus::OccCommited;
+    }
+
+    pub async fn abort(&mut self) {
+        self.release(true).await;
+
+        self.status = OccStatus::OccAborted;
+    }
+
+    #[inline]
+    pub fn is_aborted(&self) -> bool {
+        self.status.eq(&OccStatus::OccAborted)
+    }
+
+    #[inline]
+    pub fn is_commited(&self) -> bool {
+        self.status.eq(&OccStatus::OccCommited)
+    }
+}
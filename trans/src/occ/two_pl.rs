@@ -0,0 +1,692 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use byte_struct::*;
+
+use crate::memstore::memdb::MemDB;
+use crate::memstore::{is_delta_lock, is_shared_lock, MemStoreValue, SHARED_LOCK_FLAG};
+use crate::framework::scheduler::AsyncScheduler;
+use crate::common::partition::Partitioner;
+use crate::common::offload::OffloadPin;
+use crate::MAX_RESP_SIZE;
+
+use super::occ::{MemStoreItemEnum, OccStatus};
+use super::rwset::{RwSet, RwItem, RwType};
+use super::remote_helpers::batch_rpc_msg_wrapper::BatchRpcRespWrapper;
+use super::remote_helpers::batch_rpc_ctrl::BatchRpcCtrl;
+use super::remote_helpers::*;
+
+// exclusive lock word of a 2pl txn, a smaller word is an older txn.
+// the top bit is left for shared locks.
+bitfields!(
+    pub TplTimestamp: u64 {
+        pub cid:     10,
+        pub tid:     10,
+        pub peer_id: 10,
+        pub clock:   33,
+        pub shared:  1,
+    }
+);
+
+impl TplTimestamp {
+    pub fn new(peer_id: u64, tid: u32, cid: u32) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Self {
+            cid:     cid as _,
+            tid:     tid as _,
+            peer_id: peer_id,
+            clock:   (now.as_micros() as u64 >> 10) & ((1 << 33) - 1),
+            shared:  0,
+        }
+    }
+
+    // shared words count their holders, they name none
+    pub fn peer_of(word: u64) -> Option<u64> {
+        if word & SHARED_LOCK_FLAG != 0 {
+            return None;
+        }
+        Some(Self::from_raw(word).peer_id)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TplPolicy {
+    // abort on any conflict
+    NoWait,
+    // older txns wait for younger holders, younger ones abort
+    WaitDie,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TplSet {
+    Read,
+    Update,
+    Write,
+}
+
+/// Two-phase locking, locks are taken on access and released after commit.
+/// Shared holders are anonymous, so WAIT_DIE aborts when it meets them.
+/// A write of a key read before upgrades the shared lock of the read.
+pub struct TwoPl<const MAX_ITEM_SIZE: usize>
+{
+    status:      OccStatus,
+    policy:      TplPolicy,
+    part_id:     u64,
+    cid:         u32,
+    memdb:       Arc<MemDB>,
+    scheduler:   Arc<AsyncScheduler>,
+    batch_rpc:   BatchRpcCtrl,
+    readset:     RwSet<MAX_ITEM_SIZE>,
+    updateset:   RwSet<MAX_ITEM_SIZE>,
+    writeset:    RwSet<MAX_ITEM_SIZE>,
+    lock_word:   u64,
+    read_held:   Vec<bool>,
+    update_held: Vec<bool>,
+    write_held:  Vec<bool>,
+    // the read each write upgrades
+    update_upgrade: Vec<Option<usize>>,
+    write_upgrade:  Vec<Option<usize>>,
+    // upgrades issued once their reads settle
+    upgrades:    Vec<(TplSet, usize)>,
+    waits:       Vec<(TplSet, usize)>,
+    pins:        Vec<OffloadPin>,
+}
+
+impl<const MAX_ITEM_SIZE: usize> TwoPl<MAX_ITEM_SIZE>
+{
+    pub fn new(policy: TplPolicy, part_id: u64, tid: u32, cid: u32, memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>) -> Self {
+        Self {
+            status:      OccStatus::OccUnint,
+            policy:      policy,
+            part_id:     part_id,
+            cid:         cid,
+            memdb:       memdb.clone(),
+            scheduler:   scheduler.clone(),
+            batch_rpc:   BatchRpcCtrl::new(scheduler, cid),
+            readset:     RwSet::new(),
+            updateset:   RwSet::new(),
+            writeset:    RwSet::new(),
+            lock_word:   TplTimestamp::new(part_id, tid, cid).to_raw(),
+            read_held:   Vec::new(),
+            update_held: Vec::new(),
+            write_held:  Vec::new(),
+            update_upgrade: Vec::new(),
+            write_upgrade:  Vec::new(),
+            upgrades:    Vec::new(),
+            waits:       Vec::new(),
+            pins:        Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn should_wait(&self, holder: u64) -> bool {
+        match self.policy {
+            TplPolicy::NoWait => false,
+            TplPolicy::WaitDie => holder & SHARED_LOCK_FLAG == 0 && self.lock_word < holder,
+        }
+    }
+
+    #[inline]
+    fn on_conflict(&mut self, set: TplSet, idx: usize, holder: u64) {
+        if self.should_wait(holder) {
+            self.waits.push((set, idx));
+        } else {
+            self.status = OccStatus::OccMustabort;
+        }
+    }
+
+    #[inline]
+    fn upgrade_of(&self, set: TplSet, idx: usize) -> Option<usize> {
+        match set {
+            TplSet::Read => None,
+            TplSet::Update => self.update_upgrade[idx],
+            TplSet::Write => self.write_upgrade[idx],
+        }
+    }
+
+    // a blind delta takes the delta lock, one on a read row upgrades it
+    #[inline]
+    fn is_delta(&mut self, set: TplSet, idx: usize) -> bool {
+        set == TplSet::Write
+            && matches!(self.writeset.bucket(idx).rwtype, RwType::DELTA(_))
+            && self.write_upgrade[idx].is_none()
+    }
+
+    // the held shared lock to upgrade
+    #[inline]
+    fn held_upgrade_of(&self, set: TplSet, idx: usize) -> Option<usize> {
+        self.upgrade_of(set, idx).filter(|read_idx| self.read_held[*read_idx])
+    }
+
+    fn find_read(&mut self, table_id: usize, part_id: u64, key: u64) -> Option<usize> {
+        let readset = &mut self.readset;
+        (0..readset.get_len()).find(|i| {
+            let item = readset.bucket(*i);
+            item.table_id == table_id && item.part_id == part_id && item.key == key
+        })
+    }
+
+    #[inline]
+    fn part_of(&mut self, set: TplSet, idx: usize) -> u64 {
+        match set {
+            TplSet::Read => self.readset.bucket(idx).part_id,
+            TplSet::Update => self.updateset.bucket(idx).part_id,
+            TplSet::Write => self.writeset.bucket(idx).part_id,
+        }
+    }
+
+    fn local_acquire(&mut self, set: TplSet, idx: usize) {
+        let mut buf = [0u8; MAX_ITEM_SIZE];
+        let upgrade = self.held_upgrade_of(set, idx);
+        let delta = self.is_delta(set, idx);
+
+        let item = match set {
+            TplSet::Read => self.readset.bucket(idx),
+            TplSet::Update => self.updateset.bucket(idx),
+            TplSet::Write => self.writeset.bucket(idx),
+        };
+        let len = self.memdb.get_item_length(item.table_id);
+
+        let (acquired, holder) = if set == TplSet::Read {
+            let meta = self.memdb.local_get_shared(item.table_id, item.key, buf.as_mut_ptr(), len as _).unwrap();
+            (meta.lock == 0 || is_shared_lock(meta.lock), meta.lock)
+        } else if delta {
+            let meta = self.memdb.local_lock_delta(item.table_id, item.key, self.lock_word).unwrap();
+            (is_delta_lock(meta.lock), meta.lock)
+        } else {
+            // insert if absent, as blind writes lock here too
+            let meta = match upgrade {
+                Some(_) => self.memdb.local_upgrade_shared(item.table_id, item.key, self.lock_word).unwrap(),
+                None => self.memdb.local_lock(item.table_id, item.key, self.lock_word).unwrap(),
+            };
+            let acquired = meta.lock == self.lock_word;
+            if acquired && set == TplSet::Update {
+                self.memdb.local_get_readonly(item.table_id, item.key, buf.as_mut_ptr(), len as _);
+            }
+            (acquired, meta.lock)
+        };
+
+        if !acquired {
+            return self.on_conflict(set, idx, holder);
+        }
+
+        if set != TplSet::Write {
+            item.value.set_raw_data(buf.as_ptr(), len as _);
+        }
+
+        match set {
+            TplSet::Read => self.read_held[idx] = holder != 0,
+            TplSet::Update => self.update_held[idx] = true,
+            TplSet::Write => self.write_held[idx] = true,
+        }
+        if let Some(read_idx) = upgrade {
+            self.read_held[read_idx] = false;
+        }
+    }
+
+    fn remote_acquire(&mut self, set: TplSet, idx: usize) {
+        let upgrade = self.held_upgrade_of(set, idx).is_some();
+        let delta = self.is_delta(set, idx);
+        let item = match set {
+            TplSet::Read => self.readset.bucket(idx),
+            TplSet::Update => self.updateset.bucket(idx),
+            TplSet::Write => self.writeset.bucket(idx),
+        };
+
+        if set == TplSet::Read {
+            let remote_req = ReadReqItem{
+                table_id: item.table_id,
+                key:      item.key,
+                read_idx: idx,
+            };
+            self.batch_rpc.append_req::<ReadReqItem>(
+                &remote_req,
+                item.part_id,
+                0,
+                occ_rpc_id::TPL_READ_RPC
+            );
+        } else {
+            let remote_req = TplLockReqItem{
+                table_id: item.table_id,
+                key:      item.key,
+                idx:      idx,
+                lock:     self.lock_word,
+                update:   set == TplSet::Update,
+                upgrade:  upgrade,
+                delta:    delta,
+            };
+            self.batch_rpc.append_req::<TplLockReqItem>(
+                &remote_req,
+                item.part_id,
+                0,
+                occ_rpc_id::TPL_LOCK_RPC
+            );
+        }
+    }
+
+    fn acquire(&mut self, set: TplSet, idx: usize, part_id: u64) {
+        if part_id == self.part_id {
+            self.local_acquire(set, idx);
+        } else {
+            self.remote_acquire(set, idx);
+        }
+    }
+
+    // a write upgrading a read in flight waits for the read to settle
+    fn acquire_write(&mut self, set: TplSet, idx: usize, part_id: u64) {
+        match self.upgrade_of(set, idx) {
+            Some(read_idx) if part_id != self.part_id || self.waits.contains(&(TplSet::Read, read_idx)) => {
+                self.upgrades.push((set, idx));
+            }
+            _ => self.acquire(set, idx, part_id),
+        }
+    }
+
+    // the reads not waiting have settled, true if any upgrade is issued
+    fn issue_upgrades(&mut self) -> bool {
+        let upgrades = std::mem::take(&mut self.upgrades);
+        let mut issued = false;
+
+        for (set, idx) in upgrades {
+            let read_idx = self.upgrade_of(set, idx).unwrap();
+            if self.waits.contains(&(TplSet::Read, read_idx)) {
+                self.upgrades.push((set, idx));
+            } else {
+                let part_id = self.part_of(set, idx);
+                self.acquire(set, idx, part_id);
+                issued = true;
+            }
+        }
+        issued
+    }
+
+    fn process_lock_resp(&mut self) {
+        let (mut resp_buf, resp_num) = match self.batch_rpc.get_resp_buf_num() {
+            Some(resp) => resp,
+            None => {
+                self.status = OccStatus::OccMustabort;
+                return;
+            }
+        };
+
+        for _ in 0..resp_num {
+            let mut wrapper = BatchRpcRespWrapper::new(resp_buf, MAX_RESP_SIZE);
+            let header = wrapper.get_header();
+
+            for _ in 0..header.num {
+                let item = wrapper.get_item::<TplLockRespItem>().clone();
+                let raw_data = wrapper.get_extra_data_const_ptr::<TplLockRespItem>();
+
+                let set = if !header.write {
+                    TplSet::Read
+                } else if item.update {
+                    TplSet::Update
+                } else {
+                    TplSet::Write
+                };
+
+                let acquired = match set {
+                    TplSet::Read => item.lock == 0 || is_shared_lock(item.lock),
+                    _ if self.is_delta(set, item.idx) => is_delta_lock(item.lock),
+                    _ => item.lock == self.lock_word,
+                };
+
+                if acquired {
+                    if let Some(read_idx) = self.upgrade_of(set, item.idx) {
+                        self.read_held[read_idx] = false;
+                    }
+                    match set {
+                        TplSet::Read => {
+                            self.readset.bucket(item.idx).value.set_raw_data(raw_data, item.length as _);
+                            self.read_held[item.idx] = item.lock != 0;
+                        }
+                        TplSet::Update => {
+                            self.updateset.bucket(item.idx).value.set_raw_data(raw_data, item.length as _);
+                            self.update_held[item.idx] = true;
+                        }
+                        TplSet::Write => {
+                            self.write_held[item.idx] = true;
+                        }
+                    }
+                } else {
+                    self.on_conflict(set, item.idx, item.lock);
+                }
+
+                wrapper.shift_to_next_item::<TplLockRespItem>(item.length);
+            }
+
+            resp_buf = unsafe { resp_buf.byte_add(crate::MAX_PACKET_SIZE) };
+        }
+    }
+
+    // until all the issued locks are acquired or the txn must abort
+    async fn acquire_pending(&mut self) {
+        loop {
+            self.batch_rpc.send_batch_reqs();
+            self.batch_rpc.wait_until_done().await;
+
+            self.process_lock_resp();
+            self.batch_rpc.restart_batch();
+
+            let issued = self.status.ne(&OccStatus::OccMustabort) && self.issue_upgrades();
+
+            if self.status.eq(&OccStatus::OccMustabort) {
+                self.waits.clear();
+                self.upgrades.clear();
+                break;
+            }
+            if self.waits.is_empty() {
+                if issued {
+                    continue;
+                }
+                break;
+            }
+
+            self.scheduler.yield_now(self.cid).await;
+
+            let waits = std::mem::take(&mut self.waits);
+            for (set, idx) in waits {
+                let part_id = self.part_of(set, idx);
+                self.acquire(set, idx, part_id);
+            }
+        }
+    }
+}
+
+impl<const MAX_ITEM_SIZE: usize> TwoPl<MAX_ITEM_SIZE>
+{
+    fn commit_writes_on(&mut self, update: bool, commit_ts: u64) {
+        let ref_set = if update {
+            &mut self.updateset
+        } else {
+            &mut self.writeset
+        };
+
+        for i in 0..ref_set.get_len() {
+            let item = ref_set.bucket(i);
+
+            if item.part_id == self.part_id {
+                // local
+                match item.rwtype {
+                    RwType::ERASE => {
+                        self.memdb.local_erase(item.table_id, item.key);
+                    }
+                    RwType::INSERT | RwType::UPDATE => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_upd_val_seq_at(item.table_id, item.key, raw, MAX_ITEM_SIZE as u32, commit_ts);
+                    }
+                    RwType::DELTA(merge_id) => {
+                        let raw = item.value.get_raw_ptr();
+                        self.memdb.local_apply_delta(item.table_id, item.key, merge_id, raw, item.value.get_length(), commit_ts);
+                    }
+                    _ => {}
+                }
+            } else if let RwType::DELTA(merge_id) = item.rwtype {
+                // remote
+                let length = item.value.get_length();
+                let remote_req = DeltaReqItem{
+                    table_id:  item.table_id,
+                    key:       item.key,
+                    commit_ts: commit_ts,
+                    merge_id:  merge_id,
+                    length:    length,
+                };
+
+                self.batch_rpc.append_req_with_data(
+                    &remote_req,
+                    item.value.get_raw_ptr(),
+                    length as usize,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::DELTA_RPC,
+                );
+            } else {
+                // remote
+                let length = match item.rwtype {
+                    RwType::ERASE => 0,
+                    _ => item.value.get_length(),
+                };
+                let remote_req = CommitReqItem{
+                    table_id:  item.table_id,
+                    key:       item.key,
+                    commit_ts: commit_ts,
+                    length:    length,
+                };
+
+                self.batch_rpc.append_req_with_data(
+                    &remote_req,
+                    item.value.get_raw_ptr(),
+                    length as usize,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::COMMIT_RPC,
+                );
+            }
+        }
+    }
+
+    async fn commit_writes(&mut self) {
+        self.batch_rpc.restart_batch();
+        let commit_ts = self.memdb.alloc_commit_ts();
+        self.commit_writes_on(true, commit_ts);
+        self.commit_writes_on(false, commit_ts);
+        self.memdb.finish_commit_ts(commit_ts);
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+    }
+
+    fn release_on(&mut self, set: TplSet, aborted: bool) {
+        let (ref_set, held) = match set {
+            TplSet::Read => (&mut self.readset, &self.read_held),
+            TplSet::Update => (&mut self.updateset, &self.update_held),
+            TplSet::Write => (&mut self.writeset, &self.write_held),
+        };
+
+        for i in 0..ref_set.get_len() {
+            if !held[i] {
+                continue;
+            }
+
+            let item = ref_set.bucket(i);
+            let shared = set == TplSet::Read;
+            let insert = aborted && item.rwtype == RwType::INSERT;
+            // as is_delta
+            let delta = set == TplSet::Write
+                && matches!(item.rwtype, RwType::DELTA(_))
+                && self.write_upgrade[i].is_none();
+
+            if item.part_id == self.part_id {
+                // local
+                if shared {
+                    self.memdb.local_unlock_shared(item.table_id, item.key);
+                } else if delta {
+                    self.memdb.local_unlock_delta(item.table_id, item.key, self.lock_word);
+                } else if insert {
+                    self.memdb.local_erase(item.table_id, item.key);
+                } else {
+                    self.memdb.local_unlock(item.table_id, item.key, self.lock_word);
+                }
+            } else if insert {
+                let remote_req = AbortReqItem{
+                    table_id: item.table_id,
+                    key:      item.key,
+                    insert:   true,
+                    delta:    false,
+                };
+
+                self.batch_rpc.append_req::<AbortReqItem>(
+                    &remote_req,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::ABORT_RPC
+                );
+            } else {
+                let remote_req = TplReleaseReqItem{
+                    table_id: item.table_id,
+                    key:      item.key,
+                    lock:     self.lock_word,
+                    shared:   shared,
+                    delta:    delta,
+                };
+
+                self.batch_rpc.append_req::<TplReleaseReqItem>(
+                    &remote_req,
+                    item.part_id,
+                    0,
+                    occ_rpc_id::TPL_RELEASE_RPC
+                );
+            }
+        }
+    }
+
+    async fn release(&mut self, aborted: bool) {
+        self.batch_rpc.restart_batch();
+        self.release_on(TplSet::Read, aborted);
+        self.release_on(TplSet::Update, aborted);
+        self.release_on(TplSet::Write, aborted);
+
+        self.batch_rpc.send_batch_reqs();
+        self.batch_rpc.wait_until_done().await;
+    }
+}
+
+impl<const MAX_ITEM_SIZE: usize> TwoPl<MAX_ITEM_SIZE>
+{
+    // an aborted txn starts again with its lock word, so WAIT_DIE ages it
+    // until it is the oldest and no longer dies
+    pub fn start(&mut self) {
+        self.pins.clear();
+        self.batch_rpc.restart_batch();
+        self.readset.clear();
+        self.updateset.clear();
+        self.writeset.clear();
+        self.read_held.clear();
+        self.update_held.clear();
+        self.write_held.clear();
+        self.update_upgrade.clear();
+        self.write_upgrade.clear();
+        self.upgrades.clear();
+        self.waits.clear();
+        self.status = OccStatus::OccInprogress;
+    }
+
+    // as OccRemote::place
+    pub fn place(&mut self, placement: &dyn Partitioner, key: u64) -> u64 {
+        if !placement.pin(key as _, &mut self.pins) {
+            self.status = OccStatus::OccMustabort;
+        }
+        placement.part_of(key as _) as _
+    }
+
+    pub fn read<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        let read_idx = self.readset.get_len();
+
+        self.readset.push(RwItem::new(
+            table_id,
+            part_id,
+            RwType::READ,
+            key,
+            MemStoreItemEnum::from_raw(T::default()),
+            0
+        ));
+        self.read_held.push(false);
+
+        self.acquire(TplSet::Read, read_idx, part_id);
+        read_idx
+    }
+
+    pub fn fetch_write<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64) -> usize {
+        let update_idx = self.updateset.get_len();
+
+        self.updateset.push(RwItem::new(
+            table_id,
+            part_id,
+            RwType::UPDATE,
+            key,
+            MemStoreItemEnum::from_raw(T::default()),
+            0
+        ));
+        self.update_held.push(false);
+        let read_idx = self.find_read(table_id, part_id, key);
+        self.update_upgrade.push(read_idx);
+
+        self.acquire_write(TplSet::Update, update_idx, part_id);
+        update_idx
+    }
+
+    // blind writes are locked on access as well
+    pub fn write<T: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, rwtype: RwType) -> usize {
+        let write_idx = self.writeset.get_len();
+
+        self.writeset.push(RwItem::new(
+            table_id,
+            part_id,
+            rwtype,
+            key,
+            MemStoreItemEnum::default(),
+            0
+        ));
+        self.write_held.push(false);
+        let read_idx = self.find_read(table_id, part_id, key);
+        self.write_upgrade.push(read_idx);
+
+        self.acquire_write(TplSet::Write, write_idx, part_id);
+        write_idx
+    }
+
+    // the delta lock is taken unless the row is read, only the read is saved
+    pub fn apply_delta<D: MemStoreValue>(&mut self, table_id: usize, part_id: u64, key: u64, merge_id: u32, delta: &D) -> usize {
+        let write_idx = self.write::<D>(table_id, part_id, key, RwType::DELTA(merge_id));
+        self.set_value(false, write_idx, delta);
+
+        write_idx
+    }
+
+    pub async fn get_value<'trans, T: MemStoreValue + 'trans>(&mut self, update: bool, idx: usize) -> &'trans T {
+        self.acquire_pending().await;
+
+        if update {
+            return self.updateset.bucket(idx).value.get_inner();
+        } else {
+            return self.readset.bucket(idx).value.get_inner();
+        }
+    }
+
+    pub fn set_value<T: MemStoreValue>(&mut self, update: bool, idx: usize, value: &T) {
+        if update {
+            self.updateset.bucket(idx).value.set_inner(value);
+        } else {
+            self.writeset.bucket(idx).value.set_inner(value);
+        }
+    }
+
+    pub async fn commit(&mut self) {
+        self.acquire_pending().await;
+        if self.status.eq(&OccStatus::OccMustabort) {
+            return self.abort().await;
+        }
+
+        self.commit_writes().await;
+
+        self.release(false).await;
+
+        self.status = OccStatus::OccCommited;
+    }
+
+    pub async fn abort(&mut self) {
+        self.release(true).await;
+
+        self.status = OccStatus::OccAborted;
+    }
+
+    #[inline]
+    pub fn is_aborted(&self) -> bool {
+        self.status.eq(&OccStatus::OccAborted)
+    }
+
+    #[inline]
+    pub fn is_commited(&self) -> bool {
+        self.status.eq(&OccStatus::OccCommited)
+    }
+}
@@ -1,36 +1,64 @@
 use std::alloc::Layout;
 use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use errno::errno;
+use errno::{errno, Errno};
 use libc::{ free, memalign };
 use ll_alloc::LockedHeap;
 use rdma_sys::*;
 
+use super::device::RdmaDeviceConfig;
 use super::rcconn::RdmaRcConn;
+use super::srq::RdmaSharedRecvQueue;
+use super::udadapter::{RdmaUdAdapter, RdmaUdAddr};
 
-use crate::{TransError, TransResult, MAX_RECV_SIZE, MAX_SEND_SIZE, NPAGES, PEERNUMS};
+use crate::common::cluster::ClusterConfig;
+use crate::common::connection::{recv_config, send_config};
+use crate::{TransError, TransResult, MAX_RECV_SIZE, MAX_SEND_SIZE, NPAGES};
+
+// a restarted peer may take a while to listen again, it is dialed once per interval
+const RECONNECT_INTERVAL_MS: u64 = 100;
 
 pub struct RdmaBaseAllocator {
     allocator: LockedHeap,
-    lm: *mut u8
+    lm: *mut u8,
+    len: usize,
 }
 
+// the heap is locked, the region is only freed on drop
+unsafe impl Send for RdmaBaseAllocator {}
+unsafe impl Sync for RdmaBaseAllocator {}
+
 impl RdmaBaseAllocator {
     pub fn new() -> Self {
-        let mr_length = 4096 * NPAGES as usize;
+        Self::with_pages(NPAGES as _)
+    }
+
+    // e.g. a region shared by the threads of a node, with its tables in it
+    pub fn with_pages(npages: usize) -> Self {
+        let mr_length = 4096 * npages;
         let lm = unsafe { memalign(4096, mr_length) };
-        let allocator = unsafe { LockedHeap::new(lm as _, (NPAGES * 4096) as usize) };
+        let allocator = unsafe { LockedHeap::new(lm as _, mr_length) };
         Self {
             allocator: allocator,
-            lm: lm as _
+            lm: lm as _,
+            len: mr_length,
         }
     }
 
-    fn get_lm(&self) -> *mut u8 {
+    // base of the registered region, the tables placed in it are exported to the peers
+    pub fn get_lm(&self) -> *mut u8 {
         self.lm
     }
 
+    #[inline]
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
     #[inline]
     pub unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         self.allocator.alloc(layout)
@@ -48,32 +76,208 @@ impl Drop for RdmaBaseAllocator {
     }
 }
 
+/// A failed call of the connection setup, with the errno it left, or the status of
+/// the failed completion, and the address it was for, so that a misconfigured
+/// deployment fails with a clear message.
+#[derive(Debug)]
+pub struct RdmaSetupError {
+    pub call: &'static str,
+    pub errno: i32,
+    pub wc_status: Option<ibv_wc_status::Type>,
+    pub addr: String,
+}
+
+impl RdmaSetupError {
+    fn new(call: &'static str, errno: i32, addr: &str) -> TransError {
+        TransError::TransSetupError(Self {
+            call: call,
+            errno: errno,
+            wc_status: None,
+            addr: addr.to_string(),
+        })
+    }
+
+    // right after the failed call, before errno is overwritten
+    fn last(call: &'static str, addr: &str) -> TransError {
+        Self::new(call, errno().0, addr)
+    }
+
+    // a completion with an error, errno says nothing then
+    fn failed_wc(call: &'static str, status: ibv_wc_status::Type, addr: &str) -> TransError {
+        TransError::TransSetupError(Self {
+            call: call,
+            errno: 0,
+            wc_status: Some(status),
+            addr: addr.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for RdmaSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.wc_status {
+            Some(status) => write!(f, "{} failed for {}, wc status = {}", self.call, self.addr, status),
+            None => write!(f, "{} failed for {}, errno = {} ({})", self.call, self.addr, self.errno, Errno(self.errno)),
+        }
+    }
+}
+
+// ip:port without the nul terminators of rdma cm
+fn format_addr(ip: &str, port: &str) -> String {
+    format!("{}:{}", ip.trim_end_matches('\0'), port.trim_end_matches('\0'))
+}
+
 #[derive(Clone, Copy, Debug)]
 struct RemoteMeta {
     peer_id: u64,
     raddr: u64,
+    rlen: u64,
     rid: u32,
 }
 
 pub struct RdmaControl {
     self_id: u64,
     listen_fd: Option<*mut rdma_cm_id>,
+    listen_addr: String,
     connections: HashMap<u64, Arc<Mutex<RdmaRcConn>>>,
     allocator: Arc<RdmaBaseAllocator>,
+    // all the connections share one receive queue, created with the first qp
+    use_srq: bool,
+    srq: Option<Arc<Mutex<RdmaSharedRecvQueue>>>,
+    // the nic port of the thread
+    device: RdmaDeviceConfig,
+    // the last dial of each broken peer
+    redials: HashMap<u64, Instant>,
 }
 
 impl RdmaControl {
     pub fn new(self_id: u64) -> Self {
-        let allocator = Arc::new(RdmaBaseAllocator::new());
+        Self::new_on(self_id, &Arc::new(RdmaBaseAllocator::new()))
+    }
 
+    // the connections register the given region, e.g. one shared by the threads of a node
+    pub fn new_on(self_id: u64, allocator: &Arc<RdmaBaseAllocator>) -> Self {
         Self {
             self_id: self_id,
             listen_fd: None,
+            listen_addr: String::new(),
             connections: HashMap::new(),
-            allocator: allocator,
+            allocator: allocator.clone(),
+            use_srq: false,
+            srq: None,
+            device: RdmaDeviceConfig::default(),
+            redials: HashMap::new(),
         }
     }
 
+    // before init and connect, the connections are then set up on the port
+    pub fn set_device(&mut self, device: RdmaDeviceConfig) {
+        self.device = device;
+    }
+
+    #[inline]
+    pub fn get_device(&self) -> &RdmaDeviceConfig {
+        &self.device
+    }
+
+    // the device rdma cm resolved for id must be the configured one
+    fn check_device(&self, id: *mut rdma_cm_id, addr: &str) -> TransResult<()> {
+        if !self.device.matches(unsafe { (*id).verbs }, unsafe { (*id).port_num }) {
+            return Err(RdmaSetupError::new("device selection", libc::ENODEV, addr));
+        }
+        Ok(())
+    }
+
+    // the local ip of the device to dial from, none if not configured
+    fn resolve_src_addr(&self) -> TransResult<*mut rdma_addrinfo> {
+        let ip = match self.device.ip.as_ref() {
+            Some(ip) => format!("{}\0", ip),
+            None => return Ok(std::ptr::null_mut()),
+        };
+
+        let mut hints = unsafe { std::mem::zeroed::<rdma_addrinfo>() };
+        let mut res: *mut rdma_addrinfo = std::ptr::null_mut();
+        hints.ai_flags = RAI_PASSIVE.try_into().unwrap();
+        hints.ai_port_space = rdma_port_space::RDMA_PS_TCP as i32;
+        let ret = unsafe { rdma_getaddrinfo(ip.as_ptr().cast(), std::ptr::null(), &hints, &mut res) };
+        if ret != 0 {
+            return Err(RdmaSetupError::last("rdma_getaddrinfo", &ip));
+        }
+        Ok(res)
+    }
+
+    // before init and connect, the qps are then created on the srq. It needs the pd of
+    // the first connection, its recvs are posted when that one creates it
+    pub fn enable_srq(&mut self) {
+        self.use_srq = true;
+    }
+
+    pub fn get_srq(&self) -> Option<Arc<Mutex<RdmaSharedRecvQueue>>> {
+        self.srq.clone()
+    }
+
+    // the qp of id on the srq, whose buffers are registered by lmr
+    fn create_shared_qp(&mut self, id: *mut rdma_cm_id, lmr: *mut ibv_mr, addr: &str) -> TransResult<()> {
+        if self.srq.is_none() {
+            let srq = RdmaSharedRecvQueue::new(id, lmr, &self.allocator)?;
+            self.srq = Some(Arc::new(Mutex::new(srq)));
+        }
+
+        let mut init_attr = Self::default_init_attr();
+        {
+            let srq = self.srq.as_ref().unwrap().lock().unwrap();
+            init_attr.srq = srq.get_srq();
+            init_attr.recv_cq = srq.get_cq();
+        }
+
+        let ret = unsafe { rdma_create_qp(id, (*id).pd, &mut init_attr) };
+        if ret != 0 {
+            return Err(RdmaSetupError::last("rdma_create_qp", addr));
+        }
+
+        Ok(())
+    }
+
+    // the recv of the remote meta, with a srq it lands in a buffer of the ring
+    fn post_meta_recv(&self, id: *mut rdma_cm_id, recv_addr: *mut u8, lmr: *mut ibv_mr, addr: &str) -> TransResult<()> {
+        let len = std::mem::size_of::<RemoteMeta>();
+        if self.use_srq {
+            return Ok(());
+        }
+
+        let ret = unsafe { rdma_post_recv(id, recv_addr as _, recv_addr as _, len, lmr) };
+        if ret != 0 {
+            return Err(RdmaSetupError::last("rdma_post_recv", addr));
+        }
+        Ok(())
+    }
+
+    fn wait_meta_recv(&self, id: *mut rdma_cm_id, recv_addr: *mut u8, addr: &str) -> TransResult<()> {
+        if self.use_srq {
+            let qp_num = unsafe { (*(*id).qp).qp_num };
+            let len = std::mem::size_of::<RemoteMeta>();
+            let status = self.srq.as_ref().unwrap().lock().unwrap().wait_handshake(qp_num, recv_addr, len)?;
+            if status != ibv_wc_status::IBV_WC_SUCCESS {
+                return Err(RdmaSetupError::failed_wc("ibv_poll_cq", status, addr));
+            }
+            return Ok(());
+        }
+
+        let mut wc = unsafe { std::mem::zeroed::<ibv_wc>() };
+        let mut ret = 0;
+        while ret == 0 {
+            ret = unsafe { rdma_get_recv_comp(id, &mut wc) };
+        }
+
+        if ret < 0 {
+            return Err(RdmaSetupError::last("rdma_get_recv_comp", addr));
+        }
+        if wc.status != ibv_wc_status::IBV_WC_SUCCESS {
+            return Err(RdmaSetupError::failed_wc("rdma_get_recv_comp", wc.status, addr));
+        }
+        Ok(())
+    }
+
     #[inline]
     fn default_init_attr() -> ibv_qp_init_attr {
         let mut init_attr = unsafe { std::mem::zeroed::<ibv_qp_init_attr>() };
@@ -87,7 +291,8 @@ impl RdmaControl {
         init_attr
     }
 
-    pub fn init(&mut self, server_ip: &str, port: &str) {
+    pub fn init(&mut self, server_ip: &str, port: &str) -> TransResult<()> {
+        let addr = format_addr(server_ip, port);
         let mut hints = unsafe { std::mem::zeroed::<rdma_addrinfo>() };
         let mut res: *mut rdma_addrinfo = std::ptr::null_mut();
         hints.ai_flags = RAI_PASSIVE.try_into().unwrap();
@@ -102,100 +307,175 @@ impl RdmaControl {
         };
 
         if ret != 0 {
-            println!("rdma_getaddrinfo");
+            return Err(RdmaSetupError::last("rdma_getaddrinfo", &addr));
         }
 
         let mut listen_id = std::ptr::null_mut();
 
         let mut init_attr = Self::default_init_attr();
-        ret = unsafe { rdma_create_ep(&mut listen_id, res, std::ptr::null_mut(), &mut init_attr) };
-        // Check to see if we got inline data allowed or not
+        // with a srq, the qps of the requests are created on accept
+        let init_attr_ptr = if self.use_srq { std::ptr::null_mut() } else { &mut init_attr as *mut _ };
+        ret = unsafe { rdma_create_ep(&mut listen_id, res, std::ptr::null_mut(), init_attr_ptr) };
         if ret != 0 {
-            println!("rdma_create_ep");
-            unsafe {
-                rdma_freeaddrinfo(res);
-            }
+            let err = RdmaSetupError::last("rdma_create_ep", &addr);
+            unsafe { rdma_freeaddrinfo(res); }
+            return Err(err);
         }
-        ret = unsafe { rdma_listen(listen_id, 10) };
+        unsafe { rdma_freeaddrinfo(res); }
 
-        println!("rdma listen sucessfully");
+        ret = unsafe { rdma_listen(listen_id, 10) };
         if ret != 0 {
-            println!("rdma_listen");
-            unsafe {
-                rdma_destroy_ep(listen_id);
-            }
+            let err = RdmaSetupError::last("rdma_listen", &addr);
+            unsafe { rdma_destroy_ep(listen_id); }
+            return Err(err);
         }
 
+        println!("rdma listen sucessfully");
         self.listen_fd = Some(listen_id);
+        self.listen_addr = addr;
+        Ok(())
     }
 
     pub fn connect(&mut self, peer_id: u64, ip: &str, port: &str) -> TransResult<()> {
+        let addr = format_addr(ip, port);
         let mut hints = unsafe { std::mem::zeroed::<rdma_addrinfo>() };
         let mut res: *mut rdma_addrinfo = std::ptr::null_mut();
 
         hints.ai_port_space = rdma_port_space::RDMA_PS_TCP as i32;
+        let src_res = self.resolve_src_addr()?;
+        if !src_res.is_null() {
+            hints.ai_src_addr = unsafe { (*src_res).ai_src_addr };
+            hints.ai_src_len = unsafe { (*src_res).ai_src_len };
+        }
+
         let mut ret =
             unsafe { rdma_getaddrinfo(ip.as_ptr().cast(), port.as_ptr().cast(), &hints, &mut res) };
-
         if ret != 0 {
-            println!("rdma_getaddrinfo");
-            return Err(TransError::TransRdmaError);
+            let err = RdmaSetupError::last("rdma_getaddrinfo", &addr);
+            if !src_res.is_null() {
+                unsafe { rdma_freeaddrinfo(src_res); }
+            }
+            return Err(err);
+        }
+        if !src_res.is_null() {
+            unsafe { rdma_freeaddrinfo(src_res); }
         }
 
         let mut attr = Self::default_init_attr();
         let mut id: *mut rdma_cm_id = std::ptr::null_mut();
 
-        ret = unsafe { rdma_create_ep(&mut id, res, std::ptr::null_mut(), &mut attr) };
-
+        let attr_ptr = if self.use_srq { std::ptr::null_mut() } else { &mut attr as *mut _ };
+        ret = unsafe { rdma_create_ep(&mut id, res, std::ptr::null_mut(), attr_ptr) };
         if ret != 0 {
-            println!("rdma_create_ep");
-            return Err(TransError::TransRdmaError);
+            let err = RdmaSetupError::last("rdma_create_ep", &addr);
+            unsafe { rdma_freeaddrinfo(res); }
+            return Err(err);
         }
+        unsafe { rdma_freeaddrinfo(res); }
+
+        let lmr = match self.check_device(id, &addr).and_then(|_| self.setup_qp(id, &addr)) {
+            Ok(lmr) => lmr,
+            Err(err) => {
+                unsafe { rdma_destroy_ep(id); }
+                return Err(err);
+            }
+        };
+
+        let remote = match self.exchange_meta(id, lmr, &addr, true) {
+            Ok(remote) => remote,
+            Err(err) => {
+                Self::release_ep(id, lmr);
+                return Err(err);
+            }
+        };
 
-        // bind mr and send meta
-        // let mr_length = 4096 * NPAGES as usize;
-        // let lm = unsafe { memalign(4096, mr_length) };
-        let mr_length = 4096 * NPAGES as usize;
+        println!("connect successfully!");
+        println!("{:}:{:}:{:}", remote.peer_id, remote.raddr, remote.rid);
+
+        let connection = self.new_connection(peer_id, id, lmr, &remote);
+        self.install_connection(peer_id, connection)
+    }
+
+    // registers the region on the pd of id, and creates the qp on the srq if shared
+    fn setup_qp(&mut self, id: *mut rdma_cm_id, addr: &str) -> TransResult<*mut ibv_mr> {
+        let mr_length = self.allocator.get_len();
         let lm = self.allocator.get_lm() as _;
         let access = ibv_access_flags::IBV_ACCESS_LOCAL_WRITE.0
             | ibv_access_flags::IBV_ACCESS_REMOTE_READ.0
-            | ibv_access_flags::IBV_ACCESS_REMOTE_WRITE.0;
+            | ibv_access_flags::IBV_ACCESS_REMOTE_WRITE.0
+            | ibv_access_flags::IBV_ACCESS_REMOTE_ATOMIC.0;
         let lmr = unsafe { ibv_reg_mr((*id).pd, lm, mr_length, access as _) };
+        if lmr.is_null() {
+            return Err(RdmaSetupError::last("ibv_reg_mr", addr));
+        }
 
+        if self.use_srq {
+            if let Err(err) = self.create_shared_qp(id, lmr, addr) {
+                unsafe { rdma_dereg_mr(lmr); }
+                return Err(err);
+            }
+        }
+        Ok(lmr)
+    }
+
+    // the qp and the region of a connection that failed to set up
+    fn release_ep(id: *mut rdma_cm_id, lmr: *mut ibv_mr) {
+        unsafe {
+            rdma_dereg_mr(lmr);
+            rdma_destroy_ep(id);
+        }
+    }
+
+    // sends the meta of the local region and receives the remote one,
+    // the active side connects and the passive side accepts in between.
+    // it stays on the qp instead of the framed config exchange of the ud bootstrap:
+    // the meta is fixed size, the cm already handshakes the peers and a lost
+    // one fails the completion, a tcp port per peer would buy nothing
+    fn exchange_meta(&self, id: *mut rdma_cm_id, lmr: *mut ibv_mr, addr: &str, active: bool) -> TransResult<RemoteMeta> {
         let send_recv_layout = Layout::from_size_align(
             std::mem::size_of::<RemoteMeta>(),
             std::mem::align_of::<RemoteMeta>(),
         )
         .unwrap();
         let send_addr = unsafe { self.allocator.alloc(send_recv_layout) };
+        let recv_addr = unsafe { self.allocator.alloc(send_recv_layout) };
+
+        let remote = self.post_and_wait_meta(id, lmr, addr, active, send_addr, recv_addr);
+
+        unsafe {
+            self.allocator.dealloc(send_addr, send_recv_layout);
+            self.allocator.dealloc(recv_addr, send_recv_layout);
+        }
+        remote
+    }
 
+    fn post_and_wait_meta(
+        &self,
+        id: *mut rdma_cm_id,
+        lmr: *mut ibv_mr,
+        addr: &str,
+        active: bool,
+        send_addr: *mut u8,
+        recv_addr: *mut u8,
+    ) -> TransResult<RemoteMeta> {
         unsafe {
             *(send_addr as *mut RemoteMeta) = RemoteMeta {
                 peer_id: self.self_id,
                 raddr: (*lmr).addr as u64,
+                rlen: (*lmr).length as u64,
                 rid: (*lmr).rkey,
             };
         }
 
-        let recv_addr = unsafe { self.allocator.alloc(send_recv_layout) };
-        ret = unsafe {
-            rdma_post_recv(
-                id,
-                recv_addr as _,
-                recv_addr as _,
-                std::mem::size_of::<RemoteMeta>(),
-                lmr,
-            )
-        };
-        if ret != 0 {
-            println!("rdma_post_recv");
-            return Err(TransError::TransRdmaError);
-        }
+        self.post_meta_recv(id, recv_addr, lmr, addr)?;
 
-        ret = unsafe { rdma_connect(id, std::ptr::null_mut()) };
+        let mut ret = if active {
+            unsafe { rdma_connect(id, std::ptr::null_mut()) }
+        } else {
+            unsafe { rdma_accept(id, std::ptr::null_mut()) }
+        };
         if ret != 0 {
-            println!("rdma_connect, errno = {}", errno());
-            return Err(TransError::TransRdmaError);
+            return Err(RdmaSetupError::last(if active { "rdma_connect" } else { "rdma_accept" }, addr));
         }
 
         ret = unsafe {
@@ -210,8 +490,7 @@ impl RdmaControl {
         };
 
         if ret != 0 {
-            println!("rdma_post_send");
-            return Err(TransError::TransRdmaError);
+            return Err(RdmaSetupError::last("rdma_post_send", addr));
         }
 
         let mut wc = unsafe { std::mem::zeroed::<ibv_wc>() };
@@ -219,178 +498,238 @@ impl RdmaControl {
             ret = unsafe { rdma_get_send_comp(id, &mut wc) };
         }
         if ret < 0 {
-            println!("rdma_get_send_comp");
-            return Err(TransError::TransRdmaError);
+            return Err(RdmaSetupError::last("rdma_get_send_comp", addr));
         }
-
-        ret = 0;
-        while ret == 0 {
-            ret = unsafe { rdma_get_recv_comp(id, &mut wc) };
+        if wc.status != ibv_wc_status::IBV_WC_SUCCESS {
+            return Err(RdmaSetupError::failed_wc("rdma_get_send_comp", wc.status, addr));
         }
 
-        println!("connect successfully!");
-        let recv_data = unsafe { *(recv_addr as *mut RemoteMeta) };
-        println!(
-            "{:}:{:}:{:}",
-            recv_data.peer_id, recv_data.raddr, recv_data.rid
-        );
+        self.wait_meta_recv(id, recv_addr, addr)?;
 
-        let connection = RdmaRcConn::new(
+        Ok(unsafe { *(recv_addr as *mut RemoteMeta) })
+    }
+
+    fn new_connection(&self, peer_id: u64, id: *mut rdma_cm_id, lmr: *mut ibv_mr, remote: &RemoteMeta) -> RdmaRcConn {
+        let mut connection = RdmaRcConn::new(
             peer_id,
             id,
-            lm as *mut u8,
+            self.allocator.get_lm(),
             lmr,
-            recv_data.raddr,
-            recv_data.rid,
+            remote.raddr,
+            remote.rlen,
+            remote.rid,
             &self.allocator,
         );
+        if self.use_srq {
+            connection.set_shared_recv();
+        }
+        connection
+    }
 
-        unsafe {
-            self.allocator.dealloc(send_addr, send_recv_layout);
-            self.allocator.dealloc(recv_addr, send_recv_layout);
+    // a restarted peer is re-established in place, the schedulers hold its connection
+    fn install_connection(&mut self, peer_id: u64, connection: RdmaRcConn) -> TransResult<()> {
+        match self.connections.get(&peer_id) {
+            Some(old) => old.lock().unwrap().reestablish(connection),
+            None => {
+                self.connections.insert(peer_id, Arc::new(Mutex::new(connection)));
+                Ok(())
+            }
         }
+    }
 
-        self.connections
-            .insert(peer_id, Arc::new(Mutex::new(connection)));
+    // dials a restarted peer again unless it was dialed within the interval, true once
+    // it listens. Called by the thread polling the connections, with a srq the handshake
+    // is otherwise taken by its poller
+    pub fn try_reconnect(&mut self, peer_id: u64, ip: &str, port: &str) -> bool {
+        let interval = Duration::from_millis(RECONNECT_INTERVAL_MS);
+        if self.redials.get(&peer_id).map_or(false, |last| last.elapsed() < interval) {
+            return false;
+        }
+        self.redials.insert(peer_id, Instant::now());
 
-        Ok(())
+        if self.connect(peer_id, ip, port).is_err() {
+            return false;
+        }
+        self.redials.remove(&peer_id);
+        true
+    }
+
+    // accepts a restarted peer if its request is already waiting on the listener of init,
+    // true once peer_id is back, another peer accepted meanwhile is installed as well
+    pub fn try_accept_reconnect(&mut self, peer_id: u64) -> TransResult<bool> {
+        while self.has_request() {
+            if self.accept()? == peer_id {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // the listener has a connection request, without waiting for one
+    fn has_request(&self) -> bool {
+        let listen_id = match self.listen_fd {
+            Some(listen_id) => listen_id,
+            None => return false,
+        };
+
+        let mut fds = libc::pollfd {
+            fd: unsafe { (*(*listen_id).channel).fd },
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fds, 1, 0) > 0 }
     }
 
     pub fn get_connection(&self, peer_id: u64) -> Arc<Mutex<RdmaRcConn>> {
         self.connections.get(&peer_id).unwrap().clone()
     }
 
-    fn accept(&mut self) {
-        if self.listen_fd.is_none() {
-            return;
-        }
+    // the peer accepted
+    fn accept(&mut self) -> TransResult<u64> {
+        let listen_id = self.listen_fd.ok_or(TransError::TransRdmaError)?;
+        let addr = self.listen_addr.clone();
+
         let mut id: *mut rdma_cm_id = std::ptr::null_mut();
-        let mut ret = unsafe { rdma_get_request(self.listen_fd.unwrap(), &mut id) };
+        let ret = unsafe { rdma_get_request(listen_id, &mut id) };
         if ret != 0 {
-            println!("rdma_get_request");
-            return;
+            return Err(RdmaSetupError::last("rdma_get_request", &addr));
         }
 
-        let mut init_attr = Self::default_init_attr();
+        // the qp is not created yet with a srq
+        if !self.use_srq {
+            let mut init_attr = Self::default_init_attr();
+            let mut qp_attr = unsafe { std::mem::zeroed::<ibv_qp_attr>() };
+            let ret = unsafe {
+                ibv_query_qp(
+                    (*id).qp,
+                    &mut qp_attr,
+                    ibv_qp_attr_mask::IBV_QP_CAP.0.try_into().unwrap(),
+                    &mut init_attr,
+                )
+            };
 
-        let mut qp_attr = unsafe { std::mem::zeroed::<ibv_qp_attr>() };
-        ret = unsafe {
-            ibv_query_qp(
-                (*id).qp,
-                &mut qp_attr,
-                ibv_qp_attr_mask::IBV_QP_CAP.0.try_into().unwrap(),
-                &mut init_attr,
-            )
+            if ret != 0 {
+                let err = RdmaSetupError::last("ibv_query_qp", &addr);
+                unsafe { rdma_destroy_ep(id); }
+                return Err(err);
+            }
+        }
+
+        let lmr = match self.check_device(id, &addr).and_then(|_| self.setup_qp(id, &addr)) {
+            Ok(lmr) => lmr,
+            Err(err) => {
+                unsafe { rdma_destroy_ep(id); }
+                return Err(err);
+            }
         };
 
-        if ret != 0 {
-            println!("ibv_query_qp");
-            return;
-        }
+        let remote = match self.exchange_meta(id, lmr, &addr, false) {
+            Ok(remote) => remote,
+            Err(err) => {
+                Self::release_ep(id, lmr);
+                return Err(err);
+            }
+        };
 
-        // bind mr and send meta
-        // let mr_length = 4096 * NPAGES as usize;
-        // let lm = unsafe { memalign(4096, mr_length) };
-        let mr_length = 4096 * NPAGES as usize;
-        let lm = self.allocator.get_lm() as _;
-        let access = ibv_access_flags::IBV_ACCESS_LOCAL_WRITE.0
-            | ibv_access_flags::IBV_ACCESS_REMOTE_READ.0
-            | ibv_access_flags::IBV_ACCESS_REMOTE_WRITE.0;
-        let lmr = unsafe { ibv_reg_mr((*id).pd, lm, mr_length, access as _) };
+        println!("accept successfully!");
+        println!("{:}:{:}:{:}", remote.peer_id, remote.raddr, remote.rid);
 
-        let send_recv_layout = Layout::from_size_align(
-            std::mem::size_of::<RemoteMeta>(),
-            std::mem::align_of::<RemoteMeta>(),
-        )
-        .unwrap();
-        let send_addr = unsafe { self.allocator.alloc(send_recv_layout) };
+        let connection = self.new_connection(remote.peer_id, id, lmr, &remote);
+        self.install_connection(remote.peer_id, connection)?;
+        Ok(remote.peer_id)
+    }
 
-        unsafe {
-            *(send_addr as *mut RemoteMeta) = RemoteMeta {
-                peer_id: self.self_id,
-                raddr: (*lmr).addr as u64,
-                rid: (*lmr).rkey,
-            };
+    pub fn listen_task(&mut self, peer_num: usize) -> TransResult<()> {
+        while self.connections.len() < peer_num {
+            self.accept()?;
         }
+        Ok(())
+    }
 
-        let recv_addr = unsafe { self.allocator.alloc(send_recv_layout) };
-        ret = unsafe {
-            rdma_post_recv(
-                id,
-                recv_addr as _,
-                recv_addr as _,
-                std::mem::size_of::<RemoteMeta>(),
-                lmr,
-            )
-        };
-        if ret != 0 {
-            println!("rdma_post_recv");
+    // dials the peers of the thread tid of this node in the cluster then accepts the others
+    pub fn connect_cluster(&mut self, cluster: &ClusterConfig, tid: usize) -> TransResult<()> {
+        let node = cluster.get_node(self.self_id).ok_or(TransError::TransConfigError)?;
+        if let Some(device) = node.get_rdma_device(tid) {
+            self.set_device(device.clone());
         }
-
-        ret = unsafe { rdma_accept(id, std::ptr::null_mut()) };
-        if ret != 0 {
-            println!("rdma_accept");
-            return;
+        if node.srq {
+            self.enable_srq();
         }
 
-        ret = unsafe {
-            rdma_post_send(
-                id,
-                send_addr as _,
-                send_addr as _,
-                std::mem::size_of::<RemoteMeta>(),
-                lmr,
-                ibv_send_flags::IBV_SEND_SIGNALED.0 as _,
-            )
-        };
-
-        if ret != 0 {
-            println!("rdma_post_send");
+        for peer_id in node.connects.iter() {
+            let peer = cluster.get_node(*peer_id).ok_or(TransError::TransConfigError)?;
+            self.connect(*peer_id, &peer.get_ip(), &peer.get_conn_port(tid))?;
         }
 
-        let mut wc = unsafe { std::mem::zeroed::<ibv_wc>() };
-        while ret == 0 {
-            ret = unsafe { rdma_get_send_comp(id, &mut wc) };
-        }
-        if ret < 0 {
-            println!("rdma_get_send_comp");
+        let accepts = cluster.count_accepts(self.self_id);
+        if accepts > 0 {
+            let listen_ip = match self.device.ip.as_ref() {
+                Some(ip) => format!("{}\0", ip),
+                None => String::from("0.0.0.0\0"),
+            };
+            self.init(&listen_ip, &node.get_conn_port(tid))?;
+            self.listen_task(node.connects.len() + accepts)?;
         }
+        Ok(())
+    }
 
-        ret = 0;
-        while ret == 0 {
-            ret = unsafe { rdma_get_recv_comp(id, &mut wc) };
+    // the ud qp of the thread tid on its device and the addresses of the ud peers of the node,
+    // after connect_cluster. They have no rc connection to carry them, so the config
+    // handshake exchanges them with the same thread of each peer
+    pub fn connect_cluster_ud(&self, cluster: &ClusterConfig, tid: usize) -> TransResult<(Arc<Mutex<RdmaUdAdapter>>, Vec<RdmaUdAddr>)> {
+        let node = cluster.get_node(self.self_id).ok_or(TransError::TransConfigError)?;
+        let ud = RdmaUdAdapter::open(self.self_id, tid as _, &self.device, &self.allocator)?;
+        let local = ud.get_local_addr();
+
+        let peer_ids = cluster.get_ud_peer_ids(self.self_id);
+        let mut dsts: Vec<SocketAddr> = Vec::new();
+        for peer_id in peer_ids.iter() {
+            let peer = cluster.get_node(*peer_id).ok_or(TransError::TransConfigError)?;
+            dsts.push(peer.get_ud_addr(tid).parse().map_err(|_| TransError::TransConfigError)?);
         }
 
-        println!("accept successfully!");
-        let recv_data = unsafe { *(recv_addr as *mut RemoteMeta) };
-        println!(
-            "{:}:{:}:{:}",
-            recv_data.peer_id, recv_data.raddr, recv_data.rid
-        );
+        // the peers send theirs meanwhile
+        let sender = std::thread::spawn(move || dsts.into_iter().try_for_each(|dst| send_config(dst, local)));
+
+        let listen_addr: SocketAddr = format!("0.0.0.0:{}", node.ud_port as usize + tid)
+            .parse()
+            .map_err(|_| TransError::TransConfigError)?;
+        let mut addrs: Vec<RdmaUdAddr> = Vec::new();
+        while addrs.len() < peer_ids.len() {
+            let addr: RdmaUdAddr = recv_config(listen_addr)?;
+            let known = peer_ids.contains(&addr.peer_id) && addr.tid == tid as u64;
+            if known && !addrs.iter().any(|other| other.peer_id == addr.peer_id) {
+                addrs.push(addr);
+            }
+        }
 
-        let connection = RdmaRcConn::new(
-            recv_data.peer_id,
-            id,
-            lm as *mut u8,
-            lmr,
-            recv_data.raddr,
-            recv_data.rid,
-            &self.allocator,
-        );
+        sender.join().map_err(|_| TransError::TransSyncError)??;
+        Ok((Arc::new(Mutex::new(ud)), addrs))
+    }
 
-        unsafe {
-            self.allocator.dealloc(send_addr, send_recv_layout);
-            self.allocator.dealloc(recv_addr, send_recv_layout);
+    // one step of re-establishing the connection to a restarted peer the same way
+    // connect_cluster set it up, it does not wait for a peer that is not back yet
+    pub fn reconnect_cluster(&mut self, cluster: &ClusterConfig, tid: usize, peer_id: u64) -> TransResult<bool> {
+        let node = cluster.get_node(self.self_id).ok_or(TransError::TransConfigError)?;
+        if node.connects.contains(&peer_id) {
+            let peer = cluster.get_node(peer_id).ok_or(TransError::TransConfigError)?;
+            Ok(self.try_reconnect(peer_id, &peer.get_ip(), &peer.get_conn_port(tid)))
+        } else {
+            self.try_accept_reconnect(peer_id)
         }
+    }
 
+    // the peers whose connections went to error
+    pub fn get_broken_peers(&self) -> Vec<u64> {
         self.connections
-            .insert(recv_data.peer_id, Arc::new(Mutex::new(connection)));
+            .iter()
+            .filter(|(_, conn)| conn.lock().unwrap().is_broken())
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
     }
 
-    pub fn listen_task(&mut self, peer_num: usize) {
-        while self.connections.len() < peer_num {
-            self.accept();
-        }
+    pub fn get_peer_ids(&self) -> Vec<u64> {
+        self.connections.keys().cloned().collect()
     }
 
     pub fn get_allocator(&self) -> Arc<RdmaBaseAllocator> {
@@ -0,0 +1,166 @@
+use std::ffi::CStr;
+
+use rdma_sys::*;
+
+use crate::{TransError, TransResult, DEFAULT_GID_INDEX, DEFAULT_PORT_NUM};
+
+// not exported by the bindings
+const LINK_LAYER_ETHERNET: u8 = 2;
+
+/// The nic port a worker thread is pinned to. Rdma cm resolves the device
+/// from the ip, so the ip of the port is bound and the resolved device is checked.
+#[derive(Clone, Debug)]
+pub struct RdmaDeviceConfig {
+    // e.g. mlx5_0, any device if none
+    pub name: Option<String>,
+    pub port_num: u8,
+    pub gid_index: i32,
+    // the local ip of the port, bound by the listener and the dialer
+    pub ip: Option<String>,
+}
+
+impl Default for RdmaDeviceConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            port_num: DEFAULT_PORT_NUM,
+            gid_index: DEFAULT_GID_INDEX,
+            ip: None,
+        }
+    }
+}
+
+impl RdmaDeviceConfig {
+    // the device and the port rdma cm picked are the configured ones
+    pub fn matches(&self, context: *mut ibv_context, port_num: u8) -> bool {
+        match self.name.as_ref() {
+            Some(name) => get_device_name(context) == *name && port_num == self.port_num,
+            None => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RdmaPortInfo {
+    pub port_num: u8,
+    pub active: bool,
+    pub ethernet: bool,
+    pub lid: u16,
+    pub gid_tbl_len: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RdmaDeviceInfo {
+    pub name: String,
+    pub ports: Vec<RdmaPortInfo>,
+    // the atomics of the nic are atomic with those of the cpu too
+    pub atomic_glob: bool,
+}
+
+pub fn get_device_name(context: *mut ibv_context) -> String {
+    unsafe {
+        CStr::from_ptr(ibv_get_device_name((*context).device))
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+fn query_device(context: *mut ibv_context) -> TransResult<ibv_device_attr> {
+    let mut device_attr = unsafe { std::mem::zeroed::<ibv_device_attr>() };
+    if unsafe { ibv_query_device(context, &mut device_attr) } != 0 {
+        return Err(TransError::TransRdmaError);
+    }
+    Ok(device_attr)
+}
+
+fn query_ports(context: *mut ibv_context) -> TransResult<Vec<RdmaPortInfo>> {
+    let device_attr = query_device(context)?;
+
+    // ports are numbered from 1
+    let mut ports = Vec::new();
+    for port_num in 1..=device_attr.phys_port_cnt {
+        let mut port_attr = unsafe { std::mem::zeroed::<ibv_port_attr>() };
+        if unsafe { ___ibv_query_port(context, port_num, &mut port_attr) } != 0 {
+            return Err(TransError::TransRdmaError);
+        }
+
+        ports.push(RdmaPortInfo {
+            port_num: port_num,
+            active: port_attr.state == ibv_port_state::IBV_PORT_ACTIVE,
+            ethernet: port_attr.link_layer == LINK_LAYER_ETHERNET,
+            lid: port_attr.lid,
+            gid_tbl_len: port_attr.gid_tbl_len,
+        });
+    }
+    Ok(ports)
+}
+
+// the devices of the machine with their ports
+pub fn list_rdma_devices() -> TransResult<Vec<RdmaDeviceInfo>> {
+    let mut num = 0;
+    let list = unsafe { ibv_get_device_list(&mut num) };
+    if list.is_null() {
+        return Err(TransError::TransRdmaError);
+    }
+
+    let mut devices = Vec::new();
+    for i in 0..num as usize {
+        let context = unsafe { ibv_open_device(*list.add(i)) };
+        if context.is_null() {
+            continue;
+        }
+
+        let ports = query_ports(context);
+        let atomic_glob = query_device(context)
+            .map_or(false, |attr| attr.atomic_cap == ibv_atomic_cap::IBV_ATOMIC_GLOB);
+        devices.push(RdmaDeviceInfo {
+            name: get_device_name(context),
+            ports: ports.unwrap_or_default(),
+            atomic_glob: atomic_glob,
+        });
+        unsafe { ibv_close_device(context); }
+    }
+
+    unsafe { ibv_free_device_list(list); }
+    Ok(devices)
+}
+
+// whether the RDMA CAS of the peers and the CAS of the local cpu may lock the same word,
+// i.e. the device of the config, or every device if any, has global atomics
+pub fn atomics_are_global(config: &RdmaDeviceConfig) -> bool {
+    let devices = match list_rdma_devices() {
+        Ok(devices) => devices,
+        Err(_) => return false,
+    };
+
+    let mut matched = devices
+        .iter()
+        .filter(|device| config.name.as_ref().map_or(true, |name| device.name == *name))
+        .peekable();
+    matched.peek().is_some() && matched.all(|device| device.atomic_glob)
+}
+
+// opens the named device, e.g. for a ud adapter on a chosen port
+pub fn open_rdma_device(name: &str) -> TransResult<*mut ibv_context> {
+    let mut num = 0;
+    let list = unsafe { ibv_get_device_list(&mut num) };
+    if list.is_null() {
+        return Err(TransError::TransRdmaError);
+    }
+
+    let mut context = std::ptr::null_mut();
+    for i in 0..num as usize {
+        let device = unsafe { *list.add(i) };
+        let device_name = unsafe { CStr::from_ptr(ibv_get_device_name(device)) };
+        if device_name.to_string_lossy() == name {
+            context = unsafe { ibv_open_device(device) };
+            break;
+        }
+    }
+
+    unsafe { ibv_free_device_list(list); }
+    if context.is_null() {
+        return Err(TransError::TransConfigError);
+    }
+    Ok(context)
+}
@@ -1,7 +1,11 @@
 pub mod control;
+pub mod device;
 pub mod one_side;
 pub mod rcconn;
 pub mod two_sides;
+pub mod soft_verbs;
+pub mod srq;
+pub mod udadapter;
 
 pub use control::RdmaBaseAllocator;
 
@@ -4,4 +4,17 @@ use crate::TransResult;
 
 pub trait OneSideComm {
     fn post_batch(&mut self, send_wr: *mut ibv_send_wr, num: u64) -> TransResult<()>;
+
+    // signaled, len bytes at remote_off of the remote region into local_buf
+    fn post_read(&mut self, local_buf: *mut u8, len: u32, remote_off: u64, wr_id: u64) -> TransResult<()>;
+
+    // signaled, len bytes of local_buf to remote_off of the remote region
+    fn post_write(&mut self, local_buf: *const u8, len: u32, remote_off: u64, wr_id: u64) -> TransResult<()>;
+
+    // signaled, swaps the 8-byte word at remote_off if it equals compare,
+    // the old word is returned in local_buf
+    fn post_cas(&mut self, local_buf: *mut u8, remote_off: u64, compare: u64, swap: u64, wr_id: u64) -> TransResult<()>;
+
+    // signaled, adds to the 8-byte word at remote_off, the old word is returned in local_buf
+    fn post_faa(&mut self, local_buf: *mut u8, remote_off: u64, add: u64, wr_id: u64) -> TransResult<()>;
 }
@@ -1,12 +1,15 @@
 use std::alloc::Layout;
 // use std::sync::{Mutex, MutexGuard};
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Mutex, Weak};
 
 use ll_alloc::LockedHeap;
 use rdma_sys::ibv_wr_opcode::IBV_WR_SEND;
 use rdma_sys::*;
 
 use super::{one_side::OneSideComm, two_sides::TwoSidesComm};
+use super::soft_verbs::SoftVerbs;
+use super::srq::RdmaSharedRecvQueue;
+use super::udadapter::RdmaUdAdapter;
 use super::{RdmaRecvCallback, RdmaSendCallback};
 use super::{DEFAULT_RDMA_RECV_HANDLER, DEFAULT_RDMA_SEND_HANDLER};
 use super::control::RdmaBaseAllocator;
@@ -18,6 +21,7 @@ struct RdmaRcMeta {
     lm: *mut u8,
     lmr: *mut ibv_mr,
     raddr: u64,
+    rlen: u64,
     rid: u32,
 }
 
@@ -52,9 +56,15 @@ impl Default for RdmaElement {
     }
 }
 
-// TODO: Recv elements can be shared between connections ?
-// How to treat buffers to store reqs? They cannot be shared.
-// Using SRQs to solve this problem
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RdmaConnState {
+    Connected,
+    // the qp went to error, e.g. the peer died, until it is re-established
+    Broken,
+}
+
+// Recv elements are per connection unless the qp is on a shared receive queue,
+// whose buffers are posted and polled by the srq and demultiplexed by the scheduler.
 pub struct RdmaRcConn {
     conn_id: u64,
     meta: RdmaRcMeta,
@@ -63,6 +73,16 @@ pub struct RdmaRcConn {
     rwcs: [ibv_wc; MAX_RECV_SIZE],
     rhandler: Weak<dyn RdmaRecvCallback + Send + Sync + 'static>,
     whandler: Weak<dyn RdmaSendCallback + Send + Sync + 'static>,
+    // software verbs, for test
+    soft: Option<SoftVerbs>,
+    // recvs come from the srq of the worker
+    shared_recv: bool,
+    // two-sided primitives only, a view of the peer thread tid on the ud qp of the worker,
+    // and the seq the sends carry, set by the scheduler
+    ud: Option<(Arc<Mutex<RdmaUdAdapter>>, u64, u32)>,
+    state: RdmaConnState,
+    // recvs are posted, posted again when re-established
+    started: bool,
 }
 
 unsafe impl Send for RdmaRcConn {}
@@ -76,6 +96,7 @@ impl RdmaRcConn {
         lm: *mut u8,
         lmr: *mut ibv_mr,
         raddr: u64,
+        rlen: u64,
         rid: u32,
         allocator: &Arc<RdmaBaseAllocator>,
     ) -> Self {
@@ -84,6 +105,7 @@ impl RdmaRcConn {
             lm: lm,
             lmr: lmr,
             raddr: raddr,
+            rlen: rlen,
             rid: rid,
         };
 
@@ -97,6 +119,114 @@ impl RdmaRcConn {
             rwcs: unsafe { std::mem::zeroed() },
             rhandler: Arc::downgrade(&DEFAULT_RDMA_RECV_HANDLER) as _,
             whandler: Arc::downgrade(&DEFAULT_RDMA_SEND_HANDLER) as _,
+            soft: None,
+            shared_recv: false,
+            ud: None,
+            state: RdmaConnState::Connected,
+            started: false,
+        }
+    }
+
+    // a connection backed by the software verbs, whose remote region is the memory of remote
+    // only one-sided primitives are supported
+    pub fn new_soft(
+        conn_id: u64,
+        remote: &Arc<RdmaBaseAllocator>,
+        allocator: &Arc<RdmaBaseAllocator>,
+    ) -> Self {
+        Self::from_soft(conn_id, SoftVerbs::new(remote), allocator)
+    }
+
+    // both ends of a soft connection between a and b, the sends of one are the recvs of the other
+    pub fn new_soft_pair(
+        a_id: u64,
+        a_allocator: &Arc<RdmaBaseAllocator>,
+        b_id: u64,
+        b_allocator: &Arc<RdmaBaseAllocator>,
+    ) -> (Self, Self) {
+        let (a_soft, b_soft) = SoftVerbs::new_pair(b_allocator, a_allocator);
+        (
+            Self::from_soft(b_id, a_soft, a_allocator),
+            Self::from_soft(a_id, b_soft, b_allocator),
+        )
+    }
+
+    // as new_soft_pair, but the end of b is on the soft srq of b's worker
+    pub fn new_soft_pair_on_srq(
+        a_id: u64,
+        a_allocator: &Arc<RdmaBaseAllocator>,
+        b_id: u64,
+        b_allocator: &Arc<RdmaBaseAllocator>,
+        b_srq: &Arc<Mutex<RdmaSharedRecvQueue>>,
+    ) -> TransResult<(Self, Self)> {
+        let attached = b_srq.lock().unwrap().attach_soft().ok_or(TransError::TransRdmaError)?;
+        let (a_soft, b_soft) = SoftVerbs::new_pair_on_srq(b_allocator, a_allocator, attached);
+
+        let mut b = Self::from_soft(a_id, b_soft, b_allocator);
+        b.set_shared_recv();
+        Ok((Self::from_soft(b_id, a_soft, a_allocator), b))
+    }
+
+    fn from_soft(
+        conn_id: u64,
+        soft: SoftVerbs,
+        allocator: &Arc<RdmaBaseAllocator>,
+    ) -> Self {
+        let meta = RdmaRcMeta {
+            conn_id: std::ptr::null_mut(),
+            lm: allocator.get_lm(),
+            lmr: std::ptr::null_mut(),
+            raddr: soft.get_remote_addr(),
+            rlen: soft.get_remote_len(),
+            rid: 0,
+        };
+
+        Self {
+            conn_id: conn_id,
+            meta: meta,
+            allocator: allocator.clone(),
+            elements: RdmaElement::default(),
+            rwcs: unsafe { std::mem::zeroed() },
+            rhandler: Arc::downgrade(&DEFAULT_RDMA_RECV_HANDLER) as _,
+            whandler: Arc::downgrade(&DEFAULT_RDMA_SEND_HANDLER) as _,
+            soft: Some(soft),
+            shared_recv: false,
+            ud: None,
+            state: RdmaConnState::Connected,
+            started: false,
+        }
+    }
+
+    // the sends go to the thread tid of the peer conn_id as datagrams,
+    // the recvs are polled by the adapter
+    pub fn new_ud(
+        conn_id: u64,
+        tid: u64,
+        ud: &Arc<Mutex<RdmaUdAdapter>>,
+        allocator: &Arc<RdmaBaseAllocator>,
+    ) -> Self {
+        let meta = RdmaRcMeta {
+            conn_id: std::ptr::null_mut(),
+            lm: allocator.get_lm(),
+            lmr: std::ptr::null_mut(),
+            raddr: 0,
+            rlen: 0,
+            rid: 0,
+        };
+
+        Self {
+            conn_id: conn_id,
+            meta: meta,
+            allocator: allocator.clone(),
+            elements: RdmaElement::default(),
+            rwcs: unsafe { std::mem::zeroed() },
+            rhandler: Arc::downgrade(&DEFAULT_RDMA_RECV_HANDLER) as _,
+            whandler: Arc::downgrade(&DEFAULT_RDMA_SEND_HANDLER) as _,
+            soft: None,
+            shared_recv: false,
+            ud: Some((ud.clone(), tid, 0)),
+            state: RdmaConnState::Connected,
+            started: false,
         }
     }
 
@@ -104,27 +234,101 @@ impl RdmaRcConn {
         return self.conn_id;
     }
 
-    pub fn init_and_start_recvs(&mut self) -> TransResult<()> {
-        for i in 0..MAX_RECV_SIZE {
-            let addr = self.alloc_mr(MAX_PACKET_SIZE).unwrap() as u64;
-            self.elements.rsges[i] = ibv_sge {
-                addr: addr,
-                length: MAX_PACKET_SIZE as _,
-                lkey: unsafe { (*self.meta.lmr).lkey },
-            };
+    // the peer thread of a datagram conn
+    pub(crate) fn get_ud_tid(&self) -> Option<u64> {
+        self.ud.as_ref().map(|(_, tid, _)| *tid)
+    }
 
-            let next = if i + 1 == MAX_RECV_SIZE {
-                &mut self.elements.rwrs[0] as *mut _
-            } else {
-                &mut self.elements.rwrs[i + 1] as *mut _
-            };
+    pub(crate) fn set_ud_seq(&mut self, seq: u32) {
+        if let Some((_, _, ud_seq)) = self.ud.as_mut() {
+            *ud_seq = seq;
+        }
+    }
 
-            self.elements.rwrs[i] = ibv_recv_wr {
-                wr_id: addr,
-                sg_list: &mut self.elements.rsges[i] as *mut _,
-                next: next,
-                num_sge: 1,
-            };
+    // the qp is attached to a srq, set before init_and_start_recvs
+    pub(crate) fn set_shared_recv(&mut self) {
+        self.shared_recv = true;
+    }
+
+    #[inline]
+    pub fn is_shared_recv(&self) -> bool {
+        self.shared_recv
+    }
+
+    // the local qp, by which the recvs of a srq find the connection
+    #[inline]
+    pub fn get_qp_num(&self) -> u32 {
+        if let Some(soft) = self.soft.as_ref() {
+            return soft.get_qp_num();
+        }
+        unsafe { (*(*self.meta.conn_id).qp).qp_num }
+    }
+
+    #[inline]
+    pub fn get_state(&self) -> RdmaConnState {
+        self.state
+    }
+
+    #[inline]
+    pub fn is_broken(&self) -> bool {
+        self.state == RdmaConnState::Broken
+    }
+
+    // the peer is known to be gone, e.g. by a timeout, before its qp reports an error
+    pub fn mark_broken(&mut self) {
+        self.state = RdmaConnState::Broken;
+    }
+
+    // a fresh qp to the restarted peer replaces the broken one,
+    // the callbacks are kept and the recvs are posted again if they were
+    pub(crate) fn reestablish(&mut self, mut fresh: RdmaRcConn) -> TransResult<()> {
+        let started = self.started;
+        if started && !self.shared_recv {
+            for i in 0..MAX_RECV_SIZE {
+                self.deallocate_mr(self.elements.rsges[i].addr as _, MAX_PACKET_SIZE);
+            }
+        }
+
+        fresh.rhandler = self.rhandler.clone();
+        fresh.whandler = self.whandler.clone();
+        // the old qp and its id are destroyed on drop
+        *self = fresh;
+
+        if started {
+            self.init_and_start_recvs()?;
+        }
+        Ok(())
+    }
+
+    pub fn init_and_start_recvs(&mut self) -> TransResult<()> {
+        if self.soft.is_some() {
+            self.started = true;
+            return Ok(());
+        }
+
+        // the recvs of a shared qp are posted by the srq
+        if !self.shared_recv {
+            for i in 0..MAX_RECV_SIZE {
+                let addr = self.alloc_mr(MAX_PACKET_SIZE).unwrap() as u64;
+                self.elements.rsges[i] = ibv_sge {
+                    addr: addr,
+                    length: MAX_PACKET_SIZE as _,
+                    lkey: unsafe { (*self.meta.lmr).lkey },
+                };
+
+                let next = if i + 1 == MAX_RECV_SIZE {
+                    &mut self.elements.rwrs[0] as *mut _
+                } else {
+                    &mut self.elements.rwrs[i + 1] as *mut _
+                };
+
+                self.elements.rwrs[i] = ibv_recv_wr {
+                    wr_id: addr,
+                    sg_list: &mut self.elements.rsges[i] as *mut _,
+                    next: next,
+                    num_sge: 1,
+                };
+            }
         }
 
         for i in 0..MAX_DOORBELL_SEND_SIZE {
@@ -142,7 +346,10 @@ impl RdmaRcConn {
             self.elements.swrs[i].sg_list = &mut self.elements.ssges[i] as *mut _;
         }
 
-        self.post_recvs(MAX_RECV_SIZE as u64).unwrap();
+        if !self.shared_recv {
+            self.post_recvs(MAX_RECV_SIZE as u64).unwrap();
+        }
+        self.started = true;
         Ok(())
     }
 
@@ -154,6 +361,163 @@ impl RdmaRcConn {
         Ok(())
     }
 
+    pub fn register_send_callback(
+        &mut self,
+        handler: &Arc<impl RdmaSendCallback + Send + Sync + 'static>,
+    ) -> TransResult<()> {
+        self.whandler = Arc::downgrade(handler) as _;
+        Ok(())
+    }
+
+    // the scheduler takes both, the one-sided completions go to the send one
+    pub fn register_callbacks(
+        &mut self,
+        handler: &Arc<impl RdmaRecvCallback + RdmaSendCallback + Send + Sync + 'static>,
+    ) -> TransResult<()> {
+        self.register_recv_callback(handler)?;
+        self.register_send_callback(handler)
+    }
+
+    // both the local buffer and the remote range must lie in the registered regions
+    #[inline]
+    fn check_one_side_range(&self, local_buf: *const u8, len: u32, remote_off: u64) -> TransResult<()> {
+        let local_start = self.meta.lm as u64;
+        let local_addr = local_buf as u64;
+
+        if local_addr < local_start || local_addr + len as u64 > local_start + self.allocator.get_len() as u64 {
+            return Err(TransError::TransRdmaError);
+        }
+
+        if remote_off + len as u64 > self.meta.rlen {
+            return Err(TransError::TransRdmaError);
+        }
+
+        Ok(())
+    }
+
+    fn post_one_side(
+        &mut self,
+        wr_op: std::os::raw::c_uint,
+        local_buf: *mut u8,
+        len: u32,
+        remote_off: u64,
+        wr_id: u64,
+    ) -> TransResult<()> {
+        if self.is_broken() {
+            return Err(TransError::TransRdmaError);
+        }
+        self.check_one_side_range(local_buf, len, remote_off)?;
+
+        // the low bits of the wr_id are left to the caller
+        let wr_id = (self.elements.high_watermark + 1) << WRID_RESERVE_BITS
+            | (wr_id & ((1 << WRID_RESERVE_BITS) - 1));
+
+        if let Some(soft) = self.soft.as_mut() {
+            if wr_op == ibv_wr_opcode::IBV_WR_RDMA_READ {
+                soft.post_read(local_buf, len, remote_off, wr_id);
+            } else {
+                soft.post_write(local_buf, len, remote_off, wr_id);
+            }
+        } else {
+            let mut bad_sr: *mut ibv_send_wr = std::ptr::null_mut();
+
+            let mut sge = ibv_sge {
+                addr: local_buf as _,
+                length: len,
+                lkey: unsafe { (*self.meta.lmr).lkey },
+            };
+
+            let mut sr = unsafe { std::mem::zeroed::<ibv_send_wr>() };
+
+            sr.wr_id = wr_id;
+            sr.opcode = wr_op;
+            sr.num_sge = 1;
+            sr.next = std::ptr::null_mut();
+            sr.sg_list = &mut sge as *mut _;
+            sr.send_flags = ibv_send_flags::IBV_SEND_SIGNALED.0;
+
+            sr.wr.rdma.remote_addr = self.meta.raddr + remote_off;
+            sr.wr.rdma.rkey = self.meta.rid;
+
+            let ret = unsafe { ibv_post_send((*self.meta.conn_id).qp, &mut sr, &mut bad_sr) };
+
+            if ret != 0 {
+                return Err(TransError::TransRdmaError);
+            }
+        }
+
+        // signaled, so the pending sends before are covered
+        self.elements.high_watermark += 1;
+        self.elements.pending_sends = 0;
+
+        self.poll_in_need();
+        Ok(())
+    }
+
+    // atomics work on an aligned 8-byte word, the old word is returned in local_buf
+    fn post_atomic(
+        &mut self,
+        wr_op: std::os::raw::c_uint,
+        local_buf: *mut u8,
+        remote_off: u64,
+        compare_add: u64,
+        swap: u64,
+        wr_id: u64,
+    ) -> TransResult<()> {
+        if self.is_broken() {
+            return Err(TransError::TransRdmaError);
+        }
+        self.check_one_side_range(local_buf, 8, remote_off)?;
+        if (self.meta.raddr + remote_off) % 8 != 0 {
+            return Err(TransError::TransRdmaError);
+        }
+
+        let wr_id = (self.elements.high_watermark + 1) << WRID_RESERVE_BITS
+            | (wr_id & ((1 << WRID_RESERVE_BITS) - 1));
+
+        if let Some(soft) = self.soft.as_mut() {
+            if wr_op == ibv_wr_opcode::IBV_WR_ATOMIC_CMP_AND_SWP {
+                soft.post_cas(local_buf, remote_off, compare_add, swap, wr_id);
+            } else {
+                soft.post_faa(local_buf, remote_off, compare_add, wr_id);
+            }
+        } else {
+            let mut bad_sr: *mut ibv_send_wr = std::ptr::null_mut();
+
+            let mut sge = ibv_sge {
+                addr: local_buf as _,
+                length: 8,
+                lkey: unsafe { (*self.meta.lmr).lkey },
+            };
+
+            let mut sr = unsafe { std::mem::zeroed::<ibv_send_wr>() };
+
+            sr.wr_id = wr_id;
+            sr.opcode = wr_op;
+            sr.num_sge = 1;
+            sr.next = std::ptr::null_mut();
+            sr.sg_list = &mut sge as *mut _;
+            sr.send_flags = ibv_send_flags::IBV_SEND_SIGNALED.0;
+
+            sr.wr.atomic.remote_addr = self.meta.raddr + remote_off;
+            sr.wr.atomic.compare_add = compare_add;
+            sr.wr.atomic.swap = swap;
+            sr.wr.atomic.rkey = self.meta.rid;
+
+            let ret = unsafe { ibv_post_send((*self.meta.conn_id).qp, &mut sr, &mut bad_sr) };
+
+            if ret != 0 {
+                return Err(TransError::TransRdmaError);
+            }
+        }
+
+        self.elements.high_watermark += 1;
+        self.elements.pending_sends = 0;
+
+        self.poll_in_need();
+        Ok(())
+    }
+
     // (deprecated) raw send request for test
     #[deprecated]
     pub fn post_send(
@@ -199,6 +563,16 @@ impl RdmaRcConn {
 
     // for send primitives
     pub fn flush_pending_with_signal(&mut self, force_signal: bool) -> TransResult<()> {
+        if self.is_broken() {
+            self.elements.current_idx = 0;
+            return Err(TransError::TransRdmaError);
+        }
+
+        // the soft sends are delivered when posted
+        if self.soft.is_some() {
+            return Ok(());
+        }
+
         let mut bad_wr: *mut ibv_send_wr = std::ptr::null_mut();
         // let mut elements = self.elements.lock().unwrap();
         let current_idx = self.elements.current_idx;
@@ -234,6 +608,7 @@ impl RdmaRcConn {
             }
 
             if ret != 0 {
+                self.state = RdmaConnState::Broken;
                 return Err(TransError::TransRdmaError);
             }
         }
@@ -303,6 +678,22 @@ impl RdmaRcConn {
 
     // This func can only be called in master coroutine, so it is impossible to be recusively locked.
     pub fn poll_recvs(&mut self) -> i32 {
+        if self.shared_recv || self.ud.is_some() || self.is_broken() {
+            return 0;
+        }
+
+        if self.soft.is_some() {
+            let mut poll_result = 0;
+            while let Some(mut msg) = self.soft.as_mut().unwrap().poll_recv() {
+                self.rhandler
+                    .upgrade()
+                    .unwrap()
+                    .rdma_recv_handler(self, msg.as_mut_ptr() as *mut u8);
+                poll_result += 1;
+            }
+            return poll_result;
+        }
+
         let poll_result = unsafe {
             ibv_poll_cq(
                 (*self.meta.conn_id).recv_cq,
@@ -311,6 +702,12 @@ impl RdmaRcConn {
             )
         };
         for i in 0..poll_result as usize {
+            // flushed, the qp is in error
+            if self.rwcs[i].status != ibv_wc_status::IBV_WC_SUCCESS {
+                self.state = RdmaConnState::Broken;
+                continue;
+            }
+
             let addr = self.rwcs[i].wr_id as *mut u8;
             self.rhandler
                 .upgrade()
@@ -319,7 +716,7 @@ impl RdmaRcConn {
         }
         // self.flush_pending().unwrap();
 
-        if poll_result > 0 {
+        if poll_result > 0 && !self.is_broken() {
             self.post_recvs(poll_result as _).unwrap();
         }
 
@@ -329,10 +726,29 @@ impl RdmaRcConn {
 
     pub fn poll_send(&mut self) -> i32 {
         let mut wc: ibv_wc = unsafe { std::mem::zeroed() };
-        let poll_result =
-            unsafe { ibv_poll_cq((*self.meta.conn_id).send_cq, 1, &mut wc as *mut _) };
+        let poll_result = match self.soft.as_mut() {
+            Some(soft) => match soft.poll_one() {
+                Some((wr_id, opcode)) => {
+                    wc.wr_id = wr_id;
+                    wc.opcode = opcode;
+                    1
+                }
+                None => 0,
+            },
+            None if self.ud.is_some() => 0,
+            None => unsafe { ibv_poll_cq((*self.meta.conn_id).send_cq, 1, &mut wc as *mut _) },
+        };
 
-        if poll_result > 0 {
+        if poll_result > 0 && wc.status != ibv_wc_status::IBV_WC_SUCCESS {
+            self.state = RdmaConnState::Broken;
+            self.elements.low_watermark = self.elements.high_watermark;
+
+            // the opcode is undefined in an error completion, only the one-sided
+            // primitives carry a cid, their routines are woken to see the broken state
+            if wc.wr_id & ((1 << WRID_RESERVE_BITS) - 1) != 0 {
+                self.whandler.upgrade().unwrap().rdma_send_handler(wc.wr_id);
+            }
+        } else if poll_result > 0 {
             self.elements.low_watermark = wc.wr_id >> WRID_RESERVE_BITS;
 
             match wc.opcode {
@@ -355,6 +771,9 @@ impl RdmaRcConn {
     pub fn poll_until_complete(&mut self) {
         loop {
             self.poll_send();
+            if self.is_broken() {
+                break;
+            }
 
             let elements = &self.elements;
             if (elements.high_watermark - elements.low_watermark) <= elements.pending_sends {
@@ -440,17 +859,55 @@ impl OneSideComm for RdmaRcConn {
         self.poll_in_need();
         Ok(())
     }
+
+    #[inline]
+    fn post_read(&mut self, local_buf: *mut u8, len: u32, remote_off: u64, wr_id: u64) -> TransResult<()> {
+        self.post_one_side(ibv_wr_opcode::IBV_WR_RDMA_READ, local_buf, len, remote_off, wr_id)
+    }
+
+    #[inline]
+    fn post_write(&mut self, local_buf: *const u8, len: u32, remote_off: u64, wr_id: u64) -> TransResult<()> {
+        self.post_one_side(ibv_wr_opcode::IBV_WR_RDMA_WRITE, local_buf as _, len, remote_off, wr_id)
+    }
+
+    #[inline]
+    fn post_cas(&mut self, local_buf: *mut u8, remote_off: u64, compare: u64, swap: u64, wr_id: u64) -> TransResult<()> {
+        self.post_atomic(ibv_wr_opcode::IBV_WR_ATOMIC_CMP_AND_SWP, local_buf, remote_off, compare, swap, wr_id)
+    }
+
+    #[inline]
+    fn post_faa(&mut self, local_buf: *mut u8, remote_off: u64, add: u64, wr_id: u64) -> TransResult<()> {
+        self.post_atomic(ibv_wr_opcode::IBV_WR_ATOMIC_FETCH_AND_ADD, local_buf, remote_off, add, 0, wr_id)
+    }
 }
 
 impl TwoSidesComm for RdmaRcConn {
     // for send primitives
     #[inline]
     fn flush_pending(&mut self) -> TransResult<()> {
+        if let Some((ud, _, _)) = self.ud.as_ref() {
+            return ud.lock().unwrap().flush_pending();
+        }
         return self.flush_pending_with_signal(false);
     }
 
     // for send primitives
     fn send_pending(&mut self, msg: *mut u8, length: u32) -> TransResult<()> {
+        if let Some((ud, tid, seq)) = self.ud.as_ref() {
+            return ud.lock().unwrap().send_pending(self.conn_id, *tid, *seq, msg, length);
+        }
+
+        if self.is_broken() {
+            return Err(TransError::TransRdmaError);
+        }
+
+        if let Some(soft) = self.soft.as_mut() {
+            if length as usize > MAX_PACKET_SIZE || !soft.post_send(msg, length) {
+                return Err(TransError::TransRdmaError);
+            }
+            return Ok(());
+        }
+
         let current_idx = self.elements.current_idx as usize;
         // update metas
         self.elements.current_idx += 1;
@@ -470,9 +927,14 @@ impl TwoSidesComm for RdmaRcConn {
 
 impl Drop for RdmaRcConn {
     fn drop(&mut self) {
+        if self.soft.is_some() || self.ud.is_some() {
+            return;
+        }
+
         unsafe {
-            rdma_dereg_mr(self.meta.lmr);
             rdma_disconnect(self.meta.conn_id);
+            rdma_dereg_mr(self.meta.lmr);
+            rdma_destroy_ep(self.meta.conn_id);
             // free(self.meta.lm as *mut _);
         }
     }
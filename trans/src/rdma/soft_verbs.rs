@@ -0,0 +1,203 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rdma_sys::ibv_wc_opcode;
+
+use super::control::RdmaBaseAllocator;
+
+// the sends of one direction of a soft pair, in words to keep the items aligned
+type SoftMailbox = Arc<Mutex<VecDeque<Vec<u64>>>>;
+// the sends into a soft srq, tagged with the qp of the receiving end
+pub(crate) type SoftSrqInbox = Arc<Mutex<VecDeque<(u32, Vec<u64>)>>>;
+
+// software stand-in of the verbs,
+// the remote region is the memory of another allocator in this process,
+// work requests are executed when posted and complete in order
+pub struct SoftVerbs {
+    remote: Arc<RdmaBaseAllocator>,
+    comps:  VecDeque<(u64, ibv_wc_opcode::Type)>,
+    // sends and recvs, only between the two ends of a pair
+    outbox: Option<SoftMailbox>,
+    inbox:  Option<SoftMailbox>,
+    // the sends go to the srq of the peer instead, as on its qp srq_qp_num
+    srq_outbox: Option<(SoftSrqInbox, u32)>,
+    // the local qp, set if the recvs come from a srq
+    qp_num: u32,
+}
+
+impl SoftVerbs {
+    pub fn new(remote: &Arc<RdmaBaseAllocator>) -> Self {
+        Self {
+            remote: remote.clone(),
+            comps:  VecDeque::new(),
+            outbox: None,
+            inbox:  None,
+            srq_outbox: None,
+            qp_num: 0,
+        }
+    }
+
+    // the two ends of a connection, a_remote is the memory a reaches
+    pub fn new_pair(a_remote: &Arc<RdmaBaseAllocator>, b_remote: &Arc<RdmaBaseAllocator>) -> (Self, Self) {
+        let a_to_b: SoftMailbox = Arc::new(Mutex::new(VecDeque::new()));
+        let b_to_a: SoftMailbox = Arc::new(Mutex::new(VecDeque::new()));
+
+        let mut a = Self::new(a_remote);
+        a.outbox = Some(a_to_b.clone());
+        a.inbox = Some(b_to_a.clone());
+
+        let mut b = Self::new(b_remote);
+        b.outbox = Some(b_to_a);
+        b.inbox = Some(a_to_b);
+
+        (a, b)
+    }
+
+    // as new_pair, but the sends of a are received by the srq of b, where b is the qp b_qp_num
+    pub(crate) fn new_pair_on_srq(
+        a_remote: &Arc<RdmaBaseAllocator>,
+        b_remote: &Arc<RdmaBaseAllocator>,
+        b_srq: (SoftSrqInbox, u32),
+    ) -> (Self, Self) {
+        let b_to_a: SoftMailbox = Arc::new(Mutex::new(VecDeque::new()));
+
+        let mut b = Self::new(b_remote);
+        b.outbox = Some(b_to_a.clone());
+        b.qp_num = b_srq.1;
+
+        let mut a = Self::new(a_remote);
+        a.inbox = Some(b_to_a);
+        a.srq_outbox = Some(b_srq);
+
+        (a, b)
+    }
+
+    #[inline]
+    pub fn get_qp_num(&self) -> u32 {
+        self.qp_num
+    }
+
+    #[inline]
+    pub fn get_remote_addr(&self) -> u64 {
+        self.remote.get_lm() as u64
+    }
+
+    #[inline]
+    pub fn get_remote_len(&self) -> u64 {
+        self.remote.get_len() as u64
+    }
+
+    pub fn post_read(&mut self, local_buf: *mut u8, len: u32, remote_off: u64, wr_id: u64) {
+        unsafe {
+            let src = self.remote.get_lm().add(remote_off as usize);
+            std::ptr::copy(src, local_buf, len as usize);
+        }
+        self.comps.push_back((wr_id, ibv_wc_opcode::IBV_WC_RDMA_READ));
+    }
+
+    pub fn post_write(&mut self, local_buf: *const u8, len: u32, remote_off: u64, wr_id: u64) {
+        unsafe {
+            let dst = self.remote.get_lm().add(remote_off as usize);
+            std::ptr::copy(local_buf, dst, len as usize);
+        }
+        self.comps.push_back((wr_id, ibv_wc_opcode::IBV_WC_RDMA_WRITE));
+    }
+
+    // the old value of the remote word is returned in local_buf, as the atomic verbs do
+    pub fn post_cas(&mut self, local_buf: *mut u8, remote_off: u64, compare: u64, swap: u64, wr_id: u64) {
+        let old = unsafe {
+            let word = &*(self.remote.get_lm().add(remote_off as usize) as *const AtomicU64);
+            match word.compare_exchange(compare, swap, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(old) => old,
+                Err(old) => old,
+            }
+        };
+        unsafe { (local_buf as *mut u64).write_unaligned(old) };
+        self.comps.push_back((wr_id, ibv_wc_opcode::IBV_WC_COMP_SWAP));
+    }
+
+    pub fn post_faa(&mut self, local_buf: *mut u8, remote_off: u64, add: u64, wr_id: u64) {
+        let old = unsafe {
+            let word = &*(self.remote.get_lm().add(remote_off as usize) as *const AtomicU64);
+            word.fetch_add(add, Ordering::SeqCst)
+        };
+        unsafe { (local_buf as *mut u64).write_unaligned(old) };
+        self.comps.push_back((wr_id, ibv_wc_opcode::IBV_WC_FETCH_ADD));
+    }
+
+    #[inline]
+    pub fn poll_one(&mut self) -> Option<(u64, ibv_wc_opcode::Type)> {
+        self.comps.pop_front()
+    }
+
+    // the message is copied out when posted, false if there is no peer to take it
+    pub fn post_send(&mut self, msg: *const u8, len: u32) -> bool {
+        if self.outbox.is_none() && self.srq_outbox.is_none() {
+            return false;
+        }
+
+        let mut words = vec![0u64; (len as usize + 7) / 8];
+        unsafe { std::ptr::copy_nonoverlapping(msg, words.as_mut_ptr() as *mut u8, len as usize) };
+        match self.srq_outbox.as_ref() {
+            Some((inbox, qp_num)) => inbox.lock().unwrap().push_back((*qp_num, words)),
+            None => self.outbox.as_ref().unwrap().lock().unwrap().push_back(words),
+        }
+        true
+    }
+
+    #[inline]
+    pub fn poll_recv(&mut self) -> Option<Vec<u64>> {
+        self.inbox.as_ref()?.lock().unwrap().pop_front()
+    }
+}
+
+#[derive(Default)]
+struct SoftUdQueues {
+    // by the receiving (peer_id, tid), the imm of the sender and the msg
+    queues:    HashMap<(u64, u64), VecDeque<(u32, Vec<u64>)>>,
+    drops:     usize,
+    dst_drops: HashMap<(u64, u64), usize>,
+}
+
+// the datagrams between the soft ud adapters of a process, delivered when sent unless lost
+#[derive(Clone, Default)]
+pub struct SoftUdNet {
+    inner: Arc<Mutex<SoftUdQueues>>,
+}
+
+impl SoftUdNet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // the next n datagrams are lost, whoever sends them
+    pub fn drop_next(&self, n: usize) {
+        self.inner.lock().unwrap().drops = n;
+    }
+
+    // the next n datagrams to dst are lost
+    pub fn drop_next_to(&self, dst: (u64, u64), n: usize) {
+        self.inner.lock().unwrap().dst_drops.insert(dst, n);
+    }
+
+    pub(crate) fn send(&self, dst: (u64, u64), imm: u32, msg: *const u8, len: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.drops > 0 {
+            inner.drops -= 1;
+            return;
+        }
+        if let Some(drops) = inner.dst_drops.get_mut(&dst).filter(|drops| **drops > 0) {
+            *drops -= 1;
+            return;
+        }
+
+        let mut words = vec![0u64; (len as usize + 7) / 8];
+        unsafe { std::ptr::copy_nonoverlapping(msg, words.as_mut_ptr() as *mut u8, len as usize) };
+        inner.queues.entry(dst).or_default().push_back((imm, words));
+    }
+
+    pub(crate) fn recv(&self, dst: (u64, u64)) -> Option<(u32, Vec<u64>)> {
+        self.inner.lock().unwrap().queues.get_mut(&dst)?.pop_front()
+    }
+}
@@ -0,0 +1,282 @@
+use std::alloc::Layout;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use rdma_sys::*;
+
+use super::control::RdmaBaseAllocator;
+use super::soft_verbs::SoftSrqInbox;
+use crate::*;
+
+/// A receive queue shared by the connections of a worker, so that the recv buffers
+/// do not grow with the peers. A completion carries the local qp it arrives on,
+/// by which the scheduler finds the connection.
+/// The handshakes of the later connections land in the ring too.
+pub struct RdmaSharedRecvQueue {
+    srq: *mut ibv_srq,
+    cq: *mut ibv_cq,
+    lkey: u32,
+    allocator: Arc<RdmaBaseAllocator>,
+    started: bool,
+    recv_head: u64,
+    // in vecs, so the links of the ring do not move with the struct
+    rsges: Vec<ibv_sge>,
+    rwrs: Vec<ibv_recv_wr>,
+    rwcs: Vec<ibv_wc>,
+    // the recvs of other qps polled while waiting for a handshake, given to the next poll_recvs,
+    // and the handshake buffers that wait for them to be posted again in ring order
+    stash: VecDeque<ibv_wc>,
+    deferred: u64,
+    // software srq, for test, the sends of the soft connections tagged with their qp
+    soft: Option<SoftSrqInbox>,
+    soft_qp_num: u32,
+}
+
+unsafe impl Send for RdmaSharedRecvQueue {}
+
+impl RdmaSharedRecvQueue {
+    // on the pd of id, lmr must be registered on the same pd.
+    // The buffers are posted at once, before the first handshake
+    pub fn new(id: *mut rdma_cm_id, lmr: *mut ibv_mr, allocator: &Arc<RdmaBaseAllocator>) -> TransResult<Self> {
+        let depth = MAX_SRQ_RECV_SIZE;
+
+        let cq = unsafe {
+            ibv_create_cq((*id).verbs, depth as _, std::ptr::null_mut(), std::ptr::null_mut(), 0)
+        };
+        if cq.is_null() {
+            println!("ibv_create_cq");
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mut attr = unsafe { std::mem::zeroed::<ibv_srq_init_attr>() };
+        attr.attr.max_wr = depth as _;
+        attr.attr.max_sge = 1;
+
+        let srq = unsafe { ibv_create_srq((*id).pd, &mut attr) };
+        if srq.is_null() {
+            println!("ibv_create_srq");
+            unsafe { ibv_destroy_cq(cq); }
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mut queue = Self::with_queues(srq, cq, unsafe { (*lmr).lkey }, allocator);
+        queue.init_and_start_recvs()?;
+        Ok(queue)
+    }
+
+    // a srq of the software verbs, the soft connections made on it send into its inbox
+    pub fn new_soft(allocator: &Arc<RdmaBaseAllocator>) -> TransResult<Self> {
+        let mut queue = Self::with_queues(std::ptr::null_mut(), std::ptr::null_mut(), 0, allocator);
+        queue.soft = Some(Arc::new(Mutex::new(VecDeque::new())));
+        queue.init_and_start_recvs()?;
+        Ok(queue)
+    }
+
+    fn with_queues(srq: *mut ibv_srq, cq: *mut ibv_cq, lkey: u32, allocator: &Arc<RdmaBaseAllocator>) -> Self {
+        Self {
+            srq: srq,
+            cq: cq,
+            lkey: lkey,
+            allocator: allocator.clone(),
+            started: false,
+            recv_head: 0,
+            rsges: (0..MAX_SRQ_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            rwrs: (0..MAX_SRQ_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            rwcs: (0..MAX_SRQ_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            stash: VecDeque::new(),
+            deferred: 0,
+            soft: None,
+            soft_qp_num: 0,
+        }
+    }
+
+    // the inbox and a fresh qp number for a soft connection, none on a verbs srq
+    pub(crate) fn attach_soft(&mut self) -> Option<(SoftSrqInbox, u32)> {
+        let inbox = self.soft.as_ref()?.clone();
+        self.soft_qp_num += 1;
+        Some((inbox, self.soft_qp_num))
+    }
+
+    #[inline]
+    pub fn get_srq(&self) -> *mut ibv_srq {
+        self.srq
+    }
+
+    #[inline]
+    pub fn get_cq(&self) -> *mut ibv_cq {
+        self.cq
+    }
+
+    pub fn init_and_start_recvs(&mut self) -> TransResult<()> {
+        if self.started {
+            return Ok(());
+        }
+
+        let layout = Layout::from_size_align(MAX_PACKET_SIZE, std::mem::align_of::<usize>()).unwrap();
+        for i in 0..MAX_SRQ_RECV_SIZE {
+            let addr = unsafe { self.allocator.alloc(layout) };
+            if addr.is_null() {
+                return Err(TransError::TransRdmaError);
+            }
+
+            self.rsges[i] = ibv_sge {
+                addr: addr as u64,
+                length: MAX_PACKET_SIZE as _,
+                lkey: self.lkey,
+            };
+
+            let next = if i + 1 == MAX_SRQ_RECV_SIZE {
+                &mut self.rwrs[0] as *mut _
+            } else {
+                &mut self.rwrs[i + 1] as *mut _
+            };
+
+            self.rwrs[i] = ibv_recv_wr {
+                wr_id: addr as u64,
+                sg_list: &mut self.rsges[i] as *mut _,
+                next: next,
+                num_sge: 1,
+            };
+        }
+
+        self.started = true;
+        self.post_recvs(MAX_SRQ_RECV_SIZE as u64)
+    }
+
+    // batch recv, with the handshake buffers deferred meanwhile
+    pub fn post_recvs(&mut self, recv_num: u64) -> TransResult<()> {
+        let recv_num = recv_num + self.deferred;
+        self.deferred = 0;
+        if recv_num == 0 {
+            return Ok(());
+        }
+
+        if self.soft.is_some() {
+            self.recv_head = (self.recv_head + recv_num) % (MAX_SRQ_RECV_SIZE as u64);
+            return Ok(());
+        }
+
+        let recv_head = self.recv_head;
+        let recv_tail = (recv_head + recv_num - 1) % MAX_SRQ_RECV_SIZE as u64;
+
+        let temp = self.rwrs[recv_tail as usize].next;
+        self.rwrs[recv_tail as usize].next = std::ptr::null_mut();
+
+        let mut bad_wr = std::ptr::null_mut();
+        let ret = unsafe {
+            ibv_post_srq_recv(self.srq, &mut self.rwrs[recv_head as usize], &mut bad_wr as *mut _)
+        };
+
+        self.rwrs[recv_tail as usize].next = temp;
+        if ret != 0 {
+            return Err(TransError::TransRdmaError);
+        }
+
+        self.recv_head = (recv_tail + 1) % (MAX_SRQ_RECV_SIZE as u64);
+        Ok(())
+    }
+
+    // the completions are fetched by recv(i) then given back by post_recvs
+    pub fn poll_recvs(&mut self) -> usize {
+        if !self.started {
+            return 0;
+        }
+
+        if !self.stash.is_empty() {
+            let stashed = self.stash.len();
+            for i in 0..stashed {
+                self.rwcs[i] = self.stash.pop_front().unwrap();
+            }
+            return stashed;
+        }
+
+        if self.soft.is_some() {
+            return self.poll_soft_recvs();
+        }
+
+        let poll_result = unsafe {
+            ibv_poll_cq(self.cq, MAX_SRQ_RECV_SIZE as _, &mut self.rwcs[0] as *mut _)
+        };
+
+        if poll_result > 0 { poll_result as usize } else { 0 }
+    }
+
+    // the local qp number and the msg of the i-th polled completion,
+    // none if it failed with its qp, the buffer is still posted again
+    #[inline]
+    pub fn recv(&self, i: usize) -> Option<(u32, *mut u8)> {
+        if self.rwcs[i].status != ibv_wc_status::IBV_WC_SUCCESS {
+            return None;
+        }
+        Some((self.rwcs[i].qp_num, self.rwcs[i].wr_id as *mut u8))
+    }
+
+    // the sends of the soft connections are copied into the buffers in ring order
+    fn poll_soft_recvs(&mut self) -> usize {
+        let mut inbox = self.soft.as_ref().unwrap().lock().unwrap();
+        let mut poll_result = 0;
+        while poll_result < MAX_SRQ_RECV_SIZE {
+            let (qp_num, words) = match inbox.pop_front() {
+                Some(recv) => recv,
+                None => break,
+            };
+
+            let idx = (self.recv_head as usize + poll_result) % MAX_SRQ_RECV_SIZE;
+            let buf = self.rsges[idx].addr;
+            unsafe {
+                std::ptr::copy_nonoverlapping(words.as_ptr() as *const u8, buf as *mut u8, words.len() * 8);
+            }
+
+            self.rwcs[poll_result].status = ibv_wc_status::IBV_WC_SUCCESS;
+            self.rwcs[poll_result].qp_num = qp_num;
+            self.rwcs[poll_result].wr_id = buf;
+            poll_result += 1;
+        }
+        poll_result
+    }
+
+    // busy polls the meta of the connection on qp_num into dst, the status of its completion,
+    // the recvs of the other qps are kept for the next poll_recvs
+    pub fn wait_handshake(&mut self, qp_num: u32, dst: *mut u8, len: usize) -> TransResult<ibv_wc_status::Type> {
+        let mut wc = unsafe { std::mem::zeroed::<ibv_wc>() };
+        loop {
+            let ret = unsafe { ibv_poll_cq(self.cq, 1, &mut wc as *mut _) };
+            if ret < 0 {
+                return Err(TransError::TransRdmaError);
+            }
+            if ret == 0 {
+                continue;
+            }
+            if wc.qp_num != qp_num {
+                self.stash.push_back(wc);
+                continue;
+            }
+
+            if wc.status == ibv_wc_status::IBV_WC_SUCCESS {
+                unsafe { std::ptr::copy_nonoverlapping(wc.wr_id as *const u8, dst, len) };
+            }
+
+            // the buffer goes back in ring order, after the stashed ones
+            if self.stash.is_empty() && self.deferred == 0 {
+                self.post_recvs(1)?;
+            } else {
+                self.deferred += 1;
+            }
+
+            return Ok(wc.status);
+        }
+    }
+}
+
+impl Drop for RdmaSharedRecvQueue {
+    fn drop(&mut self) {
+        if self.soft.is_some() {
+            return;
+        }
+
+        unsafe {
+            ibv_destroy_srq(self.srq);
+            ibv_destroy_cq(self.cq);
+        }
+    }
+}
@@ -1,4 +1,602 @@
-#[allow(unused)]
-struct RdmaUdAdapter {
+use std::alloc::Layout;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-}
\ No newline at end of file
+use rdma_sys::*;
+
+use super::control::RdmaBaseAllocator;
+use super::device::{list_rdma_devices, open_rdma_device, RdmaDeviceConfig};
+use super::soft_verbs::SoftUdNet;
+use crate::common::connection::{ ConfigExchangeError, ConfigSerialize };
+use crate::*;
+
+// the sender of a datagram and the seq of its request in its imm,
+// peer_id << (UD_TID_BITS + UD_SEQ_BITS) | tid << UD_SEQ_BITS | seq
+const UD_TID_BITS: u32 = 8;
+const UD_SEQ_BITS: u32 = 12;
+pub const UD_SEQ_MASK: u32 = (1 << UD_SEQ_BITS) - 1;
+
+// what a peer needs to send to the qp, exchanged out of band
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RdmaUdAddr {
+    pub peer_id: u64,
+    pub tid: u64,
+    pub lid: u16,
+    pub qpn: u32,
+    pub gid: [u8; 16],
+}
+
+// sent by the bootstrap in little endian, field by field
+const UD_ADDR_LENGTH: usize = 8 + 8 + 2 + 4 + 16;
+
+impl ConfigSerialize for RdmaUdAddr {
+    fn serialize(data: RdmaUdAddr) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(UD_ADDR_LENGTH);
+        buf.extend_from_slice(&data.peer_id.to_le_bytes());
+        buf.extend_from_slice(&data.tid.to_le_bytes());
+        buf.extend_from_slice(&data.lid.to_le_bytes());
+        buf.extend_from_slice(&data.qpn.to_le_bytes());
+        buf.extend_from_slice(&data.gid);
+        buf
+    }
+
+    fn deserialize(data: &[u8]) -> Result<RdmaUdAddr, ConfigExchangeError> {
+        if data.len() != UD_ADDR_LENGTH {
+            return Err(ConfigExchangeError::Malformed(format!("ud address of {} bytes", data.len())));
+        }
+
+        Ok(RdmaUdAddr {
+            peer_id: u64::from_le_bytes(data[0..8].try_into().unwrap()),
+            tid: u64::from_le_bytes(data[8..16].try_into().unwrap()),
+            lid: u16::from_le_bytes(data[16..18].try_into().unwrap()),
+            qpn: u32::from_le_bytes(data[18..22].try_into().unwrap()),
+            gid: data[22..38].try_into().unwrap(),
+        })
+    }
+}
+
+/// A UD queue pair of a thread, datagram RPCs to and from all the peers go through it.
+/// The address handles of the peers are created once and cached, a msg must fit in the
+/// MTU of the port. Datagrams may be lost, the scheduler sends the requests again.
+pub struct RdmaUdAdapter {
+    self_id: u64,
+    tid: u64,
+    pd: *mut ibv_pd,
+    port_num: u8,
+    gid_index: i32,
+    qp: *mut ibv_qp,
+    send_cq: *mut ibv_cq,
+    recv_cq: *mut ibv_cq,
+    lmr: *mut ibv_mr,
+    allocator: Arc<RdmaBaseAllocator>,
+    local: RdmaUdAddr,
+    // (peer_id, tid) -> (ah, remote qpn)
+    ahs: HashMap<(u64, u64), (*mut ibv_ah, u32)>,
+    // recvs
+    recv_head: u64,
+    rsges: Vec<ibv_sge>,
+    rwrs: Vec<ibv_recv_wr>,
+    rwcs: Vec<ibv_wc>,
+    // sends, doorbell batched as the rc connections
+    current_idx: u64,
+    pending_sends: u64,
+    high_watermark: u64,
+    low_watermark: u64,
+    ssges: Vec<ibv_sge>,
+    swrs: Vec<ibv_send_wr>,
+    // the largest msg, the mtu of the port at most a packet
+    max_msg: usize,
+    // the device and the pd opened for the adapter, closed on drop
+    opened: Option<*mut ibv_context>,
+    // software datagrams, for test
+    soft: Option<SoftUdNet>,
+}
+
+unsafe impl Send for RdmaUdAdapter {}
+
+impl RdmaUdAdapter {
+    pub fn new(
+        self_id: u64,
+        tid: u64,
+        context: *mut ibv_context,
+        pd: *mut ibv_pd,
+        device: &RdmaDeviceConfig,
+        allocator: &Arc<RdmaBaseAllocator>,
+    ) -> TransResult<Self> {
+        if tid >= 1 << UD_TID_BITS || self_id >= 1 << (32 - UD_TID_BITS - UD_SEQ_BITS) {
+            return Err(TransError::TransRdmaError);
+        }
+
+        let send_cq = unsafe {
+            ibv_create_cq(context, MAX_SEND_SIZE as _, std::ptr::null_mut(), std::ptr::null_mut(), 0)
+        };
+        let recv_cq = unsafe {
+            ibv_create_cq(context, MAX_UD_RECV_SIZE as _, std::ptr::null_mut(), std::ptr::null_mut(), 0)
+        };
+        if send_cq.is_null() || recv_cq.is_null() {
+            println!("ibv_create_cq");
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mut init_attr = unsafe { std::mem::zeroed::<ibv_qp_init_attr>() };
+        init_attr.send_cq = send_cq;
+        init_attr.recv_cq = recv_cq;
+        init_attr.qp_type = ibv_qp_type::IBV_QPT_UD;
+        init_attr.cap.max_send_wr = MAX_SEND_SIZE as _;
+        init_attr.cap.max_recv_wr = MAX_UD_RECV_SIZE as _;
+        init_attr.cap.max_send_sge = 1;
+        init_attr.cap.max_recv_sge = 1;
+        init_attr.sq_sig_all = 0;
+
+        let qp = unsafe { ibv_create_qp(pd, &mut init_attr) };
+        if qp.is_null() {
+            println!("ibv_create_qp");
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mr_length = allocator.get_len();
+        let lmr = unsafe {
+            ibv_reg_mr(pd, allocator.get_lm() as _, mr_length, ibv_access_flags::IBV_ACCESS_LOCAL_WRITE.0 as _)
+        };
+        if lmr.is_null() {
+            println!("ibv_reg_mr");
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mut adapter = Self {
+            self_id: self_id,
+            tid: tid,
+            pd: pd,
+            port_num: device.port_num,
+            gid_index: device.gid_index,
+            qp: qp,
+            send_cq: send_cq,
+            recv_cq: recv_cq,
+            lmr: lmr,
+            allocator: allocator.clone(),
+            local: RdmaUdAddr::default(),
+            ahs: HashMap::new(),
+            recv_head: 0,
+            rsges: (0..MAX_UD_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            rwrs: (0..MAX_UD_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            rwcs: (0..MAX_UD_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            current_idx: 0,
+            pending_sends: 0,
+            high_watermark: 0,
+            low_watermark: 0,
+            ssges: (0..MAX_DOORBELL_SEND_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            swrs: (0..MAX_DOORBELL_SEND_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            max_msg: MAX_PACKET_SIZE,
+            opened: None,
+            soft: None,
+        };
+
+        adapter.bring_up()?;
+        adapter.local = adapter.query_local_addr(context)?;
+        adapter.init_and_start_recvs()?;
+        Ok(adapter)
+    }
+
+    // on a device and a pd of its own, the named device of the config or the first one
+    pub fn open(
+        self_id: u64,
+        tid: u64,
+        device: &RdmaDeviceConfig,
+        allocator: &Arc<RdmaBaseAllocator>,
+    ) -> TransResult<Self> {
+        let name = match device.name.as_ref() {
+            Some(name) => name.clone(),
+            None => list_rdma_devices()?.first().ok_or(TransError::TransConfigError)?.name.clone(),
+        };
+
+        let context = open_rdma_device(&name)?;
+        let pd = unsafe { ibv_alloc_pd(context) };
+        if pd.is_null() {
+            println!("ibv_alloc_pd");
+            unsafe { ibv_close_device(context); }
+            return Err(TransError::TransRdmaError);
+        }
+
+        match Self::new(self_id, tid, context, pd, device, allocator) {
+            Ok(mut adapter) => {
+                adapter.opened = Some(context);
+                Ok(adapter)
+            }
+            Err(err) => {
+                unsafe {
+                    ibv_dealloc_pd(pd);
+                    ibv_close_device(context);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    // an adapter of the software datagrams, reached as (self_id, tid) on net
+    pub fn new_soft(self_id: u64, tid: u64, net: &SoftUdNet, allocator: &Arc<RdmaBaseAllocator>) -> TransResult<Self> {
+        if tid >= 1 << UD_TID_BITS || self_id >= 1 << (32 - UD_TID_BITS - UD_SEQ_BITS) {
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mut adapter = Self {
+            self_id: self_id,
+            tid: tid,
+            pd: std::ptr::null_mut(),
+            port_num: 0,
+            gid_index: 0,
+            qp: std::ptr::null_mut(),
+            send_cq: std::ptr::null_mut(),
+            recv_cq: std::ptr::null_mut(),
+            lmr: std::ptr::null_mut(),
+            allocator: allocator.clone(),
+            local: RdmaUdAddr { peer_id: self_id, tid: tid, ..Default::default() },
+            ahs: HashMap::new(),
+            recv_head: 0,
+            rsges: (0..MAX_UD_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            rwrs: (0..MAX_UD_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            rwcs: (0..MAX_UD_RECV_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            current_idx: 0,
+            pending_sends: 0,
+            high_watermark: 0,
+            low_watermark: 0,
+            ssges: (0..MAX_DOORBELL_SEND_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            swrs: (0..MAX_DOORBELL_SEND_SIZE).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            max_msg: MAX_PACKET_SIZE,
+            opened: None,
+            soft: Some(net.clone()),
+        };
+
+        adapter.init_and_start_recvs()?;
+        Ok(adapter)
+    }
+
+    // INIT -> RTR -> RTS, a ud qp has no remote to connect
+    fn bring_up(&mut self) -> TransResult<()> {
+        let mut attr = unsafe { std::mem::zeroed::<ibv_qp_attr>() };
+        attr.qp_state = ibv_qp_state::IBV_QPS_INIT;
+        attr.pkey_index = 0;
+        attr.port_num = self.port_num;
+        attr.qkey = UD_QKEY;
+        let mask = ibv_qp_attr_mask::IBV_QP_STATE
+            | ibv_qp_attr_mask::IBV_QP_PKEY_INDEX
+            | ibv_qp_attr_mask::IBV_QP_PORT
+            | ibv_qp_attr_mask::IBV_QP_QKEY;
+        if unsafe { ibv_modify_qp(self.qp, &mut attr, mask.0 as _) } != 0 {
+            println!("ibv_modify_qp to init");
+            return Err(TransError::TransRdmaError);
+        }
+
+        attr.qp_state = ibv_qp_state::IBV_QPS_RTR;
+        if unsafe { ibv_modify_qp(self.qp, &mut attr, ibv_qp_attr_mask::IBV_QP_STATE.0 as _) } != 0 {
+            println!("ibv_modify_qp to rtr");
+            return Err(TransError::TransRdmaError);
+        }
+
+        attr.qp_state = ibv_qp_state::IBV_QPS_RTS;
+        attr.sq_psn = 0;
+        let mask = ibv_qp_attr_mask::IBV_QP_STATE | ibv_qp_attr_mask::IBV_QP_SQ_PSN;
+        if unsafe { ibv_modify_qp(self.qp, &mut attr, mask.0 as _) } != 0 {
+            println!("ibv_modify_qp to rts");
+            return Err(TransError::TransRdmaError);
+        }
+
+        Ok(())
+    }
+
+    fn query_local_addr(&mut self, context: *mut ibv_context) -> TransResult<RdmaUdAddr> {
+        let mut port_attr = unsafe { std::mem::zeroed::<ibv_port_attr>() };
+        if unsafe { ___ibv_query_port(context, self.port_num, &mut port_attr) } != 0 {
+            println!("ibv_query_port");
+            return Err(TransError::TransRdmaError);
+        }
+
+        let mut gid = unsafe { std::mem::zeroed::<ibv_gid>() };
+        if unsafe { ibv_query_gid(context, self.port_num, self.gid_index, &mut gid) } != 0 {
+            println!("ibv_query_gid");
+            return Err(TransError::TransRdmaError);
+        }
+
+        // a datagram is one packet of the path mtu, 256 << (active_mtu - 1) bytes
+        self.max_msg = std::cmp::min(128usize << port_attr.active_mtu, MAX_PACKET_SIZE);
+
+        Ok(RdmaUdAddr {
+            peer_id: self.self_id,
+            tid: self.tid,
+            lid: port_attr.lid,
+            qpn: unsafe { (*self.qp).qp_num },
+            gid: unsafe { gid.raw },
+        })
+    }
+
+    #[inline]
+    pub fn get_local_addr(&self) -> RdmaUdAddr {
+        self.local
+    }
+
+    #[inline]
+    pub fn get_max_msg_size(&self) -> usize {
+        self.max_msg
+    }
+
+    // the address handle of the peer thread, created only once
+    pub fn add_peer(&mut self, addr: &RdmaUdAddr) -> TransResult<()> {
+        if self.ahs.contains_key(&(addr.peer_id, addr.tid)) {
+            return Ok(());
+        }
+
+        if self.soft.is_some() {
+            self.ahs.insert((addr.peer_id, addr.tid), (std::ptr::null_mut(), addr.qpn));
+            return Ok(());
+        }
+
+        let mut ah_attr = unsafe { std::mem::zeroed::<ibv_ah_attr>() };
+        ah_attr.dlid = addr.lid;
+        ah_attr.sl = 0;
+        ah_attr.src_path_bits = 0;
+        ah_attr.port_num = self.port_num;
+        // routed by the gid, needed by roce
+        ah_attr.is_global = 1;
+        ah_attr.grh.dgid.raw = addr.gid;
+        ah_attr.grh.sgid_index = self.gid_index as _;
+        ah_attr.grh.hop_limit = 0xff;
+
+        let ah = unsafe { ibv_create_ah(self.pd, &mut ah_attr) };
+        if ah.is_null() {
+            println!("ibv_create_ah");
+            return Err(TransError::TransRdmaError);
+        }
+
+        self.ahs.insert((addr.peer_id, addr.tid), (ah, addr.qpn));
+        Ok(())
+    }
+
+    fn init_and_start_recvs(&mut self) -> TransResult<()> {
+        let lkey = if self.lmr.is_null() { 0 } else { unsafe { (*self.lmr).lkey } };
+        let layout = Layout::from_size_align(UD_GRH_SIZE + MAX_PACKET_SIZE, std::mem::align_of::<usize>()).unwrap();
+        for i in 0..MAX_UD_RECV_SIZE {
+            let addr = unsafe { self.allocator.alloc(layout) };
+            if addr.is_null() {
+                return Err(TransError::TransRdmaError);
+            }
+
+            self.rsges[i] = ibv_sge {
+                addr: addr as u64,
+                length: (UD_GRH_SIZE + MAX_PACKET_SIZE) as _,
+                lkey: lkey,
+            };
+
+            let next = if i + 1 == MAX_UD_RECV_SIZE {
+                &mut self.rwrs[0] as *mut _
+            } else {
+                &mut self.rwrs[i + 1] as *mut _
+            };
+
+            self.rwrs[i] = ibv_recv_wr {
+                wr_id: addr as u64,
+                sg_list: &mut self.rsges[i] as *mut _,
+                next: next,
+                num_sge: 1,
+            };
+        }
+
+        for i in 0..MAX_DOORBELL_SEND_SIZE {
+            self.ssges[i].lkey = lkey;
+
+            self.swrs[i].opcode = ibv_wr_opcode::IBV_WR_SEND_WITH_IMM;
+            self.swrs[i].num_sge = 1;
+            self.swrs[i].next = if i + 1 == MAX_DOORBELL_SEND_SIZE {
+                std::ptr::null_mut()
+            } else {
+                &mut self.swrs[i + 1] as *mut _
+            };
+            self.swrs[i].sg_list = &mut self.ssges[i] as *mut _;
+        }
+
+        self.post_recvs(MAX_UD_RECV_SIZE as u64)
+    }
+
+    // batch recv
+    pub fn post_recvs(&mut self, recv_num: u64) -> TransResult<()> {
+        if recv_num == 0 {
+            return Ok(());
+        }
+
+        if self.soft.is_some() {
+            self.recv_head = (self.recv_head + recv_num) % (MAX_UD_RECV_SIZE as u64);
+            return Ok(());
+        }
+
+        let recv_head = self.recv_head;
+        let recv_tail = (recv_head + recv_num - 1) % MAX_UD_RECV_SIZE as u64;
+
+        let temp = self.rwrs[recv_tail as usize].next;
+        self.rwrs[recv_tail as usize].next = std::ptr::null_mut();
+
+        let mut bad_wr = std::ptr::null_mut();
+        let ret = unsafe {
+            ibv_post_recv(self.qp, &mut self.rwrs[recv_head as usize], &mut bad_wr as *mut _)
+        };
+
+        self.rwrs[recv_tail as usize].next = temp;
+        if ret != 0 {
+            return Err(TransError::TransRdmaError);
+        }
+
+        self.recv_head = (recv_tail + 1) % (MAX_UD_RECV_SIZE as u64);
+        Ok(())
+    }
+
+    // the completions are fetched by recv(i) then given back by post_recvs
+    pub fn poll_recvs(&mut self) -> usize {
+        if self.soft.is_some() {
+            return self.poll_soft_recvs();
+        }
+
+        let poll_result = unsafe {
+            ibv_poll_cq(self.recv_cq, MAX_UD_RECV_SIZE as _, &mut self.rwcs[0] as *mut _)
+        };
+
+        if poll_result > 0 { poll_result as usize } else { 0 }
+    }
+
+    // the datagrams are copied after the grh of the buffers in ring order
+    fn poll_soft_recvs(&mut self) -> usize {
+        let net = self.soft.clone().unwrap();
+        let mut poll_result = 0;
+        while poll_result < MAX_UD_RECV_SIZE {
+            let (imm, words) = match net.recv((self.self_id, self.tid)) {
+                Some(recv) => recv,
+                None => break,
+            };
+
+            let idx = (self.recv_head as usize + poll_result) % MAX_UD_RECV_SIZE;
+            let buf = self.rsges[idx].addr;
+            unsafe {
+                let dst = (buf as *mut u8).add(UD_GRH_SIZE);
+                std::ptr::copy_nonoverlapping(words.as_ptr() as *const u8, dst, words.len() * 8);
+            }
+
+            let wc = &mut self.rwcs[poll_result];
+            wc.status = ibv_wc_status::IBV_WC_SUCCESS;
+            wc.wc_flags = ibv_wc_flags::IBV_WC_WITH_IMM.0;
+            wc.imm_data_invalidated_rkey_union.imm_data = imm.to_be();
+            wc.wr_id = buf;
+            poll_result += 1;
+        }
+        poll_result
+    }
+
+    // the sender (peer_id, tid), the seq and the msg after the grh of the i-th polled
+    // completion, None if the recv failed
+    pub fn recv(&self, i: usize) -> Option<(u64, u64, u32, *mut u8)> {
+        let wc = &self.rwcs[i];
+        if wc.status != ibv_wc_status::IBV_WC_SUCCESS
+            || wc.wc_flags & ibv_wc_flags::IBV_WC_WITH_IMM.0 == 0
+        {
+            return None;
+        }
+
+        let imm = u32::from_be(unsafe { wc.imm_data_invalidated_rkey_union.imm_data }) as u64;
+        let msg = unsafe { (wc.wr_id as *mut u8).add(UD_GRH_SIZE) };
+        let sender = imm >> UD_SEQ_BITS;
+        Some((sender >> UD_TID_BITS, sender & ((1 << UD_TID_BITS) - 1), imm as u32 & UD_SEQ_MASK, msg))
+    }
+
+    #[inline]
+    fn imm_of(&self, seq: u32) -> u32 {
+        ((self.self_id << UD_TID_BITS | self.tid) << UD_SEQ_BITS) as u32 | (seq & UD_SEQ_MASK)
+    }
+
+    // msg is in the registered region and fits in the mtu, a reply carries the seq of its request
+    pub fn send_pending(&mut self, peer_id: u64, tid: u64, seq: u32, msg: *mut u8, length: u32) -> TransResult<()> {
+        if length as usize > self.max_msg {
+            return Err(TransError::TransRdmaError);
+        }
+
+        let (ah, qpn) = match self.ahs.get(&(peer_id, tid)) {
+            Some(ah) => *ah,
+            None => return Err(TransError::TransRdmaError),
+        };
+
+        if let Some(net) = self.soft.as_ref() {
+            net.send((peer_id, tid), self.imm_of(seq), msg, length);
+            return Ok(());
+        }
+
+        let current_idx = self.current_idx as usize;
+        self.current_idx += 1;
+
+        self.ssges[current_idx].addr = msg as _;
+        self.ssges[current_idx].length = length;
+        self.swrs[current_idx].imm_data_invalidated_rkey_union.imm_data = self.imm_of(seq).to_be();
+
+        self.swrs[current_idx].send_flags = 0;
+        self.swrs[current_idx].wr.ud = ud_t {
+            ah: ah,
+            remote_qpn: qpn,
+            remote_qkey: UD_QKEY,
+        };
+
+        if current_idx + 1 == MAX_DOORBELL_SEND_SIZE {
+            return self.flush_pending();
+        }
+        Ok(())
+    }
+
+    pub fn flush_pending(&mut self) -> TransResult<()> {
+        if self.soft.is_some() {
+            return Ok(());
+        }
+
+        let current_idx = self.current_idx;
+        let need_signal = self.pending_sends + current_idx >= MAX_SIGNAL_PENDINGS as u64;
+        if current_idx > 0 {
+            self.current_idx = 0;
+            self.high_watermark += current_idx;
+            if need_signal {
+                self.pending_sends = 0;
+            } else {
+                self.pending_sends += current_idx;
+            }
+
+            let last = (current_idx - 1) as usize;
+            self.swrs[last].next = std::ptr::null_mut();
+            if need_signal {
+                self.swrs[last].send_flags |= ibv_send_flags::IBV_SEND_SIGNALED.0;
+                self.swrs[last].wr_id = self.high_watermark;
+            }
+
+            let mut bad_wr: *mut ibv_send_wr = std::ptr::null_mut();
+            let ret = unsafe { ibv_post_send(self.qp, &mut self.swrs[0] as *mut _, &mut bad_wr as *mut _) };
+
+            if last + 1 < MAX_DOORBELL_SEND_SIZE {
+                self.swrs[last].next = &mut self.swrs[last + 1] as *mut _;
+            }
+
+            if ret != 0 {
+                return Err(TransError::TransRdmaError);
+            }
+        }
+
+        while self.high_watermark - self.low_watermark >= (MAX_SEND_SIZE / 2) as u64 {
+            self.poll_send();
+        }
+        Ok(())
+    }
+
+    pub fn poll_send(&mut self) -> i32 {
+        if self.soft.is_some() {
+            return 0;
+        }
+
+        let mut wc: ibv_wc = unsafe { std::mem::zeroed() };
+        let poll_result = unsafe { ibv_poll_cq(self.send_cq, 1, &mut wc as *mut _) };
+        if poll_result > 0 {
+            self.low_watermark = wc.wr_id;
+        }
+        poll_result
+    }
+}
+
+impl Drop for RdmaUdAdapter {
+    fn drop(&mut self) {
+        if self.soft.is_some() {
+            return;
+        }
+
+        unsafe {
+            for (_, (ah, _)) in self.ahs.iter() {
+                ibv_destroy_ah(*ah);
+            }
+            ibv_destroy_qp(self.qp);
+            ibv_destroy_cq(self.send_cq);
+            ibv_destroy_cq(self.recv_cq);
+            ibv_dereg_mr(self.lmr);
+            if let Some(context) = self.opened {
+                ibv_dealloc_pd(self.pd);
+                ibv_close_device(context);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+#![feature(get_mut_unchecked)]
+use std::cell::Cell;
+
+use trans::occ::occ_remote::OccRemote;
+use trans::occ::two_pl::{TplPolicy, TwoPl};
+use trans::occ::{CcProtocol, RwType};
+
+mod common;
+use common::{balance_of, new_cluster, Account, Cluster};
+
+async fn deposit(cluster: &Cluster, done: &Cell<bool>) -> bool {
+    let mut txn = OccRemote::<8>::new(0, 0, 1, &cluster.memdb_a, &cluster.scheduler_a);
+    txn.start();
+
+    let idx = txn.fetch_write::<Account>(0, 1, 2);
+    let balance = txn.get_value::<Account>(true, idx).await.balance;
+    txn.set_value(true, idx, &Account{ balance: balance + 2 });
+
+    txn.commit().await;
+    done.set(true);
+    txn.is_commited()
+}
+
+// reads account 2 under a shared lock and blindly writes account 3 under an exclusive one
+async fn tpl_read_write(cluster: &Cluster, done: &Cell<bool>) -> bool {
+    let mut txn = TwoPl::<8>::new(TplPolicy::NoWait, 0, 0, 1, &cluster.memdb_a, &cluster.scheduler_a);
+    txn.start();
+
+    let read_idx = txn.read::<Account>(0, 1, 2);
+    let write_idx = txn.write::<Account>(0, 1, 3, RwType::UPDATE);
+    let balance = txn.get_value::<Account>(false, read_idx).await.balance;
+    txn.set_value(false, write_idx, &Account{ balance: balance });
+
+    txn.commit().await;
+    done.set(true);
+    txn.is_commited()
+}
+
+async fn poll_until(cluster: &Cluster, done: &Cell<bool>) {
+    while !done.get() {
+        cluster.scheduler_b.poll_recvs();
+        cluster.scheduler_a.poll_recvs();
+        tokio::task::yield_now().await;
+    }
+}
+
+#[test]
+fn broken_conn_test()
+{
+    let cluster = new_cluster(CcProtocol::Occ);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // the participant locks the account, then both ends break before the reply is taken
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            deposit(&cluster, &done),
+            async {
+                cluster.scheduler_b.poll_recvs();
+                assert_ne!(balance_of(&cluster.memdb_b, 2).0, 0);
+
+                cluster.conn_a.lock().unwrap().mark_broken();
+                cluster.conn_b.lock().unwrap().mark_broken();
+                poll_until(&cluster, &done).await;
+            },
+        )
+    });
+
+    // the waiting txn is failed and the lock of its peer is released
+    assert_eq!(commited, false);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 200));
+
+    // a request to the broken peer fails at once instead of being dropped
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            deposit(&cluster, &done),
+            poll_until(&cluster, &done),
+        )
+    });
+    assert_eq!(commited, false);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 200));
+}
+
+#[test]
+fn broken_conn_tpl_test()
+{
+    let cluster = new_cluster(CcProtocol::TwoPlNoWait);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            tpl_read_write(&cluster, &done),
+            async {
+                cluster.scheduler_b.poll_recvs();
+                assert_ne!(balance_of(&cluster.memdb_b, 2).0, 0);
+                assert_ne!(cluster.memdb_b.local_get_meta(0, 3).unwrap().lock, 0);
+
+                cluster.conn_a.lock().unwrap().mark_broken();
+                cluster.conn_b.lock().unwrap().mark_broken();
+                poll_until(&cluster, &done).await;
+            },
+        )
+    });
+
+    // both the anonymous shared lock and the exclusive one of the 2pl word are released
+    assert_eq!(commited, false);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 200));
+    assert_eq!(cluster.memdb_b.local_get_meta(0, 3).unwrap().lock, 0);
+}
@@ -0,0 +1,268 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use trans::framework::scheduler::AsyncScheduler;
+use trans::memstore::memdb::{MemDB, TableSchema};
+use trans::memstore::{delta_add_u64, RobinhoodMemStore, DELTA_LOCK_FLAG, SHARED_LOCK_FLAG};
+use trans::occ::occ_local::OccLocal;
+use trans::occ::tictoc::{TicToc, TicTocWord};
+use trans::occ::two_pl::{TplPolicy, TwoPl};
+use trans::occ::RwType;
+use trans::rdma::RdmaBaseAllocator;
+
+#[repr(C)]
+#[derive(Clone, Default)]
+struct Account {
+    balance: u64,
+}
+
+#[test]
+fn tpl_shared_lock_test()
+{
+    let mut memdb = Arc::new(MemDB::new());
+    let memstore = RobinhoodMemStore::<Account>::new();
+
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+
+    let mut occ = OccLocal::<8>::new(1, &memdb);
+    occ.start();
+    let idx = occ.write::<Account>(0, 0, 10037, RwType::INSERT);
+    occ.set_value(false, idx, &Account{
+        balance: 100
+    });
+    occ.commit();
+    assert_eq!(occ.is_commited(), true);
+
+    let mut value = Account::default();
+    let ptr = &mut value as *mut Account as *mut u8;
+    let len = std::mem::size_of::<Account>() as u32;
+
+    // readers share the lock
+    let meta = memdb.local_get_shared(0, 10037, ptr, len).unwrap();
+    assert_eq!(meta.lock, SHARED_LOCK_FLAG | 1);
+    assert_eq!(value.balance, 100);
+    let meta = memdb.local_get_shared(0, 10037, ptr, len).unwrap();
+    assert_eq!(meta.lock, SHARED_LOCK_FLAG | 2);
+
+    // writers are excluded until all the readers leave
+    let meta = memdb.local_lock(0, 10037, 73).unwrap();
+    assert_ne!(meta.lock, 73);
+
+    memdb.local_unlock_shared(0, 10037);
+    memdb.local_unlock_shared(0, 10037);
+
+    let meta = memdb.local_lock(0, 10037, 73).unwrap();
+    assert_eq!(meta.lock, 73);
+
+    let meta = memdb.local_get_shared(0, 10037, ptr, len).unwrap();
+    assert_eq!(meta.lock, 73);
+}
+
+#[test]
+fn delta_lock_test()
+{
+    let mut memdb = Arc::new(MemDB::new());
+    let memstore = RobinhoodMemStore::<Account>::new();
+
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+    Arc::get_mut(&mut memdb).unwrap().add_merge(1, delta_add_u64::<0>);
+
+    let mut occ = OccLocal::<8>::new(1, &memdb);
+    occ.start();
+    let idx = occ.write::<Account>(0, 0, 10037, RwType::INSERT);
+    occ.set_value(false, idx, &Account{
+        balance: 100
+    });
+    occ.commit();
+    assert_eq!(occ.is_commited(), true);
+
+    let mut value = Account::default();
+    let ptr = &mut value as *mut Account as *mut u8;
+    let len = std::mem::size_of::<Account>() as u32;
+
+    // the writers of deltas share the lock
+    let meta = memdb.local_lock_delta(0, 10037, 71).unwrap();
+    assert_eq!(meta.lock, DELTA_LOCK_FLAG | 1);
+    let meta = memdb.local_lock_delta(0, 10037, 72).unwrap();
+    assert_eq!(meta.lock, DELTA_LOCK_FLAG | 2);
+
+    // and exclude the readers and the other writers
+    let meta = memdb.local_get_shared(0, 10037, ptr, len).unwrap();
+    assert_eq!(meta.lock, DELTA_LOCK_FLAG | 2);
+    let meta = memdb.local_lock(0, 10037, 73).unwrap();
+    assert_ne!(meta.lock, 73);
+
+    let delta = 23u64;
+    memdb.local_apply_delta(0, 10037, 1, &delta as *const u64 as _, 8, 0);
+    memdb.local_apply_delta(0, 10037, 1, &delta as *const u64 as _, 8, 0);
+
+    // only the holders release it
+    memdb.local_unlock_delta(0, 10037, 73);
+    memdb.local_unlock_delta(0, 10037, 71);
+    memdb.local_unlock_delta(0, 10037, 71);
+    assert_eq!(memdb.local_get_meta(0, 10037).unwrap().lock, DELTA_LOCK_FLAG | 1);
+    memdb.local_unlock_delta(0, 10037, 72);
+
+    let meta = memdb.local_lock(0, 10037, 73).unwrap();
+    assert_eq!(meta.lock, 73);
+    let meta = memdb.local_lock_delta(0, 10037, 71).unwrap();
+    assert_eq!(meta.lock, 73);
+
+    memdb.local_get_readonly(0, 10037, ptr, len);
+    assert_eq!(value.balance, 146);
+}
+
+#[test]
+fn tictoc_word_test()
+{
+    let word = TicTocWord::new(100, 0);
+    assert_eq!(word.rts(), 100);
+
+    let word = word.extend(150);
+    assert_eq!(word.wts, 100);
+    assert_eq!(word.rts(), 150);
+
+    // delta overflows, wts is pushed forward
+    let word = word.extend(100 + 0x20000);
+    assert_eq!(word.rts(), 100 + 0x20000);
+    assert!(word.wts > 100);
+}
+
+fn new_accounts(keys: &[u64]) -> Arc<MemDB> {
+    let mut memdb = Arc::new(MemDB::new());
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), RobinhoodMemStore::<Account>::new());
+
+    for key in keys {
+        let mut occ = OccLocal::<8>::new(1, &memdb);
+        occ.start();
+        let idx = occ.write::<Account>(0, 0, *key, RwType::INSERT);
+        occ.set_value(false, idx, &Account{
+            balance: 100
+        });
+        occ.commit();
+        assert_eq!(occ.is_commited(), true);
+    }
+    memdb
+}
+
+fn balance_of(memdb: &MemDB, key: u64) -> u64 {
+    let mut value = Account::default();
+    let ptr = &mut value as *mut Account as *mut u8;
+    memdb.local_get_readonly(0, key, ptr, std::mem::size_of::<Account>() as _).unwrap();
+    value.balance
+}
+
+#[test]
+fn tpl_txn_test()
+{
+    let memdb = new_accounts(&[1, 2, 3]);
+    let scheduler = Arc::new(AsyncScheduler::new(0, 4, &Arc::new(RdmaBaseAllocator::new())));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // a reader conflicts with a writer and aborts under NO_WAIT
+    let mut writer = TwoPl::<8>::new(TplPolicy::NoWait, 0, 0, 1, &memdb, &scheduler);
+    writer.start();
+    let idx = writer.fetch_write::<Account>(0, 0, 1);
+
+    let mut reader = TwoPl::<8>::new(TplPolicy::NoWait, 0, 0, 2, &memdb, &scheduler);
+    reader.start();
+    reader.read::<Account>(0, 0, 1);
+    runtime.block_on(reader.commit());
+    assert!(reader.is_aborted());
+
+    writer.set_value(true, idx, &Account{
+        balance: 200
+    });
+    runtime.block_on(writer.commit());
+    assert!(writer.is_commited());
+    assert_eq!(memdb.local_get_meta(0, 1).unwrap().lock, 0);
+
+    // a read then a write of the same key upgrades the shared lock
+    let mut txn = TwoPl::<8>::new(TplPolicy::NoWait, 0, 0, 1, &memdb, &scheduler);
+    txn.start();
+    txn.read::<Account>(0, 0, 1);
+    let idx = txn.fetch_write::<Account>(0, 0, 1);
+    let balance = runtime.block_on(txn.get_value::<Account>(true, idx)).balance;
+    assert_eq!(balance, 200);
+
+    let lock = memdb.local_get_meta(0, 1).unwrap().lock;
+    assert!(lock != 0 && lock & SHARED_LOCK_FLAG == 0);
+
+    txn.set_value(true, idx, &Account{
+        balance: balance + 1
+    });
+    runtime.block_on(txn.commit());
+    assert!(txn.is_commited());
+    assert_eq!(memdb.local_get_meta(0, 1).unwrap().lock, 0);
+    assert_eq!(balance_of(&memdb, 1), 201);
+}
+
+#[test]
+fn tpl_wait_die_test()
+{
+    let memdb = new_accounts(&[1, 2]);
+    let scheduler = Arc::new(AsyncScheduler::new(0, 4, &Arc::new(RdmaBaseAllocator::new())));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    let mut older = TwoPl::<8>::new(TplPolicy::WaitDie, 0, 0, 1, &memdb, &scheduler);
+    std::thread::sleep(Duration::from_millis(5));
+    let mut younger = TwoPl::<8>::new(TplPolicy::WaitDie, 0, 0, 2, &memdb, &scheduler);
+
+    // the younger dies on the lock of the older
+    older.start();
+    younger.start();
+    older.fetch_write::<Account>(0, 0, 1);
+    younger.fetch_write::<Account>(0, 0, 1);
+    runtime.block_on(younger.commit());
+    assert!(younger.is_aborted());
+    runtime.block_on(older.commit());
+    assert!(older.is_commited());
+
+    // started again with its first timestamp, it is older than a later txn and waits for it
+    std::thread::sleep(Duration::from_millis(5));
+    let mut later = TwoPl::<8>::new(TplPolicy::WaitDie, 0, 0, 3, &memdb, &scheduler);
+    later.start();
+    let idx = later.fetch_write::<Account>(0, 0, 2);
+
+    younger.start();
+    let restarted_idx = younger.fetch_write::<Account>(0, 0, 2);
+
+    later.set_value(true, idx, &Account{
+        balance: 300
+    });
+    runtime.block_on(async {
+        let (balance, _) = tokio::join!(
+            async {
+                let balance = younger.get_value::<Account>(true, restarted_idx).await.balance;
+                younger.commit().await;
+                balance
+            },
+            later.commit(),
+        );
+        assert_eq!(balance, 300);
+    });
+    assert!(later.is_commited());
+    assert!(younger.is_commited());
+}
+
+#[test]
+fn tictoc_read_own_write_test()
+{
+    let memdb = new_accounts(&[1]);
+    let scheduler = Arc::new(AsyncScheduler::new(0, 4, &Arc::new(RdmaBaseAllocator::new())));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // the read of a key the txn also writes is not refused by its own write lock
+    let mut txn = TicToc::<8>::new(0, 0, 1, &memdb, &scheduler);
+    txn.start();
+    txn.read::<Account>(0, 0, 1);
+    let idx = txn.fetch_write::<Account>(0, 0, 1);
+    let balance = runtime.block_on(txn.get_value::<Account>(true, idx)).balance;
+    txn.set_value(true, idx, &Account{
+        balance: balance + 1
+    });
+    runtime.block_on(txn.commit());
+    assert!(txn.is_commited());
+    assert_eq!(memdb.local_get_meta(0, 1).unwrap().lock, 0);
+    assert_eq!(balance_of(&memdb, 1), 101);
+}
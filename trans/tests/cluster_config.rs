@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use trans::common::cluster::{ClusterConfig, NodeRole};
+use trans::common::partition::PartitionerConfig;
+use trans::occ::CcProtocol;
+
+#[test]
+fn cluster_config_test()
+{
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("configs/cluster.json");
+    let cluster = ClusterConfig::load(&path).unwrap();
+
+    let host = cluster.get_node(0).unwrap();
+    assert_eq!(host.role, NodeRole::Host);
+    assert_eq!(host.get_ip(), "10.10.10.6\0");
+    assert_eq!(host.get_conn_port(3), "7475\0");
+    assert_eq!(host.get_pci_addr(3), Some("af:00.0"));
+    assert_eq!(host.get_pci_addr(4), Some("af:00.1"));
+    let device = host.get_rdma_device(4).unwrap();
+    assert_eq!(device.name.as_deref(), Some("mlx5_1"));
+    assert_eq!(device.gid_index, 3);
+    assert_eq!(host.get_rdma_device(0).unwrap().gid_index, 0);
+
+    let dpu = cluster.get_node(100).unwrap();
+    assert_eq!(dpu.get_comm_name(2), "comm2\0");
+    assert_eq!(dpu.get_dma_addr(1), "10.10.10.26:7573");
+    assert!(dpu.get_rdma_device(0).is_none());
+
+    assert_eq!(cluster.host_of_part(0), Some(0));
+    assert_eq!(cluster.dpu_of_part(0), Some(100));
+    assert_eq!(cluster.dpu_of_part(1), None);
+    assert_eq!(cluster.host_of_dpu(100), Some(0));
+
+    // the client dials both, the host dials the dpu
+    assert_eq!(cluster.count_accepts(0), 1);
+    assert_eq!(cluster.count_accepts(100), 2);
+    assert_eq!(cluster.get_peer_ids(0), vec![100, 1]);
+
+    let accounts = cluster.get_placement("small_bank.accounts").unwrap();
+    assert!(matches!(accounts, PartitionerConfig::Split { ratio: 30, .. }));
+    assert_eq!(cluster.get_cc_protocol(), Some(CcProtocol::Occ));
+
+    // unknown peers and shared partitions are rejected
+    assert!(ClusterConfig::from_json(r#"{"nodes": [
+        {"id": 0, "role": "host", "ip": "a", "port": 1, "threads": 1, "connects": [5]}
+    ]}"#).is_err());
+    assert!(ClusterConfig::from_json(r#"{"nodes": [
+        {"id": 0, "role": "host", "ip": "a", "port": 1, "threads": 1, "partitions": [0]},
+        {"id": 1, "role": "host", "ip": "b", "port": 1, "threads": 1, "partitions": [0]}
+    ]}"#).is_err());
+    assert!(ClusterConfig::from_json(r#"{"nodes": [], "cc_protocol": "mvto"}"#).is_err());
+}
@@ -0,0 +1,127 @@
+#![cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+
+use std::sync::Arc;
+
+use trans::doca_comm_chan::connection::DocaCommChannel;
+use trans::doca_comm_chan::{doca_comm_info_type, CommRecvStatus, DocaCommHeaderMeta};
+use trans::framework::scheduler::AsyncScheduler;
+use trans::rdma::RdmaBaseAllocator;
+
+// the size of a comm buffer
+const MAX_CONN_MSG_SIZE: usize = 2048;
+// the buffers of a channel
+const MAX_CONN_INFO_BUFS: usize = 32;
+
+fn recv_blocking(chan: &DocaCommChannel) -> trans::doca_comm_chan::comm_buf::DocaCommBuf {
+    let mut buf = chan.alloc_buf(0).unwrap();
+    loop {
+        buf.set_payload(MAX_CONN_MSG_SIZE);
+        match chan.recv_info(&mut buf) {
+            CommRecvStatus::Received => return buf,
+            CommRecvStatus::Empty => std::thread::yield_now(),
+            CommRecvStatus::Lost => panic!("the comm channel is lost"),
+        }
+    }
+}
+
+#[test]
+fn comm_emu_test()
+{
+    let name = format!("test_{}\0", std::process::id());
+    let server_name = name.clone();
+    let server = std::thread::spawn(move || DocaCommChannel::new_server(&server_name, "", ""));
+    let client = DocaCommChannel::new_client(&name, "");
+    let server = server.join().unwrap();
+
+    // the messages of a flush arrive in one buffer
+    server.append_item_msg(DocaCommHeaderMeta::new(doca_comm_info_type::REQ, 1, 8, 0, 0, 3), [5u32, 6u32]);
+    server.append_slice_msg(DocaCommHeaderMeta::new(doca_comm_info_type::REQ, 2, 12, 0, 0, 3), &[7u32, 8u32, 9u32]);
+    server.append_empty_msg(DocaCommHeaderMeta::new(doca_comm_info_type::REQ, 3, 0, 0, 0, 3));
+    server.flush_pending_msgs();
+
+    let mut buf = recv_blocking(&client);
+    assert_eq!(buf.get_payload(), 4 + 8 + 4 + 12 + 4);
+    buf.start_read();
+    let header = buf.get_header().unwrap();
+    assert_eq!(header, DocaCommHeaderMeta::new(doca_comm_info_type::REQ, 1, 8, 0, 0, 3));
+    assert_eq!(*buf.get_item::<[u32; 2]>(0), [5, 6]);
+    buf.shift_to_next_msg(header.info_payload as _);
+    let header = buf.get_header().unwrap();
+    assert_eq!(header.info_id as u32, 2);
+    assert_eq!(*buf.get_item::<u32>(2), 9);
+    buf.shift_to_next_msg(header.info_payload as _);
+    let header = buf.get_header().unwrap();
+    assert_eq!(header.info_id as u32, 3);
+    buf.shift_to_next_msg(header.info_payload as _);
+    assert!(buf.get_header().is_none());
+    client.dealloc_buf(buf, 0);
+
+    // a reply of the dpu completes the waiting coroutine of the host
+    let allocator = Arc::new(RdmaBaseAllocator::new());
+    let mut scheduler = AsyncScheduler::new(0, 2, &allocator);
+    scheduler.set_comm_chan(server);
+    scheduler.prepare_comm_replys(1, 1);
+
+    client.append_item_msg(DocaCommHeaderMeta::new(doca_comm_info_type::REPLY, 0, 4, 0, 0, 1), 1u32);
+    client.flush_pending_msgs();
+    scheduler.poll_comm_chan();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    assert!(runtime.block_on(scheduler.yield_until_comm_ready(1)));
+
+    // the peer is gone, the coroutine waiting on it sees its reply failed
+    scheduler.prepare_comm_replys(1, 1);
+    drop(client);
+    scheduler.poll_comm_chan();
+    assert!(scheduler.is_comm_chan_lost());
+    assert!(!runtime.block_on(scheduler.yield_until_comm_ready(1)));
+}
+
+#[test]
+fn comm_emu_back_pressure_test()
+{
+    let name = format!("test_bp_{}\0", std::process::id());
+    let server_name = name.clone();
+    let server = std::thread::spawn(move || DocaCommChannel::new_server(&server_name, "", ""));
+    let client = DocaCommChannel::new_client(&name, "");
+    let server = server.join().unwrap();
+
+    // more than the socket and the pool hold, the client appends only while a buffer is free
+    let msg_num = 1024;
+    let mut items = [0u32; 255];
+    let mut appended = 0;
+    let mut blocked = 0;
+    let mut next = 0;
+    while next < msg_num {
+        while appended < msg_num && client.can_append(1024, false) {
+            items[0] = appended;
+            client.append_slice_msg(DocaCommHeaderMeta::new(doca_comm_info_type::REQ, 1, 1020, 0, 0, 0), &items);
+            client.flush_pending_msgs();
+            appended += 1;
+        }
+        if appended < msg_num {
+            blocked += 1;
+        }
+
+        // the queued ones follow in order as the server drains the channel
+        let mut buf = server.alloc_buf(0).unwrap();
+        buf.set_payload(MAX_CONN_MSG_SIZE);
+        match server.recv_info(&mut buf) {
+            CommRecvStatus::Received => {
+                buf.start_read();
+                while let Some(header) = buf.get_header() {
+                    assert_eq!(*buf.get_item::<u32>(0), next);
+                    next += 1;
+                    buf.shift_to_next_msg(header.info_payload);
+                }
+            }
+            CommRecvStatus::Empty => client.flush_pending_msgs(),
+            CommRecvStatus::Lost => panic!("the comm channel is lost"),
+        }
+        server.dealloc_buf(buf, 0);
+    }
+    assert!(blocked > 0);
+    assert!(client.get_send_stats().stalls > 0);
+    assert!(client.get_send_stats().max_pending <= MAX_CONN_INFO_BUFS);
+    assert!(!client.has_queued_msgs());
+    assert_eq!(client.get_send_stats().sent, msg_num as u64);
+}
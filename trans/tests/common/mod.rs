@@ -0,0 +1,158 @@
+// the fixture of the tests over soft verbs, included by each of them
+#![allow(dead_code)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use trans::framework::rpc::{RpcHandler, RpcProcessMeta};
+use trans::framework::scheduler::AsyncScheduler;
+use trans::memstore::memdb::{MemDB, TableSchema};
+use trans::memstore::RobinhoodMemStore;
+use trans::occ::occ_local::OccLocal;
+use trans::occ::{occ_rpc_id, BatchRpcProc, CcProtocol, RwType};
+use trans::rdma::rcconn::RdmaRcConn;
+use trans::rdma::RdmaBaseAllocator;
+
+#[repr(C)]
+#[derive(Clone, Default)]
+pub struct Account {
+    pub balance: u64,
+}
+
+pub struct Participant {
+    pub proc:   BatchRpcProc,
+    pub cc:     CcProtocol,
+    // the requests it ran
+    pub served: AtomicUsize,
+}
+
+impl Participant {
+    // serves the rpcs taken by the scheduler
+    pub fn register(memdb: &Arc<MemDB>, scheduler: &mut Arc<AsyncScheduler>, cc: CcProtocol) -> Arc<Self> {
+        let peer = Arc::new(Self {
+            proc:   BatchRpcProc::new(0, memdb, scheduler),
+            cc:     cc,
+            served: AtomicUsize::new(0),
+        });
+        unsafe {
+            Arc::get_mut_unchecked(scheduler).register_callback(&peer);
+        }
+        peer
+    }
+}
+
+impl RpcHandler for Participant {
+    fn rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        rpc_id: u32,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta,
+    ) {
+        self.served.fetch_add(1, Ordering::Relaxed);
+        match rpc_id {
+            occ_rpc_id::FETCHWRITE_RPC => {
+                self.proc.fetch_write_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::LOCK_RPC => {
+                self.proc.lock_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::VALIDATE_RPC => {
+                self.proc.validate_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::COMMIT_RPC => {
+                self.proc.commit_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::RELEASE_RPC => {
+                self.proc.release_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::ABORT_RPC => {
+                self.proc.abort_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::GROUP_COMMIT_RPC => {
+                self.proc.group_commit_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_READ_RPC => {
+                self.proc.tpl_read_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_LOCK_RPC => {
+                self.proc.tpl_lock_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::TPL_RELEASE_RPC => {
+                self.proc.tpl_release_rpc_handler(src_conn, msg, size, meta);
+            }
+            _ => {
+                unimplemented!();
+            }
+        }
+    }
+
+    fn peer_broken_handler(&self, peer_id: u64) {
+        self.proc.release_peer_locks(self.cc, peer_id);
+    }
+}
+
+pub fn new_accounts(keys: &[u64], balance: u64) -> Arc<MemDB> {
+    let mut memdb = Arc::new(MemDB::new());
+    let memstore = RobinhoodMemStore::<Account>::new();
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+
+    for key in keys.iter() {
+        let mut occ = OccLocal::<8>::new(1, &memdb);
+        occ.start();
+        let idx = occ.write::<Account>(0, 0, *key, RwType::INSERT);
+        occ.set_value(false, idx, &Account{ balance: balance });
+        occ.commit();
+        assert_eq!(occ.is_commited(), true);
+    }
+
+    memdb
+}
+
+// (lock, balance)
+pub fn balance_of(memdb: &Arc<MemDB>, key: u64) -> (u64, u64) {
+    let mut account = Account::default();
+    let meta = memdb.local_get_readonly(0, key, &mut account as *mut Account as _, 8).unwrap();
+    (meta.lock, account.balance)
+}
+
+// the coordinator is part 0 with account 1, the participant is part 1 with account 2
+pub struct Cluster {
+    pub memdb_a:     Arc<MemDB>,
+    pub memdb_b:     Arc<MemDB>,
+    pub conn_a:      Arc<Mutex<RdmaRcConn>>,
+    pub conn_b:      Arc<Mutex<RdmaRcConn>>,
+    pub scheduler_a: Arc<AsyncScheduler>,
+    pub scheduler_b: Arc<AsyncScheduler>,
+    pub peer:        Arc<Participant>,
+}
+
+pub fn new_cluster(cc: CcProtocol) -> Cluster {
+    let allocator_a = Arc::new(RdmaBaseAllocator::new());
+    let allocator_b = Arc::new(RdmaBaseAllocator::new());
+
+    let (conn_a, conn_b) = RdmaRcConn::new_soft_pair(0, &allocator_a, 1, &allocator_b);
+    let conn_a = Arc::new(Mutex::new(conn_a));
+    let conn_b = Arc::new(Mutex::new(conn_b));
+
+    let mut scheduler_a = Arc::new(AsyncScheduler::new(0, 4, &allocator_a));
+    Arc::get_mut(&mut scheduler_a).unwrap().append_conn(1, &conn_a);
+    conn_a.lock().unwrap().register_callbacks(&scheduler_a).unwrap();
+
+    let mut scheduler_b = Arc::new(AsyncScheduler::new(0, 4, &allocator_b));
+    Arc::get_mut(&mut scheduler_b).unwrap().append_conn(0, &conn_b);
+    conn_b.lock().unwrap().register_callbacks(&scheduler_b).unwrap();
+
+    let memdb_b = new_accounts(&[2], 200);
+    let peer = Participant::register(&memdb_b, &mut scheduler_b, cc);
+
+    Cluster {
+        memdb_a:     new_accounts(&[1], 100),
+        memdb_b:     memdb_b,
+        conn_a:      conn_a,
+        conn_b:      conn_b,
+        scheduler_a: scheduler_a,
+        scheduler_b: scheduler_b,
+        peer:        peer,
+    }
+}
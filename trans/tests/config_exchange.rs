@@ -0,0 +1,75 @@
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream};
+use std::thread::sleep;
+use std::time::Duration;
+
+use trans::common::connection::{recv_config_with, send_config_with, ConfigExchangeError, ConfigExchangeOptions, ConfigSerialize};
+
+struct TestConfig(Vec<u8>);
+
+impl ConfigSerialize for TestConfig {
+    fn serialize(data: Self) -> Vec<u8> {
+        data.0
+    }
+
+    // an empty one does not parse
+    fn deserialize(data: &[u8]) -> Result<Self, ConfigExchangeError> {
+        if data.is_empty() {
+            return Err(ConfigExchangeError::Malformed("empty config".to_string()));
+        }
+        Ok(TestConfig(data.to_vec()))
+    }
+}
+
+fn test_addr(offset: u16) -> SocketAddr {
+    format!("127.0.0.1:{}", 30000 + (std::process::id() % 10000) as u16 * 2 + offset)
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn config_exchange_test()
+{
+    let options = ConfigExchangeOptions {
+        retry_interval: Duration::from_millis(10),
+        accept_timeout: Some(Duration::from_secs(10)),
+        ..Default::default()
+    };
+
+    // larger than one read, sent before the receiver listens
+    let addr = test_addr(0);
+    let config: Vec<u8> = (0..64 * 1024).map(|i| i as u8).collect();
+    let sent = config.clone();
+    let sender_options = options.clone();
+    let sender = std::thread::spawn(move || send_config_with(addr, TestConfig(sent), &sender_options));
+    sleep(Duration::from_millis(50));
+
+    // a stray connection is dropped, the receiver waits for the config
+    let stray = std::thread::spawn(move || {
+        if let Ok(mut stream) = TcpStream::connect(addr) {
+            let _ = stream.write_all(b"GET / HTTP/1.1\r\n\r\n");
+        }
+    });
+
+    let received = recv_config_with::<TestConfig>(addr, &options).unwrap();
+    assert!(received.0 == config);
+    assert!(sender.join().unwrap().is_ok());
+    stray.join().unwrap();
+
+    // a malformed config is refused to its sender, which does not send it again
+    let sender_options = options.clone();
+    let sender = std::thread::spawn(move || send_config_with(addr, TestConfig(Vec::new()), &sender_options));
+    let options = ConfigExchangeOptions {
+        retries: 0,
+        ..options
+    };
+    assert!(recv_config_with::<TestConfig>(addr, &options).is_err());
+    assert!(sender.join().unwrap().is_err());
+
+    // nobody sends
+    let options = ConfigExchangeOptions {
+        accept_timeout: Some(Duration::from_millis(50)),
+        ..Default::default()
+    };
+    assert!(recv_config_with::<TestConfig>(test_addr(1), &options).is_err());
+}
@@ -0,0 +1,94 @@
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use trans::common::connection::ConfigSerialize;
+use trans::common::consistency::{check, check_with_dpu, serve_check, serve_quiesce, ConsistencyReport, Discrepancy, DpuSnapshot, HostSnapshot};
+use trans::framework::quiesce::QuiesceGate;
+use trans::memstore::memdb::{MemDB, TableSchema, ValueDB};
+use trans::memstore::{RobinhoodMemStore, RobinhoodValueStore};
+
+#[repr(C)]
+#[derive(Clone, Default)]
+struct Account {
+    balance: u64,
+}
+
+// the keys of a partition of two, as the host-offload loaders place them
+fn new_dbs() -> (Arc<MemDB>, Arc<ValueDB>) {
+    let mut memdb = Arc::new(MemDB::new());
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), RobinhoodMemStore::<PhantomData<usize>>::new());
+    let mut valuedb = Arc::new(ValueDB::new());
+    Arc::get_mut(&mut valuedb).unwrap().add_schema(0, TableSchema::default(), RobinhoodValueStore::<Account>::new());
+
+    for key in (0..1000).step_by(2) {
+        let account = Account { balance: key };
+        memdb.local_lock(0, key, 0);
+        memdb.local_upd_val_seq(0, key, &account as *const _ as _, 0);
+        valuedb.local_put_value(0, key, &account as *const _ as _, std::mem::size_of::<Account>() as _);
+    }
+    (memdb, valuedb)
+}
+
+fn snapshot_check(memdb: &MemDB, valuedb: &ValueDB) -> ConsistencyReport {
+    let host = HostSnapshot::of_valuedb(valuedb, &[0]).unwrap();
+    assert_eq!(HostSnapshot::deserialize(&HostSnapshot::serialize(host.clone())).unwrap(), host);
+    check(&host, &DpuSnapshot::of_memdb(memdb, &[0]).unwrap())
+}
+
+#[test]
+fn consistency_test()
+{
+    let (memdb, valuedb) = new_dbs();
+    let report = snapshot_check(&memdb, &valuedb);
+    assert!(report.is_consistent());
+    assert_eq!(report.tables[0].host_keys, 500);
+    assert_eq!(report.tables[0].dpu_keys, 500);
+
+    // a lost insert on each side and a lock left behind
+    valuedb.local_erase_value(0, 10);
+    memdb.local_erase(0, 20);
+    memdb.local_lock(0, 30, 7);
+
+    let report = snapshot_check(&memdb, &valuedb);
+    assert_eq!(report.discrepancies, vec![
+        Discrepancy::MissingOnHost { table_id: 0, key: 10 },
+        Discrepancy::MissingOnDpu { table_id: 0, key: 20 },
+        Discrepancy::LockHeld { table_id: 0, key: 30, lock: 7 },
+    ]);
+    let data = ConsistencyReport::serialize(report.clone());
+    assert_eq!(ConsistencyReport::deserialize(&data).unwrap(), report);
+    // a truncated report is refused, not read as zeros
+    assert!(ConsistencyReport::deserialize(&data[..data.len() - 1]).is_err());
+    assert!(HostSnapshot::of_valuedb(&valuedb, &[1]).is_err());
+
+    // a txn in flight holds the check back
+    let gate = QuiesceGate::new();
+    tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(gate.enter());
+    assert!(!gate.pause(Duration::from_millis(10)));
+    gate.leave();
+    assert!(gate.pause(Duration::from_millis(10)));
+    gate.resume();
+
+    // a peer host with a txn in flight refuses the pause, so no report
+    let peer = Arc::new(QuiesceGate::new());
+    let peer_addr: SocketAddr = "127.0.0.1:27482".parse().unwrap();
+    let peer_clone = peer.clone();
+    std::thread::spawn(move || serve_quiesce(&peer_clone, peer_addr, Duration::from_millis(10)));
+    tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(peer.enter());
+
+    let dpu_addr: SocketAddr = "127.0.0.1:27480".parse().unwrap();
+    let host_addr: SocketAddr = "127.0.0.1:27481".parse().unwrap();
+    assert!(check_with_dpu(&gate, &valuedb, &[0], dpu_addr, host_addr, &[peer_addr], Duration::from_secs(10)).is_err());
+    assert!(!gate.is_paused());
+    assert!(!peer.is_paused());
+    peer.leave();
+
+    let dpu = std::thread::spawn(move || serve_check(&memdb, dpu_addr, host_addr).unwrap());
+    let report = check_with_dpu(&gate, &valuedb, &[0], dpu_addr, host_addr, &[peer_addr], Duration::from_secs(10)).unwrap();
+    assert_eq!(report, dpu.join().unwrap());
+    assert_eq!(report.discrepancies.len(), 3);
+    assert!(!gate.is_paused());
+    assert!(!peer.is_paused());
+}
@@ -0,0 +1,166 @@
+#![cfg(all(feature = "doca_emu", not(feature = "doca_deps")))]
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use trans::doca_dma::emu::EmuDmaRegion;
+use trans::doca_dma::{DmaCompletion, DmaSegment, DocaDmaConn, DocaDmaControl};
+use trans::framework::scheduler::AsyncScheduler;
+use trans::rdma::RdmaBaseAllocator;
+
+#[test]
+fn dma_emu_test()
+{
+    let path = std::env::temp_dir().join(format!("trans_dma_test_{}", std::process::id()));
+    let region = Arc::new(EmuDmaRegion::create(&path, 128 * 1024).unwrap());
+    // the dpu side maps the same file
+    let peer = EmuDmaRegion::open(&path, 128 * 1024).unwrap();
+
+    let mut conn = DocaDmaConn::new(&region, 2, Duration::from_millis(1));
+    let mut local_buf = conn.get_local_buf(1);
+    unsafe { local_buf.get_mut_slice::<u8>(16).fill(0x5a); }
+
+    conn.post_write_dma_reqs(local_buf.get_off(), 64, 16, 1);
+    // not before the latency
+    assert_eq!(conn.poll_dma_comp(), DmaCompletion::Empty);
+    std::thread::sleep(Duration::from_millis(2));
+    assert_eq!(conn.poll_dma_comp(), DmaCompletion::Done(1));
+    assert_eq!(unsafe { *peer.get_base().add(64) }, 0x5a);
+
+    // out of the region
+    conn.post_read_dma_reqs(local_buf.get_off(), 128 * 1024, 16, 1);
+    std::thread::sleep(Duration::from_millis(2));
+    assert_eq!(conn.poll_dma_comp(), DmaCompletion::Failed(1));
+
+    // through the scheduler
+    let allocator = Arc::new(RdmaBaseAllocator::new());
+    let conn = Arc::new(Mutex::new(DocaDmaConn::new(&region, 2, Duration::ZERO)));
+    let mut scheduler = AsyncScheduler::new(0, 2, &allocator);
+    scheduler.set_dma_conn(&conn);
+
+    let mut read_buf = scheduler.dma_get_local_buf(1);
+    unsafe { read_buf.get_mut_slice::<u8>(16).fill(0); }
+    scheduler.post_read_dma_req(read_buf.get_off(), 64, 16, 1);
+    scheduler.poll_dma_comps();
+    assert!(scheduler.busy_until_dma_ready(1).is_ok());
+    assert!(unsafe { read_buf.get_const_slice::<u8>(16) }.iter().all(|b| *b == 0x5a));
+
+    // more single requests than the work queue holds wait for their turn
+    for _ in 0..3 {
+        scheduler.post_write_dma_req(read_buf.get_off(), 256, 16, 1);
+    }
+    assert!(!conn.lock().unwrap().has_room());
+    assert!(scheduler.busy_until_dma_ready(1).is_ok());
+    assert!(conn.lock().unwrap().has_room());
+}
+
+#[test]
+fn dma_emu_remote_pool_test()
+{
+    let path = std::env::temp_dir().join(format!("trans_dma_pool_test_{}", std::process::id()));
+    // two remote buffers
+    let region = Arc::new(EmuDmaRegion::create(&path, 2 * 1024).unwrap());
+    let conn = Arc::new(Mutex::new(DocaDmaConn::new(&region, 2, Duration::ZERO)));
+
+    let allocator = Arc::new(RdmaBaseAllocator::new());
+    let mut scheduler = AsyncScheduler::new(0, 2, &allocator);
+    scheduler.set_dma_conn(&conn);
+
+    let first = scheduler.dma_alloc_remote_buf().unwrap();
+    let second = scheduler.dma_alloc_remote_buf().unwrap();
+    assert_ne!(first.get_off(), second.get_off());
+    // a burst past the pool is refused, not a panic
+    assert!(scheduler.dma_alloc_remote_buf().is_none());
+
+    let stats = scheduler.dma_remote_buf_stats();
+    assert_eq!((stats.capacity, stats.in_use, stats.exhausted), (2, 2, 1));
+
+    scheduler.dma_dealloc_remote_buf(first);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let third = runtime.block_on(scheduler.yield_until_dma_remote_buf(1));
+    assert!(third.get_off() < 2 * 1024);
+    assert_eq!(scheduler.dma_remote_buf_stats().peak_in_use, 2);
+}
+
+#[test]
+fn dma_emu_batch_test()
+{
+    // neighbours on both sides become one job
+    let segs: Vec<DmaSegment> = (0..4).map(|i| DmaSegment::new(i * 64, 4096 + i * 64, 64)).collect();
+    assert_eq!(DmaSegment::coalesce(&segs), vec![DmaSegment::new(0, 4096, 256)]);
+
+    let path = std::env::temp_dir().join(format!("trans_dma_batch_test_{}", std::process::id()));
+    let region = Arc::new(EmuDmaRegion::create(&path, 128 * 1024).unwrap());
+    let conn = Arc::new(Mutex::new(DocaDmaConn::new(&region, 2, Duration::ZERO)));
+
+    let allocator = Arc::new(RdmaBaseAllocator::new());
+    let mut scheduler = AsyncScheduler::new(0, 2, &allocator);
+    scheduler.set_dma_conn(&conn);
+
+    // more segments than the work queue holds, with gaps between them
+    let mut local_buf = scheduler.dma_get_local_buf(1);
+    let local = unsafe { local_buf.get_mut_slice::<u8>(1024) };
+    let segs: Vec<DmaSegment> = (0..12)
+        .map(|i| {
+            local[i * 80..i * 80 + 64].fill(i as u8 + 1);
+            DmaSegment::new(local_buf.get_off() + i * 80, i * 128, 64)
+        })
+        .collect();
+
+    scheduler.post_dma_batch(&segs, true, 1);
+    assert!(!conn.lock().unwrap().has_room());
+    // the first wave completes and the rest is submitted, then the rest completes
+    scheduler.poll_dma_comps();
+    scheduler.poll_dma_comps();
+    assert!(scheduler.busy_until_dma_ready(1).is_ok());
+
+    for i in 0..12 {
+        assert_eq!(unsafe { *region.get_base().add(i * 128 + 63) }, i as u8 + 1);
+        assert_eq!(unsafe { *region.get_base().add(i * 128 + 64) }, 0);
+    }
+
+    // a failed segment fails the whole batch
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let bad = [DmaSegment::new(local_buf.get_off(), 0, 64), DmaSegment::new(local_buf.get_off(), 128 * 1024, 64)];
+    scheduler.post_dma_batch(&bad, false, 1);
+    scheduler.poll_dma_comps();
+    assert!(runtime.block_on(scheduler.yield_until_dma_ready(1)).is_err());
+}
+
+#[test]
+fn dma_emu_threads_test()
+{
+    let addr: SocketAddr = "127.0.0.1:27470".parse().unwrap();
+
+    // the dpu takes the regions of both its threads from one handshake
+    let dpu = std::thread::spawn(move || {
+        let mut control = DocaDmaControl::new();
+        control.listen_on_threads("", addr, 2, 2).unwrap();
+        control
+    });
+
+    let mut host = DocaDmaControl::new();
+    host.set_remote_buf_num(4);
+    let regions = host.export_threads(addr, 2).unwrap();
+    let control = dpu.join().unwrap();
+
+    let conn0 = control.get_thread_conn(0).unwrap();
+    let conn1 = control.get_thread_conn(1).unwrap();
+    assert!(!Arc::ptr_eq(&conn0, &conn1));
+    assert!(Arc::ptr_eq(&conn0, &control.get_conn().unwrap()));
+    assert!(control.get_thread_conn(2).is_none());
+
+    // each thread writes its own region
+    for (tid, conn) in [conn0, conn1].iter().enumerate() {
+        let mut conn = conn.lock().unwrap();
+        assert_eq!(conn.get_remote_buf_stats().capacity, 4);
+
+        let mut local_buf = conn.get_local_buf(1);
+        unsafe { local_buf.get_mut_slice::<u8>(16).fill(tid as u8 + 1); }
+        conn.post_write_dma_reqs(local_buf.get_off(), 0, 16, 1);
+        assert_eq!(conn.poll_dma_comp(), DmaCompletion::Done(1));
+    }
+    assert_eq!(unsafe { *regions[0].get_base() }, 1);
+    assert_eq!(unsafe { *regions[1].get_base() }, 2);
+}
@@ -0,0 +1,110 @@
+#![feature(get_mut_unchecked)]
+use std::cell::Cell;
+use std::sync::Arc;
+
+use trans::occ::occ_remote::OccRemote;
+use trans::occ::{CcProtocol, GroupCommitCtrl};
+
+mod common;
+use common::{balance_of, new_cluster, Account, Cluster};
+
+async fn transfer(cluster: &Cluster, group: &Arc<GroupCommitCtrl>, remote: bool, done: &Cell<bool>) -> bool {
+    let mut txn = OccRemote::<8>::new_grouped(0, 0, 1, &cluster.memdb_a, &cluster.scheduler_a, group);
+    txn.start();
+
+    let idx1 = txn.fetch_write::<Account>(0, 0, 1);
+    let balance1 = txn.get_value::<Account>(true, idx1).await.balance;
+    txn.set_value(true, idx1, &Account{ balance: balance1 + 1 });
+
+    if remote {
+        let idx2 = txn.fetch_write::<Account>(0, 1, 2);
+        let balance2 = txn.get_value::<Account>(true, idx2).await.balance;
+        txn.set_value(true, idx2, &Account{ balance: balance2 + 2 });
+    }
+
+    txn.commit().await;
+    done.set(true);
+    txn.is_commited()
+}
+
+// the participant is polled once every few rounds, so the epoch stays in flight a while
+async fn poll_until(cluster: &Cluster, done: &Cell<bool>, check: impl Fn()) {
+    let mut round = 0;
+    while !done.get() {
+        if round % 16 == 0 {
+            cluster.scheduler_b.poll_recvs();
+        }
+        check();
+        cluster.scheduler_a.poll_recvs();
+        round += 1;
+        tokio::task::yield_now().await;
+    }
+}
+
+#[test]
+fn group_commit_test()
+{
+    let cluster = new_cluster(CcProtocol::Occ);
+    let group = Arc::new(GroupCommitCtrl::new(&cluster.scheduler_a, 1));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            transfer(&cluster, &group, true, &done),
+            poll_until(&cluster, &done, || {
+                // the local write shows up only after the epoch is installed remotely
+                let (_, local) = balance_of(&cluster.memdb_a, 1);
+                let (_, remote) = balance_of(&cluster.memdb_b, 2);
+                if local == 101 {
+                    assert_eq!(remote, 202);
+                }
+            }),
+        )
+    });
+    assert_eq!(commited, true);
+    assert_eq!(balance_of(&cluster.memdb_a, 1), (0, 101));
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 202));
+
+    // a local-only txn opens no epoch, it commits without the participant
+    let done = Cell::new(false);
+    let commited = runtime.block_on(transfer(&cluster, &group, false, &done));
+    assert_eq!(commited, true);
+    assert_eq!(balance_of(&cluster.memdb_a, 1), (0, 102));
+}
+
+#[test]
+fn group_abort_test()
+{
+    let cluster = new_cluster(CcProtocol::Occ);
+    let group = Arc::new(GroupCommitCtrl::new(&cluster.scheduler_a, 1));
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // held by another coordinator, the lock fails and no epoch is joined
+    cluster.memdb_b.local_lock(0, 2, 0xdead);
+
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            transfer(&cluster, &group, true, &done),
+            poll_until(&cluster, &done, || {}),
+        )
+    });
+    assert_eq!(commited, false);
+    assert_eq!(balance_of(&cluster.memdb_a, 1), (0, 100));
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0xdead, 200));
+
+    // the group is not left waiting on the aborted txn
+    cluster.memdb_b.local_unlock(0, 2, 0xdead);
+
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            transfer(&cluster, &group, true, &done),
+            poll_until(&cluster, &done, || {}),
+        )
+    });
+    assert_eq!(commited, true);
+    assert_eq!(balance_of(&cluster.memdb_a, 1), (0, 101));
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 202));
+}
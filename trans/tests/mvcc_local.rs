@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use trans::memstore::memdb::{MemDB, TableSchema};
+use trans::memstore::MvccMemStore;
+use trans::occ::occ_local::OccLocal;
+use trans::occ::RwType;
+
+#[repr(C)]
+#[derive(Clone, Default)]
+struct Account {
+    balance: u64,
+}
+
+fn update_balance(memdb: &Arc<MemDB>, key: u64, balance: u64, rwtype: RwType) {
+    let mut occ = OccLocal::<8>::new(1, memdb);
+    occ.start();
+
+    let idx = occ.write::<Account>(0, 0, key, rwtype);
+    occ.set_value(false, idx, &Account{
+        balance: balance
+    });
+
+    occ.commit();
+    assert_eq!(occ.is_commited(), true);
+}
+
+fn snapshot_balance(memdb: &Arc<MemDB>, key: u64, read_ts: u64) -> Option<u64> {
+    let mut value = Account::default();
+    let ptr = &mut value as *mut Account as *mut u8;
+    let len = std::mem::size_of::<Account>();
+
+    memdb.local_get_snapshot(0, key, ptr, len as _, read_ts).map(|_| value.balance)
+}
+
+#[test]
+fn mvcc_snapshot_test()
+{
+    let mut memdb = Arc::new(MemDB::new());
+    let clock = memdb.get_clock();
+    let memstore = MvccMemStore::<Account>::new(&clock);
+
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+
+    update_balance(&memdb, 10037, 100, RwType::INSERT);
+
+    // the snapshot keeps seeing the old balance
+    let read_ts = memdb.begin_snapshot();
+
+    update_balance(&memdb, 10037, 200, RwType::UPDATE);
+    update_balance(&memdb, 10037, 300, RwType::UPDATE);
+
+    memdb.collect_versions();
+    assert_eq!(snapshot_balance(&memdb, 10037, read_ts), Some(100));
+
+    let new_ts = memdb.begin_snapshot();
+    assert_eq!(snapshot_balance(&memdb, 10037, new_ts), Some(300));
+
+    memdb.end_snapshot(read_ts);
+    memdb.end_snapshot(new_ts);
+
+    // old versions are collected once no snapshot needs them
+    memdb.collect_versions();
+    assert_eq!(snapshot_balance(&memdb, 10037, read_ts), None);
+    assert_eq!(snapshot_balance(&memdb, 10037, new_ts), Some(300));
+
+    // a remote snapshot ahead of this clock, later commits are after it
+    let remote_ts = new_ts + 10;
+    assert!(memdb.join_snapshot(remote_ts));
+    update_balance(&memdb, 10037, 400, RwType::UPDATE);
+    assert_eq!(snapshot_balance(&memdb, 10037, remote_ts), Some(300));
+    memdb.end_snapshot(remote_ts);
+
+    // a commit before the remote snapshot is still installing
+    let commit_ts = memdb.alloc_commit_ts();
+    assert!(!memdb.join_snapshot(commit_ts));
+    memdb.finish_commit_ts(commit_ts);
+    assert!(memdb.join_snapshot(commit_ts));
+    memdb.end_snapshot(commit_ts);
+}
@@ -1,7 +1,8 @@
 use std::sync::Arc;
 
-use trans::memstore::memdb::{MemDB, TableSchema};
-use trans::memstore::{MemStoreValue, RobinhoodMemStore};
+use trans::memstore::memdb::{MemDB, ValueDB, TableSchema, proc_id};
+use trans::memstore::{MemStoreValue, RobinhoodMemStore, RobinhoodValueStore, delta_add_u64};
+use trans::app::tpcc::stock_order_quantity;
 use trans::occ::occ_local::OccLocal;
 use trans::occ::RwType;
 
@@ -92,6 +93,145 @@ fn test_conflicts(memdb: &Arc<MemDB>) {
 
 }
 
+fn test_deltas(memdb: &Arc<MemDB>) {
+    let mut occ1 = OccLocal::<8>::new(1,memdb);
+    occ1.start();
+    occ1.apply_delta(0, 0, 10037, 1, &100u64);
+
+    // concurrent deltas on the same item both commit
+    let mut occ2 = OccLocal::<8>::new(2,memdb);
+    occ2.start();
+    occ2.apply_delta(0, 0, 10037, 1, &23u64);
+
+    occ1.commit();
+    assert_eq!(occ1.is_commited(), true);
+
+    occ2.commit();
+    assert_eq!(occ2.is_commited(), true);
+
+    let mut occ3 = OccLocal::<8>::new(3,memdb);
+    occ3.start();
+    let idx3 = occ3.read::<Account>(0, 0, 10037);
+    assert_eq!(occ3.get_value::<Account>(false, idx3).balance, 34567 + 123);
+
+    occ3.commit();
+    assert_eq!(occ3.is_commited(), true);
+}
+
+#[test]
+fn occlocal_delta_test()
+{
+    let mut memdb = Arc::new(MemDB::new());
+    let memstore = RobinhoodMemStore::<Account>::new();
+
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+    Arc::get_mut(&mut memdb).unwrap().add_merge(1, delta_add_u64::<0>);
+
+    prepare_data(&memdb);
+
+    test_deltas(&memdb);
+}
+
+#[test]
+fn delta_set_seq_test()
+{
+    let mut memdb = Arc::new(MemDB::new());
+    let memstore = RobinhoodMemStore::<Account>::new();
+
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+    Arc::get_mut(&mut memdb).unwrap().add_merge(1, delta_add_u64::<0>);
+
+    prepare_data(&memdb);
+
+    let delta = 23u64;
+    let meta = memdb.local_apply_delta_set_seq(0, 10037, 1, &delta as *const u64 as _, 8, 0x1234).unwrap();
+    assert_eq!(meta.seq, 0x1234);
+
+    let mut account = Account{ balance: 0 };
+    memdb.local_get_readonly(0, 10037, &mut account as *mut Account as _, 8);
+    assert_eq!(account.balance, 34567 + 23);
+}
+
+#[test]
+fn valuedb_delta_test()
+{
+    let mut valuedb = Arc::new(ValueDB::new());
+    let valuestore = RobinhoodValueStore::<Account>::new();
+
+    Arc::get_mut(&mut valuedb).unwrap().add_schema(0, TableSchema::default(), valuestore);
+    Arc::get_mut(&mut valuedb).unwrap().add_merge(1, delta_add_u64::<0>);
+
+    let account = Account{ balance: 100 };
+    valuedb.local_put_value(0, 10037, &account as *const Account as _, 8);
+
+    let delta = 23u64;
+    assert_eq!(valuedb.local_apply_delta(0, 10037, 1, &delta as *const u64 as _, 8), true);
+    assert_eq!(valuedb.local_apply_delta(0, 10038, 1, &delta as *const u64 as _, 8), false);
+
+    let mut account = Account{ balance: 0 };
+    valuedb.local_get_value(0, 10037, &mut account as *mut Account as _, 8);
+    assert_eq!(account.balance, 123);
+}
+
+#[test]
+fn stock_merge_commutes_test()
+{
+    for quantity in 10..=100u64 {
+        for a in 1..=10u64 {
+            for b in 1..=10u64 {
+                let ab = stock_order_quantity(stock_order_quantity(quantity, a), b);
+                let ba = stock_order_quantity(stock_order_quantity(quantity, b), a);
+                assert_eq!(ab, ba);
+                assert!(ab >= 10 && ab <= 100);
+            }
+        }
+    }
+}
+
+fn proc_deposit(memdb: &Arc<MemDB>, tid: u32, args: &[u8], out: &mut [u8]) -> Option<usize> {
+    let amount = unsafe { std::ptr::read_unaligned(args.as_ptr() as *const u64) };
+
+    let mut occ = OccLocal::<8>::new_on(tid, 1023, memdb);
+    occ.start();
+    let idx = occ.fetch_write::<Account>(0, 0, 10037);
+    let balance = occ.get_value::<Account>(true, idx).balance + amount;
+    occ.set_value(true, idx, &Account{
+        balance: balance
+    });
+    occ.commit();
+
+    if !occ.is_commited() {
+        return None;
+    }
+
+    out[..8].copy_from_slice(&balance.to_ne_bytes());
+    Some(8)
+}
+
+#[test]
+fn occlocal_proc_test()
+{
+    let mut memdb = Arc::new(MemDB::new());
+    let memstore = RobinhoodMemStore::<Account>::new();
+
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), memstore);
+    let id = Arc::get_mut(&mut memdb).unwrap().add_procedure("deposit", proc_deposit);
+    assert_eq!(id, proc_id("deposit"));
+
+    prepare_data(&memdb);
+
+    let procedure = memdb.get_procedure(id).unwrap();
+    let mut out = [0u8; 8];
+    assert_eq!(procedure(&memdb, 1, &100u64.to_ne_bytes(), &mut out), Some(8));
+    assert_eq!(u64::from_ne_bytes(out), 34567 + 100);
+
+    // aborts on an item locked by a routine
+    let mut occ = OccLocal::<8>::new(1, &memdb);
+    occ.start();
+    occ.fetch_write::<Account>(0, 0, 10037);
+    assert_eq!(procedure(&memdb, 1, &100u64.to_ne_bytes(), &mut out), None);
+}
+
 #[test]
 fn occlocal_test()
 {
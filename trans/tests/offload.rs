@@ -0,0 +1,101 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use trans::common::offload::{offload_owner, MemDBMigrator, OffloadCtrl, OffloadMap};
+use trans::memstore::memdb::{MemDB, TableSchema};
+use trans::memstore::RobinhoodMemStore;
+
+#[repr(C)]
+#[derive(Clone, Default)]
+struct Account {
+    balance: u64,
+}
+
+fn static_on_dpu(key: usize) -> bool {
+    key % 100 < 30
+}
+
+fn new_db(on_dpu: bool) -> Arc<MemDB> {
+    let mut memdb = Arc::new(MemDB::new());
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), RobinhoodMemStore::<Account>::new());
+
+    for key in (0..1000).filter(|key| static_on_dpu(*key) == on_dpu) {
+        let account = Account { balance: key as u64 * 10 };
+        memdb.local_lock(0, key as _, 0);
+        memdb.local_upd_val_seq(0, key as _, &account as *const _ as _, std::mem::size_of::<Account>() as _);
+    }
+    memdb
+}
+
+fn get_balance(memdb: &MemDB, key: u64) -> Option<u64> {
+    let mut account = Account::default();
+    let meta = memdb.local_get_readonly(0, key, &mut account as *mut _ as _, std::mem::size_of::<Account>() as _)?;
+    if meta.seq == 0 {
+        return None;
+    }
+    Some(account.balance)
+}
+
+#[test]
+fn offload_test()
+{
+    let host = new_db(false);
+    let dpu = new_db(true);
+    let map = Arc::new(OffloadMap::new(1000, 100, static_on_dpu));
+    assert!(map.on_dpu(529) && !map.on_dpu(530));
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let mut ctrl = OffloadCtrl::new(&map, MemDBMigrator::new(&host, &dpu, &[0]), 0.9);
+    ctrl.set_drain_timeout(Duration::from_millis(1));
+
+    // range 5 is hot, the dpu should serve most of the accesses
+    for key in 0..1000 {
+        assert!(map.pin(key));
+        map.unpin(key);
+    }
+    for _ in 0..10000 {
+        assert!(map.pin(550));
+        map.unpin(550);
+    }
+
+    assert_eq!(runtime.block_on(ctrl.rebalance()), 1);
+    assert_eq!(ctrl.get_stats().to_dpu, 1);
+    assert_eq!(map.get_version(), 1);
+    for key in 500..600 {
+        assert!(map.on_dpu(key));
+        assert_eq!(get_balance(&dpu, key as _), Some(key as u64 * 10));
+        assert_eq!(get_balance(&host, key as _), None);
+    }
+    // the other ranges stay as loaded
+    assert!(!map.on_dpu(430));
+    assert_eq!(get_balance(&host, 430), Some(4300));
+
+    // a txn on the range holds it
+    let pin = map.try_pin(512).unwrap();
+    assert!(!runtime.block_on(ctrl.move_range(5, false)));
+    drop(pin);
+    assert_eq!(ctrl.get_stats().aborted, 1);
+    assert!(!map.is_moving(512));
+
+    // so does a record locked on the side it leaves
+    dpu.local_lock(0, 512, 7);
+    assert!(!runtime.block_on(ctrl.move_range(5, false)));
+    assert_eq!(get_balance(&dpu, 513), Some(5130));
+    dpu.local_unlock(0, 512, 7);
+
+    assert!(runtime.block_on(ctrl.move_range(5, false)));
+    assert_eq!(map.get_version(), 2);
+    for key in 500..600 {
+        assert!(!map.on_dpu(key));
+        assert_eq!(get_balance(&host, key as _), Some(key as u64 * 10));
+        assert_eq!(get_balance(&dpu, key as _), None);
+    }
+
+    // a routing peer refuses the range until the owner is published
+    let peer = Arc::new(OffloadMap::new(1000, 100, static_on_dpu));
+    assert!(peer.freeze(5));
+    assert!(peer.try_pin(550).is_none());
+    peer.publish(5, offload_owner::HOST, 2);
+    assert!(peer.try_pin(550).is_some());
+    assert!(!peer.on_dpu(529) && peer.get_version() == 2);
+}
@@ -0,0 +1,209 @@
+#![feature(get_mut_unchecked)]
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+
+use trans::common::offload::OffloadMigrator;
+use trans::framework::rpc::{RpcHandler, RpcProcessMeta};
+use trans::framework::scheduler::AsyncScheduler;
+use trans::memstore::memdb::{MemDB, TableSchema};
+use trans::memstore::RobinhoodMemStore;
+use trans::occ::{occ_rpc_id, BatchRpcProc, RpcMigrator};
+use trans::rdma::rcconn::RdmaRcConn;
+use trans::rdma::RdmaBaseAllocator;
+
+#[repr(C)]
+#[derive(Clone, Default)]
+struct Account {
+    balance: u64,
+}
+
+struct Dpu {
+    proc: BatchRpcProc,
+}
+
+impl RpcHandler for Dpu {
+    fn rpc_handler(
+        &self,
+        src_conn: &mut RdmaRcConn,
+        rpc_id: u32,
+        msg: *mut u8,
+        size: u32,
+        meta: RpcProcessMeta,
+    ) {
+        match rpc_id {
+            occ_rpc_id::OFFLOAD_INSTALL_RPC => {
+                self.proc.offload_install_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::OFFLOAD_EXTRACT_RPC => {
+                self.proc.offload_extract_rpc_handler(src_conn, msg, size, meta);
+            }
+            occ_rpc_id::OFFLOAD_RELEASE_RPC => {
+                self.proc.offload_release_rpc_handler(src_conn, msg, size, meta);
+            }
+            _ => {
+                unimplemented!();
+            }
+        }
+    }
+}
+
+fn new_db(keys: std::ops::Range<u64>) -> Arc<MemDB> {
+    let mut memdb = Arc::new(MemDB::new());
+    Arc::get_mut(&mut memdb).unwrap().add_schema(0, TableSchema::default(), RobinhoodMemStore::<Account>::new());
+
+    for key in keys {
+        let account = Account { balance: key * 10 };
+        memdb.local_lock(0, key, 0);
+        memdb.local_upd_val_seq(0, key, &account as *const _ as _, std::mem::size_of::<Account>() as _);
+    }
+    memdb
+}
+
+// (lock, balance) of a record ever written
+fn get_balance(memdb: &MemDB, key: u64) -> Option<(u64, u64)> {
+    let mut account = Account::default();
+    let meta = memdb.local_get_readonly(0, key, &mut account as *mut _ as _, std::mem::size_of::<Account>() as _)?;
+    if meta.seq == 0 {
+        return None;
+    }
+    Some((meta.lock, account.balance))
+}
+
+// the host is part 0, its dpu is 1
+struct Cluster {
+    host:        Arc<MemDB>,
+    dpu:         Arc<MemDB>,
+    conn_a:      Arc<Mutex<RdmaRcConn>>,
+    conn_b:      Arc<Mutex<RdmaRcConn>>,
+    scheduler_a: Arc<AsyncScheduler>,
+    scheduler_b: Arc<AsyncScheduler>,
+    _dpu:        Arc<Dpu>,
+}
+
+fn new_cluster() -> Cluster {
+    let allocator_a = Arc::new(RdmaBaseAllocator::new());
+    let allocator_b = Arc::new(RdmaBaseAllocator::new());
+
+    let (conn_a, conn_b) = RdmaRcConn::new_soft_pair(0, &allocator_a, 1, &allocator_b);
+    let conn_a = Arc::new(Mutex::new(conn_a));
+    let conn_b = Arc::new(Mutex::new(conn_b));
+
+    let mut scheduler_a = Arc::new(AsyncScheduler::new(0, 4, &allocator_a));
+    Arc::get_mut(&mut scheduler_a).unwrap().append_conn(1, &conn_a);
+    conn_a.lock().unwrap().register_callbacks(&scheduler_a).unwrap();
+
+    let mut scheduler_b = Arc::new(AsyncScheduler::new(0, 4, &allocator_b));
+    Arc::get_mut(&mut scheduler_b).unwrap().append_conn(0, &conn_b);
+    conn_b.lock().unwrap().register_callbacks(&scheduler_b).unwrap();
+
+    let dpu = new_db(10..20);
+    let handler = Arc::new(Dpu {
+        proc: BatchRpcProc::new(0, &dpu, &scheduler_b),
+    });
+    unsafe {
+        Arc::get_mut_unchecked(&mut scheduler_b).register_callback(&handler);
+    }
+
+    Cluster {
+        host:        new_db(0..10),
+        dpu:         dpu,
+        conn_a:      conn_a,
+        conn_b:      conn_b,
+        scheduler_a: scheduler_a,
+        scheduler_b: scheduler_b,
+        _dpu:        handler,
+    }
+}
+
+async fn migrate(migrator: &RpcMigrator, keys: &[usize], to_dpu: bool, done: &Cell<bool>) -> bool {
+    let moved = migrator.migrate(keys, to_dpu).await;
+    done.set(true);
+    moved
+}
+
+async fn poll_until(cluster: &Cluster, done: &Cell<bool>) {
+    while !done.get() {
+        cluster.scheduler_b.poll_recvs();
+        cluster.scheduler_a.poll_recvs();
+        tokio::task::yield_now().await;
+    }
+}
+
+fn run_migrate(cluster: &Cluster, migrator: &RpcMigrator, keys: &[usize], to_dpu: bool) -> bool {
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let done = Cell::new(false);
+    let (moved, _) = runtime.block_on(async {
+        tokio::join!(
+            migrate(migrator, keys, to_dpu, &done),
+            poll_until(cluster, &done),
+        )
+    });
+    moved
+}
+
+#[test]
+fn rpc_migrator_test()
+{
+    let cluster = new_cluster();
+    let migrator = RpcMigrator::new(&cluster.host, &cluster.scheduler_a, 1, 1, &[], &[0]);
+
+    // the records move with their values, the side they left drops them
+    assert!(run_migrate(&cluster, &migrator, &[0, 1, 2, 3, 4], true));
+    for key in 0..5 {
+        assert_eq!(get_balance(&cluster.dpu, key), Some((0, key * 10)));
+        assert_eq!(get_balance(&cluster.host, key), None);
+    }
+
+    assert!(run_migrate(&cluster, &migrator, &[10, 11, 12, 13, 14], false));
+    for key in 10..15 {
+        assert_eq!(get_balance(&cluster.host, key), Some((0, key * 10)));
+        assert_eq!(get_balance(&cluster.dpu, key), None);
+    }
+
+    // a record locked on the dpu fails the pull, the dpu keeps and unlocks the rest
+    cluster.dpu.local_lock(0, 15, 0xdead);
+    assert!(!run_migrate(&cluster, &migrator, &[15, 16, 17], false));
+    assert_eq!(get_balance(&cluster.dpu, 15), Some((0xdead, 150)));
+    for key in 15..18 {
+        assert_eq!(get_balance(&cluster.host, key), None);
+    }
+    for key in 16..18 {
+        assert_eq!(get_balance(&cluster.dpu, key), Some((0, key * 10)));
+    }
+}
+
+#[test]
+fn rpc_migrator_release_failed_test()
+{
+    let cluster = new_cluster();
+    let migrator = RpcMigrator::new(&cluster.host, &cluster.scheduler_a, 1, 1, &[], &[0]);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // the link breaks once the host installed the records, before the dpu drops its copies
+    let done = Cell::new(false);
+    let (moved, _) = runtime.block_on(async {
+        tokio::join!(
+            migrate(&migrator, &[16, 17, 18], false, &done),
+            async {
+                while !done.get() {
+                    if get_balance(&cluster.host, 16).is_some() {
+                        cluster.conn_a.lock().unwrap().mark_broken();
+                        cluster.conn_b.lock().unwrap().mark_broken();
+                    }
+                    cluster.scheduler_b.poll_recvs();
+                    cluster.scheduler_a.poll_recvs();
+                    tokio::task::yield_now().await;
+                }
+            },
+        )
+    });
+
+    // the host serves the records, the copies left on the dpu stay locked by the extract
+    assert!(moved);
+    for key in 16..19 {
+        assert_eq!(get_balance(&cluster.host, key), Some((0, key * 10)));
+        let (lock, balance) = get_balance(&cluster.dpu, key).unwrap();
+        assert_ne!(lock, 0);
+        assert_eq!(balance, key * 10);
+    }
+}
@@ -0,0 +1,262 @@
+use std::alloc::Layout;
+use std::sync::{Arc, Mutex};
+
+use trans::framework::scheduler::AsyncScheduler;
+use trans::memstore::memdb::{MemDB, TableSchema};
+use trans::memstore::{RobinhoodMemStore, ERASED_LOCK, MEMNODE_LOCK_OFF, MEMNODE_VALUE_OFF};
+use trans::occ::{CasLockCtrl, RemoteAddrCache};
+use trans::rdma::rcconn::RdmaRcConn;
+use trans::rdma::RdmaBaseAllocator;
+
+#[test]
+fn soft_one_side_test()
+{
+    let local = Arc::new(RdmaBaseAllocator::new());
+    let remote = Arc::new(RdmaBaseAllocator::new());
+
+    let conn = Arc::new(Mutex::new(RdmaRcConn::new_soft(1, &remote, &local)));
+    let mut scheduler = Arc::new(AsyncScheduler::new(0, 2, &local));
+    Arc::get_mut(&mut scheduler).unwrap().append_conn(1, &conn);
+    conn.lock().unwrap().register_send_callback(&scheduler).unwrap();
+
+    let layout = Layout::from_size_align(64, 8).unwrap();
+    let write_buf = unsafe { local.alloc(layout) };
+    let read_buf = unsafe { local.alloc(layout) };
+    unsafe {
+        std::ptr::write_bytes(write_buf, 0x5a, 64);
+        std::ptr::write_bytes(read_buf, 0, 64);
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    scheduler.post_write(1, write_buf, 64, 128, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+
+    scheduler.post_read(1, read_buf, 64, 128, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+
+    let read = unsafe { std::slice::from_raw_parts(read_buf, 64) };
+    assert!(read.iter().all(|b| *b == 0x5a));
+
+    // out of the registered regions
+    assert!(scheduler.post_read(1, read_buf, 64, 512 * 4096, 1).is_err());
+    let mut stack_buf = [0u8; 64];
+    assert!(scheduler.post_read(1, stack_buf.as_mut_ptr(), 64, 0, 1).is_err());
+    runtime.block_on(scheduler.yield_until_ready(1));
+}
+
+#[test]
+fn soft_atomic_test()
+{
+    let local = Arc::new(RdmaBaseAllocator::new());
+    let remote = Arc::new(RdmaBaseAllocator::new());
+
+    let conn = Arc::new(Mutex::new(RdmaRcConn::new_soft(1, &remote, &local)));
+    let mut scheduler = Arc::new(AsyncScheduler::new(0, 2, &local));
+    Arc::get_mut(&mut scheduler).unwrap().append_conn(1, &conn);
+    conn.lock().unwrap().register_send_callback(&scheduler).unwrap();
+
+    let layout = Layout::from_size_align(8, 8).unwrap();
+    let old_buf = scheduler.alloc_mr(layout);
+    unsafe { *(old_buf as *mut u64) = 0; }
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // the lock word starts unlocked
+    scheduler.post_write(1, old_buf, 8, 256, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+
+    scheduler.post_cas(1, old_buf, 256, 0, 42, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+    assert_eq!(unsafe { *(old_buf as *const u64) }, 0);
+
+    // held by 42 already
+    scheduler.post_cas(1, old_buf, 256, 0, 7, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+    assert_eq!(unsafe { *(old_buf as *const u64) }, 42);
+
+    scheduler.post_faa(1, old_buf, 256, 3, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+    assert_eq!(unsafe { *(old_buf as *const u64) }, 42);
+
+    scheduler.post_read(1, old_buf, 8, 256, 1).unwrap();
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+    assert_eq!(unsafe { *(old_buf as *const u64) }, 45);
+
+    // not aligned
+    assert!(scheduler.post_cas(1, old_buf, 260, 0, 1, 1).is_err());
+}
+
+#[test]
+fn stable_node_test()
+{
+    let remote = Arc::new(RdmaBaseAllocator::new());
+
+    let len = RobinhoodMemStore::<u64, 64>::region_len();
+    let ptr = unsafe { remote.alloc(Layout::from_size_align(len, 8).unwrap()) };
+    let store = RobinhoodMemStore::<u64, 64>::new_from_raw(ptr, len).unwrap();
+
+    let mut memdb = MemDB::new();
+    memdb.add_schema(0, TableSchema::default().with_one_side_read(), store);
+    memdb.set_region(remote.get_lm(), remote.get_len(), 7);
+
+    let insert = |key: u64| {
+        memdb.local_lock(0, key, 0);
+        memdb.local_upd_val_seq(0, key, &key as *const _ as _, 8);
+    };
+    // (lock, value) of the node at offset, as a peer reads it
+    let node_at = |offset: u64| unsafe {
+        let node = remote.get_lm().add(offset as usize);
+        (*(node.add(MEMNODE_LOCK_OFF) as *const u64), *(node.add(MEMNODE_VALUE_OFF) as *const u64))
+    };
+
+    insert(10037);
+    let offset = memdb.local_get_node_offset(0, 10037).unwrap();
+    assert_eq!(node_at(offset), (0, 10037));
+
+    // the inserts and erases of the other keys do not move the node
+    for key in 1..41 {
+        insert(key);
+    }
+    for key in 1..41 {
+        if key % 2 == 0 {
+            memdb.local_erase(0, key);
+        }
+    }
+    assert_eq!(memdb.local_get_node_offset(0, 10037), Some(offset));
+    assert_eq!(node_at(offset), (0, 10037));
+
+    // an erased node stays locked for the peers still holding its offset
+    memdb.local_erase(0, 10037);
+    assert_eq!(memdb.local_get_node_offset(0, 10037), None);
+    assert_eq!(node_at(offset).0, ERASED_LOCK);
+
+    // inserted again in its own tomb
+    insert(10037);
+    assert_eq!(memdb.local_get_node_offset(0, 10037), Some(offset));
+    assert_eq!(node_at(offset), (0, 10037));
+
+    for key in 1..41 {
+        let mut value: u64 = 0;
+        memdb.local_get_readonly(0, key, &mut value as *mut u64 as _, 8);
+        assert_eq!(value, if key % 2 == 1 { key } else { 0 });
+    }
+}
+
+#[test]
+fn layout_probe_test()
+{
+    let local = Arc::new(RdmaBaseAllocator::new());
+    let remote = Arc::new(RdmaBaseAllocator::new());
+
+    let conn = Arc::new(Mutex::new(RdmaRcConn::new_soft(1, &remote, &local)));
+    let mut scheduler = Arc::new(AsyncScheduler::new(0, 2, &local));
+    Arc::get_mut(&mut scheduler).unwrap().append_conn(1, &conn);
+    conn.lock().unwrap().register_send_callback(&scheduler).unwrap();
+
+    let len = RobinhoodMemStore::<u64, 64>::region_len();
+    let ptr = unsafe { remote.alloc(Layout::from_size_align(len, 8).unwrap()) };
+    let store = RobinhoodMemStore::<u64, 64>::new_from_raw(ptr, len).unwrap();
+
+    let mut memdb = MemDB::new();
+    memdb.add_schema(0, TableSchema::default().with_one_side_read(), store);
+    memdb.set_region(remote.get_lm(), remote.get_len(), 7);
+
+    for key in 1..41 {
+        memdb.local_lock(0, key, 0);
+        memdb.local_upd_val_seq(0, key, &key as *const u64 as _, 8);
+        if key % 2 == 0 {
+            memdb.local_erase(0, key);
+        }
+    }
+
+    let layout = memdb.export_layout(0).unwrap();
+    assert_eq!(layout.rkey, 7);
+
+    // the peer finds the nodes from the exported layout alone, the erased ones are not found
+    let addrs = RemoteAddrCache::new(&scheduler, 2);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let mut probed = 0;
+    for key in 1..41 {
+        if !addrs.post_probe(1, &layout, key, 1, 0) {
+            continue;
+        }
+        scheduler.poll_sends();
+        runtime.block_on(scheduler.yield_until_ready(1));
+
+        assert_eq!(addrs.probed_offset(&layout, key, 1, 0), memdb.local_get_node_offset(0, key));
+        probed += 1;
+    }
+    assert!(probed > 0);
+}
+
+// (lock, value) of the row at the owner
+fn row_of(memdb: &MemDB, key: u64) -> (u64, u64) {
+    let mut value: u64 = 0;
+    let meta = memdb.local_get_readonly(0, key, &mut value as *mut u64 as _, 8).unwrap();
+    (meta.lock, value)
+}
+
+#[test]
+fn cas_lock_test()
+{
+    let local = Arc::new(RdmaBaseAllocator::new());
+    let remote = Arc::new(RdmaBaseAllocator::new());
+
+    let conn = Arc::new(Mutex::new(RdmaRcConn::new_soft(1, &remote, &local)));
+    let mut scheduler = Arc::new(AsyncScheduler::new(0, 2, &local));
+    Arc::get_mut(&mut scheduler).unwrap().append_conn(1, &conn);
+    conn.lock().unwrap().register_send_callback(&scheduler).unwrap();
+
+    // the owner places the table in its region
+    let len = RobinhoodMemStore::<u64, 64>::region_len();
+    let ptr = unsafe { remote.alloc(Layout::from_size_align(len, 8).unwrap()) };
+    let store = RobinhoodMemStore::<u64, 64>::new_from_raw(ptr, len).unwrap();
+
+    let mut memdb = MemDB::new();
+    memdb.add_schema(0, TableSchema::default().with_cas_lock(), store);
+    memdb.set_region(remote.get_lm(), remote.get_len(), 0);
+
+    let value: u64 = 4567;
+    memdb.local_lock(0, 10037, 0);
+    memdb.local_upd_val_seq(0, 10037, &value as *const _ as _, 8);
+    let offset = memdb.local_get_node_offset(0, 10037).unwrap();
+
+    let cas_locks = CasLockCtrl::new(&scheduler, &Arc::new(RemoteAddrCache::new(&scheduler, 2)), 2);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // locked by the peer, the owner cannot take it
+    assert!(cas_locks.post_lock(1, offset, 0xa, 1, 0));
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+    assert!(cas_locks.is_locked(1, 0));
+
+    memdb.local_lock(0, 10037, 0xb);
+    assert_eq!(row_of(&memdb, 10037), (0xa, 4567));
+
+    // the release of another lock leaves it to the peer
+    memdb.local_unlock(0, 10037, 0xb);
+    assert_eq!(row_of(&memdb, 10037), (0xa, 4567));
+
+    // e.g. by its RELEASE_RPC
+    memdb.local_unlock(0, 10037, 0xa);
+    assert_eq!(row_of(&memdb, 10037), (0, 4567));
+
+    // locked by the owner, the CAS of the peer fails
+    memdb.local_lock(0, 10037, 0xb);
+    assert!(cas_locks.post_lock(1, offset, 0xa, 1, 1));
+    scheduler.poll_sends();
+    runtime.block_on(scheduler.yield_until_ready(1));
+    assert!(!cas_locks.is_locked(1, 1));
+    assert_eq!(row_of(&memdb, 10037), (0xb, 4567));
+
+    memdb.local_unlock(0, 10037, 0xb);
+    assert_eq!(row_of(&memdb, 10037), (0, 4567));
+}
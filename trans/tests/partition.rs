@@ -0,0 +1,57 @@
+use serde_json::json;
+
+use trans::common::cluster::ClusterConfig;
+use trans::common::partition::{PartitionerConfig, PlacementView};
+
+#[test]
+fn partition_test()
+{
+    let hash = PartitionerConfig::from_json(&json!({"kind": "hash", "parts": 4})).unwrap();
+    assert_eq!(hash, PartitionerConfig::Hash { parts: 4, mixed: false });
+    assert_eq!(hash.build(0, PlacementView::Plain).part_of(7), 3);
+
+    let range = PartitionerConfig::from_json(&json!({"kind": "range", "width": 100, "parts": 3})).unwrap();
+    assert_eq!(range, PartitionerConfig::Range { bounds: vec![100, 200] });
+    let range = range.build(0, PlacementView::Plain);
+    assert_eq!(range.part_of(99), 0);
+    assert_eq!(range.part_of(100), 1);
+    assert_eq!(range.part_of(1000), 2);
+
+    // a hot key pinned to partition 1
+    let lookup = PartitionerConfig::from_json(&json!({
+        "kind": "lookup", "entries": [[42, 1]], "fallback": {"kind": "hash", "parts": 2}
+    })).unwrap().build(0, PlacementView::Plain);
+    assert_eq!(lookup.part_of(42), 1);
+    assert_eq!(lookup.part_of(44), 0);
+
+    // 30 of every 100 keys of a partition on its dpu
+    let split = PartitionerConfig::from_json(&json!({
+        "kind": "split", "modulo": 100, "ratio": 30, "base": {"kind": "hash", "parts": 2}
+    })).unwrap();
+    assert_eq!(split.build(0, PlacementView::Plain).part_of(20), 0);
+
+    let host = split.build(0, PlacementView::Host);
+    assert_eq!(host.part_of(20), 100);
+    assert_eq!(host.part_of(40), 0);
+    assert_eq!(host.part_of(21), 1);
+
+    let hybrid = split.build(0, PlacementView::Hybrid);
+    assert_eq!(hybrid.part_of(20), 0);
+    assert_eq!(hybrid.part_of(21), 101);
+    assert_eq!(hybrid.part_of(41), 1);
+
+    // bad configs are rejected
+    assert!(PartitionerConfig::from_json(&json!({"kind": "hash", "parts": 0})).is_err());
+    assert!(PartitionerConfig::from_json(&json!({"kind": "range", "bounds": [200, 100]})).is_err());
+    assert!(PartitionerConfig::from_json(&json!({
+        "kind": "split", "modulo": 100, "ratio": 130, "base": {"kind": "hash", "parts": 2}
+    })).is_err());
+    assert!(PartitionerConfig::from_json(&json!({"kind": "tree"})).is_err());
+
+    let cluster = ClusterConfig::from_json(r#"{
+        "nodes": [{"id": 0, "role": "host", "ip": "a", "port": 1, "threads": 1, "partitions": [0]}],
+        "placements": {"tpcc.orders": {"kind": "hash", "parts": 1, "mixed": true}}
+    }"#).unwrap();
+    assert_eq!(cluster.get_placement("tpcc.orders"), Some(&PartitionerConfig::Hash { parts: 1, mixed: true }));
+    assert!(cluster.get_placement("tpcc.stocks").is_none());
+}
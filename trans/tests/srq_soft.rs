@@ -0,0 +1,130 @@
+#![feature(get_mut_unchecked)]
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+
+use trans::framework::scheduler::AsyncScheduler;
+use trans::memstore::memdb::MemDB;
+use trans::occ::occ_remote::OccRemote;
+use trans::occ::CcProtocol;
+use trans::rdma::rcconn::RdmaRcConn;
+use trans::rdma::srq::RdmaSharedRecvQueue;
+use trans::rdma::RdmaBaseAllocator;
+
+mod common;
+use common::{balance_of, new_accounts, Account, Participant};
+
+// two coordinators, parts 0 and 2, reach the participant part 1 whose connections share a srq
+struct Cluster {
+    memdb_a:     Arc<MemDB>,
+    memdb_b:     Arc<MemDB>,
+    memdb_c:     Arc<MemDB>,
+    scheduler_a: Arc<AsyncScheduler>,
+    scheduler_b: Arc<AsyncScheduler>,
+    scheduler_c: Arc<AsyncScheduler>,
+    _peer:       Arc<Participant>,
+}
+
+fn new_coordinator(id: u64, allocator: &Arc<RdmaBaseAllocator>, conn: RdmaRcConn) -> Arc<AsyncScheduler> {
+    let conn = Arc::new(Mutex::new(conn));
+    let mut scheduler = Arc::new(AsyncScheduler::new(0, 4, allocator));
+    Arc::get_mut(&mut scheduler).unwrap().append_conn(id, &conn);
+    conn.lock().unwrap().register_callbacks(&scheduler).unwrap();
+    scheduler
+}
+
+fn new_cluster() -> Cluster {
+    let allocator_a = Arc::new(RdmaBaseAllocator::new());
+    let allocator_b = Arc::new(RdmaBaseAllocator::new());
+    let allocator_c = Arc::new(RdmaBaseAllocator::new());
+    let srq = Arc::new(Mutex::new(RdmaSharedRecvQueue::new_soft(&allocator_b).unwrap()));
+
+    let (conn_a, conn_ba) = RdmaRcConn::new_soft_pair_on_srq(0, &allocator_a, 1, &allocator_b, &srq).unwrap();
+    let (conn_c, conn_bc) = RdmaRcConn::new_soft_pair_on_srq(2, &allocator_c, 1, &allocator_b, &srq).unwrap();
+    assert!(conn_ba.is_shared_recv());
+    assert_ne!(conn_ba.get_qp_num(), conn_bc.get_qp_num());
+
+    let scheduler_a = new_coordinator(1, &allocator_a, conn_a);
+    let scheduler_c = new_coordinator(1, &allocator_c, conn_c);
+
+    let conn_ba = Arc::new(Mutex::new(conn_ba));
+    let conn_bc = Arc::new(Mutex::new(conn_bc));
+    let mut scheduler_b = Arc::new(AsyncScheduler::new(0, 4, &allocator_b));
+    {
+        let scheduler = Arc::get_mut(&mut scheduler_b).unwrap();
+        scheduler.append_conn(0, &conn_ba);
+        scheduler.append_conn(2, &conn_bc);
+        scheduler.set_srq(&srq);
+    }
+    conn_ba.lock().unwrap().register_callbacks(&scheduler_b).unwrap();
+    conn_bc.lock().unwrap().register_callbacks(&scheduler_b).unwrap();
+
+    let memdb_b = new_accounts(&[2, 3], 200);
+    let peer = Participant::register(&memdb_b, &mut scheduler_b, CcProtocol::Occ);
+
+    Cluster {
+        memdb_a:     new_accounts(&[], 0),
+        memdb_b:     memdb_b,
+        memdb_c:     new_accounts(&[], 0),
+        scheduler_a: scheduler_a,
+        scheduler_b: scheduler_b,
+        scheduler_c: scheduler_c,
+        _peer:       peer,
+    }
+}
+
+async fn deposit(memdb: &Arc<MemDB>, scheduler: &Arc<AsyncScheduler>, part_id: u64, key: u64, done: &Cell<bool>) -> bool {
+    let mut txn = OccRemote::<8>::new(part_id, 0, 1, memdb, scheduler);
+    txn.start();
+
+    let idx = txn.fetch_write::<Account>(0, 1, key);
+    let balance = txn.get_value::<Account>(true, idx).await.balance;
+    txn.set_value(true, idx, &Account{ balance: balance + key });
+
+    txn.commit().await;
+    done.set(true);
+    txn.is_commited()
+}
+
+async fn poll_until(cluster: &Cluster, done: &[&Cell<bool>]) {
+    while done.iter().any(|done| !done.get()) {
+        cluster.scheduler_b.poll_recvs();
+        cluster.scheduler_a.poll_recvs();
+        cluster.scheduler_c.poll_recvs();
+        tokio::task::yield_now().await;
+    }
+}
+
+#[test]
+fn srq_rpc_test()
+{
+    let cluster = new_cluster();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    // the requests of both coordinators are demultiplexed by their qp and replied on their conn
+    let done_a = Cell::new(false);
+    let done_c = Cell::new(false);
+    let (commited_a, commited_c, _) = runtime.block_on(async {
+        tokio::join!(
+            deposit(&cluster.memdb_a, &cluster.scheduler_a, 0, 2, &done_a),
+            deposit(&cluster.memdb_c, &cluster.scheduler_c, 2, 3, &done_c),
+            poll_until(&cluster, &[&done_a, &done_c]),
+        )
+    });
+    assert_eq!(commited_a, true);
+    assert_eq!(commited_c, true);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 202));
+    assert_eq!(balance_of(&cluster.memdb_b, 3), (0, 203));
+
+    // the ring buffers are posted again, it keeps taking requests past its depth
+    for _ in 0..200 {
+        let done = Cell::new(false);
+        let (commited, _) = runtime.block_on(async {
+            tokio::join!(
+                deposit(&cluster.memdb_a, &cluster.scheduler_a, 0, 2, &done),
+                poll_until(&cluster, &[&done]),
+            )
+        });
+        assert_eq!(commited, true);
+    }
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 202 + 2 * 200));
+}
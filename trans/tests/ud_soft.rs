@@ -0,0 +1,117 @@
+#![feature(get_mut_unchecked)]
+use std::cell::Cell;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+
+use trans::framework::scheduler::AsyncScheduler;
+use trans::memstore::memdb::MemDB;
+use trans::occ::occ_remote::OccRemote;
+use trans::occ::CcProtocol;
+use trans::rdma::soft_verbs::SoftUdNet;
+use trans::rdma::udadapter::{RdmaUdAdapter, RdmaUdAddr};
+use trans::rdma::RdmaBaseAllocator;
+
+mod common;
+use common::{balance_of, new_accounts, Account, Participant};
+
+// the coordinator part 0 reaches the participant part 1 by datagrams only, both on thread 0
+struct Cluster {
+    net:         SoftUdNet,
+    memdb_a:     Arc<MemDB>,
+    memdb_b:     Arc<MemDB>,
+    scheduler_a: Arc<AsyncScheduler>,
+    scheduler_b: Arc<AsyncScheduler>,
+    peer:        Arc<Participant>,
+}
+
+fn new_ud_scheduler(id: u64, net: &SoftUdNet) -> Arc<AsyncScheduler> {
+    let allocator = Arc::new(RdmaBaseAllocator::new());
+    let ud = RdmaUdAdapter::new_soft(id, 0, net, &allocator).unwrap();
+    // a soft adapter is reached by its id and thread alone
+    let peer_addr = RdmaUdAddr { peer_id: 1 - id, tid: 0, ..Default::default() };
+
+    let mut scheduler = Arc::new(AsyncScheduler::new(0, 4, &allocator));
+    let inner = Arc::get_mut(&mut scheduler).unwrap();
+    inner.set_ud_adapter(&Arc::new(Mutex::new(ud)));
+    inner.append_ud_peer(&peer_addr).unwrap();
+    scheduler
+}
+
+fn new_cluster() -> Cluster {
+    let net = SoftUdNet::new();
+    let scheduler_a = new_ud_scheduler(0, &net);
+    let mut scheduler_b = new_ud_scheduler(1, &net);
+
+    let memdb_b = new_accounts(&[2], 200);
+    let peer = Participant::register(&memdb_b, &mut scheduler_b, CcProtocol::Occ);
+
+    Cluster {
+        net:         net,
+        memdb_a:     new_accounts(&[], 0),
+        memdb_b:     memdb_b,
+        scheduler_a: scheduler_a,
+        scheduler_b: scheduler_b,
+        peer:        peer,
+    }
+}
+
+async fn deposit(cluster: &Cluster, done: &Cell<bool>) -> bool {
+    let mut txn = OccRemote::<8>::new(0, 0, 1, &cluster.memdb_a, &cluster.scheduler_a);
+    txn.start();
+
+    let idx = txn.fetch_write::<Account>(0, 1, 2);
+    let balance = txn.get_value::<Account>(true, idx).await.balance;
+    txn.set_value(true, idx, &Account{ balance: balance + 2 });
+
+    txn.commit().await;
+    done.set(true);
+    txn.is_commited()
+}
+
+async fn poll_until(cluster: &Cluster, done: &Cell<bool>) {
+    while !done.get() {
+        cluster.scheduler_b.poll_recvs();
+        cluster.scheduler_a.poll_recvs();
+        tokio::task::yield_now().await;
+    }
+}
+
+fn run_deposit(cluster: &Cluster, runtime: &tokio::runtime::Runtime) -> bool {
+    let done = Cell::new(false);
+    let (commited, _) = runtime.block_on(async {
+        tokio::join!(
+            deposit(cluster, &done),
+            poll_until(cluster, &done),
+        )
+    });
+    commited
+}
+
+#[test]
+fn ud_rpc_test()
+{
+    let cluster = new_cluster();
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+
+    assert_eq!(run_deposit(&cluster, &runtime), true);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 202));
+
+    // the lost request is sent again after the timeout
+    cluster.net.drop_next(1);
+    assert_eq!(run_deposit(&cluster, &runtime), true);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 204));
+
+    // a lost reply is sent again from the peer, the request does not run twice
+    let served = cluster.peer.served.load(Ordering::Relaxed);
+    assert_eq!(run_deposit(&cluster, &runtime), true);
+    let per_txn = cluster.peer.served.load(Ordering::Relaxed) - served;
+    cluster.net.drop_next_to((0, 0), 1);
+    assert_eq!(run_deposit(&cluster, &runtime), true);
+    assert_eq!(cluster.peer.served.load(Ordering::Relaxed) - served, 2 * per_txn);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 208));
+
+    // the peer never answers, the replies fail and the txn aborts instead of hanging
+    cluster.net.drop_next(usize::MAX);
+    assert_eq!(run_deposit(&cluster, &runtime), false);
+    assert_eq!(balance_of(&cluster.memdb_b, 2), (0, 208));
+}